@@ -0,0 +1,24 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{env, fs, path::Path};
+
+fn main() {
+    if env::var_os("CARGO_FEATURE_EMBEDDED_CACHE").is_none() {
+        return;
+    }
+    println!("cargo:rerun-if-env-changed=ASKALONO_EMBEDDED_CACHE");
+
+    // Without a cache to embed, embed nothing, so that builds with every
+    // feature (and docs) still succeed; `global_store` reports it at runtime.
+    let cache = match env::var("ASKALONO_EMBEDDED_CACHE") {
+        Ok(cache) if !cache.is_empty() => cache,
+        _ => {
+            println!("cargo:warning=ASKALONO_EMBEDDED_CACHE isn't set; no cache will be embedded");
+            let empty = Path::new(&env::var("OUT_DIR").unwrap()).join("no-embedded-cache.bin");
+            fs::write(&empty, []).unwrap();
+            empty.to_str().unwrap().to_owned()
+        }
+    };
+    println!("cargo:rustc-env=ASKALONO_EMBEDDED_CACHE_FILE={}", cache);
+}
@@ -24,20 +24,40 @@ fn main() {
         EMBEDDED_CACHE
     );
 
-    if Path::new(EMBEDDED_CACHE).exists() {
+    if cache_is_current() {
         println!("cargo:warning=askalono cache file already exists; not re-building");
-        return;
+    } else {
+        let store_texts = env::var("CARGO_FEATURE_DIAGNOSTICS").is_ok();
+
+        let mut store = Store::new();
+        store
+            .load_spdx(
+                Path::new("../datasets/modules/spdx-license-list-data/json/details"),
+                store_texts,
+            )
+            .expect("Couldn't create a store from SPDX data. Have submodules been initialized?");
+        let mut cache = File::create(EMBEDDED_CACHE).unwrap();
+        store.to_cache(&mut cache).unwrap();
     }
 
-    let store_texts = env::var("CARGO_FEATURE_DIAGNOSTICS").is_ok();
-
-    let mut store = Store::new();
-    store
-        .load_spdx(
-            Path::new("../datasets/modules/spdx-license-list-data/json/details"),
-            store_texts,
-        )
-        .expect("Couldn't create a store from SPDX data. Have submodules been initialized?");
-    let mut cache = File::create(EMBEDDED_CACHE).unwrap();
-    store.to_cache(&mut cache).unwrap();
+    #[cfg(feature = "static-store")]
+    write_static_store();
+}
+
+/// Whether a cache was already built, and can be loaded by this version of
+/// askalono. Caches left over from older versions are built again.
+fn cache_is_current() -> bool {
+    File::open(EMBEDDED_CACHE)
+        .map_err(Into::into)
+        .and_then(Store::from_cache)
+        .is_ok()
+}
+
+/// Generate Rust source for the embedded cache as a `StaticStore`.
+#[cfg(feature = "static-store")]
+fn write_static_store() {
+    let store = Store::from_cache(File::open(EMBEDDED_CACHE).unwrap()).unwrap();
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("askalono-store.rs");
+    let file = std::io::BufWriter::new(File::create(out).unwrap());
+    store.write_static(file).unwrap();
 }
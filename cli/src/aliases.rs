@@ -0,0 +1,76 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::path::PathBuf;
+
+use anyhow::{format_err, Error};
+use serde_derive::Serialize;
+
+use super::{commands::*, util::*};
+
+#[derive(Serialize)]
+struct CLIAliases<'a> {
+    license: &'a str,
+    aliases: &'a [String],
+}
+
+/// Print the aliases recorded for a license.
+pub fn aliases(
+    cache_filenames: &[PathBuf],
+    output_format: &OutputFormat,
+    name: &str,
+) -> Result<(), Error> {
+    let stores = load_stores(cache_filenames)?;
+    let aliases = stores
+        .all()
+        .find_map(|store| store.aliases(name).ok())
+        .ok_or_else(|| format_err!("License {} is not in the cache", name))?;
+
+    match output_format {
+        OutputFormat::Text => {
+            for alias in aliases {
+                println!("{}", alias);
+            }
+        }
+        OutputFormat::JSON => println!(
+            "{}",
+            serde_json::to_string(&CLIAliases {
+                license: name,
+                aliases,
+            })
+            .expect("must produce valid json output")
+        ),
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct CLIResolved<'a> {
+    name: &'a str,
+    license: &'a str,
+}
+
+/// Print the license a name refers to, by its own name or as an alias, or
+/// failing that, loosely (see `Store::resolve_fuzzy`).
+pub fn resolve(
+    cache_filenames: &[PathBuf],
+    output_format: &OutputFormat,
+    name: &str,
+) -> Result<(), Error> {
+    let stores = load_stores(cache_filenames)?;
+    let license = stores
+        .all()
+        .find_map(|store| store.resolve_alias(name))
+        .or_else(|| stores.all().find_map(|store| store.resolve_fuzzy(name)))
+        .ok_or_else(|| format_err!("No license or alias named {} is in the cache", name))?;
+
+    match output_format {
+        OutputFormat::Text => println!("{}", license),
+        OutputFormat::JSON => println!(
+            "{}",
+            serde_json::to_string(&CLIResolved { name, license })
+                .expect("must produce valid json output")
+        ),
+    }
+    Ok(())
+}
@@ -1,31 +1,231 @@
 // Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::{fs::File, path::Path};
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, prelude::*, BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
 
-use anyhow::Error;
+use anyhow::{format_err, Error};
 use log::info;
 
-use super::commands::*;
-use askalono::Store;
+use super::{commands::*, util::existing_store};
+use askalono::{SpdxLoadOptions, Store};
 
-pub fn cache(cache_filename: &Path, subcommand: CacheSubcommand) -> Result<(), Error> {
+const CACHE_FILENAME: &str = "askalono-cache.bin.zstd";
+
+/// Find the cache to use when none is given: the one named by
+/// `$ASKALONO_CACHE`, or else the first that exists in the user's data
+/// directory or a system-wide one, or else one in the current directory.
+pub fn default_cache_path() -> PathBuf {
+    if let Some(path) = env::var_os("ASKALONO_CACHE") {
+        return path.into();
+    }
+    user_cache_path()
+        .into_iter()
+        .chain(
+            ["/usr/local/share", "/usr/share"]
+                .iter()
+                .map(|dir| Path::new(dir).join("askalono").join(CACHE_FILENAME)),
+        )
+        .find(|path| path.is_file())
+        .unwrap_or_else(|| Path::new(".").join(CACHE_FILENAME))
+}
+
+/// Where `cache install` puts a cache: the askalono directory under
+/// `$XDG_DATA_HOME` (by default, `~/.local/share`).
+fn user_cache_path() -> Option<PathBuf> {
+    let data_dir = env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))?;
+    Some(data_dir.join("askalono").join(CACHE_FILENAME))
+}
+
+pub fn cache(
+    cache_filename: &Path,
+    output_format: &OutputFormat,
+    subcommand: CacheSubcommand,
+) -> Result<(), Error> {
     match subcommand {
-        CacheSubcommand::LoadSpdx { dir, store_texts } => {
-            cache_load_spdx(cache_filename, &dir, store_texts)
+        CacheSubcommand::LoadSpdx {
+            dir,
+            store_texts,
+            store_originals,
+            include_deprecated,
+            compact,
+            hash_grams,
+            idf,
+            index,
+            lsh,
+        } => cache_load_spdx(
+            cache_filename,
+            &dir,
+            &SpdxLoadOptions {
+                include_texts: store_texts,
+                include_originals: store_originals,
+                include_deprecated,
+            },
+            compact,
+            &ScoringAids {
+                idf,
+                index,
+                lsh,
+                hash_grams,
+            },
+        ),
+        CacheSubcommand::LoadDir { dir, store_texts } => {
+            cache_load_dir(cache_filename, &dir, store_texts)
         }
+        CacheSubcommand::UpdateSpdx { dir } => cache_update_spdx(cache_filename, &dir),
+        CacheSubcommand::Info => cache_info(cache_filename, output_format),
+        CacheSubcommand::Export { file } => cache_export(cache_filename, file.as_deref()),
+        CacheSubcommand::Import { file } => cache_import(cache_filename, &file),
+        CacheSubcommand::Install { file } => cache_install(file.as_deref()),
     }
 }
 
+/// Data computed from a store's licenses once they're all loaded, to
+/// improve or speed up matching.
+struct ScoringAids {
+    idf: bool,
+    index: Option<usize>,
+    lsh: bool,
+    /// Keep only hashes of n-grams; see `Store::hash_grams`.
+    hash_grams: bool,
+}
+
 fn cache_load_spdx(
     cache_filename: &Path,
     directory: &Path,
-    store_texts: bool,
+    options: &SpdxLoadOptions,
+    compact: bool,
+    aids: &ScoringAids,
 ) -> Result<(), Error> {
     info!("Processing licenses...");
     let mut store = Store::new();
-    store.load_spdx(directory, store_texts)?;
+    if compact {
+        store.compact_counts();
+    }
+    store.load_spdx_with_options(directory, options)?;
+    store.set_idf_weighting(aids.idf);
+    store.set_candidate_index(aids.index);
+    store.set_lsh_prefilter(aids.lsh);
+    if aids.hash_grams {
+        store.hash_grams()?;
+    }
+    let cache_file = File::create(cache_filename)?;
+    store.to_cache(&cache_file)?;
+    Ok(())
+}
+
+fn cache_load_dir(cache_filename: &Path, directory: &Path, store_texts: bool) -> Result<(), Error> {
+    info!("Processing licenses...");
+    let mut store = Store::new();
+    store.load_directory(directory, store_texts)?;
     let cache_file = File::create(cache_filename)?;
     store.to_cache(&cache_file)?;
     Ok(())
 }
+
+fn cache_update_spdx(cache_filename: &Path, directory: &Path) -> Result<(), Error> {
+    let mut store = Store::from_cache(File::open(cache_filename)?)?;
+    info!("Updating licenses...");
+    let update = store.update_from_spdx(directory)?;
+    eprintln!(
+        "Added {}, changed {}, removed {} licenses",
+        update.added.len(),
+        update.changed.len(),
+        update.removed.len()
+    );
+    for (label, names) in [
+        ("Added", &update.added),
+        ("Changed", &update.changed),
+        ("Removed", &update.removed),
+    ] {
+        for name in names {
+            info!("{}: {}", label, name);
+        }
+    }
+
+    let cache_file = File::create(cache_filename)?;
+    store.to_cache(&cache_file)?;
+    Ok(())
+}
+
+fn cache_info(cache_filename: &Path, output_format: &OutputFormat) -> Result<(), Error> {
+    let info = existing_store(cache_filename)?.info();
+
+    match output_format {
+        OutputFormat::Text => {
+            let unknown = || "unknown".to_owned();
+            println!(
+                "License list version: {}",
+                info.list_version.clone().unwrap_or_else(unknown)
+            );
+            println!(
+                "Built at: {}",
+                info.built_at
+                    .map(|secs| format!("{} (seconds since the Unix epoch)", secs))
+                    .unwrap_or_else(unknown)
+            );
+            println!("Licenses: {}", info.licenses);
+        }
+        OutputFormat::JSON => println!(
+            "{}",
+            serde_json::to_string(&info).expect("must produce valid json output")
+        ),
+    }
+    Ok(())
+}
+
+fn cache_export(cache_filename: &Path, file: Option<&Path>) -> Result<(), Error> {
+    let store = existing_store(cache_filename)?;
+    match file {
+        Some(file) => {
+            let mut writer = BufWriter::new(File::create(file)?);
+            store.to_json(&mut writer)?;
+            writer.flush()?;
+        }
+        None => store.to_json(io::stdout().lock())?,
+    }
+    Ok(())
+}
+
+fn cache_import(cache_filename: &Path, file: &Path) -> Result<(), Error> {
+    let store = Store::from_json(BufReader::new(File::open(file)?))?;
+    let cache_file = File::create(cache_filename)?;
+    store.to_cache(&cache_file)?;
+    Ok(())
+}
+
+fn cache_install(file: Option<&Path>) -> Result<(), Error> {
+    let destination = user_cache_path()
+        .ok_or_else(|| format_err!("Couldn't find a data directory; set $XDG_DATA_HOME"))?;
+    let data = match file {
+        Some(file) => fs::read(file)?,
+        None => embedded_cache()?.to_vec(),
+    };
+    // don't install something that can't be loaded
+    Store::from_cache(data.as_slice())
+        .map_err(|e| format_err!("Not a valid askalono cache: {}", e))?;
+
+    fs::create_dir_all(destination.parent().expect("cache path has a directory"))?;
+    fs::write(&destination, data)?;
+    eprintln!("Installed cache to {}", destination.display());
+    Ok(())
+}
+
+#[cfg(feature = "embedded-cache")]
+fn embedded_cache() -> Result<&'static [u8], Error> {
+    Ok(super::util::CACHE_DATA)
+}
+
+#[cfg(not(feature = "embedded-cache"))]
+fn embedded_cache() -> Result<&'static [u8], Error> {
+    Err(format_err!(
+        "askalono wasn't compiled with an embedded cache; give a cache file to install"
+    ))
+}
@@ -3,9 +3,7 @@
 
 use std::path::PathBuf;
 
-use clap::Parser;
-
-use clap::ValueEnum;
+use clap::{Args, Parser, ValueEnum};
 
 #[derive(Clone, ValueEnum)]
 #[clap(rename_all = "lower")]
@@ -18,9 +16,14 @@ pub enum OutputFormat {
 #[derive(Parser)]
 #[clap(name = "askalono", version)]
 pub struct Opt {
-    /// Path to a cache file containing compiled license information
+    /// Path to a cache file containing compiled license information. May be
+    /// given more than once to also scan with other stores; licenses found in
+    /// those are tagged with the file's name (without extensions). Defaults
+    /// to $ASKALONO_CACHE, or the first cache found in the user's data
+    /// directory, /usr/local/share/askalono, /usr/share/askalono, or the
+    /// current directory, or else the embedded cache if there is one
     #[clap(long = "cache", short = 'c')]
-    pub cache: Option<PathBuf>,
+    pub cache: Vec<PathBuf>,
 
     /// Output type: text (default), json
     #[clap(long = "format")]
@@ -37,31 +40,50 @@ pub enum Subcommand {
     #[clap(name = "identify", alias = "id")]
     Identify {
         /// File to identify
-        #[clap(name = "FILE", required_unless_present("batch"))]
+        #[clap(name = "FILE", required_unless_present("batch_mode"))]
         filename: Option<PathBuf>,
 
-        /// Try to find the location of a license within the given file
-        #[clap(long = "optimize", short = 'o')]
-        optimize: bool,
+        #[clap(flatten)]
+        scan: ScanOptions,
 
         #[clap(long = "diff", hide = true)]
         diff: bool,
 
+        /// Show N lines of the file above and below each license found
+        /// within it (with --optimize or --multiple)
+        #[clap(long = "context", value_name = "N")]
+        context: Option<usize>,
+
         /// Read in filenames on stdin for batch identification
-        #[clap(long = "batch", short = 'b')]
+        #[clap(long = "batch", short = 'b', group = "batch_mode")]
         batch: bool,
 
-        /// Detect multiple licenses in the same file
-        #[clap(long = "multiple", short = 'm')]
-        topdown: bool,
+        /// Like --batch, but filenames on stdin are separated by NUL
+        /// characters (as with `find -print0`)
+        #[clap(long = "batch0", group = "batch_mode")]
+        batch0: bool,
+
+        /// Read newline-delimited JSON records like `{"id": "...", "text":
+        /// "..."}` on stdin and identify each record's text; results report
+        /// the record's ID as their path
+        #[clap(long = "batch-json", group = "batch_mode")]
+        batch_json: bool,
+
+        /// In batch mode, end each result with a NUL character instead of a
+        /// newline, and write errors to stdout alongside results
+        #[clap(long = "print0", requires = "batch_mode")]
+        print0: bool,
     },
 
     /// Crawl a directory identifying license files
     #[clap(name = "crawl")]
     Crawl {
         /// Directory to crawl
-        #[clap(name = "DIR")]
-        directory: PathBuf,
+        #[clap(name = "DIR", required_unless_present("files_from"))]
+        directory: Option<PathBuf>,
+
+        #[clap(flatten)]
+        scan: ScanOptions,
 
         /// Follow symlinks
         #[clap(long = "follow")]
@@ -70,6 +92,98 @@ pub enum Subcommand {
         /// Glob of files to check (defaults to license-like files)
         #[clap(long = "glob")]
         glob: Option<String>,
+
+        /// Write results to a report file instead of stdout; errors are
+        /// still shown on the terminal
+        #[clap(long = "output")]
+        output: Option<PathBuf>,
+
+        /// Format of the report file (defaults to --format)
+        #[clap(long = "output-format", requires = "output")]
+        #[arg(value_enum)]
+        output_format: Option<OutputFormat>,
+
+        /// Report paths relative to the given directory (defaults to the
+        /// crawled directory if no value is given)
+        #[clap(long = "relative-to", value_name = "ROOT", num_args = 0..=1)]
+        relative_to: Option<Option<PathBuf>>,
+
+        /// Include each file's size, modification time, and SHA-256 hash in
+        /// results
+        #[clap(long = "with-metadata")]
+        with_metadata: bool,
+
+        /// Scan the paths listed in the given file (one per line, or `-` for
+        /// stdin) instead of crawling a directory
+        #[clap(long = "files-from", value_name = "LIST", conflicts_with = "DIR")]
+        files_from: Option<PathBuf>,
+
+        /// With --files-from, only scan listed paths matching --glob (or
+        /// license-like files)
+        #[clap(long = "filter", requires = "files_from")]
+        filter: bool,
+
+        /// Finish with the number of files walked and scanned, bytes
+        /// processed, and time taken
+        #[clap(long = "stats")]
+        stats: bool,
+    },
+
+    /// Check `SPDX-License-Identifier` tags in source files against the
+    /// licenses identified in a directory's license files
+    #[clap(name = "detect-id")]
+    DetectId {
+        /// Directory to scan
+        #[clap(name = "DIR")]
+        directory: PathBuf,
+
+        /// Follow symlinks
+        #[clap(long = "follow")]
+        follow_links: bool,
+    },
+
+    /// Compare two files with each other, without consulting the license
+    /// cache
+    #[clap(name = "compare")]
+    Compare {
+        /// File to compare against
+        #[clap(name = "FILE_A")]
+        file_a: PathBuf,
+
+        /// File to compare, listing words it adds to or removes from FILE_A
+        #[clap(name = "FILE_B")]
+        file_b: PathBuf,
+    },
+
+    /// Print the text of a license in the cache
+    #[clap(name = "show")]
+    Show {
+        /// SPDX identifier of the license
+        #[clap(name = "LICENSE")]
+        name: String,
+
+        /// Print the license's standard header, to be placed in source
+        /// files, instead of its full text
+        #[clap(long = "header")]
+        header: bool,
+    },
+
+    /// List the other identifiers a license in the cache is known by
+    #[clap(name = "aliases")]
+    Aliases {
+        /// SPDX identifier of the license
+        #[clap(name = "LICENSE")]
+        name: String,
+    },
+
+    /// Find the license in the cache that an identifier or alias refers to
+    #[clap(name = "resolve")]
+    Resolve {
+        /// License identifier or alias, matched case-insensitively if there's
+        /// no exact match, and loosely (like "Apache 2" or "GPLv3") if there's
+        /// still none
+        #[clap(name = "NAME")]
+        name: String,
     },
 
     /// Cache management actions
@@ -80,6 +194,52 @@ pub enum Subcommand {
     },
 }
 
+/// Options controlling how thoroughly each file is scanned.
+#[derive(Args, Clone)]
+pub struct ScanOptions {
+    /// Try to find the location of a license within the given file
+    #[clap(long = "optimize", short = 'o')]
+    pub optimize: bool,
+
+    /// Detect multiple licenses in the same file
+    #[clap(long = "multiple", short = 'm')]
+    pub topdown: bool,
+
+    /// With --multiple, the number of lines to move the search window by at
+    /// a time; smaller steps are slower, but find licenses more precisely
+    #[clap(long = "step", value_name = "N", default_value_t = 5,
+           value_parser = clap::value_parser!(u32).range(1..))]
+    pub step_size: u32,
+
+    /// With --optimize, the most licenses to locate within a file
+    #[clap(long = "max-passes", value_name = "N", default_value_t = 1,
+           value_parser = clap::value_parser!(u16).range(1..))]
+    pub max_passes: u16,
+
+    /// Score every license, even if the cache was built with --index or
+    /// --lsh; slower, but never misses a match
+    #[clap(long = "exact")]
+    pub exact: bool,
+
+    /// In source files, scan only the comments, picking the comment syntax
+    /// from the file's extension; finds license headers more reliably
+    #[clap(long = "comments")]
+    pub comments: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            optimize: false,
+            topdown: false,
+            step_size: 5,
+            max_passes: 1,
+            exact: false,
+            comments: false,
+        }
+    }
+}
+
 #[derive(Parser)]
 pub enum CacheSubcommand {
     /// Load an SPDX license directory (see https://github.com/spdx/license-list-data/tree/master/json/details for format)
@@ -92,5 +252,95 @@ pub enum CacheSubcommand {
         /// Store texts in cache along with match data
         #[clap(long = "store")]
         store_texts: bool,
+
+        /// Also store the verbatim (un-normalized) text of each license
+        #[clap(long = "store-originals")]
+        store_originals: bool,
+
+        /// Also load deprecated licenses, marked as such, instead of skipping
+        /// them
+        #[clap(long = "include-deprecated")]
+        include_deprecated: bool,
+
+        /// Store n-gram counts compactly, using less memory when loaded at
+        /// the cost of slower matching
+        #[clap(long = "compact")]
+        compact: bool,
+
+        /// Store hashes of n-grams rather than their words, so loading the
+        /// cache doesn't keep license words in memory; can't be combined with
+        /// --idf or --index
+        #[clap(long = "hash-grams")]
+        hash_grams: bool,
+
+        /// Weight n-grams by how rare they are among licenses when scoring,
+        /// so shared boilerplate counts for less
+        #[clap(long = "idf")]
+        idf: bool,
+
+        /// Index n-grams so that matching only fully scores this many of the
+        /// most likely licenses, which is much faster
+        #[clap(long = "index", value_name = "CANDIDATES")]
+        index: Option<usize>,
+
+        /// Keep MinHash signatures of licenses so that matching only scores
+        /// those likely to be similar, which is much faster
+        #[clap(long = "lsh")]
+        lsh: bool,
+    },
+
+    /// Load a directory of license texts (NAME.txt), each optionally with
+    /// metadata beside it (NAME.toml) listing aliases, family, headers, and
+    /// alternates
+    #[clap(name = "load-dir")]
+    LoadDir {
+        /// Directory of license texts
+        #[clap(name = "DIR")]
+        dir: PathBuf,
+
+        /// Store texts in cache along with match data
+        #[clap(long = "store")]
+        store_texts: bool,
+    },
+
+    /// Update an existing cache from a newer SPDX license directory,
+    /// processing only licenses that were added, changed, or removed
+    #[clap(name = "update-spdx")]
+    UpdateSpdx {
+        /// JSON "details" directory
+        #[clap(name = "DIR")]
+        dir: PathBuf,
+    },
+
+    /// Show which SPDX license list a cache was built from, when, and how
+    /// many licenses it holds (the embedded cache, if the cache file doesn't
+    /// exist)
+    #[clap(name = "info")]
+    Info,
+
+    /// Write a cache out as JSON, to inspect, edit, or diff it (the embedded
+    /// cache, if the cache file doesn't exist)
+    #[clap(name = "export")]
+    Export {
+        /// File to write (defaults to standard output)
+        #[clap(name = "FILE")]
+        file: Option<PathBuf>,
+    },
+
+    /// Build the cache from JSON written by `cache export`
+    #[clap(name = "import")]
+    Import {
+        /// JSON file to read
+        #[clap(name = "FILE")]
+        file: PathBuf,
+    },
+
+    /// Copy a cache into the user's data directory, where it's found without
+    /// needing --cache
+    #[clap(name = "install")]
+    Install {
+        /// Cache file to install (defaults to the embedded cache)
+        #[clap(name = "FILE")]
+        file: Option<PathBuf>,
     },
 }
@@ -0,0 +1,30 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::path::Path;
+
+use anyhow::Error;
+
+use super::{commands::*, formats::*, input::read_input};
+
+/// Compare two files directly, without a store: report how similar they are,
+/// and which words the second inserts into or deletes from the first.
+pub fn compare(output_format: &OutputFormat, file_a: &Path, file_b: &Path) -> Result<(), Error> {
+    let a = read_input(file_a)?.text_data(None);
+    let b = read_input(file_b)?.text_data(None);
+
+    let comparison = CLIComparison {
+        score: a.match_score(&b),
+        deviations: b
+            .deviations_from(&a)
+            .expect("texts are kept for comparison"),
+    };
+    match output_format {
+        OutputFormat::Text => println!("{}", comparison),
+        OutputFormat::JSON => println!(
+            "{}",
+            serde_json::to_string(&comparison).expect("must produce valid json output")
+        ),
+    }
+    Ok(())
+}
@@ -0,0 +1,149 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_derive::Serialize;
+
+/// A copyright statement found in a file.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct CLICopyright {
+    pub holder: String,
+    pub years: Option<String>,
+}
+
+/// Find copyright statements in the given text.
+///
+/// This is a heuristic: lines must begin with "copyright" (or a copyright
+/// symbol) and include either a year or a symbol to be considered, which
+/// skips over the many references to "copyright holders" inside license
+/// texts themselves. Template placeholders like `<year>` are ignored.
+pub fn find_copyrights(text: &str) -> Vec<CLICopyright> {
+    lazy_static! {
+        static ref RX: Regex = Regex::new(
+            r"(?ix)
+            ^[^\w(©]*
+            (?P<marker>(copyright|\(c\)|©)(\s*(\(c\)|©))*)
+            [\s:,]*
+            (?P<years>\d{4}(\s*[-–,]\s*\d{2,4})*)?
+            [\s,]*
+            (by\s+)?
+            (?P<holder>.*)$
+        "
+        )
+        .unwrap();
+        static ref RX_RESERVED: Regex = Regex::new(r"(?i)\s*all rights reserved\.?\s*$").unwrap();
+    }
+
+    let mut found = Vec::new();
+    for line in text.lines() {
+        let caps = match RX.captures(line.trim()) {
+            Some(c) => c,
+            None => continue,
+        };
+
+        let years = caps.name("years").map(|y| y.as_str().to_owned());
+        let marker = caps["marker"].to_lowercase();
+        if years.is_none() && !marker.contains("(c)") && !marker.contains('©') {
+            continue;
+        }
+
+        let holder = RX_RESERVED.replace(&caps["holder"], "");
+        let holder = holder.trim().trim_end_matches([',', ';']).trim();
+        if holder.is_empty() || holder.starts_with(['<', '[', '{', '$']) {
+            continue;
+        }
+
+        found.push(CLICopyright {
+            holder: holder.to_owned(),
+            years,
+        });
+    }
+    found
+}
+
+/// Distinct copyright holders and years seen across many files.
+#[derive(Default)]
+pub struct CopyrightSummary {
+    holders: BTreeMap<String, BTreeSet<String>>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CLICopyrightHolder<'a> {
+    pub holder: &'a str,
+    pub years: Vec<&'a str>,
+}
+
+impl CopyrightSummary {
+    pub fn add(&mut self, copyrights: &[CLICopyright]) {
+        for c in copyrights {
+            let years = self.holders.entry(c.holder.clone()).or_default();
+            if let Some(ref y) = c.years {
+                years.insert(y.clone());
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.holders.is_empty()
+    }
+
+    pub fn holders(&self) -> Vec<CLICopyrightHolder<'_>> {
+        self.holders
+            .iter()
+            .map(|(holder, years)| CLICopyrightHolder {
+                holder,
+                years: years.iter().map(String::as_str).collect(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_statements() {
+        let text = "Copyright (c) 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.\n\
+                    // Copyright 2010-2012 Some Person <person@example.com>\n\
+                    © Another Holder\n\
+                    THE SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS\n\
+                    copyright holders be liable for anything\n\
+                    Copyright (c) <year> <copyright holders>";
+        let found = find_copyrights(text);
+        assert_eq!(
+            vec![
+                CLICopyright {
+                    holder: "Amazon.com, Inc. or its affiliates.".into(),
+                    years: Some("2018".into()),
+                },
+                CLICopyright {
+                    holder: "Some Person <person@example.com>".into(),
+                    years: Some("2010-2012".into()),
+                },
+                CLICopyright {
+                    holder: "Another Holder".into(),
+                    years: None,
+                },
+            ],
+            found
+        );
+    }
+
+    #[test]
+    fn summarizes() {
+        let mut summary = CopyrightSummary::default();
+        summary.add(&find_copyrights("Copyright 2017 Someone"));
+        summary.add(&find_copyrights(
+            "Copyright 2018 Someone\nCopyright 2018 Else",
+        ));
+
+        let holders = summary.holders();
+        assert_eq!(2, holders.len());
+        assert_eq!("Else", holders[0].holder);
+        assert_eq!(vec!["2017", "2018"], holders[1].years);
+    }
+}
@@ -1,29 +1,60 @@
 // Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::{fs::read_to_string, path::Path};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Instant,
+};
 
 use anyhow::Error;
 use ignore::Error as IgnoreError;
 
-use askalono::TextData;
+use super::{
+    commands::*,
+    copyright::*,
+    formats::*,
+    identify::identify_input,
+    input::{file_metadata, read_input},
+    util::*,
+};
 
-use super::{commands::*, formats::*, identify::identify_data, util::*};
+/// Options controlling what a crawl scans and how it reports results.
+pub struct CrawlOptions {
+    pub scan: ScanOptions,
+    pub follow_links: bool,
+    pub glob: Option<String>,
+    /// Report file to write results to, instead of stdout.
+    pub output: Option<PathBuf>,
+    /// Format of the report file, if different from the terminal's.
+    pub report_format: Option<OutputFormat>,
+    /// Directory that reported paths are relative to.
+    pub relative_to: Option<PathBuf>,
+    /// Include file size, modification time, and hash in results.
+    pub with_metadata: bool,
+    /// File listing paths to scan (`-` for stdin), instead of walking a
+    /// directory.
+    pub files_from: Option<PathBuf>,
+    /// Apply the glob or license-file filter to paths from `files_from`.
+    pub filter: bool,
+    /// Report throughput statistics once done.
+    pub stats: bool,
+}
 
 pub fn crawl(
-    cache_filename: &Path,
+    cache_filenames: &[PathBuf],
     output_format: &OutputFormat,
-    directory: &Path,
-    follow_links: bool,
-    glob: Option<&str>,
+    directory: Option<&Path>,
+    options: &CrawlOptions,
 ) -> Result<(), Error> {
     use ignore::types::TypesBuilder;
     use ignore::WalkBuilder;
 
-    let store = load_store(cache_filename)?;
+    let stores = load_stores(cache_filenames)?;
 
     let mut types_builder = TypesBuilder::new();
-    if let Some(globstr) = glob {
+    if let Some(ref globstr) = options.glob {
         types_builder.add("custom", globstr)?;
         types_builder.select("custom");
     } else {
@@ -31,40 +62,227 @@ pub fn crawl(
         types_builder.select("license");
     }
     let matcher = types_builder.build().unwrap();
+    let mut copyright_summary = CopyrightSummary::default();
+    let mut stats = CLICrawlStats::default();
 
-    WalkBuilder::new(directory)
-        .types(matcher)
-        .follow_links(follow_links)
-        .build()
-        .filter_map(|entry| match entry {
-            Ok(entry) => Some(entry),
-            Err(error) => {
-                if let IgnoreError::WithPath { path, err } = error {
-                    FileResult::from_error(&path.to_string_lossy(), err)
-                        .print_as(output_format, true);
-                } else {
-                    FileResult::from_error("", error).print_as(output_format, false);
-                }
-                None
+    let mut reporter = match options.output {
+        Some(ref path) => Reporter::File {
+            out: BufWriter::new(File::create(path)?),
+            format: options.report_format.as_ref().unwrap_or(output_format),
+            count: 0,
+        },
+        None => Reporter::Stdout {
+            format: output_format,
+        },
+    };
+
+    let start = Instant::now();
+
+    if let Some(ref list) = options.files_from {
+        let list: Box<dyn BufRead> = if list == Path::new("-") {
+            Box::new(BufReader::new(io::stdin()))
+        } else {
+            Box::new(BufReader::new(File::open(list)?))
+        };
+
+        for line in list.lines() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
             }
-        })
-        .filter(|entry| !entry.metadata().unwrap().is_dir())
-        .for_each(|entry| {
-            let path = entry.path();
-            let path_lossy = path.to_string_lossy();
-
-            match read_to_string(path) {
-                Ok(content) => {
-                    let data = TextData::new(&content);
-                    let idres = identify_data(&store, &data, false, false, false);
-                    let fileres = FileResult::from_identification_result(&path_lossy, &idres);
-                    fileres.print_as(output_format, true);
-                }
-                Err(err) => {
-                    FileResult::from_error(&path_lossy, err).print_as(output_format, true);
+            let path = Path::new(&line);
+            stats.files_walked += 1;
+            if options.filter && !matcher.matched(path, false).is_whitelist() {
+                continue;
+            }
+            scan_file(
+                &stores,
+                path,
+                options,
+                &mut reporter,
+                &mut copyright_summary,
+                &mut stats,
+            )?;
+        }
+    } else {
+        let directory = directory.expect("no directory provided");
+        let walker = WalkBuilder::new(directory)
+            .follow_links(options.follow_links)
+            .build();
+        for entry in walker {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(error) => {
+                    if let IgnoreError::WithPath { path, err } = error {
+                        let path = display_path(&path, options.relative_to.as_deref());
+                        reporter.emit(&FileResult::from_error(&path, err), true)?;
+                    } else {
+                        reporter.emit(&FileResult::from_error("", error), false)?;
+                    }
+                    continue;
                 }
             };
-        });
+            if entry.metadata().unwrap().is_dir() {
+                continue;
+            }
+            stats.files_walked += 1;
+            if !matcher.matched(entry.path(), false).is_whitelist() {
+                continue;
+            }
+
+            scan_file(
+                &stores,
+                entry.path(),
+                options,
+                &mut reporter,
+                &mut copyright_summary,
+                &mut stats,
+            )?;
+        }
+    }
+
+    if !copyright_summary.is_empty() {
+        reporter.emit_copyright_summary(&copyright_summary)?;
+    }
+
+    if options.stats {
+        let elapsed = start.elapsed().as_secs_f64();
+        stats.wall_time_ms = elapsed * 1000.0;
+        if elapsed > 0.0 {
+            stats.files_per_second = stats.files_scanned as f64 / elapsed;
+        }
+        reporter.emit_stats(&stats)?;
+    }
+
+    if let Reporter::File { mut out, count, .. } = reporter {
+        out.flush()?;
+        eprintln!(
+            "Wrote {} results to {}",
+            count,
+            options
+                .output
+                .as_ref()
+                .expect("report has a path")
+                .display()
+        );
+    }
 
     Ok(())
 }
+
+fn scan_file(
+    stores: &Stores,
+    path: &Path,
+    options: &CrawlOptions,
+    reporter: &mut Reporter<'_>,
+    copyright_summary: &mut CopyrightSummary,
+    stats: &mut CLICrawlStats,
+) -> io::Result<()> {
+    let path_lossy = display_path(path, options.relative_to.as_deref());
+
+    let metadata = match options.with_metadata {
+        true => match file_metadata(path) {
+            Ok(metadata) => Some(metadata),
+            Err(err) => return reporter.emit(&FileResult::from_error(&path_lossy, err), true),
+        },
+        false => None,
+    };
+
+    let idres = read_input(path).and_then(|input| {
+        stats.files_scanned += 1;
+        stats.bytes_processed += input.text.len();
+        identify_input(stores, &input, Some(path), &options.scan, false).map(|mut id| {
+            id.copyrights = find_copyrights(&input.plain_text());
+            copyright_summary.add(&id.copyrights);
+            id
+        })
+    });
+    let mut fileres = FileResult::from_identification_result(&path_lossy, &idres);
+    if let Some(metadata) = metadata {
+        fileres = fileres.with_metadata(metadata);
+    }
+    reporter.emit(&fileres, true)
+}
+
+/// Format a path for output, relative to `root` if given.
+///
+/// Relative paths always use `/` as a separator so that output is the same
+/// across platforms. Paths outside of `root` are shown as-is.
+pub fn display_path(path: &Path, root: Option<&Path>) -> String {
+    let root = match root {
+        Some(root) => root,
+        None => return path.to_string_lossy().into_owned(),
+    };
+
+    let relative = path
+        .strip_prefix(root)
+        .ok()
+        .map(Path::to_path_buf)
+        .or_else(|| {
+            // the walker's paths and the root may differ in form (e.g. one is
+            // absolute); compare canonical forms instead
+            let path = path.canonicalize().ok()?;
+            let root = root.canonicalize().ok()?;
+            path.strip_prefix(root).ok().map(Path::to_path_buf)
+        });
+
+    match relative {
+        Some(relative) => relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+        None => path.to_string_lossy().into_owned(),
+    }
+}
+
+/// Destination for crawl results.
+enum Reporter<'a> {
+    /// Print results as they're found, following the usual conventions of
+    /// `FileResult::print_as`.
+    Stdout { format: &'a OutputFormat },
+    /// Write all results to a report file, also showing errors on the
+    /// terminal.
+    File {
+        out: BufWriter<File>,
+        format: &'a OutputFormat,
+        count: usize,
+    },
+}
+
+impl<'a> Reporter<'a> {
+    fn emit(&mut self, result: &FileResult<'_>, show_path: bool) -> io::Result<()> {
+        match self {
+            Reporter::Stdout { format } => {
+                result.print_as(format, show_path);
+                Ok(())
+            }
+            Reporter::File { out, format, count } => {
+                *count += 1;
+                result.write_as(out, format, show_path)?;
+                if let FileResult::Err { .. } = result {
+                    result.write_as(&mut io::stderr(), &OutputFormat::Text, show_path)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn emit_copyright_summary(&mut self, summary: &CopyrightSummary) -> io::Result<()> {
+        match self {
+            Reporter::Stdout { format } => {
+                write_copyright_summary(&mut io::stdout().lock(), format, summary)
+            }
+            Reporter::File { out, format, .. } => write_copyright_summary(out, format, summary),
+        }
+    }
+
+    fn emit_stats(&mut self, stats: &CLICrawlStats) -> io::Result<()> {
+        match self {
+            Reporter::Stdout { format } => {
+                write_crawl_stats(&mut io::stdout().lock(), format, stats)
+            }
+            Reporter::File { out, format, .. } => write_crawl_stats(out, format, stats),
+        }
+    }
+}
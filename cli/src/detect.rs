@@ -0,0 +1,209 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{format_err, Error};
+use ignore::{types::TypesBuilder, WalkBuilder};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::{
+    commands::*, crawl::display_path, formats::*, identify::identify_data, input::read_input,
+    util::*,
+};
+
+/// How far into a file to look for an identifier tag. Tags belong in the
+/// header; looking further would pick up code that merely mentions them.
+const TAG_LINES: usize = 30;
+
+/// Scan a directory for `SPDX-License-Identifier` tags in source files, and
+/// check them against the licenses identified in its license files.
+///
+/// A tagged file disagrees if any license in its expression (other than
+/// exceptions and `LicenseRef-`s) wasn't identified in a license file. The
+/// `-only`/`-or-later` forms of a license are treated as the same, since
+/// their texts are.
+pub fn detect_id(
+    cache_filenames: &[PathBuf],
+    output_format: &OutputFormat,
+    directory: &Path,
+    follow_links: bool,
+) -> Result<(), Error> {
+    let stores = load_stores(cache_filenames)?;
+
+    let mut types_builder = TypesBuilder::new();
+    types_builder.add_defaults();
+    types_builder.select("license");
+    let license_files = types_builder.build().unwrap();
+
+    let scan = ScanOptions::default();
+    let mut report = CLITagReport::default();
+    let mut tagged: Vec<(String, String)> = Vec::new();
+    let walker = WalkBuilder::new(directory)
+        .follow_links(follow_links)
+        .build();
+    for entry in walker {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                eprintln!("Error: {}", error);
+                continue;
+            }
+        };
+        if entry.file_type().is_none_or(|kind| kind.is_dir()) {
+            continue;
+        }
+        let path = entry.path();
+        let path_lossy = display_path(path, Some(directory));
+
+        if license_files.matched(path, false).is_whitelist() {
+            let input = match read_input(path) {
+                Ok(input) => input,
+                Err(error) => {
+                    eprintln!("Error: {}: {}", path_lossy, error);
+                    continue;
+                }
+            };
+            if let Ok(id) = identify_data(&stores, &input.text_data(None), &scan, false) {
+                let names = id
+                    .license
+                    .iter()
+                    .chain(id.containing.iter().map(|c| &c.license))
+                    .map(|license| license.name.clone());
+                for name in names {
+                    report.detected.push(CLIDetectedLicense {
+                        path: path_lossy.clone(),
+                        license: name,
+                    });
+                }
+            }
+        } else if let Some(expression) = find_tag(path) {
+            *report.declared.entry(expression.clone()).or_insert(0) += 1;
+            tagged.push((path_lossy, expression));
+        }
+    }
+
+    let detected: Vec<String> = report
+        .detected
+        .iter()
+        .map(|d| base_id(&d.license))
+        .collect();
+    for (path, expression) in tagged {
+        let missing: Vec<String> = expression_ids(&expression)
+            .into_iter()
+            .filter(|id| {
+                let id = stores
+                    .all()
+                    .find_map(|store| store.resolve_alias(id))
+                    .unwrap_or(id);
+                !detected.contains(&base_id(id))
+            })
+            .map(str::to_owned)
+            .collect();
+        if !missing.is_empty() {
+            report.mismatches.push(CLITagMismatch {
+                path,
+                expression,
+                missing,
+            });
+        }
+    }
+
+    report.print_as(output_format);
+    if report.mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(format_err!(
+            "{} files declare licenses not found in license files",
+            report.mismatches.len()
+        ))
+    }
+}
+
+/// Find the license expression a file declares with an
+/// `SPDX-License-Identifier` tag, if any.
+fn find_tag(path: &Path) -> Option<String> {
+    lazy_static! {
+        static ref TAG: Regex = Regex::new(r"SPDX-License-Identifier:\s*(.+)").unwrap();
+        static ref COMMENT_END: Regex =
+            Regex::new(r"\s*(\*/|-->|\*\)|#\}|%\}|-\}|\}\})?\s*$").unwrap();
+    }
+
+    // stop at the first line that isn't text, as in binary files
+    let lines = BufReader::new(File::open(path).ok()?).lines();
+    for line in lines.take(TAG_LINES).map_while(Result::ok) {
+        if let Some(captures) = TAG.captures(&line) {
+            let expression = COMMENT_END.replace(&captures[1], "");
+            if !expression.is_empty() {
+                return Some(expression.into_owned());
+            }
+        }
+    }
+    None
+}
+
+/// Get the license identifiers in an expression, leaving out operators,
+/// exceptions, and references to licenses defined elsewhere.
+fn expression_ids(expression: &str) -> Vec<&str> {
+    let mut ids = Vec::new();
+    let mut tokens = expression
+        .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+        .filter(|token| !token.is_empty());
+    while let Some(token) = tokens.next() {
+        if token.eq_ignore_ascii_case("with") {
+            tokens.next();
+            continue;
+        }
+        if token.eq_ignore_ascii_case("and")
+            || token.eq_ignore_ascii_case("or")
+            || token.starts_with("LicenseRef-")
+            || token.starts_with("DocumentRef-")
+        {
+            continue;
+        }
+        ids.push(token.trim_end_matches('+'));
+    }
+    ids
+}
+
+/// Reduce a license identifier to the part shared by its `-only` and
+/// `-or-later` forms, for comparison.
+fn base_id(id: &str) -> String {
+    let id = id.trim_end_matches('+');
+    let id = id
+        .strip_suffix("-only")
+        .or_else(|| id.strip_suffix("-or-later"))
+        .unwrap_or(id);
+    id.to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expressions() {
+        assert_eq!(vec!["MIT"], expression_ids("MIT"));
+        assert_eq!(
+            vec!["GPL-2.0", "MIT"],
+            expression_ids("(GPL-2.0+ WITH Linux-syscall-note) OR MIT")
+        );
+        assert_eq!(
+            vec!["Apache-2.0"],
+            expression_ids("Apache-2.0 AND LicenseRef-Proprietary")
+        );
+    }
+
+    #[test]
+    fn base_ids() {
+        assert_eq!("gpl-2.0", base_id("GPL-2.0-or-later"));
+        assert_eq!("gpl-2.0", base_id("GPL-2.0-only"));
+        assert_eq!("gpl-2.0", base_id("GPL-2.0+"));
+        assert_eq!("mit", base_id("MIT"));
+    }
+}
@@ -1,32 +1,54 @@
 // Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::{fmt, fmt::Display};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fmt::Display,
+    io::{self, Write},
+};
 
 use anyhow::Error;
 use serde_derive::Serialize;
 
-use super::commands::*;
-use askalono::LicenseType;
+use super::{commands::*, copyright::*, util::Stores};
+use askalono::{Deviation, DeviationKind, IdentifiedLicense, LicenseType};
 
 #[derive(Serialize, Debug)]
 #[serde(untagged)]
 pub enum FileResult<'a> {
     Ok {
         path: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        metadata: Option<CLIFileMetadata>,
         result: &'a CLIIdentification,
     },
     Err {
         path: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        metadata: Option<CLIFileMetadata>,
         error: String,
     },
 }
 
+#[derive(Serialize, Debug)]
+pub struct CLIFileMetadata {
+    pub size: u64,
+    /// Modification time in seconds since the Unix epoch, if available.
+    pub modified: Option<u64>,
+    pub sha256: String,
+}
+
 #[derive(Serialize, Debug)]
 pub struct CLIIdentification {
     pub score: f32,
     pub license: Option<CLIIdentifiedLicense>,
     pub containing: Vec<CLIContainedResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expression: Option<String>,
+    pub ambiguous: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub copyrights: Vec<CLICopyright>,
 }
 
 #[derive(Serialize, Debug)]
@@ -34,6 +56,51 @@ pub struct CLIIdentifiedLicense {
     pub name: String,
     pub kind: LicenseType,
     pub aliases: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub family: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variant_label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exception: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub store: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub deprecated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub superseded_by: Option<String>,
+}
+
+/// The result of comparing two files with each other.
+#[derive(Serialize, Debug)]
+pub struct CLIComparison {
+    pub score: f32,
+    /// Differences in the second file, relative to the first.
+    pub deviations: Vec<Deviation>,
+}
+
+/// Licenses identified and declared across a directory, by `detect-id`.
+#[derive(Serialize, Debug, Default)]
+pub struct CLITagReport {
+    pub detected: Vec<CLIDetectedLicense>,
+    /// Each distinct expression declared, with the number of files declaring
+    /// it.
+    pub declared: BTreeMap<String, usize>,
+    pub mismatches: Vec<CLITagMismatch>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CLIDetectedLicense {
+    pub path: String,
+    pub license: String,
+}
+
+/// A file whose declared expression includes licenses not identified in any
+/// license file.
+#[derive(Serialize, Debug)]
+pub struct CLITagMismatch {
+    pub path: String,
+    pub expression: String,
+    pub missing: Vec<String>,
 }
 
 #[derive(Serialize, Debug)]
@@ -41,6 +108,22 @@ pub struct CLIContainedResult {
     pub score: f32,
     pub license: CLIIdentifiedLicense,
     pub line_range: (usize, usize),
+    /// Left out where it wouldn't be an offset into the file, as for text
+    /// decoded from UTF-16.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub byte_range: Option<(usize, usize)>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub char_range: Option<(usize, usize)>,
+    pub ambiguous: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context: Option<CLIContext>,
+}
+
+/// Lines of the original text surrounding a region, for `--context`.
+#[derive(Serialize, Debug)]
+pub struct CLIContext {
+    pub before: Vec<String>,
+    pub after: Vec<String>,
 }
 
 impl<'a> FileResult<'a> {
@@ -49,9 +132,14 @@ impl<'a> FileResult<'a> {
         result: &'a Result<CLIIdentification, Error>,
     ) -> FileResult<'a> {
         match result {
-            Ok(id) => FileResult::Ok { path, result: id },
+            Ok(id) => FileResult::Ok {
+                path,
+                metadata: None,
+                result: id,
+            },
             Err(e) => FileResult::Err {
                 path,
+                metadata: None,
                 error: format!("{}", e),
             },
         }
@@ -60,10 +148,23 @@ impl<'a> FileResult<'a> {
     pub fn from_error(path: &'a str, error: impl Display) -> FileResult<'a> {
         FileResult::Err {
             path,
+            metadata: None,
             error: format!("{}", error),
         }
     }
 
+    pub fn with_metadata(mut self, file_metadata: CLIFileMetadata) -> FileResult<'a> {
+        match self {
+            FileResult::Ok {
+                ref mut metadata, ..
+            }
+            | FileResult::Err {
+                ref mut metadata, ..
+            } => *metadata = Some(file_metadata),
+        }
+        self
+    }
+
     pub fn print_as(&self, output_format: &OutputFormat, show_path: bool) {
         match output_format {
             // with the default text format, follow the unixy conventions of
@@ -78,27 +179,264 @@ impl<'a> FileResult<'a> {
         }
     }
 
+    /// Write this result to a report. Unlike `print_as`, errors are written
+    /// to the same place as successes.
+    pub fn write_as(
+        &self,
+        out: &mut dyn Write,
+        output_format: &OutputFormat,
+        show_path: bool,
+    ) -> io::Result<()> {
+        self.write_terminated(out, output_format, show_path, '\n')
+    }
+
+    /// Write this result to a report, ending it with `terminator` instead of
+    /// a newline.
+    pub fn write_terminated(
+        &self,
+        out: &mut dyn Write,
+        output_format: &OutputFormat,
+        show_path: bool,
+        terminator: char,
+    ) -> io::Result<()> {
+        match output_format {
+            OutputFormat::Text => write!(out, "{}{}", self.as_text(show_path), terminator),
+            OutputFormat::JSON => write!(out, "{}{}", self.as_json(), terminator),
+        }
+    }
+
     fn as_text(&self, show_path: bool) -> String {
-        match self {
-            FileResult::Ok { path, result } => {
-                if show_path {
-                    format!("{}\n{}", path, result)
-                } else {
-                    format!("{}", result)
-                }
-            }
-            FileResult::Err { path, error } => {
-                if show_path {
-                    format!("{}\nError: {}", path, error)
+        let (path, metadata, body) = match self {
+            FileResult::Ok {
+                path,
+                metadata,
+                result,
+            } => (path, metadata, format!("{}", result)),
+            FileResult::Err {
+                path,
+                metadata,
+                error,
+            } => (path, metadata, format!("Error: {}", error)),
+        };
+
+        let mut out = String::new();
+        if show_path {
+            out.push_str(path);
+            out.push('\n');
+        }
+        if let Some(metadata) = metadata {
+            out.push_str(&format!("{}\n", metadata));
+        }
+        out.push_str(&body);
+        out
+    }
+
+    fn as_json(&self) -> String {
+        serde_json::to_string(self).expect("must produce valid json output")
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct CLICopyrightSummary<'a> {
+    copyright_holders: Vec<CLICopyrightHolder<'a>>,
+}
+
+pub fn write_copyright_summary(
+    out: &mut dyn Write,
+    output_format: &OutputFormat,
+    summary: &CopyrightSummary,
+) -> io::Result<()> {
+    let holders = summary.holders();
+    match output_format {
+        OutputFormat::Text => {
+            writeln!(out, "\nCopyright holders:")?;
+            for h in &holders {
+                if h.years.is_empty() {
+                    writeln!(out, "  {}", h.holder)?;
                 } else {
-                    format!("Error: {}", error)
+                    writeln!(out, "  {} ({})", h.holder, h.years.join(", "))?;
                 }
             }
+            Ok(())
         }
+        OutputFormat::JSON => writeln!(
+            out,
+            "{}",
+            serde_json::to_string(&CLICopyrightSummary {
+                copyright_holders: holders
+            })
+            .expect("must produce valid json output")
+        ),
     }
+}
 
-    fn as_json(&self) -> String {
-        serde_json::to_string(self).expect("must produce valid json output")
+/// Throughput of a crawl, for comparing configurations.
+#[derive(Serialize, Debug, Default)]
+pub struct CLICrawlStats {
+    /// Files seen, whether or not they were scanned.
+    pub files_walked: usize,
+    /// Files read and identified.
+    pub files_scanned: usize,
+    /// Bytes of text identified.
+    pub bytes_processed: usize,
+    /// Time from the start of the crawl to the end.
+    pub wall_time_ms: f64,
+    /// Files scanned per second of wall time.
+    pub files_per_second: f64,
+}
+
+#[derive(Serialize, Debug)]
+struct CLICrawlStatsRecord<'a> {
+    crawl_stats: &'a CLICrawlStats,
+}
+
+pub fn write_crawl_stats(
+    out: &mut dyn Write,
+    output_format: &OutputFormat,
+    stats: &CLICrawlStats,
+) -> io::Result<()> {
+    match output_format {
+        OutputFormat::Text => {
+            writeln!(out, "\nCrawl statistics:")?;
+            writeln!(out, "  Files walked: {}", stats.files_walked)?;
+            writeln!(out, "  Files scanned: {}", stats.files_scanned)?;
+            writeln!(out, "  Bytes processed: {}", stats.bytes_processed)?;
+            writeln!(out, "  Wall time: {:.1} ms", stats.wall_time_ms)?;
+            writeln!(out, "  Files/sec: {:.1}", stats.files_per_second)
+        }
+        OutputFormat::JSON => writeln!(
+            out,
+            "{}",
+            serde_json::to_string(&CLICrawlStatsRecord { crawl_stats: stats })
+                .expect("must produce valid json output")
+        ),
+    }
+}
+
+impl fmt::Display for CLIComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Score: {:.3}", self.score)?;
+        if self.deviations.is_empty() {
+            return write!(f, "\nNo differences after normalization");
+        }
+        write!(f, "\nDifferences:")?;
+        for deviation in &self.deviations {
+            let sign = match deviation.kind {
+                DeviationKind::Insertion => '+',
+                DeviationKind::Deletion => '-',
+            };
+            write!(
+                f,
+                "\n  {} {} (word {})",
+                sign, deviation.text, deviation.position
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl CLITagReport {
+    pub fn print_as(&self, output_format: &OutputFormat) {
+        match output_format {
+            OutputFormat::Text => println!("{}", self),
+            OutputFormat::JSON => println!(
+                "{}",
+                serde_json::to_string(self).expect("must produce valid json output")
+            ),
+        }
+    }
+}
+
+impl fmt::Display for CLITagReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Detected licenses:")?;
+        if self.detected.is_empty() {
+            write!(f, "\n  (none)")?;
+        }
+        for d in &self.detected {
+            write!(f, "\n  {}: {}", d.path, d.license)?;
+        }
+
+        write!(f, "\nDeclared identifiers:")?;
+        if self.declared.is_empty() {
+            write!(f, "\n  (none)")?;
+        }
+        for (expression, files) in &self.declared {
+            let plural = if *files == 1 { "" } else { "s" };
+            write!(f, "\n  {}: {} file{}", expression, files, plural)?;
+        }
+
+        if self.mismatches.is_empty() {
+            return Ok(());
+        }
+        write!(f, "\nMismatched files:")?;
+        for m in &self.mismatches {
+            write!(
+                f,
+                "\n  {}: {} (no license file for {})",
+                m.path,
+                m.expression,
+                m.missing.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for CLIFileMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Size: {} bytes", self.size)?;
+        if let Some(modified) = self.modified {
+            write!(f, "\nModified: {}", modified)?;
+        }
+        write!(f, "\nSHA-256: {}", self.sha256)
+    }
+}
+
+impl CLIIdentifiedLicense {
+    pub fn new(stores: &Stores, license: &IdentifiedLicense<'_>) -> CLIIdentifiedLicense {
+        let store = stores.get(license.store);
+        CLIIdentifiedLicense {
+            aliases: store.aliases(license.name).unwrap().clone(),
+            name: license.name.to_owned(),
+            kind: license.kind,
+            family: license.family.map(str::to_owned),
+            variant_label: license.variant_label.map(str::to_owned),
+            exception: license.exception.map(str::to_owned),
+            store: license.store.map(str::to_owned),
+            deprecated: store.is_deprecated(license.name),
+            superseded_by: store.superseded_by(license.name).map(str::to_owned),
+        }
+    }
+
+    /// A note that the license is deprecated, if it is.
+    fn deprecation_note(&self) -> Option<String> {
+        if !self.deprecated {
+            return None;
+        }
+        Some(match self.superseded_by {
+            Some(ref successor) => format!("Deprecated; superseded by {}", successor),
+            None => "Deprecated".to_owned(),
+        })
+    }
+
+    /// The license's name, with its exception if it has one.
+    fn full_name(&self) -> String {
+        match self.exception {
+            Some(ref exception) => format!("{} WITH {}", self.name, exception),
+            None => self.name.clone(),
+        }
+    }
+
+    fn kind_description(&self) -> String {
+        let kind = match self.variant_label {
+            Some(ref label) => format!("{}: {}", self.kind, label),
+            None => format!("{}", self.kind),
+        };
+        match self.store {
+            Some(ref store) => format!("{}, from {}", kind, store),
+            None => kind,
+        }
     }
 }
 
@@ -108,11 +446,30 @@ impl fmt::Display for CLIIdentification {
             write!(
                 f,
                 "License: {} ({})\nScore: {:.3}",
-                license.name, license.kind, self.score
+                license.full_name(),
+                license.kind_description(),
+                self.score
             )?;
         } else {
             write!(f, "License: Unknown\nScore: {:.3}", self.score)?;
         }
+        if self.ambiguous {
+            write!(f, " (ambiguous)")?;
+        }
+        if let Some(note) = self.license.as_ref().and_then(|l| l.deprecation_note()) {
+            write!(f, "\n{}", note)?;
+        }
+
+        for c in &self.copyrights {
+            match c.years {
+                Some(ref years) => write!(f, "\nCopyright: {} {}", years, c.holder)?,
+                None => write!(f, "\nCopyright: {}", c.holder)?,
+            }
+        }
+
+        if let Some(ref expression) = self.expression {
+            write!(f, "\nExpression: {}", expression)?;
+        }
 
         if self.containing.is_empty() {
             return Ok(());
@@ -123,11 +480,35 @@ impl fmt::Display for CLIIdentification {
             write!(
                 f,
                 "\n  License: {} ({})\n  Score: {:.3}\n  Lines: {} - {}",
-                res.license.name, res.license.kind, res.score, res.line_range.0, res.line_range.1
+                res.license.full_name(),
+                res.license.kind_description(),
+                res.score,
+                res.line_range.0,
+                res.line_range.1
             )?;
+            if res.ambiguous {
+                write!(f, "\n  Ambiguous: another license scored nearly as well")?;
+            }
             if !res.license.aliases.is_empty() {
                 write!(f, "\n  Aliases: {}", res.license.aliases.join(", "))?;
             }
+            if let Some(note) = res.license.deprecation_note() {
+                write!(f, "\n  {}", note)?;
+            }
+            if let Some(ref context) = res.context {
+                for (label, lines) in [("Before", &context.before), ("After", &context.after)] {
+                    if lines.is_empty() {
+                        continue;
+                    }
+                    write!(f, "\n  {}:", label)?;
+                    for line in lines {
+                        write!(f, "\n    |")?;
+                        if !line.is_empty() {
+                            write!(f, " {}", line)?;
+                        }
+                    }
+                }
+            }
         }
 
         Ok(())
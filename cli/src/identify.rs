@@ -2,106 +2,224 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::{
-    fs::read_to_string,
-    io::{prelude::*, stdin},
+    io::{self, prelude::*, stdin, stdout},
     path::{Path, PathBuf},
     time::Instant,
 };
 
 use anyhow::{format_err, Error};
 use log::info;
+use serde_derive::Deserialize;
 
-use super::{commands::*, formats::*, util::*};
-use askalono::{ScanMode, ScanStrategy, Store, TextData};
+use super::{
+    commands::*,
+    formats::*,
+    input::{decode_input, prepare_text, read_input, Input},
+    util::*,
+};
+use askalono::{looks_binary, AnalysisOptions, CommentStyle, ScanMode, TextData};
 
 const MIN_SCORE: f32 = 0.8;
 
+/// How filenames are read and results are framed in batch mode.
+pub struct BatchOptions {
+    pub input: BatchInput,
+    /// Results (including errors) are written to stdout, each terminated by
+    /// NUL rather than a newline.
+    pub nul_output: bool,
+}
+
+/// What batch mode reads from stdin.
+pub enum BatchInput {
+    /// Newline-separated filenames.
+    Lines,
+    /// NUL-separated filenames.
+    Nul,
+    /// Newline-delimited JSON records carrying text to identify inline.
+    Records,
+}
+
+/// A single text to identify in `BatchInput::Records` mode. The ID is
+/// reported in place of a path.
+#[derive(Deserialize)]
+struct BatchRecord {
+    id: String,
+    text: String,
+}
+
 pub fn identify(
-    cache_filename: &Path,
+    cache_filenames: &[PathBuf],
     output_format: &OutputFormat,
     filename: Option<PathBuf>,
-    optimize: bool,
+    scan: &ScanOptions,
     want_diff: bool,
-    batch: bool,
-    topdown: bool,
+    context: Option<usize>,
+    batch: Option<BatchOptions>,
 ) -> Result<(), Error> {
     // load the cache from disk or embedded data
     let cache_inst = Instant::now();
-    let store = load_store(cache_filename)?;
+    let stores = load_stores(cache_filenames)?;
     info!(
         "Cache loaded in {} ms",
         cache_inst.elapsed().subsec_nanos() as f32 / 1_000_000.0
     );
+    let identify_content = |input: &Input, path: Option<&Path>| {
+        identify_input(&stores, input, path, scan, want_diff).map(|mut id| {
+            if let Some(lines) = context {
+                add_context(&mut id, &input.text, lines);
+            }
+            id
+        })
+    };
 
     // normal identification
-    if !batch {
-        let filename = filename.expect("no filename provided");
-        let stdin_indicator: PathBuf = "-".into();
-        let content = if filename == stdin_indicator {
-            let mut buf = String::new();
-            stdin().read_to_string(&mut buf)?;
-            buf
-        } else {
-            read_to_string(&filename)?
-        };
+    let batch = match batch {
+        Some(batch) => batch,
+        None => {
+            let filename = filename.expect("no filename provided");
+            let stdin_indicator: PathBuf = "-".into();
+            let (content, path) = if filename == stdin_indicator {
+                let mut buf = Vec::new();
+                stdin().read_to_end(&mut buf)?;
+                if looks_binary(&buf) {
+                    return Err(format_err!("stdin looks like binary data"));
+                }
+                (decode_input(&buf), None)
+            } else {
+                (read_input(&filename)?, Some(filename.as_path()))
+            };
 
-        let idres = identify_data(&store, &content.into(), optimize, want_diff, topdown);
-        let file_lossy = filename.to_string_lossy();
-        let fileres = FileResult::from_identification_result(&file_lossy, &idres);
-        fileres.print_as(output_format, false);
+            let idres = identify_content(&content, path);
+            let file_lossy = filename.to_string_lossy();
+            let fileres = FileResult::from_identification_result(&file_lossy, &idres);
+            fileres.print_as(output_format, false);
 
-        return idres.map(|_| ());
-    }
+            return idres.map(|_| ());
+        }
+    };
 
-    // batch mode: read stdin line by line until eof.
+    // batch mode: read stdin one filename or record at a time until eof.
     // don't bubble up errors; just print to stderr
+    let delimiter = match batch.input {
+        BatchInput::Nul => b'\0',
+        BatchInput::Lines | BatchInput::Records => b'\n',
+    };
+    let stdin = stdin();
+    let mut input = stdin.lock();
+    let emit = |fileres: FileResult<'_>| -> io::Result<()> {
+        if batch.nul_output {
+            let mut out = stdout().lock();
+            fileres.write_terminated(&mut out, output_format, false, '\0')?;
+            out.flush()
+        } else {
+            fileres.print_as(output_format, false);
+            Ok(())
+        }
+    };
+
     loop {
-        let mut buf = String::new();
-        stdin().read_line(&mut buf)?;
+        let mut buf = Vec::new();
+        input.read_until(delimiter, &mut buf)?;
         if buf.is_empty() {
             break;
         }
+        if buf.last() == Some(&delimiter) {
+            buf.pop();
+        }
 
-        let filename: PathBuf = buf.trim().into();
-        let content = match read_to_string(filename) {
-            Ok(c) => c,
-            Err(e) => {
-                let fileres = FileResult::Err {
-                    path: &buf,
-                    error: format!("Input error: {}", e),
+        let (name, content) = match batch.input {
+            BatchInput::Records => {
+                if buf.iter().all(u8::is_ascii_whitespace) {
+                    continue;
+                }
+                match serde_json::from_slice::<BatchRecord>(&buf) {
+                    Ok(record) => (record.id, prepare_text(record.text)),
+                    Err(e) => {
+                        emit(FileResult::from_error("", format!("Invalid record: {}", e)))?;
+                        continue;
+                    }
+                }
+            }
+            BatchInput::Lines | BatchInput::Nul => {
+                // newline-separated names are trimmed for convenience, but
+                // with NUL separators every other byte is part of the name
+                let name = String::from_utf8_lossy(&buf);
+                let name = match batch.input {
+                    BatchInput::Nul => name.into_owned(),
+                    _ => name.trim().to_owned(),
                 };
-                fileres.print_as(output_format, false);
-                continue;
+                if name.is_empty() {
+                    continue;
+                }
+
+                match read_input(Path::new(&name)) {
+                    Ok(content) => (name, content),
+                    Err(e) => {
+                        emit(FileResult::from_error(&name, format!("Input error: {}", e)))?;
+                        continue;
+                    }
+                }
             }
         };
 
-        let idres = identify_data(&store, &content.into(), optimize, want_diff, topdown);
-        let fileres = FileResult::from_identification_result(&buf, &idres);
-        fileres.print_as(output_format, false);
+        let path = match batch.input {
+            BatchInput::Records => None,
+            BatchInput::Lines | BatchInput::Nul => Some(Path::new(&name)),
+        };
+        let idres = identify_content(&content, path);
+        emit(FileResult::from_identification_result(&name, &idres))?;
     }
 
     Ok(())
 }
 
+/// Identify the content of a file. With `--comments`, only the comments are
+/// scanned, if the file's language can be told from its name.
+///
+/// Byte offsets are left out where they wouldn't be offsets into the file.
+pub fn identify_input(
+    stores: &Stores,
+    input: &Input,
+    path: Option<&Path>,
+    scan: &ScanOptions,
+    want_diff: bool,
+) -> Result<CLIIdentification, Error> {
+    let style = path
+        .filter(|_| scan.comments)
+        .and_then(CommentStyle::from_path);
+    let mut id = identify_data(stores, &input.text_data(style), scan, want_diff)?;
+    if !input.exact {
+        for contained in &mut id.containing {
+            contained.byte_range = None;
+        }
+    }
+    Ok(id)
+}
+
 pub fn identify_data(
-    store: &Store,
+    stores: &Stores,
     text_data: &TextData,
-    optimize: bool,
+    scan: &ScanOptions,
     want_diff: bool,
-    topdown: bool,
 ) -> Result<CLIIdentification, Error> {
     let inst = Instant::now();
-    let scan_mode = if topdown {
+    let scan_mode = if scan.topdown {
         ScanMode::TopDown
     } else {
         ScanMode::Elimination
     };
 
-    let strategy = ScanStrategy::new(store)
+    let mut strategy = stores
+        .strategy()
         .mode(scan_mode)
         .confidence_threshold(MIN_SCORE)
-        .optimize(optimize)
-        .max_passes(1);
+        .optimize(scan.optimize)
+        .step_size(scan.step_size as usize)
+        .max_passes(scan.max_passes);
+    strategy.config_mut().analysis(AnalysisOptions {
+        exact: scan.exact,
+        ..Default::default()
+    });
     let result = strategy.scan(text_data)?;
 
     info!(
@@ -119,23 +237,23 @@ pub fn identify_data(
             .iter()
             .map(|cr| CLIContainedResult {
                 score: cr.score,
-                license: CLIIdentifiedLicense {
-                    aliases: store.aliases(cr.license.name).unwrap().clone(),
-                    name: cr.license.name.to_owned(),
-                    kind: cr.license.kind,
-                },
-                line_range: cr.line_range,
+                license: CLIIdentifiedLicense::new(stores, &cr.license),
+                // stripping markup may have moved lines
+                line_range: text_data.to_original_lines(cr.line_range),
+                byte_range: cr.byte_range,
+                char_range: cr.char_range,
+                ambiguous: cr.ambiguous,
+                context: None,
             })
             .collect(),
+        expression: None,
+        ambiguous: result.ambiguous,
+        copyrights: Vec::new(),
     };
 
     // include the overall license if present
     if let Some(license) = result.license {
-        output.license = Some(CLIIdentifiedLicense {
-            aliases: store.aliases(license.name).unwrap().clone(),
-            name: license.name.to_owned(),
-            kind: license.kind,
-        });
+        output.license = Some(CLIIdentifiedLicense::new(stores, &license));
 
         if want_diff {
             diff_result(text_data, license.data);
@@ -146,6 +264,7 @@ pub fn identify_data(
 
     // not a good enough match overall, but maybe inside
     if !output.containing.is_empty() {
+        output.expression = result.expression;
         if want_diff {
             diff_result(text_data, result.containing[0].license.data);
         }
@@ -156,3 +275,29 @@ pub fn identify_data(
         "Confidence threshold not high enough for any known license",
     ))
 }
+
+/// Attach up to `lines` lines of the original text from above and below
+/// each region found in it.
+fn add_context(id: &mut CLIIdentification, content: &str, lines: usize) {
+    // line ranges are of the text as it was read
+    let text: Vec<&str> = content
+        .split('\n')
+        .map(|line| line.trim_end_matches('\r'))
+        .collect();
+    let slice = |start: usize, end: usize| -> Vec<String> {
+        let end = end.min(text.len());
+        let start = start.min(end);
+        text[start..end]
+            .iter()
+            .map(|&line| line.to_owned())
+            .collect()
+    };
+
+    for contained in &mut id.containing {
+        let (start, end) = contained.line_range;
+        contained.context = Some(CLIContext {
+            before: slice(start.saturating_sub(lines), start),
+            after: slice(end, end.saturating_add(lines)),
+        });
+    }
+}
@@ -0,0 +1,199 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{borrow::Cow, fs, io, path::Path, time::UNIX_EPOCH};
+
+use anyhow::{format_err, Error};
+use askalono::{decode_text, looks_binary, CommentStyle, Markup, TextData, TextDataOptions};
+use lazy_static::lazy_static;
+use regex::Regex;
+use sha2::{Digest, Sha256};
+
+use super::formats::CLIFileMetadata;
+
+/// The text of a file or stdin to be scanned.
+pub struct Input {
+    /// The text, decoded but otherwise as it was read.
+    pub text: String,
+    /// Markup to strip from the text before it's scanned.
+    pub markup: Option<Markup>,
+    /// Whether the text is byte for byte what was read, so that byte offsets
+    /// into it are offsets into the file. Text that was decoded from another
+    /// encoding or extracted from a PDF isn't.
+    pub exact: bool,
+}
+
+impl Input {
+    /// Preprocess the text for scanning, keeping only its comments if a
+    /// comment style is given. Markup is stripped as part of preprocessing,
+    /// so line numbers in results are those of the text as read.
+    pub fn text_data(&self, comments: Option<CommentStyle>) -> TextData {
+        let options = TextDataOptions::new()
+            .strip_markup(self.markup)
+            .extract_comments(comments);
+        TextData::new_with_options(&self.text, options)
+    }
+
+    /// Get the text with its markup stripped.
+    pub fn plain_text(&self) -> Cow<'_, str> {
+        match self.markup {
+            Some(markup) => markup.strip(&self.text).into(),
+            None => self.text.as_str().into(),
+        }
+    }
+}
+
+/// Read a file to be scanned, extracting its text first if it isn't plain
+/// text, and noting any markup (based on its extension or content).
+pub fn read_input(path: &Path) -> Result<Input, Error> {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase());
+
+    let markup = match extension.as_deref() {
+        Some("pdf") => {
+            return Ok(Input {
+                text: read_pdf(path)?,
+                markup: None,
+                exact: false,
+            })
+        }
+        Some("htm") | Some("html") | Some("xhtml") => Markup::Html,
+        Some("md") | Some("markdown") => Markup::Markdown,
+        _ => return read_text(path),
+    };
+    Ok(Input {
+        markup: Some(markup),
+        ..read_text(path)?
+    })
+}
+
+/// Read a text file of any common encoding, such as UTF-16 or Latin-1.
+/// Binary files are refused rather than decoded into noise.
+fn read_text(path: &Path) -> Result<Input, Error> {
+    let bytes = fs::read(path)?;
+    if looks_binary(&bytes) {
+        return Err(format_err!("{} looks like a binary file", path.display()));
+    }
+    Ok(decode_input(&bytes))
+}
+
+/// Decode text read from a file or stdin for scanning, as with
+/// `prepare_text`.
+pub fn decode_input(bytes: &[u8]) -> Input {
+    let text = decode_text(bytes);
+    let exact = text.as_bytes() == bytes;
+    Input {
+        exact,
+        ..prepare_text(text)
+    }
+}
+
+/// Prepare text for scanning, noting HTML markup if it looks like an HTML
+/// document.
+pub fn prepare_text(content: String) -> Input {
+    let markup = looks_like_html(&content).then_some(Markup::Html);
+    Input {
+        text: content,
+        markup,
+        exact: true,
+    }
+}
+
+fn looks_like_html(content: &str) -> bool {
+    let start: String = content
+        .trim_start_matches('\u{feff}')
+        .trim_start()
+        .chars()
+        .take(14)
+        .collect::<String>()
+        .to_lowercase();
+    start.starts_with("<!doctype html") || start.starts_with("<html")
+}
+
+/// Gather a file's size, modification time, and a hash of its exact
+/// contents.
+pub fn file_metadata(path: &Path) -> io::Result<CLIFileMetadata> {
+    let stat = fs::metadata(path)?;
+    let modified = stat
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|since| since.as_secs());
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+
+    Ok(CLIFileMetadata {
+        size: stat.len(),
+        modified,
+        sha256: format!("{:x}", hasher.finalize()),
+    })
+}
+
+#[cfg(feature = "pdf")]
+fn read_pdf(path: &Path) -> Result<String, Error> {
+    let text = pdf_extract::extract_text(path)
+        .map_err(|e| format_err!("Couldn't extract text from PDF: {}", e))?;
+    Ok(clean_pdf_text(&text))
+}
+
+#[cfg(not(feature = "pdf"))]
+fn read_pdf(path: &Path) -> Result<String, Error> {
+    Err(format_err!(
+        "{} is a PDF, but askalono wasn't compiled with PDF support (the `pdf` feature)",
+        path.display()
+    ))
+}
+
+/// Undo the most common artifacts of text extracted from PDFs: words
+/// hyphenated across line breaks, list bullets, and page breaks.
+///
+/// A hyphen at a line break may be part of the word ("non-commercial"), so
+/// it's kept and only the break is removed. Matching ignores punctuation,
+/// so "per-son" still matches "person".
+#[cfg_attr(not(feature = "pdf"), allow(dead_code))]
+fn clean_pdf_text(text: &str) -> String {
+    lazy_static! {
+        static ref HYPHENATED: Regex = Regex::new(r"(\w)-[ \t]*\r?\n[ \t]*(\w)").unwrap();
+        static ref BULLET: Regex = Regex::new(r"(?m)^[ \t]*[•◦▪▫‣∙●○■□·][ \t]*").unwrap();
+    }
+
+    let text = text.replace('\x0c', "\n");
+    let text = HYPHENATED.replace_all(&text, "$1-$2");
+    BULLET.replace_all(&text, "").into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn html_sniffing() {
+        assert!(looks_like_html("\n  <!DOCTYPE html>\n<html>"));
+        assert!(looks_like_html("<HTML lang=\"en\">"));
+        assert!(!looks_like_html("MIT License\n\n<html> in the middle"));
+        let input = prepare_text("<html>MIT &amp; stuff".into());
+        assert_eq!(Some(Markup::Html), input.markup);
+        assert_eq!("MIT & stuff", input.plain_text());
+    }
+
+    #[test]
+    fn exact_text() {
+        assert!(decode_input(b"MIT License").exact);
+        assert!(!decode_input(b"\xef\xbb\xbfMIT License").exact);
+        assert!(!decode_input(b"MIT \xa9").exact);
+    }
+
+    #[test]
+    fn pdf_cleanup() {
+        let text = "Permission is hereby granted, free of charge, to any per-\nson obtaining a copy\n\x0c • the above copyright notice\n\t▪ shall be included";
+        assert_eq!(
+            "Permission is hereby granted, free of charge, to any per-son obtaining a copy\n\nthe above copyright notice\nshall be included",
+            clean_pdf_text(text)
+        );
+        assert_eq!(
+            "for non-commercial use",
+            clean_pdf_text("for non-\ncommercial use")
+        );
+    }
+}
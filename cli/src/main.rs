@@ -3,59 +3,114 @@
 
 #![allow(clippy::match_bool)]
 
+mod aliases;
 mod cache;
 mod commands;
+mod compare;
+mod copyright;
 mod crawl;
+mod detect;
 mod formats;
 mod identify;
+mod input;
+mod show;
 mod util;
 
 use std::{path::PathBuf, process::exit};
 
+use anyhow::format_err;
 use clap::Parser;
 
 use self::commands::*;
 
+#[allow(clippy::unnecessary_unwrap)]
 fn main() {
     let options = Opt::parse();
 
     env_logger::init();
-    rayon::ThreadPoolBuilder::new().build_global().unwrap();
 
-    let cache_file: PathBuf = options
-        .cache
-        .unwrap_or_else(|| "./askalono-cache.bin.zstd".into());
+    // with no caches given, the default one is used; see `load_stores`
+    let cache_files: Vec<PathBuf> = options.cache;
 
     let output_format = options.format.unwrap_or(OutputFormat::Text);
 
     let res = match options.subcommand {
         Subcommand::Identify {
             filename,
-            optimize,
+            scan,
             diff,
+            context,
             batch,
-            topdown,
+            batch0,
+            batch_json,
+            print0,
         } => identify::identify(
-            &cache_file,
+            &cache_files,
             &output_format,
             filename,
-            optimize,
+            &scan,
             diff,
-            batch,
-            topdown,
+            context,
+            match (batch, batch0, batch_json) {
+                (_, true, _) => Some(identify::BatchInput::Nul),
+                (_, _, true) => Some(identify::BatchInput::Records),
+                (true, _, _) => Some(identify::BatchInput::Lines),
+                _ => None,
+            }
+            .map(|input| identify::BatchOptions {
+                input,
+                nul_output: print0,
+            }),
         ),
         Subcommand::Crawl {
             directory,
+            scan,
             follow_links,
             glob,
+            output,
+            output_format: report_format,
+            relative_to,
+            with_metadata,
+            files_from,
+            filter,
+            stats,
         } => crawl::crawl(
-            &cache_file,
+            &cache_files,
             &output_format,
-            &directory,
-            follow_links,
-            glob.as_deref(),
+            directory.as_deref(),
+            &crawl::CrawlOptions {
+                scan,
+                follow_links,
+                glob,
+                output,
+                report_format,
+                relative_to: relative_to.map(|root| {
+                    root.or_else(|| directory.clone())
+                        .unwrap_or_else(|| ".".into())
+                }),
+                with_metadata,
+                files_from,
+                filter,
+                stats,
+            },
         ),
-        Subcommand::Cache { subcommand } => cache::cache(&cache_file, subcommand),
+        Subcommand::DetectId {
+            directory,
+            follow_links,
+        } => detect::detect_id(&cache_files, &output_format, &directory, follow_links),
+        Subcommand::Compare { file_a, file_b } => {
+            compare::compare(&output_format, &file_a, &file_b)
+        }
+        Subcommand::Show { name, header } => show::show(&cache_files, &name, header),
+        Subcommand::Aliases { name } => aliases::aliases(&cache_files, &output_format, &name),
+        Subcommand::Resolve { name } => aliases::resolve(&cache_files, &output_format, &name),
+        Subcommand::Cache { subcommand } => match cache_files.as_slice() {
+            [] => cache::cache(&cache::default_cache_path(), &output_format, subcommand),
+            [cache_file] => cache::cache(cache_file, &output_format, subcommand),
+            _ => Err(format_err!(
+                "Cache commands work on a single cache; give --cache only once"
+            )),
+        },
     };
     if res.is_err() {
         eprintln!("Processing error: {}", res.unwrap_err());
@@ -0,0 +1,43 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::path::PathBuf;
+
+use anyhow::{format_err, Error};
+
+use super::util::*;
+use askalono::{HeaderOptions, Store};
+
+/// Print the text of a license from the cache: its verbatim text if stored,
+/// or its normalized text otherwise. With `header`, print its standard
+/// header instead.
+pub fn show(cache_filenames: &[PathBuf], name: &str, header: bool) -> Result<(), Error> {
+    let stores = load_stores(cache_filenames)?;
+    let store = stores
+        .all()
+        .find(|store| store.get_original(name).is_some())
+        .ok_or_else(|| format_err!("License {} is not in the cache", name))?;
+
+    let text = match header {
+        true => store
+            .suggest_header(name, &HeaderOptions::default())
+            .ok_or_else(|| format_err!("License {} has no standard header", name))?,
+        false => license_text(store, name).ok_or_else(|| {
+            format_err!(
+                "The cache doesn't include the text of {}; rebuild it with `cache load-spdx --store`",
+                name
+            )
+        })?,
+    };
+    println!("{}", text.trim_end());
+    Ok(())
+}
+
+fn license_text(store: &Store, name: &str) -> Option<String> {
+    if let Some(text) = store.original_text(name) {
+        return Some(text.to_owned());
+    }
+    let original = store.get_original(name)?;
+    original.text_processed()?;
+    Some(original.lines().join("\n"))
+}
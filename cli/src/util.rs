@@ -1,42 +1,134 @@
 // Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::path::Path;
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
 
-use anyhow::Error;
+use anyhow::{format_err, Error};
 
-use askalono::{Store, TextData};
+use askalono::{ScanStrategy, Store, TextData};
+
+use super::cache::default_cache_path;
 
 #[cfg(feature = "embedded-cache")]
-static CACHE_DATA: &[u8] = include_bytes!(env!("ASKALONO_EMBEDDED_CACHE"));
+pub static CACHE_DATA: &[u8] = include_bytes!(env!("ASKALONO_EMBEDDED_CACHE"));
 
-#[allow(unused_variables)]
-pub fn load_store(cache_filename: &Path) -> Result<Store, Error> {
-    #[cfg(feature = "embedded-cache")]
-    let store = Store::from_cache(CACHE_DATA)?;
+/// The embedded cache as static data, generated by the build script.
+#[cfg(feature = "static-store")]
+static STORE_DATA: askalono::StaticStore = include!(concat!(env!("OUT_DIR"), "/askalono-store.rs"));
+
+/// The stores the CLI scans with: a primary store, along with any other
+/// stores given by additional `--cache` options.
+pub struct Stores {
+    /// The store from the first cache file, or embedded data.
+    pub primary: Store,
+    /// Additional stores, named after their cache file (without extension).
+    pub extra: Vec<(String, Store)>,
+}
+
+impl Stores {
+    /// Create a scan strategy consulting every store.
+    pub fn strategy(&self) -> ScanStrategy<'_> {
+        self.extra.iter().fold(
+            ScanStrategy::new(&self.primary),
+            |strategy, (name, store)| strategy.add_store(name, store),
+        )
+    }
+
+    /// Iterate over every store, the primary store first.
+    pub fn all(&self) -> impl Iterator<Item = &Store> {
+        std::iter::once(&self.primary).chain(self.extra.iter().map(|(_, store)| store))
+    }
+
+    /// Get a store by the name a license was tagged with.
+    pub fn get(&self, name: Option<&str>) -> &Store {
+        name.and_then(|name| self.extra.iter().find(|(n, _)| n == name))
+            .map_or(&self.primary, |(_, store)| store)
+    }
+}
 
-    #[cfg(not(feature = "embedded-cache"))]
-    let store = {
-        use std::fs::File;
-        Store::from_cache(File::open(cache_filename)?)?
+/// Load the primary store from the first cache file, and any others from the
+/// rest. With no cache files, the primary store is the default cache file
+/// (see `default_cache_path`), or the embedded cache if there's none.
+pub fn load_stores(cache_filenames: &[PathBuf]) -> Result<Stores, Error> {
+    let primary = match cache_filenames.first() {
+        Some(path) => load_store(path)?,
+        None => existing_store(&default_cache_path())?,
     };
+    let mut extra: Vec<(String, Store)> = Vec::new();
+    let mut paths: Vec<&Path> = Vec::new();
+    for path in cache_filenames.iter().skip(1) {
+        // strip every extension, as in "internal.bin.zstd"
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        let name = name.split('.').next().unwrap_or_default().to_owned();
+        // licenses are tagged with the store's name, so it must be unique
+        if let Some(i) = extra.iter().position(|(other, _)| *other == name) {
+            return Err(format_err!(
+                "Caches {} and {} would both be named {}; rename one of them",
+                paths[i].display(),
+                path.display(),
+                name
+            ));
+        }
+        extra.push((name, load_store(path)?));
+        paths.push(path);
+    }
+    Ok(Stores { primary, extra })
+}
+
+/// Load the store compiled into the binary.
+#[cfg(all(feature = "embedded-cache", not(feature = "static-store")))]
+fn embedded_store() -> Result<Store, Error> {
+    Store::from_cache(CACHE_DATA)
+}
+
+/// Load the store compiled into the binary, without decoding anything.
+#[cfg(feature = "static-store")]
+fn embedded_store() -> Result<Store, Error> {
+    Store::from_static(&STORE_DATA)
+}
 
-    Ok(store)
+fn load_store(cache_filename: &Path) -> Result<Store, Error> {
+    Store::from_cache(File::open(cache_filename)?)
+}
+
+/// Load the cache file if it exists, or else the embedded cache, if compiled
+/// in.
+pub fn existing_store(cache_filename: &Path) -> Result<Store, Error> {
+    #[cfg(feature = "embedded-cache")]
+    if !cache_filename.exists() {
+        return embedded_store();
+    }
+    load_store(cache_filename)
 }
 
 #[allow(unused_variables)]
-pub fn diff_result(license: &TextData, other: &TextData) {
+pub fn diff_result(text: &TextData, license: &TextData) {
     #[cfg(feature = "diagnostics")]
     {
-        use difference::Changeset;
-
-        let license_texts = &license.text_processed().expect("license texts is stored");
-        let other_texts = &other.text_processed().expect("other texts is stored");
+        use askalono::DiffKind;
 
-        let processed = Changeset::new(license_texts, other_texts, " ");
+        let text_processed = text.text_processed().expect("text is stored");
+        let license_processed = license.text_processed().expect("license text is stored");
+        let diff = text
+            .diff(license)
+            .expect("texts are stored")
+            .into_iter()
+            .map(|chunk| match chunk.kind {
+                DiffKind::Equal => chunk.text,
+                DiffKind::Inserted => format!("[+{}+]", chunk.text),
+                DiffKind::Removed => format!("[-{}-]", chunk.text),
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
         println!(
             "{}\n\n---\n\n{}\n\n---\n\n{}",
-            &license_texts, &other_texts, processed
+            text_processed, license_processed, diff
         );
     }
 
@@ -29,6 +29,16 @@ fn run(args: &[&str]) -> Output {
     out
 }
 
+fn run_json_lines(args: &[&str]) -> Vec<Value> {
+    let cat = [&["--format=json"], args].concat();
+    let out = run(&cat);
+    from_utf8(&out.stdout)
+        .expect("output was not utf8")
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("output was not valid json"))
+        .collect()
+}
+
 fn run_json(args: &[&str]) -> Value {
     let cat = [&["--format=json"], args].concat();
     let out = run(&cat);
@@ -54,6 +64,8 @@ fn output_json() {
     );
     assert_eq!("Apache-2.0", json["result"]["license"]["name"]);
     assert_eq!("original", json["result"]["license"]["kind"]);
+    assert_eq!("Apache", json["result"]["license"]["family"]);
+    assert_eq!(false, json["result"]["ambiguous"]);
     assert_eq!(
         0,
         json["result"]["license"]["aliases"]
@@ -79,6 +91,8 @@ fn multiple_licenses() {
 
     assert_eq!("./tests/data/python-zeep.LICENSE", json["path"]);
 
+    assert_eq!("MIT AND BSD-3-Clause", json["result"]["expression"]);
+
     // The score is currently zero for any file with multiple licenses in it
     assert!(
         json["result"]["score"]
@@ -110,4 +124,959 @@ fn multiple_licenses() {
         "original",
         json["result"]["containing"][2]["license"]["kind"]
     );
+
+    // spans can be highlighted by offset, too
+    let text = std::fs::read_to_string("./tests/data/python-zeep.LICENSE").unwrap();
+    let contained = &json["result"]["containing"][0];
+    let range = |key: &str| {
+        let range = contained[key].as_array().unwrap();
+        let offset = |i: usize| range[i].as_u64().unwrap() as usize;
+        (offset(0), offset(1))
+    };
+    let (start, end) = range("byte_range");
+    let (start_line, end_line) = range("line_range");
+    let lines: Vec<&str> = text.split('\n').collect();
+    assert_eq!(lines[start_line..end_line].join("\n"), text[start..end]);
+    assert!(contained["char_range"].is_array());
+}
+
+#[test]
+fn context_lines() {
+    let json = run_json(&[
+        "id",
+        "-m",
+        "--context",
+        "2",
+        "./tests/data/python-zeep.LICENSE",
+    ]);
+    let first = &json["result"]["containing"][0];
+    assert_eq!("MIT", first["license"]["name"]);
+    assert_eq!(
+        serde_json::json!(["Copyright (c) 2016-2021 Michael van Tellingen", ""]),
+        first["context"]["before"]
+    );
+    assert_eq!(2, first["context"]["after"].as_array().unwrap().len());
+
+    let json = run_json(&["id", "-m", "./tests/data/python-zeep.LICENSE"]);
+    assert!(json["result"]["containing"][0].get("context").is_none());
+
+    let out = run(&[
+        "id",
+        "-m",
+        "--context",
+        "1",
+        "./tests/data/python-zeep.LICENSE",
+    ]);
+    let text = from_utf8(&out.stdout).unwrap();
+    assert!(text.contains("Before:\n    |\n"), "{}", text);
+}
+
+#[test]
+fn license_exception() {
+    let data = "../datasets/modules/spdx-license-list-data";
+    let exception: Value = serde_json::from_str(
+        &std::fs::read_to_string(format!(
+            "{}/json/exceptions/Classpath-exception-2.0.json",
+            data
+        ))
+        .unwrap(),
+    )
+    .unwrap();
+    let text = format!(
+        "{}\n\n{}",
+        std::fs::read_to_string(format!("{}/text/GPL-2.0-only.txt", data)).unwrap(),
+        exception["licenseExceptionText"].as_str().unwrap()
+    );
+    let input = std::env::temp_dir().join(format!("askalono-exception-{}", std::process::id()));
+    std::fs::write(&input, text).unwrap();
+    let input_arg = input.to_string_lossy().into_owned();
+
+    let json = run_json(&["id", &input_arg]);
+    let out = run(&["id", &input_arg]);
+    std::fs::remove_file(&input).unwrap();
+
+    assert_eq!(
+        "Classpath-exception-2.0",
+        json["result"]["license"]["exception"]
+    );
+    assert!(from_utf8(&out.stdout)
+        .unwrap()
+        .contains(" WITH Classpath-exception-2.0 ("));
+}
+
+#[test]
+fn html_input() {
+    let json = run_json(&["id", "./tests/data/LICENSE.html"]);
+    assert_eq!("MIT", json["result"]["license"]["name"]);
+    assert!(
+        json["result"]["score"]
+            .as_f64()
+            .expect("score must be a number")
+            > 0.95f64
+    );
+}
+
+#[test]
+fn html_line_numbers() {
+    let input = std::env::temp_dir().join(format!("askalono-lines-{}.html", std::process::id()));
+    let license = std::fs::read_to_string("./tests/data/LICENSE.html").unwrap();
+    let body: Vec<&str> = license.lines().skip(8).take(5).collect();
+    let html = format!(
+        "<!DOCTYPE html>\n<html>\n<body>\n<div>\n\n\n\n</div>\n\
+         <p>Some unrelated words about this project and how to build it.</p>\n\
+         {}\n<p>Other notes.</p>\n</body>\n</html>\n",
+        body.join("\n")
+    );
+    std::fs::write(&input, &html).unwrap();
+
+    let json = run_json(&["id", "-m", &input.to_string_lossy()]);
+    std::fs::remove_file(&input).unwrap();
+    let contained = &json["result"]["containing"][0];
+    assert_eq!("MIT", contained["license"]["name"]);
+    // line numbers are those of the file, not of the stripped text
+    let range = contained["line_range"].as_array().unwrap();
+    let lines: Vec<&str> = html.split('\n').collect();
+    let (start, end) = (
+        range[0].as_u64().unwrap() as usize,
+        range[1].as_u64().unwrap() as usize,
+    );
+    assert!(lines[start].starts_with("<p>Permission is hereby granted"));
+    assert!(lines[end - 1].starts_with("<p>THE SOFTWARE IS PROVIDED"));
+}
+
+#[test]
+fn markdown_input() {
+    let input = std::env::temp_dir().join(format!("askalono-markdown-{}.md", std::process::id()));
+    let license = std::fs::read_to_string("./tests/data/crawl/LICENSE").unwrap();
+    let markdown = format!(
+        "# License\n\n\
+         [![License: MIT](https://img.shields.io/badge/License-MIT-yellow.svg)](LICENSE)\n\n\
+         > {}\n",
+        license
+            .replace("THE SOFTWARE IS PROVIDED", "**THE SOFTWARE IS PROVIDED**")
+            .replace('\n', "\n> ")
+    );
+    std::fs::write(&input, markdown).unwrap();
+
+    let json = run_json(&["id", &input.to_string_lossy()]);
+    std::fs::remove_file(&input).unwrap();
+    assert_eq!("MIT", json["result"]["license"]["name"]);
+    assert!(json["result"]["score"].as_f64().unwrap() > 0.95, "{}", json);
+}
+
+#[test]
+fn utf16_input() {
+    let input = std::env::temp_dir().join(format!("askalono-utf16-{}", std::process::id()));
+    let license = std::fs::read_to_string("./tests/data/crawl/LICENSE").unwrap();
+    let bytes: Vec<u8> = b"\xff\xfe"
+        .iter()
+        .copied()
+        .chain(license.encode_utf16().flat_map(u16::to_le_bytes))
+        .collect();
+    std::fs::write(&input, bytes).unwrap();
+
+    let json = run_json(&["id", &input.to_string_lossy()]);
+    assert_eq!("MIT", json["result"]["license"]["name"]);
+    assert!(json["result"]["score"].as_f64().unwrap() > 0.95, "{}", json);
+
+    // byte offsets into the decoded text aren't offsets into the file
+    let license = std::fs::read_to_string("./tests/data/python-zeep.LICENSE").unwrap();
+    let bytes: Vec<u8> = license.encode_utf16().flat_map(u16::to_le_bytes).collect();
+    std::fs::write(&input, bytes).unwrap();
+    let json = run_json(&["id", "-m", &input.to_string_lossy()]);
+    std::fs::remove_file(&input).unwrap();
+    let contained = &json["result"]["containing"][0];
+    assert_eq!("MIT", contained["license"]["name"]);
+    assert!(contained.get("byte_range").is_none(), "{}", contained);
+    assert!(contained["char_range"].is_array());
+}
+
+#[test]
+fn binary_input() {
+    let input = std::env::temp_dir().join(format!("askalono-binary-{}", std::process::id()));
+    std::fs::write(&input, b"MIT License\0\x01\x02\x03").unwrap();
+
+    let out = run(&["id", &input.to_string_lossy()]);
+    std::fs::remove_file(&input).unwrap();
+    assert!(!out.status.success());
+    let stderr = from_utf8(&out.stderr).unwrap();
+    assert!(stderr.contains("looks like a binary file"), "{}", stderr);
+}
+
+#[test]
+fn crawl_copyrights() {
+    let lines = run_json_lines(&["crawl", "./tests/data/crawl"]);
+    assert_eq!(3, lines.len());
+
+    let mut files: Vec<_> = lines[..2].iter().collect();
+    files.sort_by_key(|v| v["path"].as_str().unwrap().to_owned());
+    assert_eq!("./tests/data/crawl/LICENSE", files[0]["path"]);
+    assert_eq!("MIT", files[0]["result"]["license"]["name"]);
+    assert_eq!(
+        "Michael van Tellingen",
+        files[0]["result"]["copyrights"][0]["holder"]
+    );
+    assert_eq!("2016-2021", files[0]["result"]["copyrights"][0]["years"]);
+
+    let holders = lines[2]["copyright_holders"]
+        .as_array()
+        .expect("summary must list copyright holders");
+    assert_eq!(2, holders.len());
+    assert_eq!("Michael van Tellingen", holders[0]["holder"]);
+    assert_eq!("Someone Else", holders[1]["holder"]);
+}
+
+#[test]
+fn crawl_report_file() {
+    let report = std::env::temp_dir().join(format!("askalono-report-{}.json", std::process::id()));
+    let report_arg = report.to_string_lossy().into_owned();
+    let out = run(&[
+        "crawl",
+        "--output",
+        &report_arg,
+        "--output-format",
+        "json",
+        "./tests/data/crawl",
+    ]);
+    assert!(out.status.success());
+    assert!(out.stdout.is_empty(), "results must not go to stdout");
+    assert!(from_utf8(&out.stderr).unwrap().contains("Wrote 2 results"));
+
+    let contents = std::fs::read_to_string(&report).expect("report was written");
+    std::fs::remove_file(&report).unwrap();
+    let lines: Vec<Value> = contents
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("report was not valid json"))
+        .collect();
+    assert_eq!(3, lines.len());
+    assert_eq!("MIT", lines[0]["result"]["license"]["name"]);
+    assert!(lines[2]["copyright_holders"].is_array());
+}
+
+#[test]
+fn crawl_stats() {
+    let lines = run_json_lines(&["crawl", "--stats", "./tests/data/crawl"]);
+    let stats = &lines.last().expect("crawl printed results")["crawl_stats"];
+    assert_eq!(2, stats["files_scanned"]);
+    assert_eq!(2, stats["files_walked"]);
+    let bytes = std::fs::read_to_string("./tests/data/crawl/LICENSE")
+        .unwrap()
+        .len();
+    assert!(stats["bytes_processed"].as_u64().unwrap() > bytes as u64);
+    assert!(stats["wall_time_ms"].as_f64().unwrap() > 0.0);
+
+    // files not matching the glob are walked, but not scanned
+    let lines = run_json_lines(&[
+        "crawl",
+        "--stats",
+        "--glob",
+        "COPYING",
+        "./tests/data/crawl",
+    ]);
+    let stats = &lines.last().unwrap()["crawl_stats"];
+    assert_eq!(2, stats["files_walked"]);
+    assert_eq!(0, stats["files_scanned"]);
+
+    let out = run(&["crawl", "--stats", "./tests/data/crawl"]);
+    assert!(from_utf8(&out.stdout)
+        .unwrap()
+        .contains("Crawl statistics:\n  Files walked:"));
+}
+
+#[test]
+fn crawl_relative_paths() {
+    let lines = run_json_lines(&["crawl", "./tests/data/crawl", "--relative-to"]);
+    let mut paths: Vec<_> = lines[..2]
+        .iter()
+        .map(|v| v["path"].as_str().unwrap().to_owned())
+        .collect();
+    paths.sort();
+    assert_eq!(vec!["LICENSE", "vendored/LICENSE"], paths);
+
+    let lines = run_json_lines(&["crawl", "--relative-to", "./tests", "./tests/data/crawl"]);
+    let mut paths: Vec<_> = lines[..2]
+        .iter()
+        .map(|v| v["path"].as_str().unwrap().to_owned())
+        .collect();
+    paths.sort();
+    assert_eq!(
+        vec!["data/crawl/LICENSE", "data/crawl/vendored/LICENSE"],
+        paths
+    );
+}
+
+#[test]
+fn crawl_metadata() {
+    let lines = run_json_lines(&["crawl", "--with-metadata", "./tests/data/crawl"]);
+    let file = lines
+        .iter()
+        .find(|v| v["path"] == "./tests/data/crawl/LICENSE")
+        .expect("LICENSE was crawled");
+    let size = std::fs::metadata("./tests/data/crawl/LICENSE")
+        .unwrap()
+        .len();
+    assert_eq!(size, file["metadata"]["size"]);
+    assert!(file["metadata"]["modified"].is_u64());
+    let hash = file["metadata"]["sha256"]
+        .as_str()
+        .expect("hash is present");
+    assert_eq!(64, hash.len());
+    assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+
+    let lines = run_json_lines(&["crawl", "./tests/data/crawl"]);
+    assert!(lines[0].get("metadata").is_none());
+}
+
+#[test]
+fn crawl_files_from() {
+    let list = std::env::temp_dir().join(format!("askalono-files-{}.txt", std::process::id()));
+    std::fs::write(&list, "./tests/data/crawl/LICENSE\n\n./src/main.rs\n").unwrap();
+    let list_arg = list.to_string_lossy().into_owned();
+
+    let lines = run_json_lines(&["crawl", "--files-from", &list_arg]);
+    assert_eq!(3, lines.len());
+    assert_eq!("./tests/data/crawl/LICENSE", lines[0]["path"]);
+    assert_eq!("MIT", lines[0]["result"]["license"]["name"]);
+    assert_eq!("./src/main.rs", lines[1]["path"]);
+    assert!(lines[1]["error"].is_string());
+
+    let lines = run_json_lines(&["crawl", "--files-from", &list_arg, "--filter"]);
+    std::fs::remove_file(&list).unwrap();
+    assert_eq!(2, lines.len());
+    assert_eq!("./tests/data/crawl/LICENSE", lines[0]["path"]);
+}
+
+#[test]
+fn batch_nul_delimited() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let mut child = Command::new(find_exe())
+        .args(["--format=json", "id", "--batch0", "--print0"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("launch failed");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"../LICENSE\0./tests/data/missing\nfile\0")
+        .unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert!(out.status.success());
+
+    let records: Vec<Value> = from_utf8(&out.stdout)
+        .expect("output was not utf8")
+        .split_terminator('\0')
+        .map(|record| serde_json::from_str(record).expect("output was not valid json"))
+        .collect();
+    assert_eq!(2, records.len());
+    assert_eq!("../LICENSE", records[0]["path"]);
+    assert_eq!("Apache-2.0", records[0]["result"]["license"]["name"]);
+    assert_eq!("./tests/data/missing\nfile", records[1]["path"]);
+    assert!(records[1]["error"].is_string());
+}
+
+#[test]
+fn batch_json_records() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    let license = std::fs::read_to_string("../LICENSE").unwrap();
+    let records = format!(
+        "{}\n\nnot json\n{}\n",
+        serde_json::json!({"id": "apache", "text": license}),
+        serde_json::json!({"id": "empty", "text": ""}),
+    );
+
+    let mut child = Command::new(find_exe())
+        .args(["--format=json", "id", "--batch-json"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("launch failed");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(records.as_bytes())
+        .unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert!(out.status.success());
+
+    let lines: Vec<Value> = from_utf8(&out.stdout)
+        .expect("output was not utf8")
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("output was not valid json"))
+        .collect();
+    assert_eq!(3, lines.len());
+    assert_eq!("apache", lines[0]["path"]);
+    assert_eq!("Apache-2.0", lines[0]["result"]["license"]["name"]);
+    assert!(lines[1]["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("Invalid record"));
+    assert_eq!("empty", lines[2]["path"]);
+    assert!(lines[2]["error"].is_string());
+}
+
+#[test]
+fn multiple_stores() {
+    let dir = std::env::temp_dir().join(format!("askalono-stores-{}", std::process::id()));
+    let text = "This software is for internal use at Example Corporation only.\nIt may not be shared, copied, or redistributed outside of the company\nwithout the prior written approval of the legal department.";
+    let other = "Permission is granted to use this software for evaluation purposes.\nAny other use requires a separate commercial agreement.";
+    let load_cache = |name: &str, id: &str, text: &str| {
+        let spdx_dir = dir.join(name);
+        std::fs::create_dir_all(&spdx_dir).unwrap();
+        let license = serde_json::json!({
+            "licenseId": id,
+            "isDeprecatedLicenseId": false,
+            "licenseText": text,
+        });
+        std::fs::write(spdx_dir.join(format!("{}.json", id)), license.to_string()).unwrap();
+        let cache = dir.join(format!("{}.bin.zstd", name));
+        let cache_arg = cache.to_string_lossy().into_owned();
+        let out = run(&[
+            "--cache",
+            &cache_arg,
+            "cache",
+            "load-spdx",
+            &spdx_dir.to_string_lossy(),
+        ]);
+        assert!(out.status.success());
+        cache_arg
+    };
+    let primary = load_cache("primary", "Example-Evaluation", other);
+    let internal = load_cache("internal", "Example-Internal", text);
+    let input = dir.join("LICENSE");
+    std::fs::write(&input, text).unwrap();
+    let input_arg = input.to_string_lossy().into_owned();
+
+    // the first cache is the primary store; later ones are named by file
+    let json = run_json(&["--cache", &primary, "id", &input_arg]);
+    assert!(json["result"]["license"].is_null());
+    let args = ["--cache", &primary, "--cache", &internal, "id", &input_arg];
+    let json = run_json(&args);
+    let out = run(&args);
+
+    // names must be unique, and cache commands take a single cache
+    std::fs::create_dir_all(dir.join("copy")).unwrap();
+    let copy = dir.join("copy").join("internal.bin");
+    std::fs::copy(&internal, &copy).unwrap();
+    let copy = copy.to_string_lossy().into_owned();
+    let duplicate = run(&[
+        "--cache", &primary, "--cache", &internal, "--cache", &copy, "id", &input_arg,
+    ]);
+    let info = run(&["--cache", &primary, "--cache", &internal, "cache", "info"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!("Example-Internal", json["result"]["license"]["name"]);
+    assert_eq!("internal", json["result"]["license"]["store"]);
+    let out = from_utf8(&out.stdout).unwrap();
+    assert!(
+        out.contains("License: Example-Internal (original text, from internal)"),
+        "{}",
+        out
+    );
+    assert!(!duplicate.status.success());
+    let err = from_utf8(&duplicate.stderr).unwrap();
+    assert!(err.contains("would both be named internal"), "{}", err);
+    assert!(!info.status.success());
+
+    let json = run_json(&["id", "../LICENSE"]);
+    assert!(json["result"]["license"].get("store").is_none());
+}
+
+#[test]
+fn compare_files() {
+    let json = run_json(&[
+        "compare",
+        "./tests/data/crawl/LICENSE",
+        "./tests/data/crawl/vendored/LICENSE",
+    ]);
+    assert_eq!(1.0, json["score"]);
+    assert_eq!(0, json["deviations"].as_array().unwrap().len());
+
+    let json = run_json(&[
+        "compare",
+        "./tests/data/crawl/LICENSE",
+        "./tests/data/LICENSE.html",
+    ]);
+    assert!(json["score"].as_f64().unwrap() > 0.95f64);
+    let deviations = json["deviations"].as_array().unwrap();
+    assert_eq!("deletion", deviations[0]["kind"]);
+    assert_eq!("the mit", deviations[0]["text"]);
+    assert_eq!(0, deviations[0]["position"]);
+
+    let out = run(&["compare", "./tests/data/crawl/LICENSE", "../LICENSE"]);
+    assert!(out.status.success());
+    let out = from_utf8(&out.stdout).unwrap();
+    assert!(out.starts_with("Score: 0.0"), "{}", out);
+    assert!(out.contains("\n  + apache"), "{}", out);
+}
+
+#[test]
+fn show_license() {
+    let out = run(&["show", "Apache-2.0", "--header"]);
+    assert!(out.status.success());
+    let out = from_utf8(&out.stdout).unwrap();
+    assert!(out.starts_with("Copyright [yyyy] [name of copyright owner]\n"));
+    assert!(out.contains("Licensed under the Apache License, Version 2.0"));
+
+    let out = run(&["show", "Not-A-License"]);
+    assert!(!out.status.success());
+
+    let dir = std::env::temp_dir().join(format!("askalono-show-{}", std::process::id()));
+    let spdx_dir = dir.join("json");
+    std::fs::create_dir_all(&spdx_dir).unwrap();
+    let text = "This software is for “internal use” at Example Corporation only.\n\nIt may not be shared outside of the company.";
+    let license = serde_json::json!({
+        "licenseId": "Example-Internal",
+        "isDeprecatedLicenseId": false,
+        "licenseText": text,
+    });
+    std::fs::write(spdx_dir.join("Example-Internal.json"), license.to_string()).unwrap();
+
+    let show = |flags: &[&str]| {
+        let cache = dir.join("internal.bin.zstd");
+        let cache_arg = cache.to_string_lossy().into_owned();
+        let spdx_arg = spdx_dir.to_string_lossy().into_owned();
+        let load = [
+            &["--cache", &cache_arg, "cache", "load-spdx", &spdx_arg],
+            flags,
+        ]
+        .concat();
+        assert!(run(&load).status.success());
+        let out = run(&[
+            "--cache",
+            "./embedded-cache.bin.zstd",
+            "--cache",
+            &cache_arg,
+            "show",
+            "Example-Internal",
+        ]);
+        (
+            out.status.success(),
+            from_utf8(&out.stdout).unwrap().to_owned(),
+        )
+    };
+    let without_texts = show(&[]);
+    let normalized = show(&["--store"]);
+    let verbatim = show(&["--store", "--store-originals"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(!without_texts.0);
+    assert_eq!(
+        (
+            true,
+            "This software is for 'internal use' at Example Corporation only.\n\nIt may not be shared outside of the company.\n".to_owned()
+        ),
+        normalized
+    );
+    assert_eq!((true, format!("{}\n", text)), verbatim);
+}
+
+#[test]
+fn aliases_and_resolve() {
+    let dir = std::env::temp_dir().join(format!("askalono-aliases-{}", std::process::id()));
+    let spdx_dir = dir.join("json");
+    std::fs::create_dir_all(&spdx_dir).unwrap();
+    let text = "This software is for internal use at Example Corporation only.\nIt may not be shared, copied, or redistributed outside of the company.";
+    // identical texts are loaded once, with the rest as aliases; files are
+    // read in order of name
+    for id in &["Example-A", "Example-B"] {
+        let license = serde_json::json!({
+            "licenseId": id,
+            "isDeprecatedLicenseId": false,
+            "licenseText": text,
+        });
+        std::fs::write(spdx_dir.join(format!("{}.json", id)), license.to_string()).unwrap();
+    }
+    let cache = dir.join("internal.bin.zstd");
+    let cache_arg = cache.to_string_lossy().into_owned();
+    let out = run(&[
+        "--cache",
+        &cache_arg,
+        "cache",
+        "load-spdx",
+        &spdx_dir.to_string_lossy(),
+    ]);
+    assert!(out.status.success());
+
+    let caches = [
+        "--cache",
+        "./embedded-cache.bin.zstd",
+        "--cache",
+        &cache_arg,
+    ];
+    let aliases = run_json(&[&caches[..], &["aliases", "Example-A"]].concat());
+    let resolved = run_json(&[&caches[..], &["resolve", "example-b"]].concat());
+    let text = run(&[&caches[..], &["resolve", "Example-B"]].concat());
+    let missing = run(&[&caches[..], &["resolve", "Example-C"]].concat());
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!("Example-A", aliases["license"]);
+    assert_eq!(serde_json::json!(["Example-B"]), aliases["aliases"]);
+    assert_eq!("example-b", resolved["name"]);
+    assert_eq!("Example-A", resolved["license"]);
+    assert_eq!("Example-A\n", from_utf8(&text.stdout).unwrap());
+    assert!(!missing.status.success());
+
+    let json = run_json(&["aliases", "MIT"]);
+    assert_eq!(0, json["aliases"].as_array().unwrap().len());
+    let loose = run(&["resolve", "Apache License, Version 2"]);
+    assert_eq!("Apache-2.0\n", from_utf8(&loose.stdout).unwrap());
+    assert!(!run(&["aliases", "Not-A-License"]).status.success());
+}
+
+#[test]
+fn detect_id_mismatches() {
+    let dir = std::env::temp_dir().join(format!("askalono-detect-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    std::fs::copy("./tests/data/crawl/LICENSE", dir.join("LICENSE")).unwrap();
+    std::fs::write(
+        dir.join("src/main.rs"),
+        "// SPDX-License-Identifier: MIT\nfn main() {}\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("src/lib.c"),
+        "/* SPDX-License-Identifier: MIT */\nint x;\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("src/vendored.py"),
+        "#!/usr/bin/env python\n# SPDX-License-Identifier: (Apache-2.0 WITH LLVM-exception) OR mit\n",
+    )
+    .unwrap();
+    let dir_arg = dir.to_string_lossy().into_owned();
+
+    let json = run_json(&["detect-id", &dir_arg]);
+    let out = run(&["detect-id", &dir_arg]);
+    std::fs::write(
+        dir.join("LICENSE-APACHE"),
+        std::fs::read("../LICENSE").unwrap(),
+    )
+    .unwrap();
+    let fixed = run(&["detect-id", &dir_arg]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!("LICENSE", json["detected"][0]["path"]);
+    assert_eq!("MIT", json["detected"][0]["license"]);
+    assert_eq!(2, json["declared"]["MIT"]);
+    let mismatches = json["mismatches"].as_array().unwrap();
+    assert_eq!(1, mismatches.len());
+    assert_eq!("src/vendored.py", mismatches[0]["path"]);
+    assert_eq!(
+        "(Apache-2.0 WITH LLVM-exception) OR mit",
+        mismatches[0]["expression"]
+    );
+    assert_eq!(serde_json::json!(["Apache-2.0"]), mismatches[0]["missing"]);
+
+    assert!(!out.status.success());
+    let out = from_utf8(&out.stdout).unwrap();
+    assert!(out.contains("\n  MIT: 2 files\n"), "{}", out);
+    assert!(
+        out.contains("\n  src/vendored.py: (Apache-2.0 WITH LLVM-exception) OR mit (no license file for Apache-2.0)"),
+        "{}",
+        out
+    );
+    assert!(fixed.status.success());
+}
+
+#[test]
+fn scan_tuning() {
+    let input = std::env::temp_dir().join(format!("askalono-passes-{}", std::process::id()));
+    let text = format!(
+        "{}\n\n----\n\n{}",
+        std::fs::read_to_string("../LICENSE").unwrap(),
+        std::fs::read_to_string("./tests/data/crawl/LICENSE").unwrap()
+    );
+    std::fs::write(&input, text).unwrap();
+    let input_arg = input.to_string_lossy().into_owned();
+
+    let one = run_json(&["id", "--optimize", &input_arg]);
+    let two = run_json(&["id", "--optimize", "--max-passes", "2", &input_arg]);
+    std::fs::remove_file(&input).unwrap();
+
+    assert_eq!(1, one["result"]["containing"].as_array().unwrap().len());
+    let containing = two["result"]["containing"].as_array().unwrap();
+    assert_eq!(2, containing.len());
+    assert_eq!("Apache-2.0", containing[0]["license"]["name"]);
+    assert_eq!("MIT", containing[1]["license"]["name"]);
+
+    // a coarser step finds licenses, just less precisely
+    let json = run_json(&[
+        "id",
+        "-m",
+        "--step",
+        "1",
+        "./tests/data/python-zeep.LICENSE",
+    ]);
+    assert_eq!(3, json["result"]["containing"].as_array().unwrap().len());
+    let json = run_json(&[
+        "id",
+        "-m",
+        "--step",
+        "20",
+        "./tests/data/python-zeep.LICENSE",
+    ]);
+    assert_eq!("MIT", json["result"]["containing"][0]["license"]["name"]);
+
+    assert!(!run(&["id", "-m", "--step", "0", "../LICENSE"])
+        .status
+        .success());
+}
+
+#[test]
+fn cache_install() {
+    let data_home = std::env::temp_dir().join(format!("askalono-data-{}", std::process::id()));
+    let install = |args: &[&str]| {
+        Command::new(find_exe())
+            .env("XDG_DATA_HOME", &data_home)
+            .args(["cache", "install"])
+            .args(args)
+            .output()
+            .expect("launch failed")
+    };
+
+    let out = install(&[]);
+    assert!(out.status.success());
+    let installed = data_home.join("askalono/askalono-cache.bin.zstd");
+    assert!(installed.is_file(), "embedded cache was installed");
+
+    let out = install(&["./tests/data/crawl/LICENSE"]);
+    assert!(!out.status.success(), "only valid caches are installed");
+    assert!(from_utf8(&out.stderr).unwrap().contains("Not a valid"));
+
+    // an installed cache is used in place of the embedded one
+    let spdx_dir = data_home.join("json");
+    std::fs::create_dir_all(&spdx_dir).unwrap();
+    let text = "This software is for internal use at Example Corporation only.";
+    let license = serde_json::json!({
+        "licenseId": "Example-Internal",
+        "isDeprecatedLicenseId": false,
+        "licenseText": text,
+    });
+    std::fs::write(spdx_dir.join("Example-Internal.json"), license.to_string()).unwrap();
+    let cache = data_home.join("internal.bin.zstd");
+    let cache_arg = cache.to_string_lossy().into_owned();
+    let spdx_arg = spdx_dir.to_string_lossy().into_owned();
+    assert!(
+        run(&["--cache", &cache_arg, "cache", "load-spdx", &spdx_arg])
+            .status
+            .success()
+    );
+    assert!(install(&[&cache_arg]).status.success());
+    let input = data_home.join("LICENSE");
+    std::fs::write(&input, text).unwrap();
+    let out = Command::new(find_exe())
+        .env("XDG_DATA_HOME", &data_home)
+        .env_remove("ASKALONO_CACHE")
+        .args(["--format", "json", "id"])
+        .arg(&input)
+        .output()
+        .expect("launch failed");
+    std::fs::remove_dir_all(&data_home).unwrap();
+
+    let json: Value = serde_json::from_slice(&out.stdout).unwrap();
+    assert_eq!("Example-Internal", json["result"]["license"]["name"]);
+}
+
+#[test]
+fn cache_info() {
+    let dir = std::env::temp_dir().join(format!("askalono-info-{}", std::process::id()));
+    let spdx_dir = dir.join("json").join("details");
+    std::fs::create_dir_all(&spdx_dir).unwrap();
+    // the list version is read from the index next to the details directory
+    let index = serde_json::json!({"licenseListVersion": "3.99", "licenses": []});
+    std::fs::write(dir.join("json/licenses.json"), index.to_string()).unwrap();
+    for (id, text) in &[
+        ("Example-A", "This software is for internal use only."),
+        ("Example-B", "This software may be shared with partners."),
+    ] {
+        let license = serde_json::json!({
+            "licenseId": id,
+            "isDeprecatedLicenseId": false,
+            "licenseText": text,
+        });
+        std::fs::write(spdx_dir.join(format!("{}.json", id)), license.to_string()).unwrap();
+    }
+    let cache = dir.join("internal.bin.zstd");
+    let cache_arg = cache.to_string_lossy().into_owned();
+    let out = run(&[
+        "--cache",
+        &cache_arg,
+        "cache",
+        "load-spdx",
+        &spdx_dir.to_string_lossy(),
+    ]);
+    assert!(out.status.success());
+
+    let json = run_json(&["--cache", &cache_arg, "cache", "info"]);
+    let text = run(&["--cache", &cache_arg, "cache", "info"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!("3.99", json["list_version"]);
+    assert_eq!(2, json["licenses"]);
+    assert!(json["built_at"].as_u64().is_some());
+    let text = from_utf8(&text.stdout).unwrap();
+    assert!(text.contains("License list version: 3.99"));
+    assert!(text.contains("Licenses: 2"));
+}
+
+#[test]
+fn cache_load_dir() {
+    let dir = std::env::temp_dir().join(format!("askalono-load-dir-{}", std::process::id()));
+    let licenses = dir.join("licenses");
+    std::fs::create_dir_all(&licenses).unwrap();
+    std::fs::write(
+        licenses.join("Internal-1.0.txt"),
+        "This software is for internal use only.",
+    )
+    .unwrap();
+    std::fs::write(
+        licenses.join("Internal-1.0.toml"),
+        "aliases = [\"Internal\"]\n",
+    )
+    .unwrap();
+    let cache = dir.join("internal.bin.zstd");
+    let cache_arg = cache.to_string_lossy().into_owned();
+    let out = run(&[
+        "--cache",
+        &cache_arg,
+        "cache",
+        "load-dir",
+        &licenses.to_string_lossy(),
+    ]);
+    let info = run_json(&["--cache", &cache_arg, "cache", "info"]);
+    let export = run(&["--cache", &cache_arg, "cache", "export"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(out.status.success());
+    assert_eq!(1, info["licenses"]);
+    let exported: Value = serde_json::from_slice(&export.stdout).unwrap();
+    assert_eq!(
+        serde_json::json!(["Internal"]),
+        exported["store"]["licenses"]["Internal-1.0"]["aliases"]
+    );
+}
+
+#[test]
+fn cache_load_spdx_deprecated() {
+    let dir = std::env::temp_dir().join(format!("askalono-deprecated-{}", std::process::id()));
+    let spdx_dir = dir.join("details");
+    std::fs::create_dir_all(&spdx_dir).unwrap();
+    for (id, deprecated, text) in &[
+        (
+            "Example-A",
+            false,
+            "This software is for internal use only.",
+        ),
+        (
+            "GPL-1.0",
+            true,
+            "This program is distributed under the first GNU license.",
+        ),
+    ] {
+        let license = serde_json::json!({
+            "licenseId": id,
+            "isDeprecatedLicenseId": deprecated,
+            "licenseText": text,
+        });
+        std::fs::write(spdx_dir.join(format!("{}.json", id)), license.to_string()).unwrap();
+    }
+    let cache = dir.join("internal.bin.zstd");
+    let cache_arg = cache.to_string_lossy().into_owned();
+    let out = run(&[
+        "--cache",
+        &cache_arg,
+        "cache",
+        "load-spdx",
+        "--include-deprecated",
+        &spdx_dir.to_string_lossy(),
+    ]);
+    let export = run(&["--cache", &cache_arg, "cache", "export"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(out.status.success());
+    let exported: Value = serde_json::from_slice(&export.stdout).unwrap();
+    let licenses = &exported["store"]["licenses"];
+    assert_eq!(false, licenses["Example-A"]["deprecated"]);
+    assert_eq!(true, licenses["GPL-1.0"]["deprecated"]);
+    assert_eq!("GPL-1.0-only", licenses["GPL-1.0"]["superseded_by"]);
+}
+
+#[test]
+fn cache_export_import() {
+    let dir = std::env::temp_dir().join(format!("askalono-export-{}", std::process::id()));
+    let spdx_dir = dir.join("details");
+    std::fs::create_dir_all(&spdx_dir).unwrap();
+    let license = serde_json::json!({
+        "licenseId": "Example-A",
+        "isDeprecatedLicenseId": false,
+        "licenseText": "This software is for internal use only.",
+    });
+    std::fs::write(spdx_dir.join("Example-A.json"), license.to_string()).unwrap();
+    let cache = dir.join("internal.bin.zstd");
+    let cache_arg = cache.to_string_lossy().into_owned();
+    let out = run(&[
+        "--cache",
+        &cache_arg,
+        "cache",
+        "load-spdx",
+        &spdx_dir.to_string_lossy(),
+    ]);
+    assert!(out.status.success());
+
+    let json_file = dir.join("internal.json");
+    let json_arg = json_file.to_string_lossy().into_owned();
+    let export = run(&["--cache", &cache_arg, "cache", "export", &json_arg]);
+    let stdout = run(&["--cache", &cache_arg, "cache", "export"]);
+    let exported: Value = serde_json::from_slice(&std::fs::read(&json_file).unwrap()).unwrap();
+
+    let imported = dir.join("imported.bin.zstd");
+    let imported_arg = imported.to_string_lossy().into_owned();
+    let import = run(&["--cache", &imported_arg, "cache", "import", &json_arg]);
+    let info = run_json(&["--cache", &imported_arg, "cache", "info"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(export.status.success());
+    assert_eq!(
+        exported,
+        serde_json::from_slice::<Value>(&stdout.stdout).unwrap()
+    );
+    assert!(exported["store"]["licenses"]["Example-A"].is_object());
+    assert!(import.status.success());
+    assert_eq!(1, info["licenses"]);
+}
+
+#[test]
+fn comments_only() {
+    let input = std::env::temp_dir().join(format!("askalono-comments-{}.rs", std::process::id()));
+    let header: String = std::fs::read_to_string("./tests/data/crawl/LICENSE")
+        .unwrap()
+        .lines()
+        .map(|line| format!("// {}\n", line))
+        .collect();
+    let code: String = (0..60)
+        .map(|i| {
+            format!(
+                "fn function_{}(value: u32) -> u32 {{ value * {} + 1 }}\n",
+                i, i
+            )
+        })
+        .collect();
+    std::fs::write(&input, header + &code).unwrap();
+    let input_arg = input.to_string_lossy().into_owned();
+
+    let whole = run_json(&["id", &input_arg]);
+    let comments = run_json(&["id", "--comments", &input_arg]);
+    std::fs::remove_file(&input).unwrap();
+
+    // the code drowns out the license, unless only comments are scanned
+    assert!(whole["error"].is_string(), "{}", whole);
+    assert_eq!("MIT", comments["result"]["license"]["name"]);
+    assert!(comments["result"]["score"].as_f64().unwrap() > 0.95);
 }
@@ -18,7 +18,7 @@ wasm.then(({ AskalonoStore, normalize_text }) => {
       clearTimeout(timeout);
     }
     timeout = setTimeout(() => {
-      analyze(store, normalize_text(field.value));
+      analyze(store, normalize_text(field.value).text());
       timeout = null;
     }, 200);
   });
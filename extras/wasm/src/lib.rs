@@ -12,8 +12,46 @@ use askalono::*;
 use js_sys::Array;
 use wasm_bindgen::prelude::*;
 
+#[cfg(not(feature = "static-store"))]
 static CACHE_DATA: &[u8] = include_bytes!(env!("ASKALONO_WASM_EMBEDDED_CACHE"));
 
+/// The embedded cache as static data, generated by the build script.
+#[cfg(feature = "static-store")]
+static STORE_DATA: StaticStore = include!(concat!(env!("OUT_DIR"), "/askalono-store.rs"));
+
+#[cfg(not(feature = "static-store"))]
+fn embedded_store() -> Store {
+    Store::from_cache(CACHE_DATA).unwrap()
+}
+
+/// Static stores have no license texts, so processed license text isn't
+/// available from them.
+#[cfg(feature = "static-store")]
+fn embedded_store() -> Store {
+    Store::from_static(&STORE_DATA).unwrap()
+}
+
+#[cfg(not(feature = "static-store"))]
+fn embedded_subset(names: &[&str]) -> Result<Store, JsError> {
+    Store::load_subset(CACHE_DATA, names).map_err(|e| JsError::new(&e.to_string()))
+}
+
+/// The static store's tables are used where they are, so there's nothing to
+/// save by decoding less of it; the other licenses are just left out.
+#[cfg(feature = "static-store")]
+fn embedded_subset(names: &[&str]) -> Result<Store, JsError> {
+    let mut store = embedded_store();
+    let others: Vec<String> = store
+        .licenses()
+        .filter(|name| !names.contains(&name.as_str()))
+        .cloned()
+        .collect();
+    for name in others {
+        store.remove_license(&name).unwrap();
+    }
+    Ok(store)
+}
+
 #[wasm_bindgen]
 pub struct AskalonoStore {
     store: Store,
@@ -45,6 +83,30 @@ impl MatchResult {
     }
 }
 
+#[wasm_bindgen]
+pub struct LocatedResult {
+    name: String,
+    score: f32,
+    start_line: usize,
+    end_line: usize,
+}
+
+#[wasm_bindgen]
+impl LocatedResult {
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+    pub fn score(&self) -> f32 {
+        self.score
+    }
+    pub fn start_line(&self) -> usize {
+        self.start_line
+    }
+    pub fn end_line(&self) -> usize {
+        self.end_line
+    }
+}
+
 #[wasm_bindgen]
 pub struct LicenseInfo {
     text: String,
@@ -58,28 +120,113 @@ impl LicenseInfo {
 }
 
 #[wasm_bindgen]
-pub fn normalize_text(text: &str) -> String {
+#[derive(Clone, Copy)]
+pub struct NormalizeOptions {
+    /// Also produce the single-line form that matching runs on.
+    pub aggressive: bool,
+}
+
+impl Default for NormalizeOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl NormalizeOptions {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> NormalizeOptions {
+        NormalizeOptions { aggressive: true }
+    }
+}
+
+#[wasm_bindgen]
+pub struct NormalizedText {
+    lines: Vec<String>,
+    processed: Option<String>,
+}
+
+#[wasm_bindgen]
+impl NormalizedText {
+    pub fn lines(&self) -> Array {
+        self.lines.iter().map(JsValue::from).collect()
+    }
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+    pub fn processed(&self) -> Option<String> {
+        self.processed.clone()
+    }
+}
+
+#[wasm_bindgen]
+pub fn normalize_text(text: &str, options: Option<NormalizeOptions>) -> NormalizedText {
+    let options = options.unwrap_or_default();
     let data = TextData::new(text);
-    data.lines().join("\n")
+    NormalizedText {
+        lines: data.lines().to_vec(),
+        processed: match options.aggressive {
+            true => data.text_processed().map(str::to_owned),
+            false => None,
+        },
+    }
 }
 
 #[wasm_bindgen]
 impl AskalonoStore {
     #[wasm_bindgen(constructor)]
     pub fn new() -> AskalonoStore {
-        let store = Store::from_cache(CACHE_DATA).unwrap();
+        let store = embedded_store();
         AskalonoStore { store }
     }
 
+    /// Create a store with only the named licenses, which must be exact
+    /// license names rather than aliases. Fails if any name isn't a known
+    /// license, or if no names are given.
+    pub fn with_licenses(names: &Array) -> Result<AskalonoStore, JsError> {
+        let names: Vec<String> = names.iter().filter_map(|name| name.as_string()).collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        let store = embedded_subset(&names)?;
+        let unknown: Vec<&str> = names
+            .iter()
+            .copied()
+            .filter(|&name| !store.licenses().any(|license| license == name))
+            .collect();
+        if !unknown.is_empty() {
+            return Err(JsError::new(&format!(
+                "unknown licenses: {}",
+                unknown.join(", ")
+            )));
+        }
+        if store.is_empty() {
+            return Err(JsError::new("no licenses given"));
+        }
+        Ok(AskalonoStore { store })
+    }
+
     pub fn identify(&self, text: &str) -> MatchResult {
         let matched = self.store.analyze(&text.into());
         MatchResult {
             name: matched.name.to_owned(),
             score: matched.score,
-            license_text: matched.data.lines().join("\n"),
+            license_text: matched.matched_text().unwrap_or_default(),
         }
     }
 
+    pub fn identify_all(&self, text: &str, threshold: f32) -> Array {
+        self.store
+            .analyze_all(&text.into(), threshold)
+            .into_iter()
+            .map(|m| LocatedResult {
+                name: m.matched.name.to_owned(),
+                score: m.located_score,
+                start_line: m.line_range.0,
+                end_line: m.line_range.1,
+            })
+            .map(JsValue::from)
+            .collect()
+    }
+
     pub fn licenses(&self) -> Array {
         self.store.licenses().map(JsValue::from).collect()
     }
@@ -93,6 +240,8 @@ impl AskalonoStore {
 
 #[cfg(test)]
 mod test {
+    use js_sys::Array;
+    use wasm_bindgen::JsValue;
     use wasm_bindgen_test::*;
 
     static LICENSE_TEXT: &str = include_str!("../../../LICENSE");
@@ -105,4 +254,27 @@ mod test {
 
         assert_eq!(m.name, "Apache-2.0");
     }
+
+    #[wasm_bindgen_test]
+    fn with_licenses() {
+        let names = |names: &[&str]| names.iter().map(|&n| JsValue::from(n)).collect::<Array>();
+
+        let store = super::AskalonoStore::with_licenses(&names(&["MIT", "Apache-2.0"])).unwrap();
+        assert_eq!(store.identify(LICENSE_TEXT).name, "Apache-2.0");
+
+        assert!(super::AskalonoStore::with_licenses(&names(&["MIT", "Not-A-License"])).is_err());
+        assert!(super::AskalonoStore::with_licenses(&names(&[])).is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn normalize() {
+        let normalized = super::normalize_text("Hello,\n  World!", None);
+        assert_eq!(normalized.lines, vec!["Hello,", "World!"]);
+        assert_eq!(normalized.processed.as_deref(), Some("hello world"));
+
+        let mut options = super::NormalizeOptions::new();
+        options.aggressive = false;
+        let normalized = super::normalize_text("Hello, World!", Some(options));
+        assert_eq!(normalized.processed, None);
+    }
 }
@@ -0,0 +1,298 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// A family of comment syntaxes, used to pick the comments out of a source
+/// file with `extract_comments`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommentStyle {
+    /// `//` and `/* */`, as in C, C++, Java, JavaScript, Go, and Rust.
+    C,
+    /// `/* */` alone, as in CSS, where `//` is often part of a URL.
+    Css,
+    /// `#`, as in shell scripts, Python, Ruby, Perl, and YAML.
+    Hash,
+    /// `<!-- -->`, as in XML and SVG.
+    Xml,
+    /// `--` and `--[[ ]]`, as in Lua.
+    Lua,
+    /// `--` and `/* */`, as in SQL.
+    Sql,
+    /// `--` and `{- -}`, as in Haskell and Elm.
+    Haskell,
+    /// `;`, as in Lisp, assembly, and INI files.
+    Semicolon,
+    /// `%`, as in TeX, Erlang, and MATLAB.
+    Percent,
+}
+
+/// How comments (and strings, which may contain comment markers) are
+/// written in a family of languages.
+struct Syntax {
+    lines: &'static [&'static str],
+    blocks: &'static [(&'static str, &'static str)],
+    quotes: &'static [char],
+}
+
+impl CommentStyle {
+    /// Guess the comment style of a file from its extension (without the
+    /// dot, in any case), such as `rs` or `py`. Returns `None` for unknown
+    /// extensions.
+    ///
+    /// HTML isn't included, as `strip_html` is better suited to it.
+    pub fn from_extension(extension: &str) -> Option<CommentStyle> {
+        let style = match extension.to_lowercase().as_str() {
+            "c" | "h" | "cc" | "cpp" | "cxx" | "hh" | "hpp" | "hxx" | "cs" | "java" | "js"
+            | "jsx" | "mjs" | "ts" | "tsx" | "go" | "rs" | "swift" | "kt" | "kts" | "scala"
+            | "dart" | "groovy" | "php" | "m" | "mm" | "proto" | "zig" => CommentStyle::C,
+            "css" | "scss" | "less" => CommentStyle::Css,
+            "sh" | "bash" | "zsh" | "fish" | "py" | "pyi" | "rb" | "pl" | "pm" | "r" | "tcl"
+            | "ex" | "exs" | "jl" | "nix" | "awk" | "cmake" | "ps1" | "yaml" | "yml" | "toml"
+            | "conf" | "cfg" => CommentStyle::Hash,
+            "xml" | "xsd" | "xsl" | "xslt" | "svg" | "plist" | "pom" => CommentStyle::Xml,
+            "lua" => CommentStyle::Lua,
+            "sql" => CommentStyle::Sql,
+            "hs" | "lhs" | "elm" | "purs" => CommentStyle::Haskell,
+            "lisp" | "lsp" | "el" | "clj" | "cljs" | "scm" | "rkt" | "asm" | "s" | "ini" => {
+                CommentStyle::Semicolon
+            }
+            "tex" | "sty" | "cls" | "erl" | "hrl" => CommentStyle::Percent,
+            _ => return None,
+        };
+        Some(style)
+    }
+
+    /// Guess the comment style of a file from its path: by its extension, or
+    /// for files that usually have none, by its name (such as `Makefile`).
+    pub fn from_path(path: &Path) -> Option<CommentStyle> {
+        let name = path.file_name()?.to_string_lossy();
+        match name.as_ref() {
+            "Makefile" | "makefile" | "GNUmakefile" | "Dockerfile" | "CMakeLists.txt"
+            | "Gemfile" | "Rakefile" => Some(CommentStyle::Hash),
+            _ => CommentStyle::from_extension(&path.extension()?.to_string_lossy()),
+        }
+    }
+
+    fn syntax(self) -> Syntax {
+        match self {
+            CommentStyle::C => Syntax {
+                lines: &["//"],
+                blocks: &[("/*", "*/")],
+                // backticks delimit JavaScript template literals and Go raw
+                // strings
+                quotes: &['"', '\'', '`'],
+            },
+            CommentStyle::Css => Syntax {
+                lines: &[],
+                blocks: &[("/*", "*/")],
+                quotes: &['"', '\''],
+            },
+            CommentStyle::Hash => Syntax {
+                lines: &["#"],
+                blocks: &[],
+                quotes: &['"', '\''],
+            },
+            CommentStyle::Xml => Syntax {
+                lines: &[],
+                blocks: &[("<!--", "-->")],
+                quotes: &[],
+            },
+            CommentStyle::Lua => Syntax {
+                lines: &["--"],
+                blocks: &[("--[[", "]]")],
+                quotes: &['"', '\''],
+            },
+            CommentStyle::Sql => Syntax {
+                lines: &["--"],
+                blocks: &[("/*", "*/")],
+                quotes: &['\''],
+            },
+            CommentStyle::Haskell => Syntax {
+                lines: &["--"],
+                blocks: &[("{-", "-}")],
+                quotes: &['"'],
+            },
+            CommentStyle::Semicolon => Syntax {
+                lines: &[";"],
+                blocks: &[],
+                quotes: &['"'],
+            },
+            CommentStyle::Percent => Syntax {
+                lines: &["%"],
+                blocks: &[],
+                quotes: &[],
+            },
+        }
+    }
+}
+
+/// Keep only the comments of a source file, dropping its code.
+///
+/// License headers in source files are written as comments, and the code
+/// around them only dilutes a match, so scanning the comments alone finds
+/// headers far more reliably. Comment markers are removed, along with
+/// repeats of them (as in `///` or `####`). Every line break is kept, so
+/// line numbers in the result are those of the original file.
+///
+/// Markers inside string literals are ignored. Strings are assumed to end
+/// on the line they start, so that an unusual literal can't swallow the rest
+/// of the file, and a quote that isn't closed there is taken as an ordinary
+/// character, such as an apostrophe or a Rust lifetime.
+///
+/// This can also be done while creating a `TextData`; see
+/// `TextDataOptions::extract_comments`.
+///
+/// ```
+/// use askalono::{extract_comments, CommentStyle};
+///
+/// let source = "// Licensed under the MIT license.\nfn main() {\n    println!(\"// not a comment\");\n}\n";
+/// assert_eq!(
+///     " Licensed under the MIT license.\n\n\n\n",
+///     extract_comments(source, CommentStyle::C)
+/// );
+/// ```
+pub fn extract_comments(text: &str, style: CommentStyle) -> String {
+    let syntax = style.syntax();
+    let mut out = String::with_capacity(text.len() / 2);
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        // block markers first; Lua's `--[[` starts with its line marker
+        if let Some(&(open, close)) = syntax
+            .blocks
+            .iter()
+            .find(|(open, _)| rest.starts_with(open))
+        {
+            let body = &rest[open.len()..];
+            let end = body.find(close).unwrap_or(body.len());
+            out.push_str(&body[..end]);
+            // keep words on either side of the comment apart
+            out.push(' ');
+            rest = &body[(end + close.len()).min(body.len())..];
+        } else if let Some(marker) = syntax.lines.iter().find(|line| rest.starts_with(*line)) {
+            let repeat = marker.chars().next().unwrap();
+            let body = rest[marker.len()..].trim_start_matches(repeat);
+            let end = body.find('\n').unwrap_or(body.len());
+            out.push_str(&body[..end]);
+            rest = &body[end..];
+        } else if let Some(after) = syntax
+            .quotes
+            .contains(&c)
+            .then(|| skip_string(&rest[c.len_utf8()..], c))
+            .flatten()
+        {
+            rest = after;
+        } else {
+            if c == '\n' {
+                out.push('\n');
+            }
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+
+    out
+}
+
+/// Skip past the end of a string literal, or get `None` if it isn't closed
+/// on its line.
+fn skip_string(text: &str, quote: char) -> Option<&str> {
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => return None,
+            // the guard skips the escaped character either way
+            '\\' if chars.next() == Some('\n') => return None,
+            _ if c == quote => return Some(chars.as_str()),
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extract_c() {
+        let source = "/*\n * Copyright 2018 Jane Doe\n * Licensed under the MIT license.\n */\n\
+                      #include <stdio.h>\n\
+                      int main() { printf(\"/* \\\" */\"); return 0; } // done\n";
+        let comments = extract_comments(source, CommentStyle::C);
+        assert_eq!(source.lines().count(), comments.lines().count());
+        assert_eq!(
+            "\n * Copyright 2018 Jane Doe\n * Licensed under the MIT license.\n  \n\n done\n",
+            comments
+        );
+    }
+
+    #[test]
+    fn extract_others() {
+        for (style, source, expected) in &[
+            (
+                CommentStyle::Hash,
+                "#!/bin/sh\n#### MIT ####\necho \"# no\" 'nor # this' # yes\n",
+                "!/bin/sh\n MIT ####\n yes\n",
+            ),
+            (
+                CommentStyle::Xml,
+                "<?xml version=\"1.0\"?>\n<!-- MIT -->\n<a/>\n",
+                "\n MIT  \n\n",
+            ),
+            (
+                CommentStyle::Lua,
+                "--[[ MIT\nlicense ]]\nprint(\"--\") -- yes\n",
+                " MIT\nlicense  \n yes\n",
+            ),
+            (
+                CommentStyle::Haskell,
+                "{- MIT -}\nmain = pure () -- yes",
+                " MIT  \n yes",
+            ),
+            (CommentStyle::Semicolon, ";;; MIT\n(setq x \";\")", " MIT\n"),
+            (CommentStyle::Percent, "% MIT\n\\begin{document}", " MIT\n"),
+            // quotes not closed on their line aren't strings
+            (CommentStyle::C, "char *s = \"abc\n// MIT", "\n MIT"),
+            (
+                CommentStyle::C,
+                "fn f<'a>(s: &'a str) {} // MIT\nlet c = '\"'; // yes",
+                " MIT\n yes",
+            ),
+            (
+                CommentStyle::C,
+                "const s = `// no ${x}`; /* MIT */",
+                " MIT  ",
+            ),
+            (
+                CommentStyle::Css,
+                "/* MIT */\na { background: url(http://example.com/a.png); }",
+                " MIT  \n",
+            ),
+        ] {
+            assert_eq!(*expected, extract_comments(source, *style), "{:?}", style);
+        }
+    }
+
+    #[test]
+    fn styles_from_paths() {
+        assert_eq!(
+            Some(CommentStyle::C),
+            CommentStyle::from_path(Path::new("src/main.RS"))
+        );
+        assert_eq!(
+            Some(CommentStyle::Hash),
+            CommentStyle::from_path(Path::new("build/Makefile"))
+        );
+        assert_eq!(
+            Some(CommentStyle::Css),
+            CommentStyle::from_path(Path::new("style.scss"))
+        );
+        assert_eq!(None, CommentStyle::from_path(Path::new("data.mat")));
+        assert_eq!(None, CommentStyle::from_path(Path::new("LICENSE")));
+        assert_eq!(None, CommentStyle::from_path(Path::new("README.txt")));
+    }
+}
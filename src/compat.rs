@@ -0,0 +1,326 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! A small, curated set of license compatibility and obligation data.
+//!
+//! This covers only common licenses, and only at a coarse level: enough to
+//! flag obvious problems in scan results (GPL-2.0-only code combined with
+//! Apache-2.0 code, for example) and to summarize what a set of licenses
+//! generally requires. Like the rest of askalono, it is not legal advice;
+//! anything it reports should be reviewed by someone qualified to do so.
+//!
+//! Licenses are identified by their SPDX identifiers, as found in
+//! `Match::name` or `IdentifiedLicense::name` when using the SPDX dataset.
+//! Any license not in the curated set is reported as unknown.
+//!
+//! # Examples
+//!
+//! ```
+//! use askalono::compat::{combined_obligations, is_compatible, Context, Obligation};
+//!
+//! assert_eq!(Some(true), is_compatible("MIT", "GPL-3.0-only", Context::Combined));
+//! assert_eq!(Some(false), is_compatible("Apache-2.0", "GPL-2.0-only", Context::Combined));
+//! assert_eq!(None, is_compatible("MIT", "Some-Internal-License", Context::Combined));
+//!
+//! let combined = combined_obligations(&["MIT", "Apache-2.0"]);
+//! assert!(combined.obligations.contains(&Obligation::IncludeNotice));
+//! assert!(combined.unknown.is_empty());
+//! ```
+
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+/// How two differently-licensed pieces of software are being put together.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Context {
+    /// The works are combined into a single work, such as by static linking or
+    /// copying source code.
+    Combined,
+    /// One work dynamically links against the other (as a shared library).
+    DynamicLinking,
+    /// The works are merely distributed alongside each other, such as on the
+    /// same media or in the same container image.
+    Aggregate,
+}
+
+/// Something a license generally requires of those distributing the software.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Obligation {
+    /// Retain copyright notices.
+    IncludeCopyright,
+    /// Include a copy of the license text.
+    IncludeLicense,
+    /// Include any accompanying NOTICE file.
+    IncludeNotice,
+    /// Mark modified files or versions as changed.
+    StateChanges,
+    /// Make source code available when distributing.
+    DiscloseSource,
+    /// Distribute modifications (or derived works) under the same license.
+    SameLicense,
+    /// Make source code available to users interacting over a network.
+    NetworkUseDisclose,
+}
+
+/// The overall obligations of a set of licenses.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CombinedObligations {
+    /// The union of obligations of all known licenses.
+    pub obligations: BTreeSet<Obligation>,
+    /// Licenses that aren't in the curated data set, and so weren't
+    /// considered.
+    pub unknown: Vec<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Category {
+    Permissive,
+    WeakCopyleft,
+    StrongCopyleft,
+}
+
+use self::{Category::*, Obligation::*};
+
+const NOTICES: &[Obligation] = &[IncludeCopyright, IncludeLicense];
+const COPYLEFT: &[Obligation] = &[
+    IncludeCopyright,
+    IncludeLicense,
+    StateChanges,
+    DiscloseSource,
+    SameLicense,
+];
+
+const LICENSES: &[(&str, Category, &[Obligation])] = &[
+    ("0BSD", Permissive, &[]),
+    ("CC0-1.0", Permissive, &[]),
+    ("MIT-0", Permissive, &[]),
+    ("Unlicense", Permissive, &[]),
+    ("BSD-2-Clause", Permissive, NOTICES),
+    ("BSD-3-Clause", Permissive, NOTICES),
+    ("BSL-1.0", Permissive, NOTICES),
+    ("ISC", Permissive, NOTICES),
+    ("MIT", Permissive, NOTICES),
+    (
+        "Zlib",
+        Permissive,
+        &[IncludeCopyright, IncludeLicense, StateChanges],
+    ),
+    (
+        "Apache-2.0",
+        Permissive,
+        &[
+            IncludeCopyright,
+            IncludeLicense,
+            IncludeNotice,
+            StateChanges,
+        ],
+    ),
+    (
+        "MPL-2.0",
+        WeakCopyleft,
+        &[
+            IncludeCopyright,
+            IncludeLicense,
+            DiscloseSource,
+            SameLicense,
+        ],
+    ),
+    (
+        "EPL-1.0",
+        WeakCopyleft,
+        &[IncludeLicense, DiscloseSource, SameLicense],
+    ),
+    (
+        "EPL-2.0",
+        WeakCopyleft,
+        &[IncludeLicense, DiscloseSource, SameLicense],
+    ),
+    (
+        "CDDL-1.0",
+        WeakCopyleft,
+        &[
+            IncludeCopyright,
+            IncludeLicense,
+            DiscloseSource,
+            SameLicense,
+        ],
+    ),
+    ("LGPL-2.1-only", WeakCopyleft, COPYLEFT),
+    ("LGPL-2.1-or-later", WeakCopyleft, COPYLEFT),
+    ("LGPL-3.0-only", WeakCopyleft, COPYLEFT),
+    ("LGPL-3.0-or-later", WeakCopyleft, COPYLEFT),
+    ("GPL-2.0-only", StrongCopyleft, COPYLEFT),
+    ("GPL-2.0-or-later", StrongCopyleft, COPYLEFT),
+    ("GPL-3.0-only", StrongCopyleft, COPYLEFT),
+    ("GPL-3.0-or-later", StrongCopyleft, COPYLEFT),
+    (
+        "AGPL-3.0-only",
+        StrongCopyleft,
+        &[
+            IncludeCopyright,
+            IncludeLicense,
+            StateChanges,
+            DiscloseSource,
+            SameLicense,
+            NetworkUseDisclose,
+        ],
+    ),
+    (
+        "AGPL-3.0-or-later",
+        StrongCopyleft,
+        &[
+            IncludeCopyright,
+            IncludeLicense,
+            StateChanges,
+            DiscloseSource,
+            SameLicense,
+            NetworkUseDisclose,
+        ],
+    ),
+];
+
+/// Pairs that can't be combined, even though neither is a strong copyleft
+/// license conflicting with another.
+const INCOMPATIBLE: &[(&str, &str)] = &[
+    ("Apache-2.0", "GPL-2.0-only"),
+    ("Apache-2.0", "LGPL-2.1-only"),
+    ("CDDL-1.0", "GPL-2.0-only"),
+    ("CDDL-1.0", "GPL-2.0-or-later"),
+    ("CDDL-1.0", "GPL-3.0-only"),
+    ("CDDL-1.0", "GPL-3.0-or-later"),
+    ("EPL-1.0", "GPL-2.0-only"),
+    ("EPL-1.0", "GPL-2.0-or-later"),
+    ("EPL-1.0", "GPL-3.0-only"),
+    ("EPL-1.0", "GPL-3.0-or-later"),
+    ("GPL-2.0-only", "LGPL-3.0-only"),
+    ("GPL-2.0-only", "LGPL-3.0-or-later"),
+];
+
+/// Pairs of distinct strong copyleft licenses that can be combined.
+const COPYLEFT_COMPATIBLE: &[(&str, &str)] = &[
+    ("GPL-2.0-only", "GPL-2.0-or-later"),
+    ("GPL-2.0-or-later", "GPL-3.0-only"),
+    ("GPL-2.0-or-later", "GPL-3.0-or-later"),
+    ("GPL-3.0-only", "GPL-3.0-or-later"),
+    ("AGPL-3.0-only", "AGPL-3.0-or-later"),
+    ("AGPL-3.0-only", "GPL-3.0-only"),
+    ("AGPL-3.0-only", "GPL-3.0-or-later"),
+    ("AGPL-3.0-or-later", "GPL-3.0-only"),
+    ("AGPL-3.0-or-later", "GPL-3.0-or-later"),
+];
+
+fn lookup(id: &str) -> Option<(Category, &'static [Obligation])> {
+    LICENSES
+        .iter()
+        .find(|(name, _, _)| *name == id)
+        .map(|(_, category, obligations)| (*category, *obligations))
+}
+
+fn pair_listed(list: &[(&str, &str)], a: &str, b: &str) -> bool {
+    list.iter()
+        .any(|&(x, y)| (x == a && y == b) || (x == b && y == a))
+}
+
+/// Check whether software under license `a` can be used with software under
+/// license `b` in the given context.
+///
+/// Returns `None` if either license isn't in the curated data set.
+pub fn is_compatible(a: &str, b: &str, context: Context) -> Option<bool> {
+    let (category_a, _) = lookup(a)?;
+    let (category_b, _) = lookup(b)?;
+
+    if a == b || context == Context::Aggregate {
+        return Some(true);
+    }
+
+    if pair_listed(INCOMPATIBLE, a, b) {
+        // the LGPL permits dynamic linking regardless of the other license
+        let lgpl_linked = context == Context::DynamicLinking
+            && (a.starts_with("LGPL-") || b.starts_with("LGPL-"));
+        return Some(lgpl_linked);
+    }
+
+    if category_a == StrongCopyleft && category_b == StrongCopyleft {
+        return Some(pair_listed(COPYLEFT_COMPATIBLE, a, b));
+    }
+
+    Some(true)
+}
+
+/// Collect the obligations of all of the given licenses.
+///
+/// Licenses not in the curated data set are listed in `unknown`.
+pub fn combined_obligations<S: AsRef<str>>(ids: &[S]) -> CombinedObligations {
+    let mut combined = CombinedObligations::default();
+    for id in ids {
+        let id = id.as_ref();
+        match lookup(id) {
+            Some((_, obligations)) => combined.obligations.extend(obligations),
+            None => combined.unknown.push(id.to_owned()),
+        }
+    }
+    combined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compatibility() {
+        use Context::*;
+
+        assert_eq!(Some(true), is_compatible("MIT", "BSD-3-Clause", Combined));
+        assert_eq!(
+            Some(true),
+            is_compatible("Apache-2.0", "GPL-3.0-only", Combined)
+        );
+        assert_eq!(
+            Some(false),
+            is_compatible("GPL-2.0-only", "Apache-2.0", Combined)
+        );
+        assert_eq!(
+            Some(true),
+            is_compatible("GPL-2.0-only", "Apache-2.0", Aggregate)
+        );
+        assert_eq!(
+            Some(false),
+            is_compatible("GPL-2.0-only", "GPL-3.0-only", Combined)
+        );
+        assert_eq!(
+            Some(true),
+            is_compatible("GPL-2.0-or-later", "GPL-3.0-only", Combined)
+        );
+        assert_eq!(
+            Some(true),
+            is_compatible("AGPL-3.0-only", "GPL-3.0-only", Combined)
+        );
+        assert_eq!(
+            Some(true),
+            is_compatible("MPL-2.0", "GPL-2.0-only", Combined)
+        );
+        assert_eq!(
+            Some(false),
+            is_compatible("Apache-2.0", "LGPL-2.1-only", Combined)
+        );
+        assert_eq!(
+            Some(true),
+            is_compatible("Apache-2.0", "LGPL-2.1-only", DynamicLinking)
+        );
+        assert_eq!(None, is_compatible("MIT", "nope", Combined));
+    }
+
+    #[test]
+    fn obligations() {
+        let combined = combined_obligations(&["MIT", "GPL-3.0-only", "nope"]);
+        assert_eq!(vec!["nope".to_string()], combined.unknown);
+        assert!(combined.obligations.contains(&IncludeCopyright));
+        assert!(combined.obligations.contains(&SameLicense));
+        assert!(!combined.obligations.contains(&NetworkUseDisclose));
+
+        assert!(combined_obligations(&["0BSD"]).obligations.is_empty());
+    }
+}
@@ -0,0 +1,382 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::{Deserialize, Serialize};
+
+use crate::license::TextData;
+
+/// Whether a `Deviation` adds words to a license or leaves them out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviationKind {
+    /// Words in the text that aren't in the license.
+    Insertion,
+    /// Words in the license that are missing from the text.
+    Deletion,
+}
+
+/// A run of words where a text differs from the license it matched.
+///
+/// A high score can hide a meaningful change: "MIT, plus a sentence
+/// forbidding military use" still scores well against MIT. Deviations list
+/// what was actually added or removed. See `TextData::deviations_from` and
+/// `ScanStrategyBuilder::deviations`.
+///
+/// Words are compared after askalono's normalization, so differences in
+/// case, punctuation, whitespace, and copyright lines don't count, and the
+/// words are reported in their normalized form.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deviation {
+    /// Whether the words were inserted or deleted.
+    pub kind: DeviationKind,
+    /// The words inserted or deleted, separated by spaces.
+    pub text: String,
+    /// The 0-indexed position in the license's words where the change is:
+    /// the first deleted word, or the word an insertion comes before.
+    pub position: usize,
+}
+
+/// Whether the words of a `DiffChunk` are in both texts or only one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffKind {
+    /// Words in both texts.
+    Equal,
+    /// Words only in the other text.
+    Inserted,
+    /// Words only in this text.
+    Removed,
+}
+
+/// A run of words in a diff between two texts. See `TextData::diff`.
+///
+/// As with `Deviation`, words are compared and reported after askalono's
+/// normalization.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffChunk {
+    /// Whether the words are shared, inserted, or removed.
+    pub kind: DiffKind,
+    /// The words of the run, separated by spaces.
+    pub text: String,
+}
+
+impl DiffChunk {
+    /// The number of words in this run.
+    pub fn words(&self) -> usize {
+        self.text.split(' ').count()
+    }
+}
+
+/// One step of an edit script turning one word sequence into another.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Edit {
+    Keep,
+    /// Delete the word at this index of the first sequence.
+    Delete(usize),
+    /// Insert the word at this index of the second sequence.
+    Insert(usize),
+}
+
+/// Find a shortest edit script turning `a` into `b`.
+///
+/// This is Myers' O(ND) algorithm, which is fast when the sequences are
+/// similar, as they are for a confident match. For each number of edits `d`,
+/// it records the furthest point reached along each diagonal, then walks
+/// back through those records to recover the edits.
+fn edit_script(a: &[&str], b: &[&str]) -> Vec<Edit> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (n + m) as usize;
+    let offset = max as isize + 1;
+    let mut v = vec![0isize; 2 * max + 3];
+    // the diagonals -d..=d of `v`, before each round
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    'search: for d in 0..=max as isize {
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let i = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && v[i - 1] < v[i + 1]) {
+                v[i + 1]
+            } else {
+                v[i - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[i] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    // walk back from the end
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let at = |k: isize| v[(k + d) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = if d == 0 { 0 } else { at(prev_k) };
+        let prev_y = if d == 0 { 0 } else { prev_x - prev_k };
+
+        while x > prev_x && y > prev_y {
+            edits.push(Edit::Keep);
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            if x == prev_x {
+                edits.push(Edit::Insert(prev_y as usize));
+            } else {
+                edits.push(Edit::Delete(prev_x as usize));
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    edits.reverse();
+    edits
+}
+
+/// List the runs of words inserted into or deleted from `license` to get
+/// `text`.
+pub(crate) fn deviations(license: &[&str], text: &[&str]) -> Vec<Deviation> {
+    let mut deviations: Vec<Deviation> = Vec::new();
+    // position in the license, to place insertions
+    let mut position = 0;
+    let mut last_edit = Edit::Keep;
+    for edit in edit_script(license, text) {
+        let (kind, word) = match edit {
+            Edit::Keep => {
+                position += 1;
+                last_edit = edit;
+                continue;
+            }
+            Edit::Delete(i) => {
+                position = i + 1;
+                (DeviationKind::Deletion, license[i])
+            }
+            Edit::Insert(j) => (DeviationKind::Insertion, text[j]),
+        };
+
+        // extend the current run, or start a new one
+        let continues = matches!(
+            (last_edit, edit),
+            (Edit::Delete(_), Edit::Delete(_)) | (Edit::Insert(_), Edit::Insert(_))
+        );
+        match deviations.last_mut() {
+            Some(last) if continues => {
+                last.text.push(' ');
+                last.text.push_str(word);
+            }
+            _ => deviations.push(Deviation {
+                kind,
+                text: word.to_owned(),
+                position: match edit {
+                    Edit::Delete(i) => i,
+                    _ => position,
+                },
+            }),
+        }
+        last_edit = edit;
+    }
+    deviations
+}
+
+/// Divide the words of `a` and `b` into runs that are shared, only in `b`,
+/// or only in `a`.
+pub(crate) fn diff(a: &[&str], b: &[&str]) -> Vec<DiffChunk> {
+    let mut chunks: Vec<DiffChunk> = Vec::new();
+    let mut i = 0;
+    for edit in edit_script(a, b) {
+        let (kind, word) = match edit {
+            Edit::Keep => {
+                i += 1;
+                (DiffKind::Equal, a[i - 1])
+            }
+            Edit::Delete(at) => {
+                i = at + 1;
+                (DiffKind::Removed, a[at])
+            }
+            Edit::Insert(at) => (DiffKind::Inserted, b[at]),
+        };
+        match chunks.last_mut() {
+            Some(last) if last.kind == kind => {
+                last.text.push(' ');
+                last.text.push_str(word);
+            }
+            _ => chunks.push(DiffChunk {
+                kind,
+                text: word.to_owned(),
+            }),
+        }
+    }
+    chunks
+}
+
+impl TextData {
+    /// Compare this text with another word by word, producing the runs of
+    /// words they share and those only one of them has, in order.
+    ///
+    /// Joining the `Equal` and `Removed` runs gives this text's words, and
+    /// joining the `Equal` and `Inserted` runs gives the other's. Only the
+    /// lines in view are compared. Returns `None` if either `TextData` was
+    /// stored without text.
+    ///
+    /// ```
+    /// use askalono::{DiffKind, TextData};
+    ///
+    /// let license = TextData::from("Permission is hereby granted, free of charge.");
+    /// let text = TextData::from("Permission is granted, free of all charges.");
+    /// let diff = license.diff(&text).unwrap();
+    /// let removed: usize = diff
+    ///     .iter()
+    ///     .filter(|chunk| chunk.kind == DiffKind::Removed)
+    ///     .map(|chunk| chunk.words())
+    ///     .sum();
+    /// assert_eq!(2, removed);
+    /// assert_eq!("permission is", diff[0].text);
+    /// ```
+    pub fn diff(&self, other: &TextData) -> Option<Vec<DiffChunk>> {
+        let a: Vec<&str> = self.tokens()?.collect();
+        let b: Vec<&str> = other.tokens()?.collect();
+        Some(diff(&a, &b))
+    }
+
+    /// List how this text differs from a license text, word by word.
+    ///
+    /// Returns `None` if either `TextData` was stored without text. See
+    /// `Deviation`.
+    ///
+    /// ```
+    /// use askalono::{DeviationKind, TextData};
+    ///
+    /// let license = TextData::from("Permission is hereby granted, free of charge.");
+    /// let text = TextData::from("Permission is hereby granted, free of charge, except for evil.");
+    /// let deviations = text.deviations_from(&license).unwrap();
+    /// assert_eq!(1, deviations.len());
+    /// assert_eq!(DeviationKind::Insertion, deviations[0].kind);
+    /// assert_eq!("except for evil", deviations[0].text);
+    /// ```
+    pub fn deviations_from(&self, license: &TextData) -> Option<Vec<Deviation>> {
+        let text: Vec<&str> = self.tokens()?.collect();
+        let license: Vec<&str> = license.tokens()?.collect();
+        Some(deviations(&license, &text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(s: &str) -> Vec<&str> {
+        s.split_whitespace().collect()
+    }
+
+    fn apply(a: &[&str], b: &[&str], script: &[Edit]) -> Vec<String> {
+        let mut out = Vec::new();
+        let mut i = 0;
+        for edit in script {
+            match *edit {
+                Edit::Keep => {
+                    out.push(a[i].to_owned());
+                    i += 1;
+                }
+                Edit::Delete(at) => {
+                    assert_eq!(i, at);
+                    i += 1;
+                }
+                Edit::Insert(at) => out.push(b[at].to_owned()),
+            }
+        }
+        assert_eq!(a.len(), i);
+        out
+    }
+
+    #[test]
+    fn edit_scripts() {
+        let cases = [
+            ("", ""),
+            ("a b c", "a b c"),
+            ("a b c", ""),
+            ("", "a b c"),
+            ("a b c a b b a", "c b a b a c"),
+            ("a b c d e", "a x c d y e z"),
+        ];
+        for (a, b) in cases {
+            let (a, b) = (words(a), words(b));
+            let script = edit_script(&a, &b);
+            assert_eq!(b, apply(&a, &b, &script), "{:?} -> {:?}", a, b);
+        }
+
+        // shortest: one substitution is a deletion and an insertion
+        let script = edit_script(&words("a b c"), &words("a x c"));
+        assert_eq!(2, script.iter().filter(|e| **e != Edit::Keep).count());
+    }
+
+    #[test]
+    fn runs_of_words() {
+        let license = words("aaa bbb ccc ddd eee fff");
+        let text = words("aaa xxx yyy bbb ccc fff ggg");
+        assert_eq!(
+            vec![
+                Deviation {
+                    kind: DeviationKind::Insertion,
+                    text: "xxx yyy".into(),
+                    position: 1,
+                },
+                Deviation {
+                    kind: DeviationKind::Deletion,
+                    text: "ddd eee".into(),
+                    position: 3,
+                },
+                Deviation {
+                    kind: DeviationKind::Insertion,
+                    text: "ggg".into(),
+                    position: 6,
+                },
+            ],
+            deviations(&license, &text)
+        );
+        assert!(deviations(&license, &license).is_empty());
+    }
+
+    #[test]
+    fn diff_chunks() {
+        let chunk = |kind, text: &str| DiffChunk {
+            kind,
+            text: text.into(),
+        };
+        let a = words("aaa bbb ccc ddd eee fff");
+        let b = words("xxx aaa bbb ddd yyy zzz fff");
+        assert_eq!(
+            vec![
+                chunk(DiffKind::Inserted, "xxx"),
+                chunk(DiffKind::Equal, "aaa bbb"),
+                chunk(DiffKind::Removed, "ccc"),
+                chunk(DiffKind::Equal, "ddd"),
+                chunk(DiffKind::Removed, "eee"),
+                chunk(DiffKind::Inserted, "yyy zzz"),
+                chunk(DiffKind::Equal, "fff"),
+            ],
+            diff(&a, &b)
+        );
+        assert_eq!(vec![chunk(DiffKind::Equal, "aaa")], diff(&a[..1], &a[..1]));
+        assert!(diff(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn without_text() {
+        let license = TextData::from("aaa bbb").without_text();
+        assert_eq!(None, TextData::from("aaa bbb").deviations_from(&license));
+        assert_eq!(None, TextData::from("aaa bbb").diff(&license));
+    }
+}
@@ -0,0 +1,218 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::license::TextData;
+
+/// How much of a text without a byte order mark is looked at to guess
+/// whether it's UTF-16.
+const SNIFF_BYTES: usize = 1024;
+
+/// At most one in this many pairs of bytes in a text without a byte order
+/// mark may have its zero in the wrong place for the text to still be read as
+/// UTF-16. Characters such as U+0100 or U+4E00 have a zero low byte, so a
+/// text that's mostly ASCII may still have a few.
+const STRAY_ZEROES: usize = 10;
+
+/// How much of a text is looked at for zero bytes to guess whether it's
+/// binary data, as in git.
+const BINARY_SNIFF_BYTES: usize = 8000;
+
+impl TextData {
+    /// Create a new TextData structure from text of an unknown encoding,
+    /// decoded as with `decode_text`.
+    ///
+    /// Use this for text read from files or other sources that may not be
+    /// UTF-8, rather than failing or mangling it with `String::from_utf8`.
+    pub fn from_bytes(bytes: &[u8]) -> TextData {
+        TextData::new(&decode_text(bytes))
+    }
+}
+
+/// Decode text of an unknown encoding, never failing.
+///
+/// UTF-8 and UTF-16 (either byte order) are recognized by their byte order
+/// mark, which is stripped. Without one, UTF-16 is recognized by the zero
+/// bytes that accompany ASCII text in it. Anything else that isn't valid
+/// UTF-8 is read as Latin-1, which is how most older license files are
+/// encoded. Invalid UTF-16 is replaced with U+FFFD.
+///
+/// The input is taken to be complete, so a text that ends partway through a
+/// UTF-8 character isn't UTF-8, and is read as Latin-1. If only the start of
+/// a text was read, use `decode_text_prefix` instead.
+///
+/// ```
+/// assert_eq!("MIT ©", askalono::decode_text(b"\xff\xfeM\0I\0T\0 \0\xa9\0"));
+/// assert_eq!("MIT ©", askalono::decode_text(b"MIT \xa9"));
+/// ```
+pub fn decode_text(bytes: &[u8]) -> String {
+    decode(bytes, false)
+}
+
+/// Decode the start of a text of an unknown encoding, as with `decode_text`,
+/// where the end may be cut off mid-character, as when only the start of a
+/// file is read. What's left of that character is dropped.
+///
+/// ```
+/// assert_eq!("MIT ", askalono::decode_text_prefix(b"MIT \xc2"));
+/// assert_eq!("MIT Â", askalono::decode_text(b"MIT \xc2"));
+/// ```
+pub fn decode_text_prefix(bytes: &[u8]) -> String {
+    decode(bytes, true)
+}
+
+fn decode(bytes: &[u8], truncated: bool) -> String {
+    if let Some(rest) = bytes.strip_prefix(b"\xef\xbb\xbf") {
+        return decode_utf8(rest, truncated);
+    }
+    if let Some(rest) = bytes.strip_prefix(b"\xff\xfe") {
+        return decode_utf16(rest, u16::from_le_bytes, truncated);
+    }
+    if let Some(rest) = bytes.strip_prefix(b"\xfe\xff") {
+        return decode_utf16(rest, u16::from_be_bytes, truncated);
+    }
+    match sniff_utf16(bytes) {
+        Some(from_bytes) => decode_utf16(bytes, from_bytes, truncated),
+        None => decode_utf8(bytes, truncated),
+    }
+}
+
+/// Guess whether some bytes are binary data rather than text of any
+/// encoding `decode_text` knows: whether they have a zero byte near the
+/// start, without being UTF-16.
+///
+/// Decoding binary data never fails, but what comes out is noise that's
+/// slow to scan and never matches anything, so check this first.
+///
+/// ```
+/// assert!(askalono::looks_binary(b"\x7fELF\x02\x01\x01\0\0\0"));
+/// assert!(!askalono::looks_binary(b"\xff\xfeM\0I\0T\0"));
+/// assert!(!askalono::looks_binary(b"MIT \xa9"));
+/// ```
+pub fn looks_binary(bytes: &[u8]) -> bool {
+    let sample = &bytes[..bytes.len().min(BINARY_SNIFF_BYTES)];
+    if !sample.contains(&0) {
+        return false;
+    }
+    let bom = [&b"\xff\xfe"[..], b"\xfe\xff"];
+    !bom.iter().any(|bom| bytes.starts_with(bom)) && sniff_utf16(bytes).is_none()
+}
+
+/// Guess whether text without a byte order mark is UTF-16, and in which byte
+/// order: mostly-ASCII UTF-16 has a zero in every other byte, which real
+/// UTF-8 and Latin-1 text never does. A few zeroes in the other bytes are
+/// allowed; see `STRAY_ZEROES`.
+fn sniff_utf16(bytes: &[u8]) -> Option<fn([u8; 2]) -> u16> {
+    let sample = &bytes[..bytes.len().min(SNIFF_BYTES)];
+    let pairs = sample.len() / 2;
+    if pairs < 2 {
+        return None;
+    }
+    let zeroes = |offset: usize| {
+        sample
+            .chunks_exact(2)
+            .filter(|pair| pair[offset] == 0)
+            .count()
+    };
+    let (high, low) = (zeroes(1), zeroes(0));
+    if high * 2 > pairs && low * STRAY_ZEROES <= pairs {
+        Some(u16::from_le_bytes)
+    } else if low * 2 > pairs && high * STRAY_ZEROES <= pairs {
+        Some(u16::from_be_bytes)
+    } else {
+        None
+    }
+}
+
+fn decode_utf8(bytes: &[u8], truncated: bool) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_owned(),
+        // only the last character is incomplete, and it was cut off
+        Err(e) if truncated && e.error_len().is_none() => {
+            String::from_utf8_lossy(&bytes[..e.valid_up_to()]).into_owned()
+        }
+        // not UTF-8 at all; Latin-1 maps each byte to the same code point
+        Err(_) => bytes.iter().map(|&b| char::from(b)).collect(),
+    }
+}
+
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16, truncated: bool) -> String {
+    let pairs = bytes.chunks_exact(2);
+    let odd = !pairs.remainder().is_empty();
+    let mut units: Vec<u16> = pairs.map(|pair| from_bytes([pair[0], pair[1]])).collect();
+    // a cut-off surrogate pair
+    if truncated
+        && units
+            .last()
+            .is_some_and(|&unit| (0xd800..0xdc00).contains(&unit))
+    {
+        units.pop();
+    }
+    let mut text: String = char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect();
+    if odd && !truncated {
+        text.push(char::REPLACEMENT_CHARACTER);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodings() {
+        assert_eq!("MIT ©", decode_text("MIT ©".as_bytes()));
+        assert_eq!("MIT", decode_text(b"\xef\xbb\xbfMIT"));
+        assert_eq!("MIT ©", decode_text(b"MIT \xa9"));
+        assert_eq!("MIT ©", decode_text(b"\xff\xfeM\0I\0T\0 \0\xa9\0"));
+        assert_eq!("MIT ©", decode_text(b"\xfe\xff\0M\0I\0T\0 \0\xa9"));
+        // a complete text ending in what could start a UTF-8 character
+        assert_eq!("Café", decode_text(b"Caf\xe9"));
+        assert_eq!("MIT\u{fffd}", decode_text(b"\xff\xfeM\0I\0T\0\x20"));
+    }
+
+    #[test]
+    fn truncated() {
+        assert_eq!("MIT ", decode_text_prefix(b"MIT \xc2"));
+        assert_eq!("Caf", decode_text_prefix(b"Caf\xe9"));
+        assert_eq!("MIT", decode_text_prefix(b"\xff\xfeM\0I\0T\0\x20"));
+        assert_eq!("MIT", decode_text_prefix(b"\xff\xfeM\0I\0T\0\x3d\xd8"));
+        // invalid before the end is still Latin-1
+        assert_eq!("Café ", decode_text_prefix(b"Caf\xe9 "));
+    }
+
+    #[test]
+    fn encodings_without_bom() {
+        assert_eq!("MIT ©", decode_text(b"M\0I\0T\0 \0\xa9\0"));
+        assert_eq!("MIT ©", decode_text(b"\0M\0I\0T\0 \0\xa9"));
+        // a stray zero byte isn't enough
+        assert_eq!("MI\0T", decode_text(b"MI\0T"));
+        // but a few characters with a zero low byte are allowed
+        let text = "Copyright \u{100} Example Corp.";
+        let le: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert_eq!(text, decode_text(&le));
+        let be: Vec<u8> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(text, decode_text(&be));
+        assert_eq!("", decode_text(b""));
+    }
+
+    #[test]
+    fn binary() {
+        assert!(looks_binary(b"MI\0T"));
+        assert!(!looks_binary(b"M\0I\0T\0 \0\xa9\0"));
+        assert!(!looks_binary(b"\xfe\xff\0M\0I\0T\0 \0\xa9"));
+        assert!(!looks_binary(b""));
+        // zero bytes far enough in are missed
+        let mut text = vec![b'a'; BINARY_SNIFF_BYTES];
+        text.push(0);
+        assert!(!looks_binary(&text));
+    }
+
+    #[test]
+    fn from_bytes() {
+        let data = TextData::from_bytes(b"\xff\xfea\0a\0a\0a\0a\0\n\0b\0b\0b\0b\0b\0");
+        assert_eq!(["aaaaa", "bbbbb"], data.lines());
+        assert_eq!(data, TextData::from("aaaaa\nbbbbb"));
+    }
+}
@@ -0,0 +1,145 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::cmp::{min, Reverse};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{license::TextData, store::Match};
+
+/// A breakdown of a match score into the n-grams (word pairs) behind it.
+///
+/// Scores are the Sørensen–Dice coefficient of two texts' n-grams: twice the
+/// number of shared n-grams, divided by the total number of n-grams in both.
+/// An explanation lists which n-grams were shared and which only one side
+/// had, so a score of 0.87 can be traced back to the phrases that were
+/// added or left out. See `TextData::explain_match`.
+///
+/// Each list holds n-grams with their counts, most frequent first. An n-gram
+/// appearing more often on one side counts as shared as many times as it
+/// appears on both, with the rest listed for the side it's more common on.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MatchExplanation {
+    /// The match score, as `TextData::match_score` computes it.
+    pub score: f32,
+    /// N-grams in both texts.
+    pub shared: Vec<(String, u32)>,
+    /// N-grams only in the text being explained.
+    pub extra: Vec<(String, u32)>,
+    /// N-grams only in the text it was compared against (typically, the
+    /// license).
+    pub missing: Vec<(String, u32)>,
+}
+
+impl TextData {
+    /// Explain the score of this text against another (typically a license),
+    /// listing the n-grams they share and those only one has.
+    ///
+    /// This works whether or not either `TextData` has its text stored.
+    ///
+    /// ```
+    /// use askalono::TextData;
+    ///
+    /// let license = TextData::from("you may copy and modify the software");
+    /// let text = TextData::from("you may copy the software");
+    /// let explanation = text.explain_match(&license);
+    /// assert_eq!(text.match_score(&license), explanation.score);
+    /// assert!(explanation.extra.contains(&("copy the".to_owned(), 1)));
+    /// assert!(explanation.missing.contains(&("and modify".to_owned(), 1)));
+    /// ```
+    pub fn explain_match(&self, other: &TextData) -> MatchExplanation {
+        let (ours, theirs) = (&self.match_data, &other.match_data);
+        // hashed grams are spelled from their texts, if they have them
+        let mut shared = Vec::new();
+        let mut extra = Vec::new();
+        let mut missing = Vec::new();
+        for (gram, count) in self.ngrams() {
+            let both = min(count, theirs.get_str(&gram));
+            if both > 0 {
+                shared.push((gram.clone(), both));
+            }
+            if count > both {
+                extra.push((gram, count - both));
+            }
+        }
+        for (gram, count) in other.ngrams() {
+            let both = min(count, ours.get_str(&gram));
+            if count > both {
+                missing.push((gram, count - both));
+            }
+        }
+        for grams in [&mut shared, &mut extra, &mut missing] {
+            grams.sort_by(|a, b| (Reverse(a.1), &a.0).cmp(&(Reverse(b.1), &b.0)));
+        }
+
+        MatchExplanation {
+            score: self.match_score(other),
+            shared,
+            extra,
+            missing,
+        }
+    }
+}
+
+impl<'a> Match<'a> {
+    /// Explain this match's score for the text that was analyzed, listing
+    /// the n-grams it shares with the matched license and those only one of
+    /// them has. See `TextData::explain_match`.
+    ///
+    /// A `Store`'s stop grams (see `Store::add_stop_grams`) aren't part of
+    /// its licenses, so any in the text are listed as `extra` here though
+    /// analysis ignored them, and the explanation's score is then a little
+    /// lower than this match's.
+    pub fn explain(&self, text: &TextData) -> MatchExplanation {
+        text.explain_match(self.data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::Store;
+
+    #[test]
+    fn accounts_for_every_gram() {
+        let license = TextData::from("aaa bbb ccc aaa bbb ddd eee");
+        let text = TextData::from("aaa bbb ccc fff aaa bbb aaa bbb");
+        let grams = |grams: &[(&str, u32)]| -> Vec<(String, u32)> {
+            grams.iter().map(|&(g, c)| (g.to_owned(), c)).collect()
+        };
+        let explanation = text.explain_match(&license);
+        assert_eq!(grams(&[("aaa bbb", 2), ("bbb ccc", 1)]), explanation.shared);
+        assert_eq!(
+            grams(&[
+                ("aaa bbb", 1),
+                ("bbb aaa", 1),
+                ("ccc fff", 1),
+                ("fff aaa", 1)
+            ]),
+            explanation.extra
+        );
+        assert_eq!(
+            grams(&[("bbb ddd", 1), ("ccc aaa", 1), ("ddd eee", 1)]),
+            explanation.missing
+        );
+
+        // the counts add up to the score
+        let count = |grams: &[(String, u32)]| grams.iter().map(|g| g.1).sum::<u32>() as f32;
+        let shared = count(&explanation.shared);
+        let total = 2.0 * shared + count(&explanation.extra) + count(&explanation.missing);
+        assert_eq!(explanation.score, 2.0 * shared / total);
+        assert_eq!(text.match_score(&license), explanation.score);
+    }
+
+    #[test]
+    fn explain_match() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc ddddd".into());
+        let text = TextData::from("aaaaa bbbbb ccccc eeeee");
+        let matched = store.analyze(&text);
+        let explanation = matched.explain(&text);
+        assert_eq!(matched.score, explanation.score);
+        assert_eq!(vec![("ccccc ddddd".to_owned(), 1)], explanation.missing);
+        assert_eq!(vec![("ccccc eeeee".to_owned(), 1)], explanation.extra);
+    }
+}
@@ -0,0 +1,87 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{fs::File, io::Read, path::Path};
+
+use anyhow::{format_err, Error};
+
+use crate::{
+    encoding::{decode_text, decode_text_prefix},
+    license::TextData,
+    limits::InputLimits,
+};
+
+impl TextData {
+    /// Read a file and create a `TextData` from its contents.
+    ///
+    /// The file's encoding is detected as with `decode_text`.
+    ///
+    /// Requires the `fs` feature.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<TextData, Error> {
+        TextData::from_file_with_limits(path, &InputLimits::default())
+    }
+
+    /// Read a file as with `from_file`, subject to size limits.
+    ///
+    /// With a byte limit set, only as much of the file is read as is needed
+    /// to enforce it, so oversized files can be rejected (or truncated)
+    /// without reading them whole. See `InputLimits`.
+    pub fn from_file_with_limits<P: AsRef<Path>>(
+        path: P,
+        limits: &InputLimits,
+    ) -> Result<TextData, Error> {
+        let path = path.as_ref();
+        let text = read_text(path, limits)
+            .map_err(|e| format_err!("couldn't read {}: {}", path.display(), e))?;
+        TextData::new_with_limits(&text, limits)
+            .map_err(|e| format_err!("{}: {}", path.display(), e))
+    }
+}
+
+fn read_text(path: &Path, limits: &InputLimits) -> Result<String, Error> {
+    // every encoding read here takes at most two bytes for each byte of
+    // UTF-8, so this is always enough to exceed a byte limit if the file
+    // does, with room for a byte order mark
+    let max_read = limits.max_bytes.map_or(u64::MAX, |max_bytes| {
+        (max_bytes as u64).saturating_mul(2).saturating_add(4)
+    });
+    let mut bytes = Vec::new();
+    File::open(path)?.take(max_read).read_to_end(&mut bytes)?;
+    // only the start of the file was read if it reached the limit
+    let truncated = bytes.len() as u64 == max_read;
+    Ok(if truncated {
+        decode_text_prefix(&bytes)
+    } else {
+        decode_text(&bytes)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_file() {
+        let path = std::env::temp_dir().join(format!("askalono-{}.txt", std::process::id()));
+        std::fs::write(&path, b"\xff\xfea\0a\0a\0a\0a\0\n\0b\0b\0b\0b\0b\0").unwrap();
+
+        let data = TextData::from_file(&path).unwrap();
+        assert_eq!(["aaaaa", "bbbbb"], data.lines());
+
+        let limits = InputLimits {
+            max_bytes: Some(5),
+            ..Default::default()
+        };
+        assert!(TextData::from_file_with_limits(&path, &limits).is_err());
+        let limits = InputLimits {
+            truncate: true,
+            ..limits
+        };
+        let data = TextData::from_file_with_limits(&path, &limits).unwrap();
+        assert_eq!(["aaaaa"], data.lines());
+
+        std::fs::remove_file(&path).unwrap();
+        let error = TextData::from_file(&path).unwrap_err().to_string();
+        assert!(error.starts_with("couldn't read"), "{}", error);
+    }
+}
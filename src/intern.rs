@@ -0,0 +1,301 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! A process-wide table of the words found in store data, so that n-grams
+//! can refer to them by number instead of holding strings.
+//!
+//! The table is shared by every `Store`, so that the n-grams of any text can
+//! be compared to a store's by their numbers alone. Only the words of store
+//! data (licenses, stop grams, and the like) are added, as it's built or
+//! loaded. The words of scanned text, and of any other text that's only
+//! compared against stores, are only looked up, since a word that isn't in
+//! the table can't be in any license; see `NgramSet`.
+//!
+//! Words can't be removed, since any gram may still refer to them, so the
+//! table holds the words of every store loaded by the process until it
+//! exits. That grows with the distinct words of the store data loaded,
+//! never with how much is scanned: loading the same licenses again adds
+//! nothing, and licenses share most of their words. Should the table ever
+//! fill up, adding a word fails with an error instead; grams of words that
+//! couldn't be added are kept spelled out, as for scanned text.
+//!
+//! Words are stored in segments that never move once allocated, so looking
+//! words up and resolving their numbers doesn't take a lock; only adding
+//! words does.
+
+use std::{
+    borrow::Cow,
+    hash::Hasher,
+    sync::{
+        atomic::{AtomicU32, AtomicUsize, Ordering},
+        Mutex, MutexGuard, OnceLock,
+    },
+};
+
+use anyhow::{format_err, Error};
+
+use crate::memo::FnvHasher;
+
+/// The size of the first segment of words. Each segment after it is twice
+/// the size of the one before.
+const FIRST_SEGMENT: usize = 1024;
+
+/// The most segments of words, and of indexes. This is enough segments for
+/// nearly every `u32` to number a word.
+const SEGMENTS: usize = 22;
+
+/// A segment of words, each set once it's added.
+type Segment = Box<[OnceLock<Cow<'static, str>>]>;
+
+/// Word numbers plus one, open-addressed by the hash of their words, with
+/// zero for an empty slot.
+type Index = Box<[AtomicU32]>;
+
+struct Table {
+    /// The words, by number, in segments.
+    words: [OnceLock<Segment>; SEGMENTS],
+    len: AtomicU32,
+    /// Indexes of the words, each twice the size of the last. Once one is
+    /// half full, the next replaces it (as `current`). Lookups may still be
+    /// reading the old ones, so they're kept, but together they take no more
+    /// room than the current one.
+    indexes: [OnceLock<Index>; SEGMENTS],
+    current: AtomicUsize,
+    /// Held while adding words.
+    adding: Mutex<()>,
+}
+
+static TABLE: Table = Table {
+    words: [const { OnceLock::new() }; SEGMENTS],
+    len: AtomicU32::new(0),
+    indexes: [const { OnceLock::new() }; SEGMENTS],
+    current: AtomicUsize::new(0),
+    adding: Mutex::new(()),
+};
+
+/// Find the segment of a word number, and its offset within it.
+fn locate(id: u32) -> (usize, usize) {
+    let segment = (id as usize / FIRST_SEGMENT + 1).ilog2() as usize;
+    let start = FIRST_SEGMENT * ((1 << segment) - 1);
+    (segment, id as usize - start)
+}
+
+fn hash(word: &str) -> usize {
+    let mut hasher = FnvHasher::default();
+    hasher.write(word.as_bytes());
+    hasher.finish() as usize
+}
+
+impl Table {
+    fn word(&'static self, id: u32) -> Option<&'static str> {
+        let (segment, offset) = locate(id);
+        let word = self.words.get(segment)?.get()?.get(offset)?.get()?;
+        Some(word)
+    }
+
+    fn index(&'static self) -> Option<&'static Index> {
+        self.indexes[self.current.load(Ordering::Acquire)].get()
+    }
+
+    /// Find a word in an index: its number, or else the empty slot where it
+    /// would go.
+    fn find(&'static self, index: &Index, word: &str) -> Result<u32, usize> {
+        let mask = index.len() - 1;
+        let mut slot = hash(word) & mask;
+        loop {
+            match index[slot].load(Ordering::Acquire) {
+                0 => return Err(slot),
+                n if self.word(n - 1) == Some(word) => return Ok(n - 1),
+                _ => slot = (slot + 1) & mask,
+            }
+        }
+    }
+
+    /// Add a word if it's new, returning its number, or an error if there's
+    /// no room for it.
+    fn insert(
+        &'static self,
+        _adding: &MutexGuard<'_, ()>,
+        word: Cow<'static, str>,
+    ) -> Result<u32, Error> {
+        // another thread may have added it since it was looked up
+        if let Some(index) = self.index() {
+            if let Ok(id) = self.find(index, &word) {
+                return Ok(id);
+            }
+        }
+
+        let id = self.len.load(Ordering::Acquire);
+        let (segment, offset) = locate(id);
+        if segment >= SEGMENTS {
+            return Err(format_err!("token table is full; can't add '{}'", word));
+        }
+        let index = self.reserve(id as usize + 1);
+        let hash_slot = self.find(index, &word).unwrap_err();
+        let words = self.words[segment].get_or_init(|| {
+            (0..FIRST_SEGMENT << segment)
+                .map(|_| OnceLock::new())
+                .collect()
+        });
+        // a word is stored before anything can refer to it by number
+        let _ = words[offset].set(word);
+        index[hash_slot].store(id + 1, Ordering::Release);
+        self.len.store(id + 1, Ordering::Release);
+        Ok(id)
+    }
+
+    /// Get an index with room for this many words, replacing the current one
+    /// if it's too full.
+    fn reserve(&'static self, len: usize) -> &'static Index {
+        let current = self.current.load(Ordering::Acquire);
+        if let Some(index) = self.indexes[current].get() {
+            if len * 2 <= index.len() {
+                return index;
+            }
+        }
+
+        let next = match self.indexes[current].get() {
+            Some(_) => current + 1,
+            None => current,
+        };
+        let index: Index = (0..(2 * FIRST_SEGMENT) << next)
+            .map(|_| AtomicU32::new(0))
+            .collect();
+        for id in 0..self.len.load(Ordering::Acquire) {
+            let word = self.word(id).expect("words are stored in order");
+            let slot = self.find(&index, word).unwrap_err();
+            index[slot].store(id + 1, Ordering::Relaxed);
+        }
+        let _ = self.indexes[next].set(index);
+        self.current.store(next, Ordering::Release);
+        self.indexes[next].get().unwrap()
+    }
+}
+
+/// Get the number of a word, adding it to the table if it's new. Only store
+/// data should be added; see the module docs.
+pub(crate) fn intern(token: &str) -> Result<u32, Error> {
+    if let Some(id) = lookup(token) {
+        return Ok(id);
+    }
+    let adding = TABLE.adding.lock().unwrap();
+    TABLE.insert(&adding, Cow::Owned(token.to_owned()))
+}
+
+/// Get the numbers of several words at once, which takes the table's lock
+/// at most once.
+pub(crate) fn intern_all<'a, I>(words: I) -> Result<Vec<u32>, Error>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let words: Vec<&str> = words.into_iter().collect();
+    let found = lookup_all(words.iter().copied());
+    if found.iter().all(Option::is_some) {
+        return Ok(found.into_iter().flatten().collect());
+    }
+
+    let adding = TABLE.adding.lock().unwrap();
+    words
+        .into_iter()
+        .zip(found)
+        .map(|(word, id)| match id {
+            Some(id) => Ok(id),
+            None => TABLE.insert(&adding, Cow::Owned(word.to_owned())),
+        })
+        .collect()
+}
+
+/// Get the numbers of words that last as long as the program, such as those
+/// compiled into it. New words are added without being copied.
+#[cfg(feature = "static-store")]
+pub(crate) fn intern_static(words: &[&'static str]) -> Result<Vec<u32>, Error> {
+    let adding = TABLE.adding.lock().unwrap();
+    words
+        .iter()
+        .map(|&word| TABLE.insert(&adding, Cow::Borrowed(word)))
+        .collect()
+}
+
+/// Get the number of a word, if it's in the table.
+pub(crate) fn lookup(token: &str) -> Option<u32> {
+    TABLE.find(TABLE.index()?, token).ok()
+}
+
+/// Get the numbers of several words at once, where they're in the table.
+pub(crate) fn lookup_all<'a, I>(words: I) -> Vec<Option<u32>>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let index = TABLE.index();
+    words
+        .into_iter()
+        .map(|word| TABLE.find(index?, word).ok())
+        .collect()
+}
+
+/// The number of words in the table. Words added later get numbers at least
+/// this high.
+pub(crate) fn len() -> u32 {
+    TABLE.len.load(Ordering::Acquire)
+}
+
+/// Get the word with the given number.
+///
+/// # Panics
+///
+/// Panics if no word has that number.
+pub(crate) fn resolve(id: u32) -> &'static str {
+    TABLE.word(id).expect("no word has that number")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interning() {
+        let id = intern("interningtestword").unwrap();
+        assert_eq!(id, intern("interningtestword").unwrap());
+        assert_eq!(Some(id), lookup("interningtestword"));
+        assert_eq!("interningtestword", resolve(id));
+        assert_eq!(None, lookup("interningtestunseen"));
+
+        let ids = intern_all(["interningtestword", "interningtestother"]).unwrap();
+        assert_eq!(id, ids[0]);
+        assert_ne!(id, ids[1]);
+        assert_eq!(
+            vec![Some(id), None],
+            lookup_all(["interningtestword", "interningtestunseen"])
+        );
+    }
+
+    #[test]
+    fn segments() {
+        assert_eq!((0, 0), locate(0));
+        assert_eq!((0, 1023), locate(1023));
+        assert_eq!((1, 0), locate(1024));
+        assert_eq!((1, 2047), locate(3071));
+        assert_eq!((2, 0), locate(3072));
+        // the last number there's room for
+        assert_eq!(SEGMENTS - 1, locate(u32::MAX - 1024).0);
+        assert_eq!(SEGMENTS, locate(u32::MAX - 1023).0);
+    }
+
+    #[test]
+    fn growing() {
+        // enough words to fill a few segments and indexes, from several
+        // threads at once
+        let words: Vec<String> = (0..5000).map(|i| format!("growingtest{}", i)).collect();
+        let ids: Vec<Vec<u32>> = std::thread::scope(|scope| {
+            let threads: Vec<_> = (0..4)
+                .map(|_| scope.spawn(|| words.iter().map(|word| intern(word).unwrap()).collect()))
+                .collect();
+            threads.into_iter().map(|t| t.join().unwrap()).collect()
+        });
+        assert!(ids.iter().all(|thread| *thread == ids[0]));
+        for (word, &id) in words.iter().zip(&ids[0]) {
+            assert_eq!(word, resolve(id));
+            assert_eq!(Some(id), lookup(word));
+        }
+    }
+}
@@ -5,18 +5,65 @@
 //!
 //! To get started, have a look at the `Store` struct, or one of the examples
 //! in the `examples` directory.
+//!
+//! askalono emits `tracing` spans around its more expensive operations
+//! (`Store::analyze`, `TextData::optimize_bounds`, and each window of a
+//! TopDown scan), each carrying an `elapsed_ms` field once complete. Install a
+//! `tracing` subscriber to collect them.
 
 #![warn(missing_docs)]
 #![allow(clippy::match_bool, clippy::useless_format)]
 
+pub mod compat;
+
+mod comments;
+mod deviation;
+mod encoding;
+mod explain;
+#[cfg(feature = "fs")]
+mod file;
+mod intern;
 mod license;
+mod limits;
+mod memo;
 mod ngram;
 mod preproc;
+mod scanner;
+mod schema;
 mod store;
 mod strategy;
+mod stream;
+mod timing;
 
 pub use crate::{
-    license::{LicenseType, TextData},
-    store::{Match, Store},
-    strategy::{ContainedResult, IdentifiedLicense, ScanMode, ScanResult, ScanStrategy},
+    comments::{extract_comments, CommentStyle},
+    deviation::{Deviation, DeviationKind, DiffChunk, DiffKind},
+    encoding::{decode_text, decode_text_prefix, looks_binary},
+    explain::MatchExplanation,
+    license::{LicenseType, TextData, TokenBounds},
+    limits::InputLimits,
+    memo::ResultCache,
+    preproc::{strip_html, strip_markdown, Markup, TextDataOptions},
+    scanner::Scanner,
+    schema::{ContainedRecord, FragmentRecord, ScanRecord, SCHEMA_VERSION},
+    store::{
+        AnalysisOptions, HeaderOptions, LocatedMatch, Match, MatchIter, MergePolicy, Provenance,
+        Scorer, Store, StoreInfo, ValidationIssue, DEFAULT_AMBIGUITY_EPSILON,
+    },
+    strategy::{
+        ContainedResult, FragmentResult, IdentifiedLicense, ScanMetrics, ScanMode, ScanResult,
+        ScanStrategy, ScanStrategyBuilder,
+    },
+    stream::ScanStream,
 };
+
+#[cfg(feature = "embedded-cache")]
+pub use crate::store::global_store;
+#[cfg(feature = "disk-store")]
+pub use crate::store::DiskStore;
+#[cfg(not(target_arch = "wasm32"))]
+pub use crate::store::StoreHandle;
+#[cfg(feature = "spdx")]
+pub use crate::store::{SpdxLoadOptions, SpdxUpdate};
+#[cfg(feature = "static-store")]
+pub use crate::store::{StaticCounts, StaticLicense, StaticStore};
@@ -1,13 +1,28 @@
 // Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::{collections::HashMap, fmt};
+use std::{
+    borrow::Cow,
+    collections::{hash_map, HashMap, HashSet},
+    fmt,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
 
+use anyhow::Error;
 use serde::{Deserialize, Serialize};
+use tracing::{debug_span, field};
 
 use crate::{
-    ngram::NgramSet,
-    preproc::{apply_aggressive, apply_normalizers},
+    comments::extract_comments,
+    limits::InputLimits,
+    ngram::{Gram, NgramSet, SlidingGrams, WindowGrams},
+    preproc::{
+        apply_aggressive_with, apply_normalizers_with, strip_html_tracked, strip_markdown,
+        wildcard_template, Markup, TextDataOptions, TEMPLATE_WILDCARD,
+    },
+    strategy::ScanMetrics,
+    timing::{timed, Stopwatch},
 };
 
 /// The type of a license entry (typically in a `Store`).
@@ -74,12 +89,90 @@ impl fmt::Display for LicenseType {
 /// # Ok(())
 /// # }
 /// ```
+///
+/// Two `TextData` structures are considered equal (and hash identically) when
+/// their n-gram content and line views are the same; the stored text itself
+/// isn't compared. This makes it possible to de-duplicate identical inputs
+/// using a `HashSet` or `HashMap`.
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct TextData {
-    match_data: NgramSet,
+    pub(crate) match_data: NgramSet,
     lines_view: (usize, usize),
-    lines_normalized: Option<Vec<String>>,
+    /// Shared between views of the same text, so that `with_view` is cheap.
+    lines_normalized: Option<Arc<Vec<String>>>,
     text_processed: Option<String>,
+    /// How the text was preprocessed, so that views of it are processed the
+    /// same way. Not kept in caches; stored licenses always use the defaults.
+    #[serde(skip)]
+    options: TextDataOptions,
+    /// The text this was created from, shared between views like
+    /// `lines_normalized`. Not kept in caches.
+    #[serde(skip)]
+    original: Option<Arc<OriginalText>>,
+}
+
+/// The text a `TextData` was created from, and how its lines relate to the
+/// normalized ones.
+#[derive(Debug)]
+struct OriginalText {
+    text: String,
+    /// The byte and character offsets at which each line starts.
+    line_starts: Vec<(usize, usize)>,
+    /// The original line that each normalized line came from, where
+    /// preprocessing didn't keep lines as they were.
+    line_map: Option<Vec<usize>>,
+}
+
+impl OriginalText {
+    fn new(text: &str, line_map: Option<Vec<usize>>) -> OriginalText {
+        let mut line_starts = vec![(0, 0)];
+        for (chars, (bytes, c)) in text.char_indices().enumerate() {
+            if c == '\n' {
+                line_starts.push((bytes + 1, chars + 1));
+            }
+        }
+        OriginalText {
+            text: text.to_owned(),
+            line_starts,
+            line_map,
+        }
+    }
+
+    /// Map a range of normalized lines to the original lines they came
+    /// from.
+    fn map_range(&self, (start, end): (usize, usize)) -> (usize, usize) {
+        let line_map = match self.line_map {
+            Some(ref line_map) => line_map,
+            None => return (start, end),
+        };
+        let total = self.line_starts.len();
+        let start_line = line_map.get(start).copied().unwrap_or(total);
+        if end <= start {
+            return (start_line, start_line);
+        }
+        let end_line = line_map
+            .get(end - 1)
+            .map_or(total, |&line| line + 1)
+            .max(start_line);
+        (start_line, end_line)
+    }
+
+    /// Find the byte and character offsets spanned by a range of original
+    /// lines, not including the line break (`\n` or `\r\n`) that ends the
+    /// last one.
+    fn offsets(&self, (start, end): (usize, usize)) -> ((usize, usize), (usize, usize)) {
+        let text_end = (self.text.len(), self.text.chars().count());
+        let line_start = |line: usize| self.line_starts.get(line).copied().unwrap_or(text_end);
+        let first = line_start(start);
+        let last = match self.line_starts.get(end) {
+            Some(&(bytes, chars)) if end > start => {
+                let cr = self.text.as_bytes()[..bytes - 1].ends_with(b"\r") as usize;
+                (bytes - 1 - cr, chars - 1 - cr)
+            }
+            _ => line_start(end.max(start)),
+        };
+        ((first.0, last.0), (first.1, last.1))
+    }
 }
 
 const TEXTDATA_TEXT_ERROR: &str = "TextData does not have original text";
@@ -95,17 +188,104 @@ impl TextData {
     /// this library you want to keep the text data, but askalono will throw it
     /// away in its own `Store` as it's not needed.
     pub fn new(text: &str) -> TextData {
-        let normalized = apply_normalizers(text);
+        TextData::new_with_options(text, TextDataOptions::default())
+    }
+
+    /// Create a new TextData structure from a string, choosing which
+    /// preprocessing steps to apply. See `TextDataOptions`.
+    ///
+    /// Views of the text (see `with_view` and `optimize_bounds`) are
+    /// preprocessed the same way.
+    pub fn new_with_options(text: &str, options: TextDataOptions) -> TextData {
+        let original_text = text;
+        // everything else keeps lines as they are
+        let (stripped, line_map) = match options.markup() {
+            Some(Markup::Html) => {
+                let stripped = strip_html_tracked(text);
+                let line_map = stripped.line_origins(text);
+                (Some(stripped.into_string()), Some(line_map))
+            }
+            Some(Markup::Markdown) => (Some(strip_markdown(text)), None),
+            None => (None, None),
+        };
+        let text = stripped.as_deref().unwrap_or(text);
+        let comments = options
+            .comment_style()
+            .map(|style| extract_comments(text, style));
+        let text = comments.as_deref().unwrap_or(text);
+        let normalized = apply_normalizers_with(text, &options);
+        let original = OriginalText::new(original_text, line_map);
         let normalized_joined = normalized.join("\n");
-        let processed = apply_aggressive(&normalized_joined);
-        let match_data = NgramSet::from_str(&processed, 2);
+        let processed = apply_aggressive_with(&normalized_joined, &options);
+        let match_data = NgramSet::from_str(&processed);
 
         TextData {
             match_data,
             lines_view: (0, normalized.len()),
-            lines_normalized: Some(normalized),
+            lines_normalized: Some(Arc::new(normalized)),
             text_processed: Some(processed),
+            options,
+            original: Some(Arc::new(original)),
+        }
+    }
+
+    /// Create a TextData structure from n-grams alone, without text.
+    pub(crate) fn from_match_data(match_data: NgramSet) -> TextData {
+        TextData {
+            match_data,
+            lines_view: (0, 0),
+            lines_normalized: None,
+            text_processed: None,
+            options: TextDataOptions::default(),
+            original: None,
+        }
+    }
+
+    /// Reassemble a TextData structure from its parts, as found in caches
+    /// written by earlier versions.
+    pub(crate) fn from_parts(
+        match_data: NgramSet,
+        lines_view: (usize, usize),
+        lines_normalized: Option<Vec<String>>,
+        text_processed: Option<String>,
+    ) -> TextData {
+        TextData {
+            match_data,
+            lines_view,
+            lines_normalized: lines_normalized.map(Arc::new),
+            text_processed,
+            options: TextDataOptions::default(),
+            original: None,
+        }
+    }
+
+    /// Create a new TextData structure from a string, subject to size limits.
+    ///
+    /// If the text exceeds the given limits, either an error is returned or
+    /// the text is truncated before processing, depending on the `truncate`
+    /// setting. See `InputLimits` for details.
+    pub fn new_with_limits(text: &str, limits: &InputLimits) -> Result<TextData, Error> {
+        Ok(TextData::new(limits.apply(text)?))
+    }
+
+    /// Match using an SPDX license template (`standardLicenseTemplate`)
+    /// instead of this text, per the SPDX matching guidelines.
+    ///
+    /// Variable text (`<<var;...>>`) and optional text
+    /// (`<<beginOptional>>...<<endOptional>>`) are left out of the n-grams,
+    /// and no n-gram spans them, so whatever a copy of the license
+    /// substitutes there doesn't count against it. The stored text is left
+    /// as it is, for optimizing and diffing.
+    pub fn with_template(mut self, template: &str) -> TextData {
+        let normalized =
+            apply_normalizers_with(&wildcard_template(template), &self.options).join("\n");
+        let processed = apply_aggressive_with(&normalized, &self.options);
+        let mut match_data = NgramSet::new();
+        for fixed in processed.split(TEMPLATE_WILDCARD) {
+            match_data.analyze(fixed.trim());
         }
+        self.match_data = match_data;
+        self
     }
 
     /// Consume this `TextData`, returning one without normalized/processed
@@ -114,12 +294,7 @@ impl TextData {
     /// Unless you know you don't want the text, you probably don't want to use
     /// this. Other methods on `TextData` require that text is present.
     pub fn without_text(self) -> Self {
-        TextData {
-            match_data: self.match_data,
-            lines_view: (0, 0),
-            lines_normalized: None,
-            text_processed: None,
-        }
+        TextData::from_match_data(self.match_data)
     }
 
     /// Get the bounds of the active line view.
@@ -136,6 +311,77 @@ impl TextData {
         self.lines_view
     }
 
+    /// Get the bounds of the active line view in the text this `TextData`
+    /// was created from, rather than in its normalized lines.
+    ///
+    /// Most preprocessing keeps lines as they are, so this is usually the
+    /// same as `lines_view`. Stripping HTML (see `TextDataOptions::strip_markup`)
+    /// doesn't, and this is how to find where a match is in such a file.
+    pub fn original_lines_view(&self) -> (usize, usize) {
+        self.to_original_lines(self.lines_view)
+    }
+
+    /// Map a range of normalized lines, such as a `ContainedResult`'s
+    /// `line_range` from scanning this text, to the corresponding lines of
+    /// the text this `TextData` was created from. See `original_lines_view`.
+    ///
+    /// Ranges are returned unchanged if the original text isn't known, as
+    /// for licenses loaded from a cache.
+    pub fn to_original_lines(&self, range: (usize, usize)) -> (usize, usize) {
+        match self.original {
+            Some(ref original) => original.map_range(range),
+            None => range,
+        }
+    }
+
+    /// Get the text of the lines in view exactly as it was given, before any
+    /// preprocessing, with lines separated by newlines.
+    ///
+    /// After `optimize_bounds`, this is the snippet of the input that
+    /// matched. Returns `None` if the original text isn't known, as for
+    /// licenses loaded from a cache.
+    ///
+    /// ```
+    /// use askalono::TextData;
+    ///
+    /// let text = TextData::new("fn main() {}\n// Licensed under the\n// *MIT License*\n");
+    /// let view = text.with_view(1, 3);
+    /// assert_eq!(Some("// Licensed under the\n// *MIT License*".to_owned()), view.original_text());
+    /// ```
+    pub fn original_text(&self) -> Option<String> {
+        let (bytes, _) = self.original_offsets()?;
+        let original = self.original.as_ref()?;
+        Some(original.text[bytes.0..bytes.1].to_owned())
+    }
+
+    /// Get the byte and character offsets, as 0-indexed (inclusive,
+    /// exclusive) ranges, of the lines in view in the text this `TextData`
+    /// was created from. The line break ending the last line (`\n` or
+    /// `\r\n`) isn't included.
+    ///
+    /// These suit editors and other tools that highlight spans of text
+    /// rather than lines. Returns `None` if the original text isn't known, as
+    /// for licenses loaded from a cache.
+    ///
+    /// ```
+    /// use askalono::TextData;
+    ///
+    /// let text = TextData::new("© 2018 Jane Doe\nLicensed under the MIT License\n");
+    /// let (bytes, chars) = text.with_view(1, 2).original_offsets().unwrap();
+    /// assert_eq!((17, 47), bytes);
+    /// assert_eq!((16, 46), chars);
+    /// ```
+    pub fn original_offsets(&self) -> Option<((usize, usize), (usize, usize))> {
+        let original = self.original.as_ref()?;
+        Some(original.offsets(self.original_lines_view()))
+    }
+
+    /// Add the words of this text to the token table, as is done for every
+    /// text put in a store; see `NgramSet::intern_words`.
+    pub(crate) fn intern_words(&mut self) {
+        self.match_data.intern_words();
+    }
+
     /// Clone this `TextView`, creating a copy with the given view.
     ///
     /// This will re-generate match data for the given view. It's used in
@@ -147,12 +393,14 @@ impl TextData {
     pub fn with_view(&self, start: usize, end: usize) -> Self {
         let view = &self.lines_normalized.as_ref().expect(TEXTDATA_TEXT_ERROR)[start..end];
         let view_joined = view.join("\n");
-        let processed = apply_aggressive(&view_joined);
+        let processed = apply_aggressive_with(&view_joined, &self.options);
         TextData {
-            match_data: NgramSet::from_str(&processed, 2),
+            match_data: NgramSet::from_str(&processed),
             lines_view: (start, end),
             lines_normalized: self.lines_normalized.clone(),
             text_processed: Some(processed),
+            options: self.options,
+            original: self.original.clone(),
         }
     }
 
@@ -180,12 +428,14 @@ impl TextData {
             })
             .collect();
 
-        let processed = apply_aggressive(&new_normalized.join("\n"));
+        let processed = apply_aggressive_with(&new_normalized.join("\n"), &self.options);
         TextData {
-            match_data: NgramSet::from_str(&processed, 2),
+            match_data: NgramSet::from_str(&processed),
             lines_view: (0, new_normalized.len()),
-            lines_normalized: Some(new_normalized),
+            lines_normalized: Some(Arc::new(new_normalized)),
             text_processed: Some(processed),
+            options: self.options,
+            original: self.original.clone(),
         }
     }
 
@@ -195,6 +445,69 @@ impl TextData {
             [self.lines_view.0..self.lines_view.1]
     }
 
+    /// Get the normalized lines in view, or `None` if this `TextData` was
+    /// stored without text.
+    pub(crate) fn lines_in_view(&self) -> Option<&[String]> {
+        let lines = self.lines_normalized.as_ref()?;
+        Some(&lines[self.lines_view.0..self.lines_view.1])
+    }
+
+    /// Get the options this text was preprocessed with.
+    pub(crate) fn options(&self) -> TextDataOptions {
+        self.options
+    }
+
+    /// Get the normalized lines in view joined into a single string, or
+    /// `None` if this `TextData` was stored without text.
+    pub(crate) fn normalized_text(&self) -> Option<String> {
+        let lines = self.lines_normalized.as_ref()?;
+        Some(lines[self.lines_view.0..self.lines_view.1].join("\n"))
+    }
+
+    /// Iterate over the words askalono compares: the text in view after
+    /// aggressive preprocessing, in order.
+    ///
+    /// Returns `None` if this `TextData` was stored without text.
+    ///
+    /// ```
+    /// use askalono::TextData;
+    ///
+    /// let text = TextData::new("Copyright (c) 2018 Jane Doe\n\nAll rights reserved.");
+    /// let tokens: Vec<&str> = text.tokens().unwrap().collect();
+    /// assert_eq!(vec!["all", "rights", "reserved"], tokens);
+    /// ```
+    pub fn tokens(&self) -> Option<impl Iterator<Item = &str> + '_> {
+        let processed = self.text_processed.as_ref()?;
+        Some(processed.split(' ').filter(|token| !token.is_empty()))
+    }
+
+    /// Iterate over the n-grams (word bigrams) of this text and how often
+    /// each occurs. These are what `match_score` compares, before any of a
+    /// `Store`'s stop grams are removed.
+    ///
+    /// Unlike `tokens`, this is available even without text, unless its
+    /// n-grams are hashed (see `NgramSet::hash_grams`): then they're spelled
+    /// out from the text, and there are none without it. The order of
+    /// n-grams is unspecified.
+    pub fn ngrams(&self) -> Box<dyn Iterator<Item = (String, u32)> + '_> {
+        if !self.match_data.is_hashed() {
+            return Box::new(self.match_data.spelled());
+        }
+        let mut spelled = NgramSet::new();
+        if let Some(processed) = &self.text_processed {
+            spelled.analyze(processed);
+        }
+        let grams: Vec<(String, u32)> = spelled
+            .spelled()
+            .map(|(gram, _)| {
+                let count = self.match_data.get_str(&gram);
+                (gram, count)
+            })
+            .filter(|&(_, count)| count > 0)
+            .collect();
+        Box::new(grams.into_iter())
+    }
+
     #[doc(hidden)]
     pub fn text_processed(&self) -> Option<&str> {
         self.text_processed.as_ref().map(String::as_ref)
@@ -207,6 +520,85 @@ impl TextData {
         self.match_data.dice(&other.match_data)
     }
 
+    /// Compute how much of this text appears within another, from 0 to 1.
+    ///
+    /// This is useful for spotting a fragment of a larger text: a single
+    /// clause of a license will score poorly with `match_score` against the
+    /// whole license, but highly here.
+    pub fn containment_score(&self, other: &TextData) -> f32 {
+        self.match_data.containment(&other.match_data)
+    }
+
+    /// Get this text's n-grams with the given stop grams removed. See
+    /// `Store::add_stop_grams`.
+    pub(crate) fn match_data_excluding(&self, stop_grams: &HashSet<Gram>) -> Cow<'_, NgramSet> {
+        if stop_grams.is_empty() {
+            return Cow::Borrowed(&self.match_data);
+        }
+        let mut match_data = self.match_data.clone();
+        match_data.remove_grams(stop_grams);
+        Cow::Owned(match_data)
+    }
+
+    /// Get a copy of this text with the given stop grams removed from its
+    /// n-grams.
+    pub(crate) fn excluding_grams(&self, stop_grams: &HashSet<Gram>) -> Cow<'_, TextData> {
+        match self.match_data_excluding(stop_grams) {
+            Cow::Borrowed(_) => Cow::Borrowed(self),
+            Cow::Owned(match_data) => Cow::Owned(TextData {
+                match_data,
+                lines_view: self.lines_view,
+                lines_normalized: self.lines_normalized.clone(),
+                text_processed: self.text_processed.clone(),
+                options: self.options,
+                original: self.original.clone(),
+            }),
+        }
+    }
+
+    /// Get a copy of this text with its n-grams rebuilt after replacing
+    /// tokens. `replace` is given each token and returns its replacement, if
+    /// it should be replaced.
+    ///
+    /// Only the n-grams and processed text change; the normalized lines are
+    /// kept as-is. Returns `self` if there's no text or nothing was replaced.
+    pub(crate) fn replacing_tokens<'r, F>(&self, mut replace: F) -> Cow<'_, TextData>
+    where
+        F: FnMut(&str) -> Option<&'r str>,
+    {
+        let processed = match self.text_processed {
+            Some(ref processed) => processed,
+            None => return Cow::Borrowed(self),
+        };
+
+        let mut replaced = false;
+        let mut new_processed = String::with_capacity(processed.len());
+        for (i, token) in processed.split(' ').enumerate() {
+            if i > 0 {
+                new_processed.push(' ');
+            }
+            match replace(token) {
+                Some(replacement) => {
+                    new_processed.push_str(replacement);
+                    replaced = true;
+                }
+                None => new_processed.push_str(token),
+            }
+        }
+        if !replaced {
+            return Cow::Borrowed(self);
+        }
+
+        Cow::Owned(TextData {
+            match_data: NgramSet::from_str(&new_processed),
+            lines_view: self.lines_view,
+            lines_normalized: self.lines_normalized.clone(),
+            text_processed: Some(new_processed),
+            options: self.options,
+            original: self.original.clone(),
+        })
+    }
+
     #[cfg(feature = "spdx")]
     pub(crate) fn eq_data(&self, other: &Self) -> bool {
         self.match_data.eq(&other.match_data)
@@ -227,57 +619,416 @@ impl TextData {
     /// You should check the value of `lines_view` on the returned struct to
     /// find the line ranges.
     pub fn optimize_bounds(&self, other: &TextData) -> (Self, f32) {
-        assert!(self.lines_normalized.is_some(), "{}", TEXTDATA_TEXT_ERROR);
+        self.optimize_bounds_with(other, &HashSet::new(), &mut Scratch::default())
+    }
+
+    /// Optimize a match as in `optimize_bounds`, disregarding the given stop
+    /// grams in this text and reusing the buffers in `scratch`. `other` is
+    /// expected to have had the stop grams removed already, as licenses in a
+    /// `Store` do.
+    pub(crate) fn optimize_bounds_with(
+        &self,
+        other: &TextData,
+        stop_grams: &HashSet<Gram>,
+        scratch: &mut Scratch,
+    ) -> (Self, f32) {
+        let lines = self.lines_normalized.as_ref().expect(TEXTDATA_TEXT_ERROR);
 
         let view = self.lines_view;
+        let span = debug_span!(
+            "optimize_bounds",
+            view_start = view.0,
+            view_end = view.1,
+            start = field::Empty,
+            end = field::Empty,
+            score = field::Empty,
+            elapsed_ms = field::Empty,
+        );
+        let _enter = span.enter();
+        let stopwatch = Stopwatch::start();
 
-        // optimize the ending bounds of the text match
-        let (end_optimized, _) = self.search_optimize(
-            &|end| self.with_view(view.0, end).match_score(other),
-            &|end| self.with_view(view.0, end),
+        let line_grams = scratch.line_grams(lines, self.options);
+        let Scratch { memo, metrics, .. } = scratch;
+        let mut window = SlidingGrams::new(
+            &line_grams.grams,
+            &line_grams.names,
+            &other.match_data,
+            stop_grams,
         );
-        let new_end = end_optimized.lines_view.1;
+        let mut score = |start: usize, end: usize| {
+            metrics.optimize_iterations += 1;
+            let (start, end) = line_grams.words(start, end);
+            window.dice(start, end)
+        };
+
+        // optimize the ending bounds of the text match
+        let (end, _) = search_optimize(view.0, view.1, memo, &mut |end| score(view.0, end));
 
         // then optimize the starting bounds
-        let (optimized, score) = end_optimized.search_optimize(
-            &|start| end_optimized.with_view(start, new_end).match_score(other),
-            &|start| end_optimized.with_view(start, new_end),
-        );
+        let (start, _) = search_optimize(view.0, end, memo, &mut |start| score(start, end));
+
+        // the search only approximates preprocessing of each window (see
+        // `LineGrams`), so score the chosen one properly
+        let optimized = self.with_view(start, end);
+        let score = optimized
+            .match_data_excluding(stop_grams)
+            .dice(&other.match_data);
+
+        span.record("start", start);
+        span.record("end", end);
+        span.record("score", score);
+        span.record("elapsed_ms", stopwatch.elapsed_ms());
+
         (optimized, score)
     }
 
-    fn search_optimize(
+    /// Count the n-grams of windows of `size` lines moving down the lines in
+    /// view, `step` lines at a time, passing each window's line range and
+    /// n-grams to `visit`. The last window ends at the end of the view.
+    ///
+    /// Rather than processing each window as `with_view` would, the text is
+    /// processed once (see `LineGrams`), and each window's counts are updated
+    /// as lines enter and leave it. The n-grams are only approximately those
+    /// of the window processed by itself.
+    pub(crate) fn for_each_window<F>(
         &self,
-        score: &dyn Fn(usize) -> f32,
-        value: &dyn Fn(usize) -> Self,
-    ) -> (Self, f32) {
-        // cache score checks, since they're kinda expensive
-        let mut memo: HashMap<usize, f32> = HashMap::new();
-        let mut check_score =
-            |index: usize| -> f32 { *memo.entry(index).or_insert_with(|| score(index)) };
-
-        fn search(score: &mut dyn FnMut(usize) -> f32, left: usize, right: usize) -> (usize, f32) {
-            if right - left <= 3 {
-                // find the index of the highest score in the remaining items
-                return (left..=right)
-                    .map(|x| (x, score(x)))
-                    .fold((0usize, 0f32), |acc, x| if x.1 >= acc.1 { x } else { acc });
+        size: usize,
+        step: usize,
+        scratch: &mut Scratch,
+        mut visit: F,
+    ) where
+        F: FnMut((usize, usize), &NgramSet),
+    {
+        let lines = self.lines_normalized.as_ref().expect(TEXTDATA_TEXT_ERROR);
+        let line_grams = scratch.line_grams(lines, self.options);
+        let keys: Vec<Option<Gram>> = line_grams.names.iter().map(|n| Gram::lookup(n)).collect();
+
+        let (view_start, view_end) = self.lines_view;
+        let mut counts: HashMap<usize, u32> = HashMap::new();
+        // the grams counted, as a range of `line_grams.grams`
+        let mut counted = (0, 0);
+        let mut start = view_start;
+        loop {
+            let end = (start + size).min(view_end);
+            let (first, last) = line_grams.words(start, end);
+            // the last word has no gram of its own
+            let high = last.saturating_sub(1);
+            let low = first.min(high);
+
+            // windows only move forward
+            if low >= counted.1 {
+                counts.clear();
+                counted = (low, low);
+            }
+            while counted.0 < low {
+                let gram = line_grams.grams[counted.0];
+                if let hash_map::Entry::Occupied(mut count) = counts.entry(gram) {
+                    *count.get_mut() -= 1;
+                    if *count.get() == 0 {
+                        count.remove();
+                    }
+                }
+                counted.0 += 1;
+            }
+            while counted.1 < high {
+                *counts.entry(line_grams.grams[counted.1]).or_insert(0) += 1;
+                counted.1 += 1;
             }
 
-            let low = (left * 2 + right) / 3;
-            let high = (left + right * 2) / 3;
-            let score_low = score(low);
-            let score_high = score(high);
+            let known = counts
+                .iter()
+                .filter_map(|(&gram, &n)| keys[gram].map(|key| (key, n)));
+            let mut set = NgramSet::from_counts(known);
+            for (&gram, &n) in &counts {
+                if keys[gram].is_none() {
+                    set.add_extra(line_grams.names[gram].clone(), n);
+                }
+            }
+            visit((start, end), &set);
+            if end >= view_end {
+                break;
+            }
+            start += step;
+        }
+    }
 
-            if score_low > score_high {
-                search(score, left, high - 1)
-            } else {
-                search(score, low + 1, right)
+    /// Find where another text is located in this one, word by word.
+    ///
+    /// This works like `optimize_bounds`, but moves the bounds a word at a
+    /// time instead of a line at a time, so it can find licenses that begin
+    /// or end mid-line, such as in minified files where everything is on one
+    /// line. Words are the whitespace-separated words of the normalized
+    /// lines (see `lines`), counted from the start of the text, and only
+    /// those within the line view are searched. It's slower than
+    /// `optimize_bounds` on texts with many words.
+    ///
+    /// ```
+    /// use askalono::TextData;
+    ///
+    /// let license = TextData::from("my first license");
+    /// let sample = TextData::from("foo(); bar(); my first license; baz();");
+    /// let bounds = sample.optimize_token_bounds(&license);
+    /// assert_eq!((2, 5), bounds.tokens);
+    /// assert_eq!((0, 1), bounds.lines);
+    /// assert!(bounds.score > 0.99);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if this `TextData` was stored without text.
+    pub fn optimize_token_bounds(&self, other: &TextData) -> TokenBounds {
+        let lines = self.lines_normalized.as_ref().expect(TEXTDATA_TEXT_ERROR);
+
+        let view = self.lines_view;
+        let skipped: usize = lines[..view.0]
+            .iter()
+            .map(|line| line.split_whitespace().count())
+            .sum();
+        let words: Vec<(usize, &str)> = (view.0..view.1)
+            .flat_map(|i| lines[i].split_whitespace().map(move |word| (i, word)))
+            .collect();
+
+        let Scratch {
+            joined,
+            window,
+            memo,
+            ..
+        } = &mut Scratch::default();
+        let mut score = |start: usize, end: usize| {
+            joined.clear();
+            for (i, &(line, word)) in words[start..end].iter().enumerate() {
+                if i > 0 {
+                    // keep line breaks, which some normalization relies on
+                    let breaks = line - words[start + i - 1].0;
+                    joined.extend(std::iter::repeat_n('\n', breaks));
+                    if breaks == 0 {
+                        joined.push(' ');
+                    }
+                }
+                joined.push_str(word);
+            }
+            window.fill(
+                &apply_aggressive_with(joined, &self.options),
+                &HashSet::new(),
+            );
+            window.dice(&other.match_data)
+        };
+
+        let (end, _) = search_optimize(0, words.len(), memo, &mut |end| score(0, end));
+        let (start, score) = search_optimize(0, end, memo, &mut |start| score(start, end));
+
+        let lines = if start < end {
+            (words[start].0, words[end - 1].0 + 1)
+        } else {
+            (view.0, view.0)
+        };
+        TokenBounds {
+            tokens: (skipped + start, skipped + end),
+            lines,
+            score,
+        }
+    }
+}
+
+/// Where a text was found within another, word by word. See
+/// `TextData::optimize_token_bounds`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TokenBounds {
+    /// The range of words the match spans, counted from the start of the
+    /// text. Like `TextData::lines_view`, the start is inclusive and the end
+    /// exclusive.
+    pub tokens: (usize, usize),
+    /// The range of lines containing those words, as in
+    /// `TextData::lines_view`. The first and last lines may hold words
+    /// outside the match.
+    pub lines: (usize, usize),
+    /// The score of the match.
+    pub score: f32,
+}
+
+/// How far ahead among the words of a text's lines `LineGrams` looks for
+/// each word that came out of preprocessing it, past words that were dropped.
+const LINE_LOOKAHEAD: usize = 256;
+
+/// The words of a whole text after aggressive preprocessing, and the line
+/// each came from, so that the n-grams of any range of lines can be counted
+/// without preprocessing that range again.
+///
+/// The words are exactly those of the whole text preprocessed as usual.
+/// They're traced back to their lines by finding each among the words of
+/// the lines themselves, in order. Only some words are dropped or replaced
+/// by preprocessing (a title, copyright statements, placeholders), so this
+/// only blurs the edges of those lines. The n-grams of a range of lines
+/// are an approximation, though: some preprocessing looks across lines,
+/// and acts differently on a range than on the whole text (a title is only
+/// removed from the start of a text, for instance). It's good enough to
+/// find the bounds of a match, which are then scored exactly.
+#[derive(Debug)]
+struct LineGrams {
+    /// The line each word came from, in order.
+    word_lines: Vec<usize>,
+    /// Each bigram of successive words, as an index into `names`.
+    grams: Vec<usize>,
+    /// The distinct bigrams.
+    names: Vec<String>,
+}
+
+impl LineGrams {
+    fn new(lines: &[String], options: &TextDataOptions) -> LineGrams {
+        let processed = apply_aggressive_with(&lines.join("\n"), options);
+        let words: Vec<&str> = processed
+            .split(' ')
+            .filter(|word| !word.is_empty())
+            .collect();
+
+        // the words of each line, roughly as preprocessing splits them
+        let line_words: Vec<(String, usize)> = lines
+            .iter()
+            .enumerate()
+            .flat_map(|(i, line)| {
+                let plain: String = line
+                    .chars()
+                    .filter(|&c| c.is_alphanumeric() || c == '_' || c.is_whitespace())
+                    .collect();
+                plain
+                    .to_lowercase()
+                    .split_whitespace()
+                    .map(|word| (word.to_owned(), i))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        // a word that isn't found (a placeholder, say) belongs to the line of
+        // the next word that wasn't
+        let last_line = lines.len().saturating_sub(1);
+        let mut next = 0;
+        let mut word_lines = Vec::with_capacity(words.len());
+        for word in &words {
+            let ahead = &line_words[next.min(line_words.len())..];
+            match ahead
+                .iter()
+                .take(LINE_LOOKAHEAD)
+                .position(|(line_word, _)| line_word == word)
+            {
+                Some(found) => {
+                    word_lines.push(ahead[found].1);
+                    next += found + 1;
+                }
+                None => word_lines.push(ahead.first().map_or(last_line, |&(_, line)| line)),
+            }
+        }
+
+        let mut ids: HashMap<String, usize> = HashMap::new();
+        let mut names = Vec::new();
+        let grams = words
+            .windows(2)
+            .map(|pair| {
+                let name = pair.join(" ");
+                *ids.entry(name).or_insert_with_key(|name| {
+                    names.push(name.clone());
+                    names.len() - 1
+                })
+            })
+            .collect();
+
+        LineGrams {
+            word_lines,
+            grams,
+            names,
+        }
+    }
+
+    /// The range of words on lines `start..end`.
+    fn words(&self, start: usize, end: usize) -> (usize, usize) {
+        (
+            self.word_lines.partition_point(|&line| line < start),
+            self.word_lines.partition_point(|&line| line < end),
+        )
+    }
+}
+
+/// Buffers reused across calls to `TextData::optimize_bounds_with`, so that
+/// scoring each window tried doesn't allocate.
+#[derive(Default)]
+pub(crate) struct Scratch {
+    /// The lines of the window being scored, joined.
+    joined: String,
+    window: WindowGrams,
+    /// Scores of windows already tried in the current search.
+    memo: HashMap<usize, f32>,
+    /// The words of the text last searched, with the lines and options they
+    /// came from. Views of a text share its lines, so they're only
+    /// preprocessed once however many times the text is searched.
+    line_grams: Option<(Arc<Vec<String>>, TextDataOptions, Arc<LineGrams>)>,
+    /// Counts for the scan in progress. See `ScanStrategyBuilder::metrics`.
+    pub(crate) metrics: ScanMetrics,
+}
+
+impl Scratch {
+    /// Get the words of the given lines, reusing those of the last text
+    /// searched if it's a view of the same one.
+    fn line_grams(&mut self, lines: &Arc<Vec<String>>, options: TextDataOptions) -> Arc<LineGrams> {
+        if let Some((ref cached_lines, cached_options, ref line_grams)) = self.line_grams {
+            if Arc::ptr_eq(cached_lines, lines) && cached_options == options {
+                return line_grams.clone();
             }
         }
+        let line_grams = Arc::new(timed(&mut self.metrics.normalize_ms, || {
+            LineGrams::new(lines, &options)
+        }));
+        self.line_grams = Some((lines.clone(), options, line_grams.clone()));
+        line_grams
+    }
+}
+
+/// Find the index between `left` and `right` (inclusive) with the highest
+/// score, assuming scores rise to a single peak.
+fn search_optimize(
+    left: usize,
+    right: usize,
+    memo: &mut HashMap<usize, f32>,
+    score: &mut dyn FnMut(usize) -> f32,
+) -> (usize, f32) {
+    // cache score checks, since they're kinda expensive
+    memo.clear();
+    let mut check_score =
+        |index: usize| -> f32 { *memo.entry(index).or_insert_with(|| score(index)) };
+
+    fn search(score: &mut dyn FnMut(usize) -> f32, left: usize, right: usize) -> (usize, f32) {
+        if right - left <= 3 {
+            // find the index of the highest score in the remaining items
+            return (left..=right)
+                .map(|x| (x, score(x)))
+                .fold((0usize, 0f32), |acc, x| if x.1 >= acc.1 { x } else { acc });
+        }
+
+        let low = (left * 2 + right) / 3;
+        let high = (left + right * 2) / 3;
+        let score_low = score(low);
+        let score_high = score(high);
+
+        if score_low > score_high {
+            search(score, left, high - 1)
+        } else {
+            search(score, low + 1, right)
+        }
+    }
+
+    search(&mut check_score, left, right)
+}
+
+impl PartialEq for TextData {
+    fn eq(&self, other: &Self) -> bool {
+        self.lines_view == other.lines_view && self.match_data == other.match_data
+    }
+}
 
-        let optimal = search(&mut check_score, self.lines_view.0, self.lines_view.1);
-        (value(optimal.0), optimal.1)
+impl Eq for TextData {}
+
+impl Hash for TextData {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.lines_view.hash(state);
+        self.match_data.hash(state);
     }
 }
 
@@ -296,10 +1047,174 @@ impl From<String> for TextData {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{comments::CommentStyle, preproc::Markup};
 
     // psst:
     // cargo test -- --nocapture
 
+    #[test]
+    fn tokens_and_ngrams() {
+        let text = TextData::new("Red, green!\nRed green blue.");
+        let tokens: Vec<&str> = text.tokens().unwrap().collect();
+        assert_eq!(vec!["red", "green", "red", "green", "blue"], tokens);
+
+        let mut ngrams: Vec<(String, u32)> = text.ngrams().collect();
+        ngrams.sort();
+        assert_eq!(
+            vec![
+                ("green blue".to_owned(), 1),
+                ("green red".to_owned(), 1),
+                ("red green".to_owned(), 2)
+            ],
+            ngrams
+        );
+
+        let view = text.with_view(1, 2);
+        assert_eq!(3, view.tokens().unwrap().count());
+
+        let stored = text.without_text();
+        assert!(stored.tokens().is_none());
+        assert_eq!(3, stored.ngrams().count());
+    }
+
+    #[test]
+    fn new_with_options() {
+        let text = "The Example License\n\nCopyright 2018 Jane Doe\n\
+                    // see https://example.com/license\n// for details";
+        assert_eq!(
+            Some("see http blackboxed url for details"),
+            TextData::new(text).text_processed()
+        );
+
+        let options = TextDataOptions::new()
+            .strip_title(false)
+            .strip_copyright(false)
+            .blackbox_urls(false)
+            .strip_common_prefixes(false);
+        let data = TextData::new_with_options(text, options);
+        assert_eq!(
+            Some(
+                "the example license copyright 2018 jane doe \
+                 see https examplecom license for details"
+            ),
+            data.text_processed()
+        );
+        // views are processed the same way
+        assert_eq!(
+            Some("the example license"),
+            data.with_view(0, 1).text_processed()
+        );
+        assert_eq!(
+            Some("copyright 2018 jane doe"),
+            data.with_view(0, 1)
+                .white_out()
+                .with_view(0, 3)
+                .text_processed()
+        );
+    }
+
+    #[test]
+    fn new_with_comments() {
+        let text = "#!/usr/bin/env python\n\
+                    # Licensed under the Example License.\n\
+                    import sys\n\
+                    print(\"hello # world\")\n";
+        let options = TextDataOptions::new().extract_comments(Some(CommentStyle::Hash));
+        let data = TextData::new_with_options(text, options);
+        assert_eq!(
+            Some("usr bin env python licensed under the example license"),
+            data.text_processed()
+        );
+        assert_eq!((0, 5), data.lines_view());
+        assert_eq!(
+            Some("licensed under the example license"),
+            data.with_view(1, 2).text_processed()
+        );
+    }
+
+    #[test]
+    fn new_with_markup() {
+        let text = "## The **Example** License\n\n<p>Permission is _granted_.</p>";
+        let options = TextDataOptions::new()
+            .strip_title(false)
+            .strip_markup(Some(Markup::Markdown));
+        assert_eq!(
+            Some("the example license permission is granted"),
+            TextData::new_with_options(text, options).text_processed()
+        );
+        let options = options.strip_markup(Some(Markup::Html));
+        assert_eq!(
+            Some("the example license permission is granted"),
+            TextData::new_with_options(
+                "<h2>The <b>Example</b> License</h2><p>Permission is <i>granted</i>.</p>",
+                options
+            )
+            .text_processed()
+        );
+    }
+
+    #[test]
+    fn original_lines() {
+        let text = "fn main() {}\n// Licensed under the MIT License\n";
+        let data = TextData::new(text).with_view(1, 2);
+        assert_eq!((1, 2), data.original_lines_view());
+        assert_eq!(
+            Some("// Licensed under the MIT License"),
+            data.original_text().as_deref()
+        );
+        assert_eq!(None, data.clone().without_text().original_text());
+
+        // CRLF line breaks aren't part of the span either
+        let data = TextData::new("fn main() {}\r\n// MIT License\r\n").with_view(1, 2);
+        assert_eq!(Some("// MIT License"), data.original_text().as_deref());
+        assert_eq!(Some(((14, 28), (14, 28))), data.original_offsets());
+
+        // stripping HTML doesn't keep lines as they are
+        let html = "<html><body>\n<h1>The Example License</h1><p>Permission is\n\
+                    <b>granted</b>.</p>\n</body></html>";
+        let options = TextDataOptions::new().strip_markup(Some(Markup::Html));
+        let data = TextData::new_with_options(html, options);
+        let lines = data.lines();
+        let start = lines
+            .iter()
+            .position(|line| line == "Permission is")
+            .unwrap();
+        let end = lines.iter().position(|line| line == "granted.").unwrap() + 1;
+        assert!(start > 1, "{:?}", lines);
+        let view = data.with_view(start, end);
+        assert_eq!((1, 3), view.original_lines_view());
+        assert_eq!(
+            Some("<h1>The Example License</h1><p>Permission is\n<b>granted</b>.</p>"),
+            view.original_text().as_deref()
+        );
+        assert_eq!((0, 0), data.to_original_lines((0, 0)));
+        assert_eq!((0, 4), data.to_original_lines((0, lines.len())));
+    }
+
+    #[test]
+    fn with_template() {
+        let canonical = "Copyright (c) <year> <copyright holders>\n\n\
+                         Permission is granted to the person obtaining a copy of this \
+                         software, to use it without restriction.";
+        let template = "<<beginOptional>>The Example License<<endOptional>>\n\n\
+                        Copyright (c) <<var;name=\"copyright\";original=\"<year> <copyright holders>\";\
+                        match=\".{0,5000}\">>\n\n\
+                        Permission is granted to <<var;name=\"who\";original=\"the person\";\
+                        match=\"the person|any person\">> obtaining a copy of this software, \
+                        to use it without restriction.";
+        let plain = TextData::from(canonical);
+        let templated = TextData::from(canonical).with_template(template);
+        assert_eq!(plain.lines(), templated.lines());
+
+        let filled = TextData::from(
+            "The Example License\n\nCopyright (c) 2019 Jane Doe\n\n\
+             Permission is granted to any person obtaining a copy of this software, \
+             to use it without restriction.",
+        );
+        assert_eq!(1.0, templated.match_data.containment(&filled.match_data));
+        assert!(filled.match_score(&templated) > filled.match_score(&plain));
+    }
+
     #[test]
     fn optimize_bounds() {
         let license_text = "this is a license text\nor it pretends to be one\nit's just a test";
@@ -335,6 +1250,118 @@ mod tests {
         );
     }
 
+    #[test]
+    fn line_grams() {
+        let sample = TextData::from("one two\n\nthree\n\nfour five six\n");
+        let lines = sample.lines_normalized.as_ref().unwrap();
+        let line_grams = LineGrams::new(lines, &sample.options);
+        assert_eq!(vec![0, 0, 2, 4, 4, 4], line_grams.word_lines);
+        assert_eq!((2, 6), line_grams.words(1, 5));
+        assert_eq!((3, 3), line_grams.words(3, 4));
+        // grams of successive words, including across lines
+        assert_eq!(5, line_grams.grams.len());
+        assert_eq!("two three", line_grams.names[line_grams.grams[1]]);
+
+        // they're reused for views of the same text
+        let mut scratch = Scratch::default();
+        let cached = scratch.line_grams(lines, sample.options);
+        let view = sample.with_view(2, 5);
+        let view_lines = view.lines_normalized.as_ref().unwrap();
+        assert!(Arc::ptr_eq(
+            &cached,
+            &scratch.line_grams(view_lines, view.options)
+        ));
+
+        // and the window found is scored exactly
+        let license = TextData::from("three\n\nfour five").without_text();
+        let (optimized, score) =
+            sample.optimize_bounds_with(&license, &HashSet::new(), &mut scratch);
+        assert_eq!(2, optimized.lines_view.0);
+        assert_eq!(optimized.match_score(&license), score);
+    }
+
+    #[test]
+    fn line_grams_of_whole_text() {
+        // steps that look across lines act just as they do on the text alone
+        for text in [
+            "The MIT License\n\nPermission is granted",
+            "Copyright 2020 Jane Doe\n\nPermission is granted",
+            "permission is granted\nCopyright (c) 2020 Jane\n\nmore words here",
+            "the authors shall\nin no event be liable",
+            "neither the name of\nAcme Corp nor the names of its contributors",
+            "to any per-\nson obtaining a copy",
+            "// a line here\n// a line here\n// a line here\n// a line here",
+            "one\n\n\n\ntwo",
+        ] {
+            let data = TextData::from(text);
+            let line_grams = LineGrams::new(data.lines_normalized.as_ref().unwrap(), &data.options);
+            let mut grams: Vec<&str> = line_grams
+                .grams
+                .iter()
+                .map(|&gram| line_grams.names[gram].as_str())
+                .collect();
+            grams.sort_unstable();
+            let mut expected: Vec<String> = data
+                .match_data
+                .spelled()
+                .flat_map(|(gram, count)| std::iter::repeat_n(gram, count as usize))
+                .collect();
+            expected.sort_unstable();
+            assert_eq!(expected, grams, "{:?}", text);
+        }
+
+        // and the words left are traced to their lines
+        let data = TextData::from("The MIT License\n\nPermission is granted\nto any person");
+        let line_grams = LineGrams::new(data.lines_normalized.as_ref().unwrap(), &data.options);
+        assert_eq!(vec![2, 2, 2, 3, 3, 3], line_grams.word_lines);
+    }
+
+    #[test]
+    fn for_each_window() {
+        let sample = TextData::from("zero\none two\n\nthree\nfour five six\nseven");
+        let view = sample.with_view(1, 6);
+        let mut windows = Vec::new();
+        view.for_each_window(2, 2, &mut Scratch::default(), |bounds, grams| {
+            windows.push((bounds, grams.len()));
+            // simple text like this is counted just as a window would be
+            let window = sample.with_view(bounds.0, bounds.1);
+            let counted = |set: &NgramSet| {
+                let mut counts: Vec<_> = set.into_iter().collect();
+                counts.sort();
+                counts
+            };
+            assert_eq!(counted(&window.match_data), counted(grams));
+        });
+        // the last window is cut short at the end of the view
+        assert_eq!(vec![((1, 3), 1), ((3, 5), 3), ((5, 6), 0)], windows);
+    }
+
+    #[test]
+    fn optimize_token_bounds() {
+        let license =
+            TextData::from("this is a license text or it pretends to be one").without_text();
+        let sample = TextData::from(
+            "x=1;y=2; this is a license text or it pretends to be one z=3;\n\nmore code\n",
+        );
+        let bounds = sample.optimize_token_bounds(&license);
+        assert_eq!((1, 12), bounds.tokens);
+        assert_eq!((0, 1), bounds.lines);
+        assert!((bounds.score - 1.0).abs() < 1e-6);
+
+        // spanning lines, and counting from the start of the text
+        let sample = TextData::from(
+            "header\ncode(); this is a license\ntext or it pretends to be one; more();\nend",
+        );
+        let bounds = sample.optimize_token_bounds(&license);
+        assert_eq!((2, 13), bounds.tokens);
+        assert_eq!((1, 3), bounds.lines);
+
+        // the view is respected
+        let bounds = sample.with_view(2, 4).optimize_token_bounds(&license);
+        assert!(bounds.tokens.0 >= 6);
+        assert!(bounds.lines.0 >= 2);
+    }
+
     // if a view is set on the text data, optimize_bounds must not find text
     // outside of that range
     #[test]
@@ -400,4 +1427,24 @@ mod tests {
         let c = b.white_out();
         assert_eq!(Some("aaa ddd"), c.text_processed());
     }
+
+    #[test]
+    fn eq_and_hash() {
+        use std::collections::HashSet;
+
+        let a = TextData::from("aaa\nbbb\nccc");
+        let b = TextData::from("AAA\n bbb\nccc  ");
+        let c = TextData::from("aaa\nbbb\nddd");
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+
+        // views are part of equality...
+        assert_ne!(a, a.with_view(0, 2));
+        // ...but stored text isn't
+        assert_eq!(a.clone().without_text(), b.clone().without_text());
+
+        let set: HashSet<TextData> = vec![a.clone(), b, c].into_iter().collect();
+        assert_eq!(2, set.len());
+        assert!(set.contains(&a));
+    }
 }
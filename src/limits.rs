@@ -0,0 +1,155 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{format_err, Error};
+use serde::{Deserialize, Serialize};
+
+/// Limits on the size of input text that askalono will process.
+///
+/// Unbounded input can consume an unbounded amount of memory and time, so
+/// applications accepting arbitrary files may want to cap what they feed to
+/// askalono. Inputs exceeding a limit either produce an error, or if
+/// `truncate` is set, are cut down to fit.
+///
+/// By default, no limits are applied.
+///
+/// # Examples
+///
+/// ```
+/// use askalono::{InputLimits, TextData};
+///
+/// let limits = InputLimits {
+///     max_lines: Some(2),
+///     ..Default::default()
+/// };
+/// assert!(TextData::new_with_limits("one\ntwo\nthree", &limits).is_err());
+///
+/// let limits = InputLimits {
+///     truncate: true,
+///     ..limits
+/// };
+/// let data = TextData::new_with_limits("one\ntwo\nthree", &limits).unwrap();
+/// assert_eq!(2, data.lines().len());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputLimits {
+    /// The maximum number of bytes of input.
+    pub max_bytes: Option<usize>,
+    /// The maximum number of lines of input. This must be at least 1.
+    pub max_lines: Option<usize>,
+    /// If true, oversized input is truncated to fit the limits instead of
+    /// producing an error.
+    pub truncate: bool,
+}
+
+impl InputLimits {
+    /// Check whether any limits are set.
+    pub fn is_unlimited(&self) -> bool {
+        self.max_bytes.is_none() && self.max_lines.is_none()
+    }
+
+    /// Check that the limits are usable: a limit of zero lines would leave
+    /// nothing to scan.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.max_lines == Some(0) {
+            return Err(format_err!("maximum lines must be at least 1"));
+        }
+        Ok(())
+    }
+
+    /// Apply these limits to a string, returning either the string itself, a
+    /// truncated prefix of it, or an error describing the exceeded limit.
+    ///
+    /// Byte truncation will never split a UTF-8 character.
+    pub fn apply<'t>(&self, text: &'t str) -> Result<&'t str, Error> {
+        self.validate()?;
+        let mut out = text;
+
+        if let Some(max_lines) = self.max_lines {
+            if let Some((cut, _)) = out.match_indices('\n').nth(max_lines - 1) {
+                if !self.truncate {
+                    return Err(format_err!(
+                        "input has more than the maximum of {} lines",
+                        max_lines
+                    ));
+                }
+                out = &out[..cut];
+            }
+        }
+
+        if let Some(max_bytes) = self.max_bytes {
+            if out.len() > max_bytes {
+                if !self.truncate {
+                    return Err(format_err!(
+                        "input is {} bytes, exceeding the maximum of {} bytes",
+                        text.len(),
+                        max_bytes
+                    ));
+                }
+                let mut cut = max_bytes;
+                while !out.is_char_boundary(cut) {
+                    cut -= 1;
+                }
+                out = &out[..cut];
+            }
+        }
+
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unlimited() {
+        let limits = InputLimits::default();
+        assert!(limits.is_unlimited());
+        assert_eq!("a\nb\nc", limits.apply("a\nb\nc").unwrap());
+    }
+
+    #[test]
+    fn line_limits() {
+        let limits = InputLimits {
+            max_lines: Some(2),
+            ..Default::default()
+        };
+        assert_eq!("a\nb", limits.apply("a\nb").unwrap());
+        // a trailing newline begins a third (empty) line
+        assert!(limits.apply("a\nb\n").is_err());
+        assert!(limits.apply("a\nb\nc").is_err());
+
+        let limits = InputLimits {
+            truncate: true,
+            ..limits
+        };
+        assert_eq!("a\nb", limits.apply("a\nb\nc").unwrap());
+
+        let limits = InputLimits {
+            max_lines: Some(0),
+            ..limits
+        };
+        assert!(limits.validate().is_err());
+        assert!(limits.apply("a").is_err());
+    }
+
+    #[test]
+    fn byte_limits() {
+        let limits = InputLimits {
+            max_bytes: Some(5),
+            ..Default::default()
+        };
+        assert_eq!("abcde", limits.apply("abcde").unwrap());
+        assert!(limits.apply("abcdef").is_err());
+
+        let limits = InputLimits {
+            truncate: true,
+            ..limits
+        };
+        assert_eq!("abcde", limits.apply("abcdef").unwrap());
+        // don't split a multi-byte character
+        assert_eq!("abc", limits.apply("abc🦀").unwrap());
+    }
+}
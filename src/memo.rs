@@ -0,0 +1,886 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hasher,
+    io::prelude::*,
+    ptr,
+    sync::Mutex,
+};
+
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    deviation::Deviation,
+    license::{LicenseType, TextData},
+    preproc::TextDataOptions,
+    store::{Match, Store},
+    strategy::{ContainedResult, FragmentResult, IdentifiedLicense, ScanResult},
+    ScanStrategyBuilder,
+};
+
+const RESULT_CACHE_VERSION: &[u8] = b"askalono-results-08";
+
+/// How many results a cache keeps, unless created with `with_capacity`.
+const DEFAULT_CAPACITY: usize = 4096;
+
+/// A memo of analysis and scan results, keyed by the normalized text that
+/// produced them.
+///
+/// Crawls over many projects tend to see the same license texts over and over
+/// (vendored dependencies, copied boilerplate). Passing a cache to
+/// `Store::analyze_cached` or `ScanStrategy::result_cache` returns the prior
+/// result for an identical text instead of analyzing it again. Results are
+/// also keyed on how the text was preprocessed (see `TextDataOptions`), a
+/// fingerprint of the store's contents and its scoring settings (IDF
+/// weighting, popularity, prefilters), and for scans, the strategy's
+/// configuration, including its `AnalysisOptions`. Changing the store, such
+/// as by replacing a license's text, means earlier results are no longer
+/// used.
+///
+/// A cache holds up to a fixed number of results (see `with_capacity`).
+/// Once it's full, the least recently used result is dropped to make room
+/// for each new one.
+///
+/// Results only refer to licenses by name, so a cache can be saved to disk
+/// with `save` and reused across runs with `load`.
+///
+/// The cache is safe to share between threads.
+pub struct ResultCache {
+    entries: Mutex<Entries>,
+}
+
+/// Cached results, along with when each was last used.
+struct Entries {
+    map: HashMap<u64, CacheEntry>,
+    /// Keys in `map` by when they were last used, oldest first.
+    used: BTreeMap<u64, u64>,
+    /// When the next result will have been used.
+    clock: u64,
+    capacity: usize,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    text: CachedText,
+    result: CachedResult,
+    /// When this was last used; see `Entries::used`.
+    #[serde(skip)]
+    used: u64,
+}
+
+/// A cache as it's saved: its capacity, and its entries from least to most
+/// recently used.
+#[derive(Serialize, Deserialize)]
+struct SavedCache {
+    capacity: usize,
+    entries: Vec<(u64, CacheEntry)>,
+}
+
+/// What a result was computed from. Entries are found by a hash of this,
+/// and it's compared in full on lookup, so that texts with colliding hashes
+/// can't get each other's results.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+struct CachedText {
+    /// Everything besides the lines that results depend on; see `Settings`.
+    settings: Vec<u8>,
+    /// Line ranges in results are relative to the start of the text, not
+    /// the view, so the view's position matters too.
+    view_start: usize,
+    lines: Vec<String>,
+}
+
+/// The settings a result depends on, serialized into `CachedText`. Stores
+/// are told apart by their fingerprint; see `Store::fingerprint`.
+#[derive(Serialize)]
+struct Settings<'s> {
+    text_options: TextDataOptions,
+    store: u64,
+    idf: bool,
+    popularity: Option<&'s [String]>,
+    index: bool,
+    lsh: bool,
+    config: Option<&'s ScanStrategyBuilder>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+enum CachedResult {
+    Match(CachedMatch),
+    Scan(CachedScan),
+}
+
+/// A license variant in a store, by name and position.
+#[derive(Clone, Serialize, Deserialize)]
+struct VariantRef {
+    name: String,
+    kind: LicenseType,
+    /// Index into the license's headers or alternates; 0 for originals.
+    index: usize,
+    /// The exception found along with the license, if any.
+    exception: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct CachedMatch {
+    score: f32,
+    license: VariantRef,
+    runner_up: Option<String>,
+    runner_up_score: f32,
+    ambiguous: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct CachedScan {
+    score: f32,
+    license: Option<VariantRef>,
+    containing: Vec<CachedContained>,
+    ambiguous: bool,
+    fragments: Vec<CachedFragment>,
+    deviations: Vec<Deviation>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct CachedContained {
+    score: f32,
+    license: VariantRef,
+    line_range: (usize, usize),
+    ambiguous: bool,
+    repeats: Vec<(usize, usize)>,
+    byte_range: Option<(usize, usize)>,
+    char_range: Option<(usize, usize)>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct CachedFragment {
+    score: f32,
+    license: VariantRef,
+    line_range: (usize, usize),
+    license_line_range: Option<(usize, usize)>,
+}
+
+impl Default for ResultCache {
+    fn default() -> ResultCache {
+        ResultCache::with_capacity(DEFAULT_CAPACITY)
+    }
+}
+
+impl ResultCache {
+    /// Create an empty cache, holding up to 4096 results.
+    pub fn new() -> ResultCache {
+        ResultCache::default()
+    }
+
+    /// Create an empty cache, holding up to `capacity` results. A cache
+    /// with no capacity never holds any.
+    pub fn with_capacity(capacity: usize) -> ResultCache {
+        ResultCache {
+            entries: Mutex::new(Entries {
+                map: HashMap::new(),
+                used: BTreeMap::new(),
+                clock: 0,
+                capacity,
+            }),
+        }
+    }
+
+    /// Get the most results the cache holds.
+    pub fn capacity(&self) -> usize {
+        self.entries.lock().unwrap().capacity
+    }
+
+    /// Get the number of cached results.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().map.len()
+    }
+
+    /// Check if the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forget all cached results.
+    pub fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.map.clear();
+        entries.used.clear();
+    }
+
+    /// Load a cache previously written with `save`, with the capacity it
+    /// had then.
+    pub fn load<R>(mut readable: R) -> Result<ResultCache, Error>
+    where
+        R: Read + Sized,
+    {
+        let mut header = [0u8; RESULT_CACHE_VERSION.len()];
+        readable.read_exact(&mut header)?;
+
+        if header != RESULT_CACHE_VERSION {
+            anyhow::bail!(
+                "result cache version mismatch; expected '{}', found '{}'",
+                String::from_utf8_lossy(RESULT_CACHE_VERSION),
+                String::from_utf8_lossy(&header)
+            );
+        }
+
+        let saved: SavedCache = rmp_serde::decode::from_read(readable)?;
+        let cache = ResultCache::with_capacity(saved.capacity);
+        {
+            let mut entries = cache.entries.lock().unwrap();
+            for (key, entry) in saved.entries {
+                entries.insert(key, entry);
+            }
+        }
+        Ok(cache)
+    }
+
+    /// Serialize the cached results so they can be reused with `load`.
+    pub fn save<W>(&self, mut writable: W) -> Result<(), Error>
+    where
+        W: Write + Sized,
+    {
+        let entries = self.entries.lock().unwrap();
+        let saved = SavedCache {
+            capacity: entries.capacity,
+            entries: entries
+                .used
+                .values()
+                .map(|key| (*key, entries.map[key].clone()))
+                .collect(),
+        };
+        writable.write_all(RESULT_CACHE_VERSION)?;
+        saved.serialize(&mut rmp_serde::Serializer::new(&mut writable))?;
+        Ok(())
+    }
+
+    pub(crate) fn get_match<'a>(&self, store: &'a Store, text: &TextData) -> Option<Match<'a>> {
+        let cached = match self.get(store, text, None)? {
+            CachedResult::Match(cached) => cached,
+            CachedResult::Scan(_) => return None,
+        };
+
+        let license = cached.license.resolve(store)?;
+        let runner_up = match cached.runner_up {
+            Some(ref name) => Some(store.licenses.get_key_value(name)?.0.as_str()),
+            None => None,
+        };
+        Some(Match {
+            score: cached.score,
+            name: license.name,
+            license_type: license.kind,
+            family: license.family,
+            variant_label: license.variant_label,
+            exception: license.exception,
+            runner_up,
+            runner_up_score: cached.runner_up_score,
+            ambiguous: cached.ambiguous,
+            data: license.data,
+        })
+    }
+
+    pub(crate) fn put_match(&self, store: &Store, text: &TextData, matched: &Match<'_>) {
+        let (key, license) = match (
+            text_key(store, text, None),
+            VariantRef::new(
+                store,
+                matched.name,
+                matched.license_type,
+                matched.data,
+                matched.exception,
+            ),
+        ) {
+            (Some(key), Some(license)) => (key, license),
+            _ => return,
+        };
+
+        let cached = CachedMatch {
+            score: matched.score,
+            license,
+            runner_up: matched.runner_up.map(str::to_owned),
+            runner_up_score: matched.runner_up_score,
+            ambiguous: matched.ambiguous,
+        };
+        self.insert(key, CachedResult::Match(cached));
+    }
+
+    pub(crate) fn get_scan<'a>(
+        &self,
+        store: &'a Store,
+        config: &ScanStrategyBuilder,
+        text: &TextData,
+    ) -> Option<ScanResult<'a>> {
+        let cached = match self.get(store, text, Some(config))? {
+            CachedResult::Scan(cached) => cached,
+            CachedResult::Match(_) => return None,
+        };
+
+        let license = match cached.license {
+            Some(ref license) => Some(license.resolve(store)?),
+            None => None,
+        };
+        let containing = cached
+            .containing
+            .iter()
+            .map(|c| {
+                Some(ContainedResult {
+                    score: c.score,
+                    license: c.license.resolve(store)?,
+                    line_range: c.line_range,
+                    ambiguous: c.ambiguous,
+                    repeats: c.repeats.clone(),
+                    byte_range: c.byte_range,
+                    char_range: c.char_range,
+                })
+            })
+            .collect::<Option<_>>()?;
+        let fragments = cached
+            .fragments
+            .iter()
+            .map(|f| {
+                Some(FragmentResult {
+                    score: f.score,
+                    license: f.license.resolve(store)?,
+                    line_range: f.line_range,
+                    license_line_range: f.license_line_range,
+                })
+            })
+            .collect::<Option<_>>()?;
+
+        Some(ScanResult {
+            score: cached.score,
+            license,
+            containing,
+            ambiguous: cached.ambiguous,
+            expression: None,
+            fragments,
+            deviations: cached.deviations,
+            metrics: None,
+        })
+    }
+
+    pub(crate) fn put_scan(
+        &self,
+        store: &Store,
+        config: &ScanStrategyBuilder,
+        text: &TextData,
+        result: &ScanResult<'_>,
+    ) {
+        let key = match text_key(store, text, Some(config)) {
+            Some(key) => key,
+            None => return,
+        };
+
+        if let Some(cached) = CachedScan::new(store, result) {
+            self.insert(key, CachedResult::Scan(cached));
+        }
+    }
+
+    /// Get the cached result for a text, if it was computed from exactly
+    /// the same text and settings.
+    fn get(
+        &self,
+        store: &Store,
+        text: &TextData,
+        config: Option<&ScanStrategyBuilder>,
+    ) -> Option<CachedResult> {
+        let (key, cached_text) = text_key(store, text, config)?;
+        let mut entries = self.entries.lock().unwrap();
+        if entries.map.get(&key)?.text != cached_text {
+            return None;
+        }
+        entries.touch(key).map(|entry| entry.result.clone())
+    }
+
+    fn insert(&self, (key, text): (u64, CachedText), result: CachedResult) {
+        let entry = CacheEntry {
+            text,
+            result,
+            used: 0,
+        };
+        self.entries.lock().unwrap().insert(key, entry);
+    }
+}
+
+impl Entries {
+    /// Add an entry as the most recently used, dropping the least recently
+    /// used ones if there are too many.
+    fn insert(&mut self, key: u64, mut entry: CacheEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        entry.used = self.tick();
+        self.used.insert(entry.used, key);
+        if let Some(old) = self.map.insert(key, entry) {
+            self.used.remove(&old.used);
+        }
+        while self.map.len() > self.capacity {
+            let Some((_, oldest)) = self.used.pop_first() else {
+                break;
+            };
+            self.map.remove(&oldest);
+        }
+    }
+
+    /// Mark an entry as the most recently used, and get it.
+    fn touch(&mut self, key: u64) -> Option<&CacheEntry> {
+        let now = self.tick();
+        let entry = self.map.get_mut(&key)?;
+        self.used.remove(&entry.used);
+        self.used.insert(now, key);
+        entry.used = now;
+        Some(entry)
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+}
+
+impl CachedScan {
+    /// Record a scan result, if all of its licenses can be found in the
+    /// store.
+    fn new(store: &Store, result: &ScanResult<'_>) -> Option<CachedScan> {
+        Some(CachedScan {
+            score: result.score,
+            license: match result.license {
+                Some(ref license) => Some(VariantRef::from_identified(store, license)?),
+                None => None,
+            },
+            containing: result
+                .containing
+                .iter()
+                .map(|c| {
+                    Some(CachedContained {
+                        score: c.score,
+                        license: VariantRef::from_identified(store, &c.license)?,
+                        line_range: c.line_range,
+                        ambiguous: c.ambiguous,
+                        repeats: c.repeats.clone(),
+                        byte_range: c.byte_range,
+                        char_range: c.char_range,
+                    })
+                })
+                .collect::<Option<_>>()?,
+            ambiguous: result.ambiguous,
+            fragments: result
+                .fragments
+                .iter()
+                .map(|f| {
+                    Some(CachedFragment {
+                        score: f.score,
+                        license: VariantRef::from_identified(store, &f.license)?,
+                        line_range: f.line_range,
+                        license_line_range: f.license_line_range,
+                    })
+                })
+                .collect::<Option<_>>()?,
+            deviations: result.deviations.clone(),
+        })
+    }
+}
+
+impl VariantRef {
+    /// Find the position of a variant in the store, comparing data by
+    /// address.
+    fn new(
+        store: &Store,
+        name: &str,
+        kind: LicenseType,
+        data: &TextData,
+        exception: Option<&str>,
+    ) -> Option<VariantRef> {
+        let entry = store.licenses.get(name)?;
+        let index = match kind {
+            LicenseType::Original => 0,
+            LicenseType::Alternate => entry.alternates.iter().position(|d| ptr::eq(d, data))?,
+            LicenseType::Header => entry.headers.iter().position(|d| ptr::eq(d, data))?,
+        };
+        Some(VariantRef {
+            name: name.to_owned(),
+            kind,
+            index,
+            exception: exception.map(str::to_owned),
+        })
+    }
+
+    fn from_identified(store: &Store, license: &IdentifiedLicense<'_>) -> Option<VariantRef> {
+        VariantRef::new(
+            store,
+            license.name,
+            license.kind,
+            license.data,
+            license.exception,
+        )
+    }
+
+    /// Look up the variant in the store, if it still exists.
+    fn resolve<'a>(&self, store: &'a Store) -> Option<IdentifiedLicense<'a>> {
+        let (name, entry) = store.licenses.get_key_value(&self.name)?;
+        let (data, variant_label) = match self.kind {
+            LicenseType::Original => (&entry.original, None),
+            LicenseType::Alternate => (
+                entry.alternates.get(self.index)?,
+                entry.alternate_label(self.index),
+            ),
+            LicenseType::Header => (
+                entry.headers.get(self.index)?,
+                entry.header_label(self.index),
+            ),
+        };
+        let exception = match self.exception {
+            Some(ref exception) => Some(store.exceptions.get_key_value(exception)?.0.as_str()),
+            None => None,
+        };
+        Some(IdentifiedLicense {
+            name,
+            kind: self.kind,
+            family: entry.family.as_deref(),
+            variant_label,
+            exception,
+            store: None,
+            data,
+        })
+    }
+}
+
+/// A 64-bit FNV-1a hasher.
+///
+/// This is used rather than std's hasher wherever hashes are saved to disk
+/// and must be stable across builds.
+pub(crate) struct FnvHasher(u64);
+
+impl Default for FnvHasher {
+    fn default() -> FnvHasher {
+        FnvHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        const FNV_PRIME: u64 = 0x0100_0000_01b3;
+        for &b in bytes {
+            self.0 ^= u64::from(b);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Describe a text (and anything else its results depend on) for the cache,
+/// and hash that into a key.
+///
+/// Keys are saved to disk, so this uses `FnvHasher`. Texts without stored
+/// lines can't be keyed.
+fn text_key(
+    store: &Store,
+    text: &TextData,
+    config: Option<&ScanStrategyBuilder>,
+) -> Option<(u64, CachedText)> {
+    let settings = Settings {
+        text_options: text.options(),
+        store: store.fingerprint(),
+        idf: store.idf.is_some(),
+        popularity: store.popularity.as_deref(),
+        index: store.index.is_some(),
+        lsh: store.lsh.is_some(),
+        config,
+    };
+    let cached = CachedText {
+        settings: rmp_serde::to_vec(&settings).ok()?,
+        view_start: text.lines_view().0,
+        lines: text.lines_in_view()?.to_vec(),
+    };
+
+    let mut hasher = FnvHasher::default();
+    hasher.write(&(cached.settings.len() as u64).to_le_bytes());
+    hasher.write(&cached.settings);
+    hasher.write(&(cached.view_start as u64).to_le_bytes());
+    for line in &cached.lines {
+        hasher.write(&(line.len() as u64).to_le_bytes());
+        hasher.write(line.as_bytes());
+    }
+    Some((hasher.finish(), cached))
+}
+
+/// Hash a length, as part of a longer value.
+fn write_len(hasher: &mut FnvHasher, len: usize) {
+    hasher.write(&(len as u64).to_le_bytes());
+}
+
+fn write_str(hasher: &mut FnvHasher, s: &str) {
+    write_len(hasher, s.len());
+    hasher.write(s.as_bytes());
+}
+
+/// Hash a text's n-grams and their counts, along with its lines, if kept.
+fn write_text(hasher: &mut FnvHasher, data: &TextData) {
+    let mut grams: Vec<(u64, u32)> = data.match_data.gram_hashes().collect();
+    grams.sort_unstable();
+    write_len(hasher, grams.len());
+    for (hash, count) in grams {
+        hasher.write(&hash.to_le_bytes());
+        hasher.write(&count.to_le_bytes());
+    }
+    let lines = data.lines_in_view().unwrap_or_default();
+    write_len(hasher, data.lines_view().0);
+    write_len(hasher, lines.len());
+    for line in lines {
+        write_str(hasher, line);
+    }
+}
+
+impl Store {
+    /// Get a hash of everything in the store that results depend on: its
+    /// licenses and their variants and clauses, exceptions, stop grams and
+    /// titles. This is worked out when first needed, and again after the
+    /// store changes; see `forget_derived`.
+    ///
+    /// Scoring settings (IDF weighting, prefilters, popularity) aren't
+    /// included; `ResultCache` keys on those separately.
+    pub(crate) fn fingerprint(&self) -> u64 {
+        *self.fingerprint.get_or_init(|| {
+            let mut hasher = FnvHasher::default();
+            let mut names: Vec<&String> = self.licenses.keys().collect();
+            names.sort_unstable();
+            write_len(&mut hasher, names.len());
+            for name in names {
+                let entry = &self.licenses[name];
+                write_str(&mut hasher, name);
+                write_text(&mut hasher, &entry.original);
+                for variants in [&entry.headers, &entry.alternates, &entry.clauses] {
+                    write_len(&mut hasher, variants.len());
+                    for data in variants {
+                        write_text(&mut hasher, data);
+                    }
+                }
+            }
+
+            let mut exceptions: Vec<_> = self.exceptions.iter().collect();
+            exceptions.sort_unstable_by_key(|&(name, _)| name);
+            write_len(&mut hasher, exceptions.len());
+            for (name, data) in exceptions {
+                write_str(&mut hasher, name);
+                write_text(&mut hasher, data);
+            }
+
+            let mut stop_grams: Vec<u64> =
+                self.stop_grams.iter().map(|g| g.spelled_hash()).collect();
+            stop_grams.sort_unstable();
+            write_len(&mut hasher, stop_grams.len());
+            for hash in stop_grams {
+                hasher.write(&hash.to_le_bytes());
+            }
+
+            let mut titles: Vec<_> = self.titles.iter().collect();
+            titles.sort_unstable();
+            write_len(&mut hasher, titles.len());
+            for (title, names) in titles {
+                write_str(&mut hasher, title);
+                write_len(&mut hasher, names.len());
+                for name in names {
+                    write_str(&mut hasher, name);
+                }
+            }
+
+            hasher.write(&[self.compact_counts as u8, self.hashed_grams as u8]);
+            hasher.finish()
+        })
+    }
+
+    /// Compare the given `TextData` against all licenses in the `Store`,
+    /// returning a cached result if an identical text was analyzed before.
+    ///
+    /// Otherwise identical to `analyze`; new results are added to the
+    /// cache. Texts stored without text data can't be cached, and are
+    /// always analyzed.
+    pub fn analyze_cached<'a>(&'a self, text: &TextData, cache: &ResultCache) -> Match<'a> {
+        if let Some(matched) = cache.get_match(self, text) {
+            return matched;
+        }
+        let matched = self.analyze(text);
+        cache.put_match(self, text, &matched);
+        matched
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ScanStrategy;
+
+    fn store() -> Store {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc ddddd".into());
+        store.add_license("license-2".into(), "eeeee fffff ggggg hhhhh".into());
+        store
+            .add_variant_labeled(
+                "license-2",
+                LicenseType::Header,
+                "eeeee fffff".into(),
+                Some("short".into()),
+            )
+            .unwrap();
+        store
+    }
+
+    #[test]
+    fn analyze_cached() {
+        let store = store();
+        let cache = ResultCache::new();
+        let text = TextData::from("eeeee fffff");
+
+        let fresh = store.analyze_cached(&text, &cache);
+        assert_eq!(1, cache.len());
+        let cached = store.analyze_cached(&text, &cache);
+        assert_eq!(1, cache.len());
+
+        assert_eq!(fresh.name, cached.name);
+        assert_eq!(fresh.score, cached.score);
+        assert_eq!(LicenseType::Header, cached.license_type);
+        assert_eq!(Some("short"), cached.variant_label);
+        assert!(ptr::eq(fresh.data, cached.data));
+
+        // texts without lines can't be keyed
+        store.analyze_cached(&TextData::from("aaaaa bbbbb").without_text(), &cache);
+        assert_eq!(1, cache.len());
+    }
+
+    #[test]
+    fn scans_keyed_on_config() {
+        let store = store();
+        let cache = ResultCache::new();
+        let text = TextData::from("aaaaa bbbbb ccccc ddddd");
+
+        let strategy = ScanStrategy::new(&store).result_cache(&cache);
+        let fresh = strategy.scan(&text).unwrap();
+        let cached = strategy.scan(&text).unwrap();
+        assert_eq!(1, cache.len());
+        assert_eq!("license-1", cached.license.unwrap().name);
+        assert_eq!(fresh.score, cached.score);
+
+        ScanStrategy::new(&store)
+            .confidence_threshold(0.5)
+            .result_cache(&cache)
+            .scan(&text)
+            .unwrap();
+        assert_eq!(2, cache.len());
+    }
+
+    #[test]
+    fn keyed_on_text_options() {
+        let store = store();
+        let cache = ResultCache::new();
+        let text = "Copyright 2018 Jane Doe\naaaaa bbbbb ccccc ddddd";
+
+        let stripped = store.analyze_cached(&TextData::new(text), &cache);
+        let options = TextDataOptions::new().strip_copyright(false);
+        let kept = TextData::new_with_options(text, options);
+        let expected = store.analyze(&kept).score;
+        assert_ne!(stripped.score, expected);
+        assert_eq!(expected, store.analyze_cached(&kept, &cache).score);
+        assert_eq!(2, cache.len());
+    }
+
+    #[test]
+    fn colliding_keys() {
+        let store = store();
+        let cache = ResultCache::new();
+        store.analyze_cached(&TextData::from("aaaaa bbbbb ccccc ddddd"), &cache);
+
+        // file the result under another text's key, as a collision would
+        let other = TextData::from("eeeee fffff");
+        let (key, _) = text_key(&store, &other, None).unwrap();
+        {
+            let mut entries = cache.entries.lock().unwrap();
+            let entry = entries.map.values().next().unwrap().clone();
+            entries.map.clear();
+            entries.used.clear();
+            entries.insert(key, entry);
+        }
+        assert_eq!("license-2", store.analyze_cached(&other, &cache).name);
+    }
+
+    #[test]
+    fn keyed_on_store_contents() {
+        let mut store = store();
+        let cache = ResultCache::new();
+        let text = TextData::from("aaaaa bbbbb ccccc ddddd");
+        assert_eq!(1.0, store.analyze_cached(&text, &cache).score);
+
+        // same names and number of licenses, different text
+        store.add_license("license-1".into(), "iiiii jjjjj kkkkk lllll".into());
+        assert!(store.analyze_cached(&text, &cache).score < 1.0);
+        assert_eq!(2, cache.len());
+
+        store.add_stop_grams(["aaaaa bbbbb"]);
+        store.analyze_cached(&text, &cache);
+        assert_eq!(3, cache.len());
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let store = store();
+        let cache = ResultCache::with_capacity(2);
+        let first = TextData::from("aaaaa bbbbb");
+        let second = TextData::from("eeeee fffff");
+        let third = TextData::from("ggggg hhhhh");
+
+        store.analyze_cached(&first, &cache);
+        store.analyze_cached(&second, &cache);
+        // using the first makes the second the oldest
+        store.analyze_cached(&first, &cache);
+        store.analyze_cached(&third, &cache);
+        assert_eq!(2, cache.len());
+        assert!(cache.get(&store, &first, None).is_some());
+        assert!(cache.get(&store, &second, None).is_none());
+        assert!(cache.get(&store, &third, None).is_some());
+
+        let empty = ResultCache::with_capacity(0);
+        store.analyze_cached(&first, &empty);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn save_and_load() {
+        let store = store();
+        let cache = ResultCache::new();
+        let text = TextData::from("aaaaa bbbbb ccccc ddddd");
+        store.analyze_cached(&text, &cache);
+
+        let mut buf = Vec::new();
+        cache.save(&mut buf).unwrap();
+        let loaded = ResultCache::load(buf.as_slice()).unwrap();
+        assert_eq!(1, loaded.len());
+        assert_eq!(DEFAULT_CAPACITY, loaded.capacity());
+        assert_eq!("license-1", store.analyze_cached(&text, &loaded).name);
+        assert_eq!(1, loaded.len());
+
+        // results for licenses that have gone away are ignored
+        let mut other = Store::new();
+        other.add_license("license-3".into(), "aaaaa bbbbb ccccc".into());
+        other.add_license("license-4".into(), "eeeee fffff ggggg".into());
+        let loaded = ResultCache::load(buf.as_slice()).unwrap();
+        assert_eq!("license-3", other.analyze_cached(&text, &loaded).name);
+
+        // the order entries were used in is kept
+        let cache = ResultCache::with_capacity(2);
+        let (first, second) = (TextData::from("aaaaa bbbbb"), TextData::from("eeeee"));
+        store.analyze_cached(&first, &cache);
+        store.analyze_cached(&second, &cache);
+        store.analyze_cached(&first, &cache);
+        let mut buf = Vec::new();
+        cache.save(&mut buf).unwrap();
+        let loaded = ResultCache::load(buf.as_slice()).unwrap();
+        assert_eq!(2, loaded.capacity());
+        store.analyze_cached(&text, &loaded);
+        assert!(loaded.get(&store, &first, None).is_some());
+        assert!(loaded.get(&store, &second, None).is_none());
+
+        assert!(ResultCache::load(&b"not a cache at all"[..]).is_err());
+    }
+}
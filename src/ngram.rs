@@ -3,60 +3,541 @@
 
 use std::{
     cmp::min,
-    collections::{hash_map::Iter, HashMap, VecDeque},
+    collections::{
+        hash_map::{self, DefaultHasher},
+        HashMap, HashSet,
+    },
+    fmt,
+    hash::{Hash, Hasher},
+    iter::Zip,
+    slice,
 };
 
-use serde::{Deserialize, Serialize};
+use anyhow::Error;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[cfg(feature = "static-store")]
+use crate::store::{StaticGrams, StaticIter};
+use crate::{
+    intern::{self, intern, lookup, lookup_all, resolve},
+    memo::FnvHasher,
+};
+
+/// An n-gram: a pair of successive words, as their numbers in the token
+/// table (see `intern`).
+///
+/// Grams are written out as their words separated by a space, such as
+/// "permission hereby", so that they mean the same in any process.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Gram(u32, u32);
+
+impl Gram {
+    /// Make a gram of two words, by their numbers in the token table.
+    pub fn new(first: u32, second: u32) -> Gram {
+        Gram(first, second)
+    }
+
+    /// The numbers of the gram's words in the token table.
+    #[cfg(feature = "static-store")]
+    pub(crate) fn ids(self) -> (u32, u32) {
+        (self.0, self.1)
+    }
+
+    /// Get the gram spelled by two space-separated words, adding them to the
+    /// token table if they're new, or an error if the table is full.
+    pub fn intern(gram: &str) -> Result<Gram, Error> {
+        let (first, second) = gram.split_once(' ').unwrap_or((gram, ""));
+        Ok(Gram(intern(first)?, intern(second)?))
+    }
+
+    /// Get the gram spelled by two space-separated words, if both are in the
+    /// token table. Grams of words that aren't can't be in any set.
+    pub fn lookup(gram: &str) -> Option<Gram> {
+        let (first, second) = gram.split_once(' ').unwrap_or((gram, ""));
+        Some(Gram(lookup(first)?, lookup(second)?))
+    }
+
+    /// The words of this gram.
+    pub fn words(self) -> (&'static str, &'static str) {
+        (resolve(self.0), resolve(self.1))
+    }
+
+    /// The hash of this gram as it's spelled; see `spelled_hash`.
+    pub(crate) fn spelled_hash(self) -> u64 {
+        let (first, second) = self.words();
+        words_hash(first, second)
+    }
+}
+
+/// The 64-bit hash of a gram spelled out as two space-separated words, as
+/// kept by hashed sets (see `NgramSet::hash_grams`). This is the same in
+/// every build, since hashes are saved in caches.
+pub(crate) fn spelled_hash(gram: &str) -> u64 {
+    let (first, second) = gram.split_once(' ').unwrap_or((gram, ""));
+    words_hash(first, second)
+}
+
+fn words_hash(first: &str, second: &str) -> u64 {
+    let mut hasher = FnvHasher::default();
+    hasher.write(first.as_bytes());
+    hasher.write(b" ");
+    hasher.write(second.as_bytes());
+    hasher.finish()
+}
+
+impl fmt::Display for Gram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (first, second) = self.words();
+        write!(f, "{} {}", first, second)
+    }
+}
+
+impl Serialize for Gram {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Gram {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Gram, D::Error> {
+        let gram = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        Gram::intern(&gram).map_err(de::Error::custom)
+    }
+}
+
+/// The n-grams of a text and how many times each occurs.
+///
+/// Grams whose words are all in the token table are kept by number. Those
+/// with a word that isn't, as in scanned text or sets read back from disk,
+/// are kept spelled out instead, so that scanning doesn't add to the table;
+/// they can't be in any store, but still count towards scores, and match
+/// the same grams of other sets. Store data has its words added to the
+/// table with `intern_words`, unless it's hashed instead; see `hash_grams`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "GramMap", into = "GramMap")]
 pub struct NgramSet {
+    grams: Grams,
+    size: usize,
+    /// Grams with a word that wasn't in the token table, spelled out.
+    extra: HashMap<String, u32>,
+    /// The size of the token table when the set was created. Only grams
+    /// with a word numbered at least this high can be in `extra`.
+    known: u32,
+}
+
+#[derive(Clone, Debug)]
+enum Grams {
+    Map(HashMap<Gram, u32>),
+    /// Sorted grams, with saturating counts alongside. This takes much less
+    /// memory than a map, but lookups are slower; see `pack`.
+    Packed {
+        grams: Box<[Gram]>,
+        counts: Box<[u8]>,
+    },
+    /// A table compiled into the program, used where it is; see
+    /// `StaticStore`.
+    #[cfg(feature = "static-store")]
+    Static(StaticGrams),
+    /// Sorted hashes of grams, with saturating counts alongside; see
+    /// `hash_grams`.
+    Hashed {
+        hashes: Box<[u64]>,
+        counts: Box<[u8]>,
+    },
+}
+
+/// The serialized form of an `NgramSet`, regardless of whether it's packed.
+///
+/// Words are only looked up in the token table, as for scanned text, so
+/// deserializing a set adds nothing to the table; stores add the words of
+/// their sets once they're read (see `intern_words`). Hashed sets are
+/// written as their hashes instead.
+#[derive(Serialize, Deserialize)]
+struct GramMap {
     map: HashMap<String, u32>,
-    // once Rust supports it, it'd be nice to make this
-    // a type parameter & specialize
+    /// The number of words in each gram, which is always 2.
     n: u8,
     size: usize,
+    /// Sorted gram hashes and their counts, for hashed sets.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    hashes: Vec<(u64, u32)>,
+}
+
+impl TryFrom<GramMap> for NgramSet {
+    type Error = String;
+
+    fn try_from(data: GramMap) -> Result<NgramSet, String> {
+        if data.n != 2 {
+            return Err(format!(
+                "only bigrams are supported; got n-grams of {}",
+                data.n
+            ));
+        }
+        if !data.hashes.is_empty() {
+            let spelled = data
+                .map
+                .iter()
+                .map(|(gram, &count)| (spelled_hash(gram), count));
+            return Ok(NgramSet::from_hashes(spelled.chain(data.hashes)));
+        }
+        let mut set = NgramSet::new();
+        for (gram, count) in data.map {
+            match Gram::lookup(&gram) {
+                Some(gram) => *set.map_mut().entry(gram).or_insert(0) += count,
+                None => *set.extra.entry(gram).or_insert(0) += count,
+            }
+        }
+        set.size = data.size;
+        Ok(set)
+    }
+}
+
+impl From<NgramSet> for GramMap {
+    fn from(set: NgramSet) -> GramMap {
+        let (hashes, counts) = set.stored_hashes();
+        GramMap {
+            map: set.spelled().collect(),
+            n: 2,
+            size: set.size,
+            hashes: hashes
+                .iter()
+                .zip(counts)
+                .map(|(&hash, &count)| (hash, count as u32))
+                .collect(),
+        }
+    }
 }
 
 impl NgramSet {
-    pub fn new(n: u8) -> NgramSet {
+    /// Create an empty set.
+    pub fn new() -> NgramSet {
         NgramSet {
-            map: HashMap::new(),
-            n,
+            grams: Grams::Map(HashMap::new()),
             size: 0,
+            extra: HashMap::new(),
+            known: intern::len(),
         }
     }
 
-    pub fn from_str(s: &str, n: u8) -> NgramSet {
-        let mut set = NgramSet::new(n);
+    pub fn from_str(s: &str) -> NgramSet {
+        let mut set = NgramSet::new();
         set.analyze(s);
         set
     }
 
+    /// Use a table of bigrams compiled into the program as a set.
+    #[cfg(feature = "static-store")]
+    pub(crate) fn from_static(grams: StaticGrams) -> NgramSet {
+        NgramSet {
+            size: grams.size(),
+            grams: Grams::Static(grams),
+            extra: HashMap::new(),
+            known: intern::len(),
+        }
+    }
+
+    /// Build a hashed set (see `hash_grams`) from gram hashes and their
+    /// counts.
+    pub(crate) fn from_hashes<I>(hashes: I) -> NgramSet
+    where
+        I: IntoIterator<Item = (u64, u32)>,
+    {
+        let mut map: HashMap<u64, u32> = HashMap::new();
+        for (hash, count) in hashes {
+            *map.entry(hash).or_insert(0) += count;
+        }
+        let mut entries: Vec<(u64, u8)> = map
+            .into_iter()
+            .map(|(hash, count)| (hash, min(count, u8::MAX as u32) as u8))
+            .collect();
+        entries.sort_unstable_by_key(|&(hash, _)| hash);
+
+        let size = entries.iter().map(|&(_, count)| count as usize).sum();
+        let (hashes, counts): (Vec<_>, Vec<_>) = entries.into_iter().unzip();
+        NgramSet {
+            grams: Grams::Hashed {
+                hashes: hashes.into_boxed_slice(),
+                counts: counts.into_boxed_slice(),
+            },
+            size,
+            extra: HashMap::new(),
+            known: intern::len(),
+        }
+    }
+
+    /// Build a set from grams and their counts.
+    pub fn from_counts<I>(counts: I) -> NgramSet
+    where
+        I: IntoIterator<Item = (Gram, u32)>,
+    {
+        let map: HashMap<Gram, u32> = counts.into_iter().collect();
+        let size = map.values().map(|&count| count as usize).sum();
+        NgramSet {
+            grams: Grams::Map(map),
+            size,
+            extra: HashMap::new(),
+            known: intern::len(),
+        }
+    }
+
+    /// Count the bigrams of a processed text. Words are looked up in the
+    /// token table, but not added to it; see `intern_words`.
     pub fn analyze(&mut self, s: &str) {
-        let words = s.split(' ');
-
-        let mut deque: VecDeque<&str> = VecDeque::with_capacity(self.n as usize);
-        for w in words {
-            deque.push_back(w);
-            if deque.len() == self.n as usize {
-                let parts = deque.iter().cloned().collect::<Vec<&str>>();
-                self.add_gram(parts.join(" "));
-                deque.pop_front();
+        let words: Vec<&str> = s.split(' ').collect();
+        let ids = lookup_all(words.iter().copied());
+        for (i, pair) in ids.windows(2).enumerate() {
+            match (pair[0], pair[1]) {
+                (Some(first), Some(second)) => self.add_gram(Gram(first, second)),
+                _ => self.add_extra(format!("{} {}", words[i], words[i + 1]), 1),
+            }
+        }
+    }
+
+    /// Count a gram spelled out as two space-separated words, which aren't
+    /// both in the token table.
+    pub(crate) fn add_extra(&mut self, gram: String, count: u32) {
+        *self.extra.entry(gram).or_insert(0) += count;
+        self.size += count as usize;
+    }
+
+    /// Add the words of every gram to the token table, as is done for store
+    /// data. Sets of scanned text are left as they are, so that scanning
+    /// doesn't grow the table.
+    pub fn intern_words(&mut self) {
+        if self.extra.is_empty() {
+            return;
+        }
+        let packed = matches!(self.grams, Grams::Packed { .. });
+        let extra = std::mem::take(&mut self.extra);
+        let mut unadded = HashMap::new();
+        let map = self.map_mut();
+        for (gram, count) in extra {
+            match Gram::intern(&gram) {
+                Ok(gram) => *map.entry(gram).or_insert(0) += count,
+                // the table is full; the gram still counts, spelled out
+                Err(_) => {
+                    unadded.insert(gram, count);
+                }
             }
         }
+        self.extra = unadded;
+        if packed {
+            self.pack();
+        }
+    }
+
+    /// The grams spelled out because a word wasn't in the token table, and
+    /// their counts. These aren't included when iterating over the set.
+    pub(crate) fn extra_grams(&self) -> impl Iterator<Item = (&str, u32)> {
+        self.extra
+            .iter()
+            .map(|(gram, &count)| (gram.as_str(), count))
+    }
+
+    /// Every gram of the set spelled out, whether or not its words are in
+    /// the token table, and their counts.
+    pub(crate) fn spelled(&self) -> impl Iterator<Item = (String, u32)> + '_ {
+        self.into_iter()
+            .map(|(gram, count)| (gram.to_string(), count))
+            .chain(
+                self.extra
+                    .iter()
+                    .map(|(gram, &count)| (gram.clone(), count)),
+            )
     }
 
-    fn add_gram(&mut self, gram: String) {
-        let n = self.map.entry(gram).or_insert(0);
+    pub fn add_gram(&mut self, gram: Gram) {
+        let n = self.map_mut().entry(gram).or_insert(0);
         *n += 1;
         self.size += 1;
     }
 
-    pub fn get(&self, gram: &str) -> u32 {
-        if let Some(count) = self.map.get(gram) {
-            *count
-        } else {
-            0
+    /// Get the counts as a map, unpacking them if needed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the set is hashed, as its grams can't be recovered.
+    fn map_mut(&mut self) -> &mut HashMap<Gram, u32> {
+        assert!(!self.is_hashed(), "can't add grams to a hashed set");
+        if !matches!(self.grams, Grams::Map(_)) {
+            let map = self.into_iter().collect();
+            self.grams = Grams::Map(map);
+        }
+        match self.grams {
+            Grams::Map(ref mut map) => map,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Switch to a compact representation: grams sorted in an array, with
+    /// counts saturating at 255.
+    ///
+    /// Packed sets take considerably less memory, which adds up over the
+    /// thousands of sets in a store, but looking up a gram takes a binary
+    /// search rather than a hash. Counts that high are practically unheard
+    /// of, so scores aren't affected in practice. Adding grams unpacks the
+    /// set again.
+    pub fn pack(&mut self) {
+        let map = match self.grams {
+            Grams::Map(ref mut map) => std::mem::take(map),
+            _ => return,
+        };
+        let mut entries: Vec<(Gram, u8)> = map
+            .into_iter()
+            .map(|(gram, count)| (gram, min(count, u8::MAX as u32) as u8))
+            .collect();
+        entries.sort_unstable_by_key(|&(gram, _)| gram);
+
+        let extra: usize = self.extra.values().map(|&count| count as usize).sum();
+        self.size = entries
+            .iter()
+            .map(|&(_, count)| count as usize)
+            .sum::<usize>()
+            + extra;
+        let (grams, counts): (Vec<_>, Vec<_>) = entries.into_iter().unzip();
+        self.grams = Grams::Packed {
+            grams: grams.into_boxed_slice(),
+            counts: counts.into_boxed_slice(),
+        };
+    }
+
+    /// Keep only 64-bit hashes of the grams, rather than the grams
+    /// themselves. Hashed sets are stored as packed ones are (see `pack`),
+    /// and grams spelled out because a word wasn't in the token table are
+    /// hashed too.
+    ///
+    /// Comparing sets only needs to tell which grams are equal, which
+    /// hashes do as well, unless two different grams share a hash and are
+    /// counted as one. With 64-bit hashes that's vanishingly unlikely: even
+    /// among a million distinct grams, the chance of any two sharing a hash
+    /// is under one in thirty million, and it would only nudge a score.
+    ///
+    /// The grams themselves are gone, though, so a hashed set can only be
+    /// looked up, not iterated or spelled out, and grams can no longer be
+    /// added to it.
+    pub fn hash_grams(&mut self) {
+        if self.is_hashed() {
+            return;
+        }
+        *self = NgramSet::from_hashes(self.gram_hashes());
+    }
+
+    /// Whether the set holds hashes of grams; see `hash_grams`.
+    pub fn is_hashed(&self) -> bool {
+        matches!(self.grams, Grams::Hashed { .. })
+    }
+
+    /// The hashes and counts held by a hashed set, or nothing if it isn't.
+    fn stored_hashes(&self) -> (&[u64], &[u8]) {
+        match self.grams {
+            Grams::Hashed {
+                ref hashes,
+                ref counts,
+            } => (hashes, counts),
+            _ => (&[], &[]),
+        }
+    }
+
+    /// Every gram's hash (see `spelled_hash`) and count, whether or not the
+    /// set is hashed.
+    pub(crate) fn gram_hashes(&self) -> impl Iterator<Item = (u64, u32)> + '_ {
+        let (hashes, counts) = self.stored_hashes();
+        self.into_iter()
+            .map(|(gram, count)| (gram.spelled_hash(), count))
+            .chain(
+                self.extra_grams()
+                    .map(|(gram, count)| (spelled_hash(gram), count)),
+            )
+            .chain(
+                hashes
+                    .iter()
+                    .zip(counts)
+                    .map(|(&hash, &count)| (hash, count as u32)),
+            )
+    }
+
+    /// Every gram's count.
+    fn counts(&self) -> impl Iterator<Item = u32> + '_ {
+        let (_, counts) = self.stored_hashes();
+        self.into_iter()
+            .map(|(_, count)| count)
+            .chain(self.extra.values().copied())
+            .chain(counts.iter().map(|&count| count as u32))
+    }
+
+    /// Get the count of a gram in a hashed set, by its hash.
+    fn hashed_count(&self, hash: u64) -> u32 {
+        let (hashes, counts) = self.stored_hashes();
+        match hashes.binary_search(&hash) {
+            Ok(i) => counts[i] as u32,
+            Err(_) => 0,
+        }
+    }
+
+    pub fn get(&self, gram: Gram) -> u32 {
+        let count = match self.grams {
+            Grams::Map(ref map) => map.get(&gram).copied().unwrap_or(0),
+            Grams::Packed {
+                ref grams,
+                ref counts,
+            } => match grams.binary_search(&gram) {
+                Ok(i) => counts[i] as u32,
+                Err(_) => 0,
+            },
+            #[cfg(feature = "static-store")]
+            Grams::Static(ref grams) => grams.get(gram),
+            Grams::Hashed { .. } => return self.hashed_count(gram.spelled_hash()),
+        };
+        // a word added to the table since may have been spelled out
+        let newer = gram.0 >= self.known || gram.1 >= self.known;
+        if count == 0 && newer && !self.extra.is_empty() {
+            return self.extra.get(&gram.to_string()).copied().unwrap_or(0);
+        }
+        count
+    }
+
+    /// Get the count of a gram spelled out as two space-separated words.
+    pub fn get_str(&self, gram: &str) -> u32 {
+        if self.is_hashed() {
+            return self.hashed_count(spelled_hash(gram));
+        }
+        match Gram::lookup(gram) {
+            Some(gram) => self.get(gram),
+            None => self.extra.get(gram).copied().unwrap_or(0),
+        }
+    }
+
+    /// Remove all occurrences of the given n-grams from this set.
+    pub fn remove_grams(&mut self, grams: &HashSet<Gram>) {
+        if self.is_hashed() {
+            let removed: HashSet<u64> = grams.iter().map(|gram| gram.spelled_hash()).collect();
+            let kept: Vec<(u64, u32)> = self
+                .gram_hashes()
+                .filter(|(hash, _)| !removed.contains(hash))
+                .collect();
+            *self = NgramSet::from_hashes(kept);
+            return;
+        }
+        let packed = matches!(self.grams, Grams::Packed { .. });
+        let map = self.map_mut();
+        let mut removed = 0;
+        for gram in grams {
+            if let Some(count) = map.remove(gram) {
+                removed += count as usize;
+            }
+        }
+        if !self.extra.is_empty() {
+            self.extra.retain(|gram, &mut count| {
+                let stop = Gram::lookup(gram).is_some_and(|gram| grams.contains(&gram));
+                if stop {
+                    removed += count as usize;
+                }
+                !stop
+            });
+        }
+        self.size -= removed;
+        if packed {
+            self.pack();
         }
     }
 
@@ -68,12 +549,19 @@ impl NgramSet {
         self.size == 0
     }
 
-    pub fn dice(&self, other: &NgramSet) -> f32 {
-        // no sense comparing sets of different sizes
-        if other.n != self.n {
-            return 0f32;
-        }
+    /// The number of distinct grams in this set.
+    fn distinct(&self) -> usize {
+        let numbered = match self.grams {
+            Grams::Map(ref map) => map.len(),
+            Grams::Packed { ref grams, .. } => grams.len(),
+            #[cfg(feature = "static-store")]
+            Grams::Static(ref grams) => grams.distinct(),
+            Grams::Hashed { ref hashes, .. } => hashes.len(),
+        };
+        numbered + self.extra.len()
+    }
 
+    pub fn dice(&self, other: &NgramSet) -> f32 {
         // there's obviously no match if either are empty strings;
         // if we don't check here we could end up with NaN below
         // when both are empty
@@ -81,28 +569,388 @@ impl NgramSet {
             return 0f32;
         }
 
-        // choose the smaller map to iterate
+        let matches = self.shared(other);
+        (2.0 * matches as f32) / ((self.len() + other.len()) as f32)
+    }
+
+    /// The fraction of this set's n-grams that also appear in `other`.
+    ///
+    /// Unlike `dice`, this isn't symmetric: a small set entirely contained
+    /// in a much larger one scores 1.0.
+    pub fn containment(&self, other: &NgramSet) -> f32 {
+        if self.is_empty() {
+            return 0f32;
+        }
+
+        self.shared(other) as f32 / self.len() as f32
+    }
+
+    /// The Dice coefficient, with each n-gram counting as much as `weight`
+    /// gives it rather than 1. Grams with a word outside the token table
+    /// count as much as `unknown`.
+    ///
+    /// Weights are summed as `f64` and only rounded at the end, so the order
+    /// grams are visited in (which differs between otherwise identical sets)
+    /// doesn't show in the result.
+    ///
+    /// Hashed sets (see `hash_grams`) can't give their grams to be weighted,
+    /// so if either set is hashed, this is the same as `dice`.
+    pub fn weighted_dice<F>(&self, other: &NgramSet, weight: F, unknown: f32) -> f32
+    where
+        F: Fn(Gram) -> f32,
+    {
+        if self.is_hashed() || other.is_hashed() {
+            return self.dice(other);
+        }
+        if self.is_empty() || other.is_empty() {
+            return 0f32;
+        }
+
+        let weight = |gram: Gram| weight(gram) as f64;
+        let extra_weight = |gram: &str| Gram::lookup(gram).map_or(unknown as f64, weight);
+        let total = |set: &NgramSet| -> f64 {
+            let numbered: f64 = set.into_iter().map(|(g, c)| weight(g) * c as f64).sum();
+            let extra: f64 = set
+                .extra_grams()
+                .map(|(g, c)| extra_weight(g) * c as f64)
+                .sum();
+            numbered + extra
+        };
         let (x, y) = if self.len() < other.len() {
             (self, other)
         } else {
             (other, self)
         };
+        let shared: f64 = x
+            .into_iter()
+            .map(|(gram, count)| weight(gram) * min(count, y.get(gram)) as f64)
+            .sum();
+        let shared_extra: f64 = x
+            .extra_grams()
+            .map(|(gram, count)| extra_weight(gram) * min(count, y.get_str(gram)) as f64)
+            .sum();
+        (2.0 * (shared + shared_extra) / (total(self) + total(other))) as f32
+    }
+
+    /// The number of shared n-grams over the number in either set.
+    pub fn jaccard(&self, other: &NgramSet) -> f32 {
+        if self.is_empty() || other.is_empty() {
+            return 0f32;
+        }
 
+        let matches = self.shared(other);
+        matches as f32 / (self.len() + other.len() - matches as usize) as f32
+    }
+
+    /// The cosine of the angle between the sets' vectors of n-gram counts.
+    pub fn cosine(&self, other: &NgramSet) -> f32 {
+        if self.is_empty() || other.is_empty() {
+            return 0f32;
+        }
+
+        let mut dot = 0f64;
+        self.each_pair(other, |x, y| dot += x as f64 * y as f64);
+        let norm = |set: &NgramSet| {
+            set.counts()
+                .map(|count| (count as f64).powi(2))
+                .sum::<f64>()
+                .sqrt()
+        };
+        (dot / (norm(self) * norm(other))) as f32
+    }
+
+    fn shared(&self, other: &NgramSet) -> u32 {
         let mut matches = 0;
+        self.each_pair(other, |x, y| matches += min(x, y));
+        matches
+    }
+
+    /// Call `f` with the counts in one set and in the other of each gram of
+    /// the first set. Which set comes first is up to this, so `f` should be
+    /// symmetric, and grams only in the second set don't count towards it.
+    fn each_pair<F: FnMut(u32, u32)>(&self, other: &NgramSet, mut f: F) {
+        // look up the grams of the smaller set in the larger, unless only
+        // one is hashed: that one can only be looked up
+        let (x, y) = match (self.is_hashed(), other.is_hashed()) {
+            (true, false) => (other, self),
+            (false, true) => (self, other),
+            _ if self.len() < other.len() => (self, other),
+            _ => (other, self),
+        };
+
+        if x.is_hashed() {
+            for (hash, count) in x.gram_hashes() {
+                f(count, y.hashed_count(hash));
+            }
+            return;
+        }
         for (gram, count) in x {
-            matches += min(*count, y.get(gram));
+            f(count, y.get(gram));
         }
+        for (gram, count) in x.extra_grams() {
+            f(count, y.get_str(gram));
+        }
+    }
+}
 
-        (2.0 * matches as f32) / ((self.len() + other.len()) as f32)
+impl Default for NgramSet {
+    fn default() -> NgramSet {
+        NgramSet::new()
+    }
+}
+
+// Sets are equal when they hold the same grams and counts, whether or not
+// either is packed or has grams spelled out: sets of the same size sharing
+// all of their grams hold the same ones.
+impl PartialEq for NgramSet {
+    fn eq(&self, other: &NgramSet) -> bool {
+        self.size == other.size
+            && self.distinct() == other.distinct()
+            && self.shared(other) as usize == self.size
+    }
+}
+
+impl Eq for NgramSet {}
+
+impl Hash for NgramSet {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // the same gram may be numbered in one set, spelled out in another,
+        // and hashed in a third, so hash grams by their spelled hashes; and
+        // HashMap iteration order isn't stable, so combine per-gram hashes
+        // in an order-independent way
+        let grams = self.gram_hashes().fold(0u64, |acc, (gram, count)| {
+            let mut hasher = DefaultHasher::new();
+            gram.hash(&mut hasher);
+            count.hash(&mut hasher);
+            acc.wrapping_add(hasher.finish())
+        });
+
+        self.size.hash(state);
+        grams.hash(state);
+    }
+}
+
+/// An iterator over the grams of an `NgramSet` and their counts, other than
+/// those spelled out (see `NgramSet::extra_grams`). Hashed sets have no
+/// grams to give (see `NgramSet::hash_grams`).
+pub enum Iter<'a> {
+    Map(hash_map::Iter<'a, Gram, u32>),
+    Packed(Zip<slice::Iter<'a, Gram>, slice::Iter<'a, u8>>),
+    #[cfg(feature = "static-store")]
+    Static(StaticIter<'a>),
+    Hashed,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (Gram, u32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Iter::Map(iter) => iter.next().map(|(&gram, &count)| (gram, count)),
+            Iter::Packed(iter) => iter.next().map(|(&gram, &count)| (gram, count as u32)),
+            #[cfg(feature = "static-store")]
+            Iter::Static(iter) => iter.next(),
+            Iter::Hashed => None,
+        }
     }
 }
 
 impl<'a> IntoIterator for &'a NgramSet {
-    type Item = (&'a String, &'a u32);
-    type IntoIter = Iter<'a, String, u32>;
+    type Item = (Gram, u32);
+    type IntoIter = Iter<'a>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.map.iter()
+        match self.grams {
+            Grams::Map(ref map) => Iter::Map(map.iter()),
+            Grams::Packed {
+                ref grams,
+                ref counts,
+            } => Iter::Packed(grams.iter().zip(counts.iter())),
+            #[cfg(feature = "static-store")]
+            Grams::Static(ref grams) => Iter::Static(grams.iter()),
+            Grams::Hashed { .. } => Iter::Hashed,
+        }
+    }
+}
+
+/// Bigram counts of successive windows of one text, for scoring many windows
+/// without allocating for each.
+///
+/// Counts are reset between windows rather than cleared. Windows of the same
+/// text share most of their grams, so after the first few, filling a window
+/// rarely allocates.
+#[derive(Default)]
+pub(crate) struct WindowGrams {
+    counts: HashMap<Gram, u32>,
+    /// Grams with a word outside the token table, spelled out.
+    extra: HashMap<String, u32>,
+    size: usize,
+}
+
+/// The most grams kept between windows before starting over, so that a
+/// long-lived `WindowGrams` doesn't grow without bound.
+const MAX_WINDOW_GRAMS: usize = 1 << 16;
+
+impl WindowGrams {
+    /// Count the bigrams of a processed text, skipping stop grams. This gives
+    /// the same counts as building an `NgramSet` and removing
+    /// stop grams from it.
+    ///
+    /// Words aren't added to the token table. As in `NgramSet`, grams with
+    /// a word that isn't in it are kept spelled out.
+    pub fn fill(&mut self, s: &str, stop_grams: &HashSet<Gram>) {
+        if self.counts.len() > MAX_WINDOW_GRAMS {
+            self.counts.clear();
+        }
+        self.counts.values_mut().for_each(|count| *count = 0);
+        self.extra.clear();
+        self.size = 0;
+
+        let words: Vec<&str> = s.split(' ').collect();
+        let ids = lookup_all(words.iter().copied());
+        for (i, pair) in ids.windows(2).enumerate() {
+            match (pair[0], pair[1]) {
+                (Some(first), Some(second)) => {
+                    let gram = Gram(first, second);
+                    if !stop_grams.contains(&gram) {
+                        *self.counts.entry(gram).or_insert(0) += 1;
+                        self.size += 1;
+                    }
+                }
+                _ => {
+                    let gram = format!("{} {}", words[i], words[i + 1]);
+                    *self.extra.entry(gram).or_insert(0) += 1;
+                    self.size += 1;
+                }
+            }
+        }
+    }
+
+    /// Compare the current window to a set, as with `NgramSet::dice`.
+    pub fn dice(&self, other: &NgramSet) -> f32 {
+        if self.size == 0 || other.is_empty() {
+            return 0f32;
+        }
+
+        let matches: u32 = self
+            .counts
+            .iter()
+            .filter(|&(_, &count)| count > 0)
+            .map(|(&gram, &count)| min(count, other.get(gram)))
+            .sum();
+        // spelled-out grams can only match those of another scanned text,
+        // or a hashed set
+        let extra: u32 = match other.extra.is_empty() && !other.is_hashed() {
+            true => 0,
+            false => self
+                .extra
+                .iter()
+                .map(|(gram, &count)| min(count, other.get_str(gram)))
+                .sum(),
+        };
+        (2.0 * (matches + extra) as f32) / ((self.size + other.len()) as f32)
+    }
+}
+
+/// Counts the bigrams of a range of words that moves about a text, for
+/// scoring many overlapping ranges of it against one set. Moving the range
+/// only counts the grams entering or leaving it, so unlike `WindowGrams`
+/// nothing is rescanned.
+///
+/// Grams are given as indexes into a list of distinct grams, as many
+/// windows of the same text share them.
+pub(crate) struct SlidingGrams<'a> {
+    /// Each bigram of successive words in the text.
+    grams: &'a [usize],
+    /// How many of each distinct gram the other set has, or `None` for stop
+    /// grams, which aren't counted.
+    limits: Vec<Option<u32>>,
+    counts: Vec<u32>,
+    /// The grams currently counted, as a range of `grams`.
+    range: (usize, usize),
+    matches: u32,
+    size: usize,
+    other_len: usize,
+}
+
+impl<'a> SlidingGrams<'a> {
+    /// Prepare to compare ranges of a text to `other`, skipping stop grams.
+    /// `names` holds the distinct grams that `grams` refers to, spelled out.
+    pub fn new(
+        grams: &'a [usize],
+        names: &[String],
+        other: &NgramSet,
+        stop_grams: &HashSet<Gram>,
+    ) -> SlidingGrams<'a> {
+        let usable = !other.is_empty();
+        let limits = names
+            .iter()
+            .map(|name| match Gram::lookup(name) {
+                _ if !usable => None,
+                Some(gram) if stop_grams.contains(&gram) => None,
+                Some(gram) => Some(other.get(gram)),
+                None => Some(other.get_str(name)),
+            })
+            .collect();
+        SlidingGrams {
+            grams,
+            limits,
+            counts: vec![0; names.len()],
+            range: (0, 0),
+            matches: 0,
+            size: 0,
+            other_len: other.len(),
+        }
+    }
+
+    /// Compare the bigrams of words `start..end` of the text to the other
+    /// set, as with `NgramSet::dice`.
+    pub fn dice(&mut self, start: usize, end: usize) -> f32 {
+        // the last word has no gram of its own
+        let end = end.saturating_sub(1);
+        let start = start.min(end);
+
+        while self.range.1 < end {
+            self.add(self.grams[self.range.1]);
+            self.range.1 += 1;
+        }
+        while self.range.0 > start {
+            self.range.0 -= 1;
+            self.add(self.grams[self.range.0]);
+        }
+        while self.range.0 < start {
+            self.remove(self.grams[self.range.0]);
+            self.range.0 += 1;
+        }
+        while self.range.1 > end {
+            self.range.1 -= 1;
+            self.remove(self.grams[self.range.1]);
+        }
+
+        if self.size == 0 {
+            return 0f32;
+        }
+        (2.0 * self.matches as f32) / ((self.size + self.other_len) as f32)
+    }
+
+    fn add(&mut self, gram: usize) {
+        if let Some(limit) = self.limits[gram] {
+            self.counts[gram] += 1;
+            self.size += 1;
+            if self.counts[gram] <= limit {
+                self.matches += 1;
+            }
+        }
+    }
+
+    fn remove(&mut self, gram: usize) {
+        if let Some(limit) = self.limits[gram] {
+            if self.counts[gram] <= limit {
+                self.matches -= 1;
+            }
+            self.counts[gram] -= 1;
+            self.size -= 1;
+        }
     }
 }
 
@@ -113,38 +961,267 @@ mod tests {
     // this is a pretty banal test, but it's a starting point :P
     #[test]
     fn can_construct() {
-        let set = NgramSet::new(2);
+        let set = NgramSet::new();
         assert_eq!(set.size, 0);
-        assert_eq!(set.n, 2);
     }
 
     #[test]
-    fn no_nan() {
-        let a = NgramSet::from_str("", 2);
-        let b = NgramSet::from_str("", 2);
+    fn containment() {
+        let part = NgramSet::from_str("one two three");
+        let whole = NgramSet::from_str("zero one two three four");
+        assert_eq!(1.0, part.containment(&whole));
+        assert_eq!(0.5, whole.containment(&part));
+        assert_eq!(0.0, NgramSet::new().containment(&whole));
+    }
 
-        let score = a.dice(&b);
+    #[test]
+    fn jaccard_and_cosine() {
+        let part = NgramSet::from_str("one two three");
+        let whole = NgramSet::from_str("zero one two three four");
+        assert_eq!(0.5, part.jaccard(&whole));
+        assert_eq!(0.5, whole.jaccard(&part));
+        assert!((part.cosine(&whole) - 0.5f32.sqrt()).abs() < 1e-6);
+        assert_eq!(1.0, whole.jaccard(&whole));
+        assert!((whole.cosine(&whole) - 1.0).abs() < 1e-6);
 
-        assert!(!score.is_nan());
+        let empty = NgramSet::new();
+        assert_eq!(0.0, empty.jaccard(&empty));
+        assert_eq!(0.0, empty.cosine(&whole));
     }
 
     #[test]
-    fn same_size() {
-        let a = NgramSet::from_str("", 2);
-        let b = NgramSet::from_str("", 3);
+    fn remove_grams() {
+        let mut set = NgramSet::from_str("one two one two three");
+        assert_eq!(4, set.len());
+        set.remove_grams(&[Gram::intern("one two").unwrap()].into_iter().collect());
+        assert_eq!(2, set.len());
+        assert_eq!(0, set.get_str("one two"));
+        assert_eq!(1, set.get_str("two three"));
+    }
+
+    #[test]
+    fn unknown_words() {
+        let a = NgramSet::from_str("unknownaaaaa unknownbbbbb unknownccccc");
+        let b = NgramSet::from_str("unknownaaaaa unknownbbbbb unknownddddd");
+        // scanning doesn't add to the token table
+        assert_eq!(None, lookup("unknownaaaaa"));
+        assert_eq!(2, a.len());
+        assert_eq!(1, a.get_str("unknownaaaaa unknownbbbbb"));
+        assert_eq!(0.5, a.dice(&b));
+
+        // the same grams match once the words are interned as store data
+        let mut stored = a.clone();
+        stored.intern_words();
+        assert!(lookup("unknownaaaaa").is_some());
+        assert_eq!(a, stored);
+        assert_eq!(0.5, stored.dice(&b));
+        assert_eq!(1, b.get(Gram::intern("unknownaaaaa unknownbbbbb").unwrap()));
+    }
+
+    #[test]
+    fn no_nan() {
+        let a = NgramSet::from_str("");
+        let b = NgramSet::from_str("");
 
         let score = a.dice(&b);
 
-        assert_eq!(0f32, score);
+        assert!(!score.is_nan());
+    }
+
+    #[test]
+    fn only_bigrams() {
+        let data = GramMap {
+            map: [("one two three".to_owned(), 1)].into_iter().collect(),
+            n: 3,
+            size: 1,
+            hashes: Vec::new(),
+        };
+        assert!(NgramSet::try_from(data).is_err());
     }
 
     #[test]
     fn identical() {
-        let a = NgramSet::from_str("one two three apple banana", 2);
-        let b = NgramSet::from_str("one two three apple banana", 2);
+        let a = NgramSet::from_str("one two three apple banana");
+        let b = NgramSet::from_str("one two three apple banana");
 
         let score = a.dice(&b);
 
         assert_eq!(1f32, score);
     }
+
+    #[test]
+    fn hash() {
+        let hash = |set: &NgramSet| {
+            let mut hasher = DefaultHasher::new();
+            set.hash(&mut hasher);
+            hasher.finish()
+        };
+        let a = NgramSet::from_str("alpha beta gamma delta");
+        let b = NgramSet::from_str("one two three four");
+        assert_ne!(hash(&a), hash(&b));
+
+        // equal sets hash the same however their grams are kept
+        let mut packed = a.clone();
+        packed.pack();
+        assert_eq!(hash(&a), hash(&packed));
+        let unknown = NgramSet::from_str("hashtestaaaaa hashtestbbbbb hashtestccccc");
+        let mut stored = unknown.clone();
+        stored.intern_words();
+        assert_eq!(unknown, stored);
+        assert_eq!(hash(&unknown), hash(&stored));
+    }
+
+    #[test]
+    fn packed() {
+        let text = "one two three one two four";
+        let set = NgramSet::from_str(text);
+        let mut packed = set.clone();
+        packed.pack();
+
+        assert_eq!(set, packed);
+        assert_eq!(2, packed.get_str("one two"));
+        assert_eq!(0, packed.get_str("two one"));
+        assert_eq!(1f32, packed.dice(&set));
+        assert_eq!(
+            set,
+            NgramSet::try_from(GramMap::from(packed.clone())).unwrap()
+        );
+
+        packed.remove_grams(&[Gram::intern("one two").unwrap()].into_iter().collect());
+        assert_eq!(3, packed.len());
+        assert_eq!(0, packed.get_str("one two"));
+
+        let mut many = NgramSet::from_str("one two ".repeat(300).trim_end());
+        many.pack();
+        assert_eq!(255, many.get_str("one two"));
+        assert_eq!(255, many.get_str("two one"));
+        assert_eq!(510, many.len());
+    }
+
+    #[test]
+    fn hashed() {
+        let mut stored = NgramSet::from_str("one two three hashedtestword one two");
+        stored.intern_words();
+        let text = NgramSet::from_str("one two hashedtestunseen one two three");
+        let mut hashed = stored.clone();
+        hashed.hash_grams();
+
+        assert!(hashed.is_hashed());
+        assert_eq!(stored, hashed);
+        assert_eq!(stored.len(), hashed.len());
+        assert_eq!(2, hashed.get_str("one two"));
+        assert_eq!(1, hashed.get(Gram::intern("three hashedtestword").unwrap()));
+        assert_eq!(0, hashed.get_str("two one"));
+        assert_eq!(0, hashed.get_str("hashedtestunseen one"));
+        assert_eq!(0, (&hashed).into_iter().count());
+        assert_eq!(stored.dice(&text), hashed.dice(&text));
+        assert_eq!(stored.dice(&text), text.dice(&hashed));
+        assert_eq!(stored.containment(&text), text.containment(&hashed));
+        assert_eq!(stored.cosine(&text), hashed.cosine(&text));
+        assert_eq!(
+            hashed,
+            NgramSet::try_from(GramMap::from(hashed.clone())).unwrap()
+        );
+
+        hashed.remove_grams(&[Gram::intern("one two").unwrap()].into_iter().collect());
+        assert_eq!(3, hashed.len());
+        assert_eq!(0, hashed.get_str("one two"));
+    }
+
+    #[test]
+    fn grams() {
+        let gram = Gram::intern("permission hereby").unwrap();
+        assert_eq!(Some(gram), Gram::lookup("permission hereby"));
+        assert_eq!(None, Gram::lookup("permission gramstestunseen"));
+        assert_eq!(("permission", "hereby"), gram.words());
+        assert_eq!("permission hereby", gram.to_string());
+
+        let set = NgramSet::from_str("permission hereby granted");
+        let json = serde_json::to_string(&set).unwrap();
+        assert!(json.contains("\"hereby granted\":1"), "{}", json);
+        assert_eq!(set, serde_json::from_str::<NgramSet>(&json).unwrap());
+
+        // reading a set doesn't add its words to the token table
+        let json = r#"{"map":{"gramstestaaaaa gramstestbbbbb":2},"n":2,"size":2}"#;
+        let read: NgramSet = serde_json::from_str(json).unwrap();
+        assert_eq!(None, lookup("gramstestaaaaa"));
+        assert_eq!(2, read.get_str("gramstestaaaaa gramstestbbbbb"));
+        let text =
+            NgramSet::from_str("gramstestaaaaa gramstestbbbbb gramstestaaaaa gramstestbbbbb");
+        assert_eq!(1f32, read.containment(&text));
+    }
+
+    #[test]
+    fn hash_ignores_insertion_order() {
+        fn hash_of(set: &NgramSet) -> u64 {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            set.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        let mut a = NgramSet::new();
+        a.add_gram(Gram::intern("one two").unwrap());
+        a.add_gram(Gram::intern("three four").unwrap());
+        let mut b = NgramSet::new();
+        b.add_gram(Gram::intern("three four").unwrap());
+        b.add_gram(Gram::intern("one two").unwrap());
+
+        assert_eq!(a, b);
+        assert_eq!(hash_of(&a), hash_of(&b));
+    }
+
+    #[test]
+    fn window_grams() {
+        let other = NgramSet::from_str("aaaaa bbbbb ccccc ddddd");
+        let stop_grams: HashSet<Gram> =
+            [Gram::intern("ccccc ddddd").unwrap()].into_iter().collect();
+        let mut window = WindowGrams::default();
+        for text in &[
+            "aaaaa bbbbb ccccc",
+            "",
+            "x  aaaaa bbbbb ccccc ddddd aaaaa bbbbb",
+            // words never interned
+            "aaaaa windowgramsunseen bbbbb",
+        ] {
+            window.fill(text, &stop_grams);
+            let mut set = NgramSet::from_str(text);
+            set.remove_grams(&stop_grams);
+            assert_eq!(set.dice(&other), window.dice(&other), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn sliding_grams() {
+        let other = NgramSet::from_str("aaaaa bbbbb ccccc ddddd");
+        let stop_grams: HashSet<Gram> =
+            [Gram::intern("ccccc ddddd").unwrap()].into_iter().collect();
+        let words = ["x", "aaaaa", "bbbbb", "ccccc", "ddddd", "aaaaa", "bbbbb"];
+        let mut names: Vec<String> = Vec::new();
+        let grams: Vec<usize> = words
+            .windows(2)
+            .map(|pair| {
+                let name = pair.join(" ");
+                match names.iter().position(|n| *n == name) {
+                    Some(i) => i,
+                    None => {
+                        names.push(name);
+                        names.len() - 1
+                    }
+                }
+            })
+            .collect();
+
+        let mut sliding = SlidingGrams::new(&grams, &names, &other, &stop_grams);
+        // move both ways, including to ranges that don't overlap
+        for &(start, end) in &[(1, 4), (0, 7), (2, 5), (5, 7), (0, 2), (3, 3), (1, 7)] {
+            let mut set = NgramSet::from_str(&words[start..end].join(" "));
+            set.remove_grams(&stop_grams);
+            assert_eq!(
+                set.dice(&other),
+                sliding.dice(start, end),
+                "{:?}",
+                (start, end)
+            );
+        }
+    }
 }
@@ -7,8 +7,11 @@ use std::collections::HashMap;
 use lazy_static::lazy_static;
 use log::debug;
 use regex::{Regex, Replacer};
+use serde::{Deserialize, Serialize};
 use unicode_normalization::UnicodeNormalization;
 
+use crate::comments::CommentStyle;
+
 type PreprocFn = dyn Fn(Cow<str>) -> Cow<str>;
 
 trait CowRegex {
@@ -24,37 +27,219 @@ impl CowRegex for Regex {
     }
 }
 
+/// The preprocessing steps that can be turned off with `TextDataOptions`,
+/// apart from those that always run.
+#[derive(Clone, Copy)]
+enum Step {
+    Always,
+    Placeholders,
+    Urls,
+    CommonPrefixes,
+    Title,
+    Copyright,
+}
+
 /// A list of preprocessors that normalize text without removing anything
 /// substantial. These operate on one line at a time.
-pub const PREPROC_NORMALIZE: [&PreprocFn; 6] = [
-    &normalize_unicode,
-    &remove_junk,
-    &blackbox_urls,
-    &normalize_horizontal_whitespace,
-    &normalize_punctuation,
-    &trim,
+const PREPROC_NORMALIZE: [(Step, &PreprocFn); 7] = [
+    (Step::Always, &normalize_unicode),
+    (Step::Placeholders, &normalize_placeholders),
+    (Step::Always, &remove_junk),
+    (Step::Urls, &blackbox_urls),
+    (Step::Always, &normalize_horizontal_whitespace),
+    (Step::Always, &normalize_punctuation),
+    (Step::Always, &trim),
 ];
 
 /// A list of preprocessors that more aggressively normalize/mangle text
 /// to make for friendlier matching. May remove statements and lines, and
 /// more heavily normalize punctuation.
-pub const PREPROC_AGGRESSIVE: [&PreprocFn; 8] = [
-    &remove_common_tokens,
-    &normalize_vertical_whitespace,
-    &remove_punctuation,
-    &lowercaseify,
-    &remove_title_line,
-    &remove_copyright_statements,
-    &collapse_whitespace,
-    &trim,
+const PREPROC_AGGRESSIVE: [(Step, &PreprocFn); 9] = [
+    (Step::CommonPrefixes, &remove_common_tokens),
+    (Step::Always, &normalize_vertical_whitespace),
+    (Step::Always, &remove_punctuation),
+    (Step::Always, &lowercaseify),
+    (Step::Title, &remove_title_line),
+    (Step::Copyright, &remove_copyright_statements),
+    (Step::Placeholders, &wildcard_placeholder_contexts),
+    (Step::Always, &collapse_whitespace),
+    (Step::Always, &trim),
 ];
 
+/// A kind of markup that can be stripped from a text before it's matched;
+/// see `TextDataOptions::strip_markup`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Markup {
+    /// HTML, stripped with `strip_html`.
+    Html,
+    /// Markdown, stripped with `strip_markdown`.
+    Markdown,
+}
+
+impl Markup {
+    /// Strip this kind of markup from a text.
+    pub fn strip(self, text: &str) -> String {
+        match self {
+            Markup::Html => strip_html(text),
+            Markup::Markdown => strip_markdown(text),
+        }
+    }
+}
+
+/// Options for how text is preprocessed into a `TextData`, for
+/// `TextData::new_with_options`.
+///
+/// Before texts are compared, askalono normalizes away differences that
+/// rarely matter between copies of a license: comment markers, title lines,
+/// copyright statements, and so on. Some of these steps can hurt unusual
+/// inputs (a license whose first line is significant, say, or text where
+/// every line starts with the same word), so each can be turned off. All
+/// are on by default, as they are for `TextData::new`. Steps that only
+/// normalize whitespace, case, punctuation, and Unicode always run.
+///
+/// The licenses in a `Store` are always preprocessed with every step, so
+/// turning steps off for a text compared against a store may lower its
+/// scores.
+///
+/// ```
+/// use askalono::{TextData, TextDataOptions};
+///
+/// let options = TextDataOptions::new().strip_copyright(false);
+/// let text = TextData::new_with_options("Copyright 2018 Jane Doe\nAll rights reserved.", options);
+/// assert_eq!(Some("copyright 2018 jane doe all rights reserved"), text.text_processed());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TextDataOptions {
+    placeholders: bool,
+    urls: bool,
+    common_prefixes: bool,
+    title: bool,
+    copyright: bool,
+    comments: Option<CommentStyle>,
+    markup: Option<Markup>,
+}
+
+impl Default for TextDataOptions {
+    fn default() -> Self {
+        TextDataOptions {
+            placeholders: true,
+            urls: true,
+            common_prefixes: true,
+            title: true,
+            copyright: true,
+            comments: None,
+            markup: None,
+        }
+    }
+}
+
+impl TextDataOptions {
+    /// Create options with every preprocessing step on.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace template placeholders, such as `<year>`, `[name of copyright
+    /// owner]`, or a name standing where a license template has one, with a
+    /// common word, so that templates and filled-in copies match.
+    pub fn normalize_placeholders(mut self, normalize_placeholders: bool) -> Self {
+        self.placeholders = normalize_placeholders;
+        self
+    }
+
+    /// Replace URLs with a common word, so that links to different copies
+    /// of a license match.
+    pub fn blackbox_urls(mut self, blackbox_urls: bool) -> Self {
+        self.urls = blackbox_urls;
+        self
+    }
+
+    /// Remove text that most lines start with, such as comment markers
+    /// (`//`, `#`, ` * `) in source files.
+    pub fn strip_common_prefixes(mut self, strip_common_prefixes: bool) -> Self {
+        self.common_prefixes = strip_common_prefixes;
+        self
+    }
+
+    /// Remove a title line (such as "The MIT License") at the start of the
+    /// text.
+    pub fn strip_title(mut self, strip_title: bool) -> Self {
+        self.title = strip_title;
+        self
+    }
+
+    /// Remove copyright statements.
+    pub fn strip_copyright(mut self, strip_copyright: bool) -> Self {
+        self.copyright = strip_copyright;
+        self
+    }
+
+    /// Keep only the comments of a source file written in the given style,
+    /// as with `extract_comments`, before any other preprocessing. Off by
+    /// default.
+    ///
+    /// License headers then don't have to compete with the code around them,
+    /// which finds them far more reliably in source files. Line numbers are
+    /// unaffected.
+    pub fn extract_comments(mut self, style: Option<CommentStyle>) -> Self {
+        self.comments = style;
+        self
+    }
+
+    /// The comment style set with `extract_comments`, if any.
+    pub(crate) fn comment_style(&self) -> Option<CommentStyle> {
+        self.comments
+    }
+
+    /// Strip markup of the given kind from the text, as with `strip_html` or
+    /// `strip_markdown`, before any other preprocessing. Off by default.
+    ///
+    /// Headings, emphasis, badges, and tags otherwise get in the way of
+    /// matching licenses in READMEs and web pages.
+    pub fn strip_markup(mut self, markup: Option<Markup>) -> Self {
+        self.markup = markup;
+        self
+    }
+
+    /// The markup set with `strip_markup`, if any.
+    pub(crate) fn markup(&self) -> Option<Markup> {
+        self.markup
+    }
+
+    fn runs(&self, step: Step) -> bool {
+        match step {
+            Step::Always => true,
+            Step::Placeholders => self.placeholders,
+            Step::Urls => self.urls,
+            Step::CommonPrefixes => self.common_prefixes,
+            Step::Title => self.title,
+            Step::Copyright => self.copyright,
+        }
+    }
+}
+
+/// The word that template placeholders such as `<year>` or
+/// `[name of copyright owner]` are replaced with.
+const PLACEHOLDER: &str = "placeholder";
+
+/// The word that marks where variable text was in a license template; see
+/// `wildcard_template`. Nothing spans it when n-grams are generated.
+pub(crate) const TEMPLATE_WILDCARD: &str = "askalonotemplatewildcard";
+
 pub fn apply_normalizers(text: &str) -> Vec<String> {
+    apply_normalizers_with(text, &TextDataOptions::default())
+}
+
+pub fn apply_normalizers_with(text: &str, options: &TextDataOptions) -> Vec<String> {
     let mut lines = Vec::new();
     for line in text.split('\n') {
         let mut out: Cow<str> = line.into();
-        for preproc in &PREPROC_NORMALIZE {
-            out = preproc(out);
+        for (step, preproc) in &PREPROC_NORMALIZE {
+            if options.runs(*step) {
+                out = preproc(out);
+            }
         }
         lines.push(out.into());
     }
@@ -62,21 +247,294 @@ pub fn apply_normalizers(text: &str) -> Vec<String> {
     lines
 }
 
-pub fn apply_aggressive(text: &str) -> String {
+pub fn apply_aggressive_with(text: &str, options: &TextDataOptions) -> String {
     let mut out = text.into();
-    for preproc in &PREPROC_AGGRESSIVE {
-        out = preproc(out);
+    for (step, preproc) in &PREPROC_AGGRESSIVE {
+        if options.runs(*step) {
+            out = preproc(out);
+        }
     }
     debug!("Aggressively normalized to:\n{}\n---", &out);
     out.into()
 }
 
+/// Replace the variable and optional regions of an SPDX license template
+/// (`<<var;...>>` and `<<beginOptional>>...<<endOptional>>`) with
+/// `TEMPLATE_WILDCARD`, leaving the text that every copy of the license
+/// shares.
+pub(crate) fn wildcard_template(template: &str) -> String {
+    lazy_static! {
+        // quoted attributes may contain anything, including '>'
+        static ref TAG: Regex =
+            Regex::new(r#"<<(?:[^">]|>[^>]|"(?:[^"\\]|\\.)*")*>>"#).unwrap();
+    }
+
+    let mut out = String::with_capacity(template.len());
+    let mut depth = 0usize;
+    let mut last = 0;
+    for tag in TAG.find_iter(template) {
+        if depth == 0 {
+            out.push_str(&template[last..tag.start()]);
+        }
+        last = tag.end();
+
+        let name = tag.as_str()[2..].trim_start();
+        if name.starts_with("beginOptional") {
+            if depth == 0 {
+                out.push_str(&format!(" {} ", TEMPLATE_WILDCARD));
+            }
+            depth += 1;
+        } else if name.starts_with("endOptional") {
+            depth = depth.saturating_sub(1);
+        } else if name.starts_with("var") && depth == 0 {
+            out.push_str(&format!(" {} ", TEMPLATE_WILDCARD));
+        }
+    }
+    if depth == 0 {
+        out.push_str(&template[last..]);
+    }
+    out
+}
+
+// Markup strippers
+
+/// Strip HTML markup from a document, leaving its visible text.
+///
+/// Scripts, styles, and comments are removed entirely, block-level elements
+/// become line breaks, and character entities are decoded. This isn't a full
+/// HTML parser, but it's enough for license texts saved from web pages, which
+/// otherwise match poorly due to tags and entities.
+///
+/// ```
+/// assert_eq!(
+///     "Copyright © 2018\n\nAll rights reserved.",
+///     askalono::strip_html("<p>Copyright &copy; 2018</p><p>All <b>rights</b> reserved.</p>").trim()
+/// );
+/// ```
+pub fn strip_html(text: &str) -> String {
+    strip_html_tracked(text).into_string()
+}
+
+/// Strip HTML markup as in `strip_html`, keeping track of where each line
+/// of the result came from.
+pub(crate) fn strip_html_tracked(text: &str) -> TrackedText {
+    lazy_static! {
+        static ref HIDDEN: Regex =
+            Regex::new(r"(?is)<!--.*?-->|<script\b.*?</script\s*>|<style\b.*?</style\s*>").unwrap();
+        static ref LINE_BREAK: Regex = Regex::new(r"(?i)<br\s*/?>").unwrap();
+        static ref BLOCK: Regex = Regex::new(
+            r"(?i)</?(address|article|blockquote|dd|div|dl|dt|h[1-6]|hr|li|ol|p|pre|section|table|tr|ul)\b[^>]*>"
+        )
+        .unwrap();
+        static ref TAG: Regex = Regex::new(r"<[^>]*>").unwrap();
+        static ref BLANK_LINES: Regex = Regex::new(r"\n[ \t]*(\n[ \t]*){2,}").unwrap();
+        static ref ENTITY: Regex = Regex::new(r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[a-zA-Z]+);").unwrap();
+    }
+
+    let mut text = TrackedText::new(text);
+    text.replace_all(&HIDDEN, "");
+    text.replace_all(&LINE_BREAK, "\n");
+    text.replace_all(&BLOCK, "\n\n");
+    text.replace_all(&TAG, "");
+    text.replace_all(&BLANK_LINES, "\n\n");
+    text.replace_all(&ENTITY, |caps: &regex::Captures<'_>| {
+        decode_entity(&caps[1]).unwrap_or_else(|| caps[0].to_owned())
+    });
+    text
+}
+
+/// Text being rewritten, along with where in the original text each of its
+/// bytes came from, so that its lines can be traced back to the original's.
+pub(crate) struct TrackedText {
+    text: String,
+    /// The offset in the original text of each byte, plus one past the end.
+    origins: Vec<usize>,
+}
+
+impl TrackedText {
+    fn new(text: &str) -> TrackedText {
+        TrackedText {
+            text: text.to_owned(),
+            origins: (0..=text.len()).collect(),
+        }
+    }
+
+    /// Replace every match of a regex, as with `Regex::replace_all`.
+    /// Replacements are traced to where their match started.
+    fn replace_all<R: Replacer>(&mut self, regex: &Regex, mut replace: R) {
+        let mut text = String::with_capacity(self.text.len());
+        let mut origins = Vec::with_capacity(self.origins.len());
+        let mut last = 0;
+        for caps in regex.captures_iter(&self.text) {
+            let found = caps.get(0).unwrap();
+            text.push_str(&self.text[last..found.start()]);
+            origins.extend_from_slice(&self.origins[last..found.start()]);
+            replace.replace_append(&caps, &mut text);
+            origins.resize(text.len(), self.origins[found.start()]);
+            last = found.end();
+        }
+        text.push_str(&self.text[last..]);
+        origins.extend_from_slice(&self.origins[last..]);
+        self.text = text;
+        self.origins = origins;
+    }
+
+    /// Find the line of the original text that each line came from.
+    pub(crate) fn line_origins(&self, original: &str) -> Vec<usize> {
+        let original_starts: Vec<usize> = std::iter::once(0)
+            .chain(original.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        std::iter::once(0)
+            .chain(self.text.match_indices('\n').map(|(i, _)| i + 1))
+            .map(|start| {
+                let origin = self.origins[start];
+                original_starts.partition_point(|&line_start| line_start <= origin) - 1
+            })
+            .collect()
+    }
+
+    pub(crate) fn into_string(self) -> String {
+        self.text
+    }
+}
+
+/// Strip Markdown syntax from a document, leaving its text.
+///
+/// Headings, emphasis, code spans and fences, block quotes, bullets, and
+/// horizontal rules lose their markers; links are replaced with their text,
+/// and images (such as badges) and link definitions are removed. Common
+/// inline HTML tags and comments are removed as well, and character entities
+/// are decoded. Line breaks are kept, so line numbers in the result are those
+/// of the original document.
+///
+/// ```
+/// assert_eq!(
+///     "The MIT License\n\nPermission is hereby granted, free of charge",
+///     askalono::strip_markdown(
+///         "[![MIT](https://img.shields.io/badge/license-MIT-blue.svg)](LICENSE)\n\
+///          # The MIT License\n\n\
+///          Permission is **hereby** granted, [free of charge](https://example.com)"
+///     )
+///     .trim()
+/// );
+/// ```
+pub fn strip_markdown(text: &str) -> String {
+    lazy_static! {
+        static ref COMMENT: Regex = Regex::new(r"(?s)<!--.*?-->").unwrap();
+        static ref LINE_ONLY: Regex = Regex::new(
+            r"(?mx)
+            # code fences, rules and setext heading underlines, link definitions
+            ^[\x20\t]{0,3}(?:```.*|~~~.*|(?:[-*_=][\x20\t]*){3,}|\[[^\]]+\]:[\x20\t]*\S.*)$"
+        )
+        .unwrap();
+        static ref LINE_PREFIX: Regex = Regex::new(
+            r"(?m)^[\x20\t]{0,3}(?:#{1,6}[\x20\t]+|(?:>[\x20\t]?)+|[-*+][\x20\t]+)"
+        )
+        .unwrap();
+        static ref HEADING_SUFFIX: Regex = Regex::new(r"(?m)[\x20\t]+#+[\x20\t]*$").unwrap();
+        static ref IMAGE: Regex = Regex::new(r"!\[[^\]]*\](?:\([^)]*\)|\[[^\]]*\])").unwrap();
+        static ref LINK: Regex = Regex::new(r"\[([^\]]*)\](?:\([^)]*\)|\[[^\]]*\])").unwrap();
+        static ref AUTOLINK: Regex = Regex::new(r"<((?:https?|mailto):[^>\s]*)>").unwrap();
+        static ref TAG: Regex = Regex::new(
+            r"(?i)</?(?:a|b|br|center|code|details|div|em|h[1-6]|hr|i|img|kbd|p|picture|pre|source|span|strong|sub|summary|sup)\b[^>]*>"
+        )
+        .unwrap();
+        static ref CODE: Regex = Regex::new(r"`+([^`]*)`+").unwrap();
+        static ref EMPHASIS: [Regex; 7] = [
+            Regex::new(r"\*\*\*(\S(?:.*?\S)?)\*\*\*").unwrap(),
+            Regex::new(r"\*\*(\S(?:.*?\S)?)\*\*").unwrap(),
+            Regex::new(r"\*(\S(?:.*?\S)?)\*").unwrap(),
+            Regex::new(r"\b___(\S(?:.*?\S)?)___\b").unwrap(),
+            Regex::new(r"\b__(\S(?:.*?\S)?)__\b").unwrap(),
+            Regex::new(r"\b_(\S(?:.*?\S)?)_\b").unwrap(),
+            Regex::new(r"~~(\S(?:.*?\S)?)~~").unwrap(),
+        ];
+        static ref ENTITY: Regex = Regex::new(r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[a-zA-Z]+);").unwrap();
+    }
+
+    // keep the line breaks of multi-line comments
+    let text = COMMENT.replace_all(text, |caps: &regex::Captures<'_>| {
+        "\n".repeat(caps[0].matches('\n').count())
+    });
+    let text = LINE_ONLY.replace_all_cow(text, "");
+    let text = LINE_PREFIX.replace_all_cow(text, "");
+    let text = HEADING_SUFFIX.replace_all_cow(text, "");
+    let text = IMAGE.replace_all_cow(text, "");
+    let text = LINK.replace_all_cow(text, "$1");
+    let text = AUTOLINK.replace_all_cow(text, "$1");
+    let text = TAG.replace_all_cow(text, "");
+    let mut text = CODE.replace_all_cow(text, "$1");
+    for emphasis in EMPHASIS.iter() {
+        text = emphasis.replace_all_cow(text, "$1");
+    }
+    ENTITY
+        .replace_all_cow(text, |caps: &regex::Captures<'_>| {
+            decode_entity(&caps[1]).unwrap_or_else(|| caps[0].to_owned())
+        })
+        .into_owned()
+}
+
+fn decode_entity(entity: &str) -> Option<String> {
+    if let Some(num) = entity.strip_prefix('#') {
+        let code = match num.strip_prefix(|c| c == 'x' || c == 'X') {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => num.parse().ok()?,
+        };
+        return std::char::from_u32(code).map(String::from);
+    }
+
+    let decoded = match entity {
+        "amp" => "&",
+        "lt" => "<",
+        "gt" => ">",
+        "quot" => "\"",
+        "apos" => "'",
+        "nbsp" => " ",
+        "copy" => "©",
+        "reg" => "®",
+        "trade" => "™",
+        "sect" => "§",
+        "ndash" => "–",
+        "mdash" => "—",
+        "hellip" => "…",
+        "lsquo" => "‘",
+        "rsquo" => "’",
+        "ldquo" => "“",
+        "rdquo" => "”",
+        "laquo" => "«",
+        "raquo" => "»",
+        "bull" => "•",
+        "middot" => "·",
+        _ => return None,
+    };
+    Some(decoded.to_owned())
+}
+
 // Line-by-line normalizers
 
 fn normalize_unicode(input: Cow<str>) -> Cow<str> {
     input.nfc().collect::<String>().into()
 }
 
+fn normalize_placeholders(input: Cow<str>) -> Cow<str> {
+    lazy_static! {
+        static ref RX: Regex = Regex::new(
+            r"(?x)
+            # SPDX license template variables
+            <<var;[^>]*>>
+            # <year>, <copyright holder>, [yyyy], [name of copyright owner], {fullname}
+            | <\pL[\pL\x20_-]{0,40}>
+            | \[\pL[\pL\x20_-]{0,40}\]
+            | \{\pL[\pL\x20_-]{0,40}\}
+            # $ORGANIZATION, ${YEAR}
+            | \$\{?[A-Z][A-Z_]+\}?
+        "
+        )
+        .unwrap();
+    }
+    RX.replace_all_cow(input, PLACEHOLDER)
+}
+
 fn remove_junk(input: Cow<str>) -> Cow<str> {
     lazy_static! {
         static ref RX: Regex = Regex::new(r"[^\w\s\pP]+").unwrap();
@@ -286,6 +744,38 @@ fn remove_copyright_statements(input: Cow<str>) -> Cow<str> {
     RX.replace_all_cow(input, "\n\n")
 }
 
+/// Treat the names filled in at well-known places in license texts as
+/// placeholders, so that a filled-in copy ("neither the name of Acme Corp
+/// nor...") matches its template ("neither the name of <organization>
+/// nor...") and the canonical text ("neither the name of the copyright
+/// holder nor...") alike.
+fn wildcard_placeholder_contexts(input: Cow<str>) -> Cow<str> {
+    lazy_static! {
+        // a few words between fixed phrases; the lazy repetition stops at the
+        // first closing phrase
+        static ref RX_CONTEXTS: Vec<Regex> = [
+            (r"neither\s+the\s+names?\s+of", r"nor\s+the\s+names?"),
+            (r"provided\s+by", r"as\s+is"),
+            (r"shall", r"be\s+liable"),
+        ]
+        .iter()
+        .map(|(open, close)| {
+            Regex::new(&format!(
+                r"\b(?P<open>{})\s+(?:\S+\s+){{1,6}}?(?P<close>{})\b",
+                open, close
+            ))
+            .unwrap()
+        })
+        .collect();
+        static ref RX_REPEATED: Regex = Regex::new(r"\bplaceholder(?:\s+placeholder\b)+").unwrap();
+    }
+    let mut out = input;
+    for rx in RX_CONTEXTS.iter() {
+        out = rx.replace_all_cow(out, format!("$open {} $close", PLACEHOLDER));
+    }
+    RX_REPEATED.replace_all_cow(out, PLACEHOLDER)
+}
+
 fn collapse_whitespace(input: Cow<str>) -> Cow<str> {
     lazy_static! {
         static ref RX: Regex = Regex::new(r"\s+").unwrap();
@@ -424,4 +914,124 @@ mod tests {
             "normalizers shouldnt change line counts"
         );
     }
+
+    #[test]
+    fn placeholders() {
+        assert_eq!(
+            "Copyright (c) placeholder placeholder.",
+            normalize_placeholders("Copyright (c) <year> <copyright holders>.".into())
+        );
+        assert_eq!(
+            "Copyright placeholder placeholder",
+            normalize_placeholders("Copyright [yyyy] [name of copyright owner]".into())
+        );
+        assert_eq!(
+            "placeholder, placeholder and placeholder",
+            normalize_placeholders(
+                r#"$ORGANIZATION, {fullname} and <<var;name="copyright";original="the copyright holder";match=".+">>"#.into()
+            )
+        );
+        // not placeholders
+        for text in [
+            "see <https://www.gnu.org/licenses/>",
+            "<jane@example.com>",
+            "[1]",
+            "$5",
+        ] {
+            assert_eq!(text, normalize_placeholders(text.into()));
+        }
+    }
+
+    #[test]
+    fn placeholder_contexts() {
+        let filled = apply_aggressive_with(
+            "neither the name of Acme Widgets, Inc. nor the names of its contributors\n\
+             provided by acme \"as is\"; in no event shall acme widgets be liable",
+            &TextDataOptions::default(),
+        );
+        let template = apply_aggressive_with(
+            "neither the name of the <organization> nor the names of its contributors\n\
+             provided by <copyright holder> \"as is\"; in no event shall <author> be liable",
+            &TextDataOptions::default(),
+        );
+        assert_eq!(
+            "neither the name of placeholder nor the names of its contributors \
+             provided by placeholder as is in no event shall placeholder be liable",
+            filled
+        );
+        assert_eq!(filled, template);
+
+        // too far apart to be a placeholder
+        let text = "shall not be used in advertising or otherwise to promote be liable";
+        assert_eq!(
+            text,
+            apply_aggressive_with(text, &TextDataOptions::default())
+        );
+    }
+
+    #[test]
+    fn template_wildcards() {
+        assert_eq!(
+            "a  askalonotemplatewildcard  b  askalonotemplatewildcard  c",
+            wildcard_template(
+                "a <<var;name=\"x\";original=\"<x>\";match=\".+\">> b \
+                 <<beginOptional>>one <<beginOptional>>two<<endOptional>> \
+                 <<var;name=y;original=y;match=y>><<endOptional>> c"
+            )
+        );
+        assert_eq!("no variables", wildcard_template("no variables"));
+    }
+
+    #[test]
+    fn strip_html_markup() {
+        let html = r##"<!DOCTYPE html>
+<html><head><title>MIT</title><style>p { color: red; }</style>
+<script>var x = "<p>";</script></head>
+<body><!-- a comment -->
+<h1>The MIT License</h1>
+<p>Permission is hereby granted, <a href="#">free</a>&nbsp;of&nbsp;charge&#44; to any person
+obtaining a copy<br/>of this software &amp; associated files &#x2014; &bogus;</p>
+</body></html>"##;
+        let text = strip_html(html);
+        assert!(!text.contains('<'), "tags remain: {}", text);
+        assert!(!text.contains("color"), "styles remain: {}", text);
+        assert!(!text.contains("var x"), "scripts remain: {}", text);
+        assert!(text.contains("The MIT License\n"));
+        assert!(text.contains("free of charge, to any person\nobtaining a copy\nof this"));
+        assert!(text.contains("software & associated files \u{2014} &bogus;"));
+    }
+
+    #[test]
+    fn strip_markdown_syntax() {
+        let markdown = "\
+# The MIT License #
+[![Build](https://ci.example.com/badge.svg)](https://ci.example.com) ![MIT][badge]
+
+<!-- a comment
+spanning lines -->
+Copyright (c) 2018 Jane&nbsp;Doe
+===
+
+> Permission is *hereby* granted, __free__ of charge, to `any` person
+> obtaining a copy of [this software][home] and snake_case_names
+* Redistributions must ~~not~~ retain <https://example.com>.
+---
+```
+THE SOFTWARE IS PROVIDED <b>\"AS IS\"</b>
+```
+
+[home]: https://example.com
+[badge]: https://img.shields.io/badge/license-MIT-blue.svg
+";
+        let text = strip_markdown(markdown);
+        assert_eq!(markdown.lines().count(), text.lines().count());
+        assert_eq!(
+            "The MIT License\n \n\n\n\nCopyright (c) 2018 Jane Doe\n\n\n\
+             Permission is hereby granted, free of charge, to any person\n\
+             obtaining a copy of this software and snake_case_names\n\
+             Redistributions must not retain https://example.com.\n\n\n\
+             THE SOFTWARE IS PROVIDED \"AS IS\"\n\n\n\n\n",
+            text
+        );
+    }
 }
@@ -0,0 +1,106 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::Error;
+
+use crate::{
+    license::{Scratch, TextData},
+    strategy::{ScanResult, ScanStrategy},
+};
+
+/// A `ScanStrategy` paired with working buffers that are kept between scans.
+///
+/// Optimizing the bounds of a match scores many windows of the text, and
+/// `ScanStrategy::scan` allocates fresh buffers for that on every call. When
+/// scanning many texts in a row, such as while crawling a directory, a
+/// `Scanner` reuses them instead. Results are identical to those of
+/// `ScanStrategy::scan`.
+///
+/// A `Scanner` needs to be mutable to scan, so use one per thread.
+///
+/// # Examples
+///
+/// ```rust,should_panic
+/// # use std::fs::File;
+/// # use std::error::Error;
+/// use askalono::{Scanner, ScanStrategy, Store, TextData};
+///
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// let store = Store::from_cache(File::open("askalono-cache.bin.zstd")?)?;
+/// let mut scanner = Scanner::new(ScanStrategy::new(&store).optimize(true));
+/// for text in &["what's this", "and this"] {
+///     let result = scanner.scan(&TextData::from(*text))?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Scanner<'a> {
+    strategy: ScanStrategy<'a>,
+    scratch: Scratch,
+}
+
+impl<'a> Scanner<'a> {
+    /// Create a scanner that scans with the given strategy.
+    pub fn new(strategy: ScanStrategy<'a>) -> Scanner<'a> {
+        Scanner {
+            strategy,
+            scratch: Scratch::default(),
+        }
+    }
+
+    /// Get the strategy this scanner scans with.
+    pub fn strategy(&self) -> &ScanStrategy<'a> {
+        &self.strategy
+    }
+
+    /// Scan the given text content. See `ScanStrategy::scan`.
+    pub fn scan(&mut self, text: &TextData) -> Result<ScanResult<'a>, Error> {
+        self.strategy.scan_with(text, &mut self.scratch)
+    }
+}
+
+impl<'a> From<ScanStrategy<'a>> for Scanner<'a> {
+    fn from(strategy: ScanStrategy<'a>) -> Self {
+        Scanner::new(strategy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{strategy::ScanMode, Store};
+
+    #[test]
+    fn same_as_strategy() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc\nddddd eeeee".into());
+        store.add_license("license-2".into(), "fffff ggggg hhhhh\niiiii jjjjj".into());
+
+        let texts = [
+            "aaaaa bbbbb ccccc\nddddd eeeee",
+            "zzzzz\naaaaa bbbbb ccccc\nddddd eeeee\nyyyyy\nxxxxx",
+            "fffff ggggg hhhhh\niiiii jjjjj\n\naaaaa bbbbb ccccc\nddddd eeeee",
+            "nothing to see here",
+        ];
+        for mode in [ScanMode::Elimination, ScanMode::TopDown, ScanMode::Sliding] {
+            let strategy = || {
+                ScanStrategy::new(&store)
+                    .mode(mode)
+                    .optimize(true)
+                    .confidence_threshold(0.5)
+                    .shallow_limit(1.0)
+                    .step_size(1)
+            };
+            let mut scanner = Scanner::new(strategy());
+            for text in texts {
+                let text = TextData::from(text);
+                let expected = strategy().scan(&text).unwrap();
+                let actual = scanner.scan(&text).unwrap();
+                assert_eq!(
+                    serde_json::to_value(&expected).unwrap(),
+                    serde_json::to_value(&actual).unwrap()
+                );
+            }
+        }
+    }
+}
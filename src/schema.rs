@@ -0,0 +1,302 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    deviation::Deviation,
+    license::LicenseType,
+    strategy::{ContainedResult, FragmentResult, ScanResult},
+};
+
+/// The version of the serialized form of askalono's result types.
+///
+/// `Match` and `ScanResult` (in the form of a `ScanRecord`) include this
+/// number as a `schema_version` field when serialized, so that consumers
+/// parsing JSON (or any other serde format) can detect when the shape of the
+/// output changes. It will be bumped whenever a field is renamed, removed, or
+/// changes meaning; new fields may be added without a bump.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The serialized form of a `ScanResult`.
+///
+/// `ScanResult` borrows from the `Store` that produced it, so it can only be
+/// serialized. It does so through this owned type, which can also be
+/// deserialized, so that anything consuming askalono's output can parse it
+/// back into the same shape it was written from.
+///
+/// The identified license is flattened into the top level, and every field is
+/// always present: fields with no value (such as `license` when nothing met
+/// the confidence threshold) are written as an explicit `null`.
+///
+/// # Examples
+///
+/// ```
+/// use askalono::ScanRecord;
+///
+/// let json = r#"{
+///     "schema_version": 1,
+///     "score": 0.0,
+///     "license": null,
+///     "kind": null,
+///     "family": null,
+///     "variant_label": null,
+///     "exception": null,
+///     "ambiguous": false,
+///     "containing": [],
+///     "expression": null,
+///     "fragments": [],
+///     "deviations": []
+/// }"#;
+/// let record: ScanRecord = serde_json::from_str(json).unwrap();
+/// assert_eq!(None, record.license);
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ScanRecord {
+    /// The version of the schema the record was written with. See
+    /// `SCHEMA_VERSION`.
+    pub schema_version: u32,
+    /// The confidence of the overall match from 0.0 to 1.0.
+    pub score: f32,
+    /// The name of the identified license, if any.
+    pub license: Option<String>,
+    /// The type of the identified license, if any.
+    pub kind: Option<LicenseType>,
+    /// The family of the identified license, if known.
+    pub family: Option<String>,
+    /// The label of the matched header or alternate, if it has one.
+    pub variant_label: Option<String>,
+    /// The license exception found along with the identified license, if
+    /// any. Absent in records written before this field was added.
+    #[serde(default)]
+    pub exception: Option<String>,
+    /// The name of the store the identified license came from, if it has
+    /// one. Absent in records written before this field was added.
+    #[serde(default)]
+    pub store: Option<String>,
+    /// Whether the overall match was too close to call.
+    pub ambiguous: bool,
+    /// Licenses discovered inside the text.
+    pub containing: Vec<ContainedRecord>,
+    /// An SPDX expression combining the licenses discovered inside the
+    /// text, if there's more than one. Absent in records written before this
+    /// field was added.
+    #[serde(default)]
+    pub expression: Option<String>,
+    /// License fragments found in the text.
+    pub fragments: Vec<FragmentRecord>,
+    /// How the text differs from the identified license. Absent in records
+    /// written before this field was added.
+    #[serde(default)]
+    pub deviations: Vec<Deviation>,
+}
+
+/// The serialized form of a `ContainedResult`. See `ScanRecord`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ContainedRecord {
+    /// The confidence of the match within the line range from 0.0 to 1.0.
+    pub score: f32,
+    /// The name of the license identified.
+    pub license: String,
+    /// The type of the license identified.
+    pub kind: LicenseType,
+    /// The family of the license, if known.
+    pub family: Option<String>,
+    /// The label of the matched header or alternate, if it has one.
+    pub variant_label: Option<String>,
+    /// The license exception found along with the license, if any. Absent
+    /// in records written before this field was added.
+    #[serde(default)]
+    pub exception: Option<String>,
+    /// The name of the store the license came from, if it has one.
+    #[serde(default)]
+    pub store: Option<String>,
+    /// A 0-indexed (inclusive, exclusive) range of line numbers where the
+    /// license was identified.
+    pub line_range: (usize, usize),
+    /// Whether another license scored nearly as well as this one.
+    pub ambiguous: bool,
+    /// Other line ranges where the same license was found. Absent in records
+    /// written before this field was added.
+    #[serde(default)]
+    pub repeats: Vec<(usize, usize)>,
+    /// A 0-indexed (inclusive, exclusive) range of byte offsets where the
+    /// license was identified, if known. Absent in records written before
+    /// this field was added.
+    #[serde(default)]
+    pub byte_range: Option<(usize, usize)>,
+    /// Like `byte_range`, in characters.
+    #[serde(default)]
+    pub char_range: Option<(usize, usize)>,
+}
+
+/// The serialized form of a `FragmentResult`. See `ScanRecord`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FragmentRecord {
+    /// The fraction of the scanned text found within the license, from 0.0 to
+    /// 1.0.
+    pub score: f32,
+    /// The name of the license the fragment was taken from.
+    pub license: String,
+    /// The type of the license variant the fragment was taken from.
+    pub kind: LicenseType,
+    /// The family of the license, if known.
+    pub family: Option<String>,
+    /// The label of the header or alternate, if it has one.
+    pub variant_label: Option<String>,
+    /// The name of the store the license came from, if it has one.
+    #[serde(default)]
+    pub store: Option<String>,
+    /// A 0-indexed (inclusive, exclusive) range of line numbers of the scanned
+    /// text containing the fragment.
+    pub line_range: (usize, usize),
+    /// A 0-indexed (inclusive, exclusive) range of line numbers of the
+    /// license's normalized text that the fragment covers, if known.
+    pub license_line_range: Option<(usize, usize)>,
+}
+
+impl<'a> From<&ScanResult<'a>> for ScanRecord {
+    fn from(result: &ScanResult<'a>) -> ScanRecord {
+        let license = result.license.as_ref();
+        ScanRecord {
+            schema_version: SCHEMA_VERSION,
+            score: result.score,
+            license: license.map(|l| l.name.to_owned()),
+            kind: license.map(|l| l.kind),
+            family: license.and_then(|l| l.family).map(str::to_owned),
+            variant_label: license.and_then(|l| l.variant_label).map(str::to_owned),
+            exception: license.and_then(|l| l.exception).map(str::to_owned),
+            store: license.and_then(|l| l.store).map(str::to_owned),
+            ambiguous: result.ambiguous,
+            containing: result
+                .containing
+                .iter()
+                .map(ContainedRecord::from)
+                .collect(),
+            expression: result.expression.clone(),
+            fragments: result.fragments.iter().map(FragmentRecord::from).collect(),
+            deviations: result.deviations.clone(),
+        }
+    }
+}
+
+impl<'a> From<&ContainedResult<'a>> for ContainedRecord {
+    fn from(result: &ContainedResult<'a>) -> ContainedRecord {
+        ContainedRecord {
+            score: result.score,
+            license: result.license.name.to_owned(),
+            kind: result.license.kind,
+            family: result.license.family.map(str::to_owned),
+            variant_label: result.license.variant_label.map(str::to_owned),
+            exception: result.license.exception.map(str::to_owned),
+            store: result.license.store.map(str::to_owned),
+            line_range: result.line_range,
+            ambiguous: result.ambiguous,
+            repeats: result.repeats.clone(),
+            byte_range: result.byte_range,
+            char_range: result.char_range,
+        }
+    }
+}
+
+impl<'a> From<&FragmentResult<'a>> for FragmentRecord {
+    fn from(result: &FragmentResult<'a>) -> FragmentRecord {
+        FragmentRecord {
+            score: result.score,
+            license: result.license.name.to_owned(),
+            kind: result.license.kind,
+            family: result.license.family.map(str::to_owned),
+            variant_label: result.license.variant_label.map(str::to_owned),
+            store: result.license.store.map(str::to_owned),
+            line_range: result.line_range,
+            license_line_range: result.license_line_range,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{store::Store, strategy::ScanStrategy};
+
+    fn scan_json(store: &Store, text: &str) -> serde_json::Value {
+        let result = ScanStrategy::new(store)
+            .optimize(true)
+            .scan(&text.into())
+            .unwrap();
+        serde_json::to_value(&result).unwrap()
+    }
+
+    #[test]
+    fn fields_are_stable() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+
+        let json = scan_json(&store, "aaaaa bbbbb ccccc");
+        let mut keys: Vec<_> = json.as_object().unwrap().keys().cloned().collect();
+        keys.sort();
+        assert_eq!(
+            vec![
+                "ambiguous",
+                "containing",
+                "deviations",
+                "exception",
+                "expression",
+                "family",
+                "fragments",
+                "kind",
+                "license",
+                "schema_version",
+                "score",
+                "store",
+                "variant_label",
+            ],
+            keys
+        );
+        assert_eq!("license-1", json["license"]);
+        assert_eq!("original", json["kind"]);
+    }
+
+    #[test]
+    fn explicit_nulls() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+
+        let json = scan_json(&store, "zzzzz yyyyy xxxxx");
+        for key in &[
+            "license",
+            "kind",
+            "family",
+            "variant_label",
+            "exception",
+            "store",
+        ] {
+            assert_eq!(Some(&serde_json::Value::Null), json.get(key), "{}", key);
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+        store.add_license("license-2".into(), "ddddd eeeee fffff".into());
+        store
+            .set_family("license-1", Some("license".into()))
+            .unwrap();
+
+        let text = "aaaaa bbbbb ccccc\nsome unrelated text here\nddddd eeeee fffff".into();
+        let result = ScanStrategy::new(&store)
+            .optimize(true)
+            .confidence_threshold(0.5)
+            .scan(&text)
+            .unwrap();
+        assert!(!result.containing.is_empty());
+
+        let json = serde_json::to_string(&result).unwrap();
+        let parsed: ScanRecord = serde_json::from_str(&json).unwrap();
+        assert_eq!(ScanRecord::from(&result), parsed);
+        assert_eq!(SCHEMA_VERSION, parsed.schema_version);
+        assert_eq!(json, serde_json::to_string(&parsed).unwrap());
+    }
+}
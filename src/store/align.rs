@@ -0,0 +1,92 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+/// Compare two token sequences by their longest common subsequence, from 0
+/// to 1: twice its length over the total length of both.
+///
+/// Unlike n-gram scores, this accounts for word order across the whole
+/// text, so reordered or partially duplicated texts score lower.
+pub(crate) fn alignment_score(a: &[&str], b: &[&str]) -> f32 {
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+
+    // iterate the longer sequence, keeping one row per token of the shorter
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut previous = vec![0u32; short.len() + 1];
+    let mut current = vec![0u32; short.len() + 1];
+    for token in long {
+        for (j, other) in short.iter().enumerate() {
+            current[j + 1] = if token == other {
+                previous[j] + 1
+            } else {
+                previous[j + 1].max(current[j])
+            };
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    let common = previous[short.len()] as f32;
+    2.0 * common / (a.len() + b.len()) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        license::TextData,
+        store::{base::Store, options::AnalysisOptions},
+    };
+
+    fn score(a: &str, b: &str) -> f32 {
+        let a: Vec<&str> = a.split(' ').collect();
+        let b: Vec<&str> = b.split(' ').collect();
+        alignment_score(&a, &b)
+    }
+
+    #[test]
+    fn alignment_scores() {
+        assert_eq!(1.0, score("aaa bbb ccc", "aaa bbb ccc"));
+        assert_eq!(0.0, score("aaa bbb", "ccc ddd"));
+        // "aaa ccc" in common
+        assert_eq!(4.0 / 6.0, score("aaa bbb ccc", "aaa ccc ddd"));
+        // order matters, unlike with n-grams
+        assert_eq!(2.0 / 6.0, score("aaa bbb ccc", "ccc bbb aaa"));
+        assert_eq!(1.0, alignment_score(&[], &[]));
+    }
+
+    #[test]
+    fn realigns_close_calls() {
+        // license-2 has the text's halves swapped, so it shares more bigrams
+        // with it, but license-1 has more of it in order
+        let mut store = Store::new();
+        store.add_license(
+            "license-1".into(),
+            "aaaaa bbbbb ccccc ddddd eeeee fffff xxxxx yyyyy".into(),
+        );
+        store.add_license(
+            "license-2".into(),
+            "eeeee fffff ggggg hhhhh aaaaa bbbbb ccccc ddddd".into(),
+        );
+        let text = TextData::from("aaaaa bbbbb ccccc ddddd eeeee fffff ggggg hhhhh");
+
+        let matched = store.analyze(&text);
+        assert_eq!("license-2", matched.name);
+        assert!(matched.score > 0.6 && matched.score < 0.9);
+
+        let mut options = AnalysisOptions {
+            alignment_fallback: Some((0.6, 0.9)),
+            ..Default::default()
+        };
+        let matched = store.analyze_with(&text, &options).unwrap();
+        assert_eq!("license-1", matched.name);
+        assert_eq!(0.75, matched.score);
+        assert_eq!(Some("license-2"), matched.runner_up);
+        assert_eq!(0.5, matched.runner_up_score);
+
+        // outside the band, nothing changes
+        options.alignment_fallback = Some((0.9, 1.0));
+        let matched = store.analyze_with(&text, &options).unwrap();
+        assert_eq!("license-2", matched.name);
+    }
+}
@@ -1,12 +1,24 @@
 // Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::{cmp::Ordering, fmt};
+use std::{cmp::Ordering, collections::BinaryHeap, fmt};
+
+use anyhow::{format_err, Error};
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+use tracing::{debug_span, field};
 
 use crate::{
     license::LicenseType,
     license::TextData,
-    store::base::{LicenseEntry, Store},
+    ngram::NgramSet,
+    schema::SCHEMA_VERSION,
+    store::{
+        align::alignment_score,
+        base::{LicenseEntry, Store, DEFAULT_POPULARITY},
+        clause::has_clause,
+        options::AnalysisOptions,
+    },
+    timing::Stopwatch,
 };
 
 /// Information about text that was compared against licenses in the store.
@@ -15,6 +27,9 @@ use crate::{
 /// data you can run methods like `optimize_bounds` on `TextData`.
 ///
 /// Its lifetime is tied to the lifetime of the `Store` it was generated from.
+///
+/// When serialized, the license data reference is omitted; see
+/// `SCHEMA_VERSION` for details on the serialized form.
 #[derive(Clone)]
 pub struct Match<'a> {
     /// Confidence score of the match, ranging from 0 to 1.
@@ -25,34 +40,176 @@ pub struct Match<'a> {
     /// The type of the license that matched. Useful to know if the match was
     /// the complete text, a header, or something else.
     pub license_type: LicenseType,
+    /// The family of the matched license, if known. See `Store::family_of`.
+    pub family: Option<&'a str>,
+    /// The label of the header or alternate that matched, if it was given
+    /// one. See `Store::add_variant_labeled`.
+    pub variant_label: Option<&'a str>,
+    /// The name of a license exception found in the text, if any, as in the
+    /// SPDX expression `name WITH exception`. See `Store::add_exception`.
+    pub exception: Option<&'a str>,
+    /// The name of the best-scoring license other than `name`, if the store
+    /// has more than one license. When `Store::analyze` matched the text
+    /// through its title line, only licenses sharing that title are
+    /// considered; likewise, when it stopped early, only the popular licenses
+    /// it scored are, and when it re-scored a close call by alignment, only
+    /// the licenses it aligned are.
+    pub runner_up: Option<&'a str>,
+    /// The score of `runner_up`, or 0 if there is none.
+    pub runner_up_score: f32,
+    /// Whether `runner_up` scored within `DEFAULT_AMBIGUITY_EPSILON` of this
+    /// match. Ambiguous matches are worth a human look, as the winner may be
+    /// little more than a coin toss. See `is_ambiguous` to use a different
+    /// margin.
+    pub ambiguous: bool,
     /// A reference to the license data that matched inside the `Store`. May be
     /// useful for diagnostic purposes or to further optimize the result.
     pub data: &'a TextData,
 }
 
+impl<'a> Serialize for Match<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Match", 10)?;
+        state.serialize_field("score", &self.score)?;
+        state.serialize_field("name", self.name)?;
+        state.serialize_field("license_type", &self.license_type)?;
+        state.serialize_field("family", &self.family)?;
+        state.serialize_field("variant_label", &self.variant_label)?;
+        state.serialize_field("exception", &self.exception)?;
+        state.serialize_field("runner_up", &self.runner_up)?;
+        state.serialize_field("runner_up_score", &self.runner_up_score)?;
+        state.serialize_field("ambiguous", &self.ambiguous)?;
+        state.serialize_field("schema_version", &SCHEMA_VERSION)?;
+        state.end()
+    }
+}
+
+/// Roughly how many n-gram lookups each parallel task in `analyze` should
+/// do, when the chunk size isn't set explicitly.
+#[cfg(all(feature = "rayon", not(target_arch = "wasm32")))]
+const CHUNK_WORK: usize = 20_000;
+
+/// The most n-grams a license is assumed to have when estimating the cost of
+/// scoring it. Sets are compared by iterating the smaller one, so large
+/// inputs cost no more than this per license.
+#[cfg(all(feature = "rayon", not(target_arch = "wasm32")))]
+const CHUNK_LICENSE_GRAMS: usize = 1_000;
+
+/// The score at which a match against a license named by the text's title
+/// line is accepted without scoring the rest of the store.
+const TITLE_SHORTCUT_SCORE: f32 = 0.99;
+
+/// How close to the best score a license must be for deciding clauses to
+/// choose between them. See `Store::add_clause`.
+const CLAUSE_MARGIN: f32 = 0.05;
+
+/// How many of the best-scoring licenses are re-scored by alignment when the
+/// best score is a close call. See `AnalysisOptions::alignment_fallback`.
+const ALIGNMENT_CANDIDATES: usize = 3;
+
+/// The default margin under which the top two candidates of an analysis are
+/// considered too close to call. See `Match::ambiguous`.
+pub const DEFAULT_AMBIGUITY_EPSILON: f32 = 0.01;
+
+/// Chooses which licenses and variants are scored, by name and type. See
+/// `Store::analyze_filtered`.
+pub(crate) type Filter<'f> = dyn Fn(&str, LicenseType) -> bool + Sync + 'f;
+
+pub(crate) fn any_license(_: &str, _: LicenseType) -> bool {
+    true
+}
+
+impl<'a> Match<'a> {
+    /// Check whether the runner-up license scored within `epsilon` of this
+    /// match.
+    pub fn is_ambiguous(&self, epsilon: f32) -> bool {
+        self.runner_up.is_some() && self.score - self.runner_up_score <= epsilon
+    }
+
+    /// Get the normalized text of the license variant that matched, with
+    /// lines separated by newlines.
+    ///
+    /// Returns `None` if the `Store` was built or loaded without texts.
+    pub fn matched_text(&self) -> Option<String> {
+        self.data.normalized_text()
+    }
+}
+
 /// A lighter version of Match to be used during analysis.
 /// Reduces the need for cloning a bunch of fields.
+#[derive(Clone, Copy)]
 struct PartialMatch<'a> {
     pub name: &'a str,
     pub score: f32,
     pub license_type: LicenseType,
+    /// Position of `license_type` in the store's type preference; lower is
+    /// preferred on ties.
+    pub rank: usize,
+    /// Whether the text's title line names this license.
+    pub titled: bool,
+    pub label: Option<&'a str>,
     pub data: &'a TextData,
 }
 
+// Better matches compare greater: highest score first, then ties go to
+// licenses named by the text's title, then the preferred license type, then
+// by name, so that results don't depend on iteration order.
+impl<'a> Ord for PartialMatch<'a> {
+    fn cmp(&self, other: &PartialMatch<'_>) -> Ordering {
+        self.score
+            .partial_cmp(&other.score)
+            .unwrap()
+            .then_with(|| self.titled.cmp(&other.titled))
+            .then_with(|| other.rank.cmp(&self.rank))
+            .then_with(|| other.name.cmp(self.name))
+    }
+}
+
 impl<'a> PartialOrd for PartialMatch<'a> {
     fn partial_cmp(&self, other: &PartialMatch<'_>) -> Option<Ordering> {
-        self.score.partial_cmp(&other.score)
+        Some(self.cmp(other))
     }
 }
 
 impl<'a> PartialEq for PartialMatch<'a> {
     fn eq(&self, other: &PartialMatch<'_>) -> bool {
-        self.score.eq(&other.score)
-            && self.name == other.name
-            && self.license_type == other.license_type
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<'a> Eq for PartialMatch<'a> {}
+
+/// An iterator over all of a `Store`'s licenses and their variants, in
+/// descending order of score against some text. See `Store::match_iter`.
+pub struct MatchIter<'a> {
+    store: &'a Store,
+    heap: BinaryHeap<PartialMatch<'a>>,
+    /// Name and score of the best match overall.
+    best: Option<(&'a str, f32)>,
+    /// Name and score of the best match not named `best`.
+    second: Option<(&'a str, f32)>,
+    exception: Option<&'a str>,
+}
+
+impl<'a> Iterator for MatchIter<'a> {
+    type Item = Match<'a>;
+
+    fn next(&mut self) -> Option<Match<'a>> {
+        let m = self.heap.pop()?;
+        let runner_up = match self.best {
+            Some(best) if best.0 != m.name => Some(best),
+            _ => self.second,
+        };
+        Some(self.store.build_match(&m, runner_up, self.exception))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.heap.len(), Some(self.heap.len()))
     }
 }
 
+impl<'a> ExactSizeIterator for MatchIter<'a> {}
+
 impl<'a> fmt::Debug for Match<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -66,75 +223,601 @@ impl<'a> fmt::Debug for Match<'a> {
 impl Store {
     /// Compare the given `TextData` against all licenses in the `Store`.
     ///
-    /// This parallelizes the search as much as it can to find the best match.
+    /// This parallelizes the search as much as it can to find the best match,
+    /// unless the `rayon` feature is disabled.
     /// Once a match is obtained, it can be optimized further; see methods on
     /// `TextData` for more information.
+    ///
+    /// If the text starts with the title of a license in the store (see
+    /// `add_title`) and matches that license nearly exactly, the rest of the
+    /// store is skipped. Otherwise, titled licenses win ties. If a candidate
+    /// index is built (see `set_candidate_index`) or LSH prefilter (see
+    /// `set_lsh_prefilter`), only the licenses they suggest are scored. See
+    /// `analyze_with` to change how texts are scored.
+    ///
+    /// Parallel work is performed on the current rayon thread pool, which is
+    /// the global pool unless called from inside another pool. To isolate
+    /// askalono's work from the rest of your application, see
+    /// `analyze_with_pool`.
     pub fn analyze<'a>(&'a self, text: &TextData) -> Match<'a> {
-        let mut res: Vec<PartialMatch<'a>>;
+        self.analyze_matching(text, &AnalysisOptions::default(), &any_license)
+            .expect("the store has no licenses to analyze against")
+    }
 
-        let analyze_fold =
-            |mut acc: Vec<PartialMatch<'a>>, (name, data): (&'a String, &'a LicenseEntry)| {
-                acc.push(PartialMatch {
-                    score: data.original.match_score(text),
-                    name,
-                    license_type: LicenseType::Original,
-                    data: &data.original,
-                });
-                data.alternates.iter().for_each(|alt| {
-                    acc.push(PartialMatch {
-                        score: alt.match_score(text),
-                        name,
-                        license_type: LicenseType::Alternate,
-                        data: alt,
-                    })
-                });
-                data.headers.iter().for_each(|head| {
-                    acc.push(PartialMatch {
-                        score: head.match_score(text),
-                        name,
-                        license_type: LicenseType::Header,
-                        data: head,
-                    })
-                });
-                acc
+    /// Compare the given `TextData` against all licenses in the `Store`, as
+    /// `analyze` does, with the given options. See `AnalysisOptions`.
+    ///
+    /// If early exit is enabled, popular licenses are scored first, and one
+    /// matching well enough is taken without scoring the rest. If an
+    /// alignment band is set, close calls are re-scored by alignment. With
+    /// exact analysis, any candidate index or LSH prefilter is ignored.
+    ///
+    /// Returns an error if the options are invalid or the store has no
+    /// licenses.
+    pub fn analyze_with<'a>(
+        &'a self,
+        text: &TextData,
+        options: &AnalysisOptions,
+    ) -> Result<Match<'a>, Error> {
+        options.validate()?;
+        self.analyze_matching(text, options, &any_license)
+            .ok_or_else(|| format_err!("the store has no licenses to analyze against"))
+    }
+
+    /// Compare the given `TextData` against the licenses in the `Store` that
+    /// `filter` accepts. The filter is given the name of each license along
+    /// with the type of each of its variants.
+    ///
+    /// Only the accepted variants are scored, so looking for one of a few
+    /// licenses is much faster than analyzing against the whole store.
+    /// Otherwise identical to `analyze`; the runner-up is likewise chosen
+    /// from the accepted licenses. Returns `None` if the filter accepts
+    /// nothing.
+    ///
+    /// ```
+    /// use askalono::{LicenseType, Store, TextData};
+    ///
+    /// let mut store = Store::new();
+    /// store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+    /// store.add_license("license-2".into(), "aaaaa bbbbb ddddd".into());
+    ///
+    /// let text = TextData::from("aaaaa bbbbb ccccc");
+    /// let matched = store
+    ///     .analyze_filtered(&text, |name, _| name == "license-2")
+    ///     .unwrap();
+    /// assert_eq!("license-2", matched.name);
+    /// assert_eq!(None, matched.runner_up);
+    ///
+    /// let headers = |_: &str, kind| kind == LicenseType::Header;
+    /// assert!(store.analyze_filtered(&text, headers).is_none());
+    /// ```
+    pub fn analyze_filtered<'a, F>(&'a self, text: &TextData, filter: F) -> Option<Match<'a>>
+    where
+        F: Fn(&str, LicenseType) -> bool + Sync,
+    {
+        self.analyze_matching(text, &AnalysisOptions::default(), &filter)
+    }
+
+    /// Compare the given `TextData` against the licenses `filter` accepts,
+    /// with options that have already been validated.
+    pub(crate) fn analyze_matching<'a>(
+        &'a self,
+        text: &TextData,
+        options: &AnalysisOptions,
+        filter: &Filter<'_>,
+    ) -> Option<Match<'a>> {
+        let span = debug_span!(
+            "analyze",
+            licenses = self.licenses.len(),
+            name = field::Empty,
+            score = field::Empty,
+            title_shortcut = field::Empty,
+            early_exit = field::Empty,
+            shortlisted = field::Empty,
+            aligned = field::Empty,
+            clause_decided = field::Empty,
+            elapsed_ms = field::Empty,
+        );
+        let _enter = span.enter();
+        let stopwatch = Stopwatch::start();
+        let text = self.fuzzy_corrected(text, options);
+        let text = text.as_ref();
+
+        // if the text's title names a license and the text matches it almost
+        // exactly, there's no need to look any further
+        let titled = self.title_candidates(text);
+        let mut res = self.score_licenses(text, titled, options, filter);
+        res.sort_unstable_by(|a, b| b.cmp(a));
+        let mut shortcut = res.first().is_some_and(|m| m.score >= TITLE_SHORTCUT_SCORE);
+        span.record("title_shortcut", shortcut);
+
+        // likewise if a popular license matches well enough, when allowed
+        if let (false, Some(threshold)) = (shortcut, options.early_exit) {
+            res = self.score_popular(text, titled, threshold, options, filter);
+            res.sort_unstable_by(|a, b| b.cmp(a));
+            shortcut = res.first().is_some_and(|m| m.score >= threshold);
+            span.record("early_exit", shortcut);
+        }
+
+        if !shortcut {
+            let prefiltered = self.index.is_some() || self.lsh.is_some();
+            res = if prefiltered && !options.exact {
+                let res = self.rank_candidates(text, titled, options, filter);
+                span.record("shortlisted", res.len());
+                res
+            } else {
+                self.rank_all(text, titled, options, filter)
             };
+        }
+
+        // double-check close calls, when allowed
+        if let Some(aligned) = self.realign(text, &res, options) {
+            res = aligned;
+            span.record("aligned", true);
+        }
+
+        // near-identical licenses may differ by a single clause
+        span.record("clause_decided", self.decide_by_clauses(text, &mut res));
+
+        let m = res.first()?;
+        // headers and alternates of the same license don't count as competition
+        let runner_up = res.iter().find(|r| r.name != m.name);
 
-        // parallel analysis
-        #[cfg(not(target_arch = "wasm32"))]
+        span.record("name", m.name);
+        span.record("score", m.score);
+        span.record("elapsed_ms", stopwatch.elapsed_ms());
+
+        Some(self.build_match(
+            m,
+            runner_up.map(|r| (r.name, r.score)),
+            self.find_exception(text),
+        ))
+    }
+
+    /// Compare the given `TextData` against all licenses in the `Store`,
+    /// returning up to `n` of the best-matching licenses, best first.
+    ///
+    /// Each license appears once, as its best-scoring variant. The first
+    /// match is the one `analyze_with` would find with the same options,
+    /// except that, as with `match_iter`, every license is scored and no
+    /// shortcut is taken. When close calls are re-scored by alignment (see
+    /// `AnalysisOptions::alignment_fallback`), the re-scored licenses come
+    /// first, followed by the rest as they were scored.
+    ///
+    /// Each match's `runner_up` is the best-scoring license other than its
+    /// own: the second match for the first, and the first for the rest.
+    /// Returns an error if the options are invalid.
+    pub fn analyze_top_n<'a>(
+        &'a self,
+        text: &TextData,
+        n: usize,
+        options: &AnalysisOptions,
+    ) -> Result<Vec<Match<'a>>, Error> {
+        options.validate()?;
+        let text = self.fuzzy_corrected(text, options);
+        let text = text.as_ref();
+        let titled = self.title_candidates(text);
+        let mut res = self.rank_all(text, titled, options, &any_license);
+        if let Some(mut aligned) = self.realign(text, &res, options) {
+            res.retain(|m| aligned.iter().all(|a| a.name != m.name));
+            aligned.append(&mut res);
+            res = aligned;
+        }
+        self.decide_by_clauses(text, &mut res);
+
+        let mut top: Vec<PartialMatch<'a>> = Vec::with_capacity(n);
+        for m in res {
+            if top.len() == n {
+                break;
+            }
+            if top.iter().all(|t| t.name != m.name) {
+                top.push(m);
+            }
+        }
+
+        let runner_up = |i: usize| {
+            let other = if i == 0 { top.get(1) } else { top.first() };
+            other.map(|m| (m.name, m.score))
+        };
+        let exception = self.find_exception(text);
+        Ok(top
+            .iter()
+            .enumerate()
+            .map(|(i, m)| self.build_match(m, runner_up(i), exception))
+            .collect())
+    }
+
+    /// Compare the given `TextData` against all licenses in the `Store`,
+    /// yielding every license and variant as a `Match` from best to worst.
+    ///
+    /// Every license still needs to be scored up front, but ranking is done
+    /// as the iterator is consumed. If you only need the first few results
+    /// (for example, the best match from an allowlist), this avoids sorting
+    /// the entire store. Every license is always scored, so unlike
+    /// `analyze`, no shortcut is taken for texts with a known title.
+    ///
+    /// Each match's `runner_up` is the best-scoring license other than its
+    /// own, among all licenses. Of the options, only the scorer, fuzzy
+    /// tokens, and type preference apply. Returns an error if the options
+    /// are invalid.
+    pub fn match_iter<'a>(
+        &'a self,
+        text: &TextData,
+        options: &AnalysisOptions,
+    ) -> Result<MatchIter<'a>, Error> {
+        options.validate()?;
+        let text = self.fuzzy_corrected(text, options);
+        let text = text.as_ref();
+        let titled = self.title_candidates(text);
+        let res = self.score_all(text, titled, options, &any_license);
+
+        let best = res.iter().max();
+        let second = best.and_then(|best| res.iter().filter(|r| r.name != best.name).max());
+        let best = best.map(|r| (r.name, r.score));
+        let second = second.map(|r| (r.name, r.score));
+
+        Ok(MatchIter {
+            store: self,
+            heap: BinaryHeap::from(res),
+            best,
+            second,
+            exception: self.find_exception(text),
+        })
+    }
+
+    /// Score the given text against every license and variant in the store,
+    /// in no particular order. `titled` lists the licenses named by the
+    /// text's title line. Only variants accepted by `filter` are scored.
+    fn score_all<'a>(
+        &'a self,
+        text: &TextData,
+        titled: &[String],
+        options: &AnalysisOptions,
+        filter: &Filter<'_>,
+    ) -> Vec<PartialMatch<'a>> {
+        let input = text.match_data_excluding(&self.stop_grams);
+        let input = input.as_ref();
+
+        let analyze_fold = |mut acc: Vec<PartialMatch<'a>>,
+                            entry: (&'a String, &'a LicenseEntry)| {
+            let titled = titled.contains(entry.0);
+            self.score_entry(&mut acc, entry, input, titled, options, filter);
+            acc
+        };
+
+        // parallel analysis, a chunk of licenses per task
+        #[cfg(all(feature = "rayon", not(target_arch = "wasm32")))]
         {
             use rayon::prelude::*;
-            res = self
-                .licenses
-                .par_iter()
-                .fold(Vec::new, analyze_fold)
+            let entries: Vec<_> = self.licenses.iter().collect();
+            entries
+                .par_chunks(self.chunk_size(input.len(), options))
+                .map(|chunk| chunk.iter().copied().fold(Vec::new(), analyze_fold))
                 .reduce(
                     Vec::new,
                     |mut a: Vec<PartialMatch<'a>>, b: Vec<PartialMatch<'a>>| {
                         a.extend(b);
                         a
                     },
-                );
-            res.par_sort_unstable_by(|a, b| b.partial_cmp(a).unwrap());
+                )
         }
 
         // single-threaded analysis
-        #[cfg(target_arch = "wasm32")]
+        #[cfg(not(all(feature = "rayon", not(target_arch = "wasm32"))))]
         {
-            res = self
-                .licenses
+            self.licenses
                 .iter()
                 // len of licenses isn't strictly correct, but it'll do
-                .fold(Vec::with_capacity(self.licenses.len()), analyze_fold);
-            res.sort_unstable_by(|a, b| b.partial_cmp(a).unwrap());
+                .fold(Vec::with_capacity(self.licenses.len()), analyze_fold)
         }
+    }
+
+    /// Score the given text against every license and variant in the store,
+    /// sorted best first.
+    fn rank_all<'a>(
+        &'a self,
+        text: &TextData,
+        titled: &[String],
+        options: &AnalysisOptions,
+        filter: &Filter<'_>,
+    ) -> Vec<PartialMatch<'a>> {
+        let mut res = self.score_all(text, titled, options, filter);
 
-        let m = &res[0];
+        #[cfg(all(feature = "rayon", not(target_arch = "wasm32")))]
+        {
+            use rayon::prelude::*;
+            res.par_sort_unstable_by(|a, b| b.cmp(a));
+        }
+        #[cfg(not(all(feature = "rayon", not(target_arch = "wasm32"))))]
+        res.sort_unstable_by(|a, b| b.cmp(a));
 
-        Match {
+        res
+    }
+
+    /// Score the given text against the licenses suggested by the store's
+    /// candidate index and LSH prefilter, those they don't cover, and those
+    /// named by the text's title line, sorted best first. If neither suggests
+    /// anything, every license is scored; if only one license is left, the
+    /// most promising of those pruned is scored too. See
+    /// `set_candidate_index` and `set_lsh_prefilter`.
+    fn rank_candidates<'a>(
+        &'a self,
+        text: &TextData,
+        titled: &[String],
+        options: &AnalysisOptions,
+        filter: &Filter<'_>,
+    ) -> Vec<PartialMatch<'a>> {
+        let input = text.match_data_excluding(&self.stop_grams);
+        let mut names = Vec::new();
+        if let Some(ref index) = self.index {
+            names.extend(index.shortlist(&input));
+        }
+        if let Some(ref lsh) = self.lsh {
+            names.extend(lsh.candidates(&input));
+        }
+        if names.is_empty() {
+            return self.rank_all(text, titled, options, filter);
+        }
+
+        names.extend(titled.iter().map(String::as_str));
+        names.extend(self.licenses.keys().map(String::as_str).filter(|n| {
+            self.index.as_ref().is_some_and(|index| !index.contains(n))
+                || self.lsh.as_ref().is_some_and(|lsh| !lsh.contains(n))
+        }));
+        names.sort_unstable();
+        names.dedup();
+
+        let mut acc = Vec::new();
+        for entry in names.iter().filter_map(|n| self.licenses.get_key_value(*n)) {
+            let titled = titled.contains(entry.0);
+            self.score_entry(&mut acc, entry, input.as_ref(), titled, options, filter);
+        }
+        acc.sort_unstable_by(|a, b| b.cmp(a));
+
+        // pruning usually leaves a single license; score the most promising
+        // of the rest too, so there's still a runner-up
+        let lone = acc.first().map(|m| m.name);
+        if lone.is_some() && acc.iter().all(|m| Some(m.name) == lone) {
+            let skip = |n: &str| names.binary_search(&n).is_ok();
+            let nearest = match (&self.index, &self.lsh) {
+                (Some(index), _) => index.nearest(&input, skip),
+                (None, Some(lsh)) => lsh.nearest(&input, skip),
+                (None, None) => None,
+            };
+            if let Some(entry) = nearest.and_then(|n| self.licenses.get_key_value(n)) {
+                self.score_entry(&mut acc, entry, input.as_ref(), false, options, filter);
+                acc.sort_unstable_by(|a, b| b.cmp(a));
+            }
+        }
+        acc
+    }
+
+    /// Score the given text against only the named licenses (which are
+    /// assumed to be named by its title) and their variants.
+    fn score_licenses<'a>(
+        &'a self,
+        text: &TextData,
+        names: &[String],
+        options: &AnalysisOptions,
+        filter: &Filter<'_>,
+    ) -> Vec<PartialMatch<'a>> {
+        let mut acc = Vec::new();
+        if names.is_empty() {
+            return acc;
+        }
+
+        let input = text.match_data_excluding(&self.stop_grams);
+        for entry in names.iter().filter_map(|n| self.licenses.get_key_value(n)) {
+            self.score_entry(&mut acc, entry, input.as_ref(), true, options, filter);
+        }
+        acc
+    }
+
+    /// Score the given text against popular licenses in order of popularity,
+    /// stopping at the first to reach `threshold`.
+    fn score_popular<'a>(
+        &'a self,
+        text: &TextData,
+        titled: &[String],
+        threshold: f32,
+        options: &AnalysisOptions,
+        filter: &Filter<'_>,
+    ) -> Vec<PartialMatch<'a>> {
+        let popularity: Vec<&str> = match options.popularity.as_ref().or(self.popularity.as_ref()) {
+            Some(names) => names.iter().map(String::as_str).collect(),
+            None => DEFAULT_POPULARITY.to_vec(),
+        };
+
+        let input = text.match_data_excluding(&self.stop_grams);
+        let mut acc = Vec::new();
+        for entry in popularity
+            .into_iter()
+            .filter_map(|n| self.licenses.get_key_value(n))
+        {
+            let titled = titled.contains(entry.0);
+            self.score_entry(&mut acc, entry, input.as_ref(), titled, options, filter);
+            if acc.iter().any(|m| m.score >= threshold) {
+                break;
+            }
+        }
+        acc
+    }
+
+    /// Re-score the best few licenses in `ranked` (sorted best first) by
+    /// alignment with the text, if the best score is in the options'
+    /// alignment band. Returns them sorted best first, or `None` if they
+    /// weren't re-scored. See `AnalysisOptions::alignment_fallback`.
+    fn realign<'a>(
+        &'a self,
+        text: &TextData,
+        ranked: &[PartialMatch<'a>],
+        options: &AnalysisOptions,
+    ) -> Option<Vec<PartialMatch<'a>>> {
+        let (low, high) = options.alignment_fallback?;
+        let best = ranked.first()?;
+        if best.score < low || best.score > high {
+            return None;
+        }
+
+        let tokens: Vec<&str> = text.tokens()?.collect();
+        let mut aligned: Vec<PartialMatch<'a>> = Vec::with_capacity(ALIGNMENT_CANDIDATES);
+        for m in ranked {
+            if aligned.len() == ALIGNMENT_CANDIDATES {
+                break;
+            }
+            // only the best variant of each license
+            if aligned.iter().any(|a| a.name == m.name) {
+                continue;
+            }
+            let license: Vec<&str> = m.data.tokens()?.collect();
+            aligned.push(PartialMatch {
+                score: alignment_score(&tokens, &license),
+                ..*m
+            });
+        }
+        aligned.sort_unstable_by(|a, b| b.cmp(a));
+        Some(aligned)
+    }
+
+    /// Among the licenses in `ranked` (sorted best first) scoring close to
+    /// the best, move those that agree with the text on the presence of
+    /// each deciding clause ahead of those that don't. Returns whether the
+    /// best match changed. See `add_clause`.
+    fn decide_by_clauses(&self, text: &TextData, ranked: &mut [PartialMatch<'_>]) -> bool {
+        let best = match ranked.first() {
+            Some(best) => best.score,
+            None => return false,
+        };
+        let close = ranked
+            .iter()
+            .take_while(|m| m.score >= best - CLAUSE_MARGIN)
+            .count();
+        if close < 2 || ranked[..close].iter().all(|m| m.name == ranked[0].name) {
+            return false;
+        }
+
+        let clauses = self.deciding_clauses(ranked[..close].iter().map(|m| m.name));
+        let input = text.match_data_excluding(&self.stop_grams);
+        let in_text: Vec<bool> = clauses
+            .iter()
+            .map(|clause| has_clause(clause, &input))
+            .collect();
+        let disagreements: Vec<usize> = ranked[..close]
+            .iter()
+            .map(|m| {
+                clauses
+                    .iter()
+                    .zip(&in_text)
+                    .filter(|&(clause, &present)| has_clause(clause, &m.data.match_data) != present)
+                    .count()
+            })
+            .collect();
+
+        // a stable sort keeps the score order among equals
+        let winner = ranked[0].name;
+        let mut order: Vec<usize> = (0..close).collect();
+        order.sort_by_key(|&i| disagreements[i]);
+        let reordered: Vec<PartialMatch<'_>> = order.iter().map(|&i| ranked[i]).collect();
+        for (slot, m) in ranked.iter_mut().zip(reordered) {
+            *slot = m;
+        }
+        ranked[0].name != winner
+    }
+
+    /// Score a license (given by name and entry) and those of its variants
+    /// accepted by `filter`, adding them to `acc`.
+    fn score_entry<'a>(
+        &'a self,
+        acc: &mut Vec<PartialMatch<'a>>,
+        (name, data): (&'a String, &'a LicenseEntry),
+        input: &NgramSet,
+        titled: bool,
+        options: &AnalysisOptions,
+        filter: &Filter<'_>,
+    ) {
+        let score = |license: &NgramSet| self.score(options.scorer, license, input);
+        if filter(name, LicenseType::Original) {
+            acc.push(PartialMatch {
+                score: score(&data.original.match_data),
+                name,
+                license_type: LicenseType::Original,
+                rank: options.type_rank(LicenseType::Original),
+                titled,
+                label: None,
+                data: &data.original,
+            });
+        }
+        if !data.alternates.is_empty() && filter(name, LicenseType::Alternate) {
+            data.alternates.iter().enumerate().for_each(|(i, alt)| {
+                acc.push(PartialMatch {
+                    score: score(&alt.match_data),
+                    name,
+                    license_type: LicenseType::Alternate,
+                    rank: options.type_rank(LicenseType::Alternate),
+                    titled,
+                    label: data.alternate_label(i),
+                    data: alt,
+                })
+            });
+        }
+        if !data.headers.is_empty() && filter(name, LicenseType::Header) {
+            data.headers.iter().enumerate().for_each(|(i, head)| {
+                acc.push(PartialMatch {
+                    score: score(&head.match_data),
+                    name,
+                    license_type: LicenseType::Header,
+                    rank: options.type_rank(LicenseType::Header),
+                    titled,
+                    label: data.header_label(i),
+                    data: head,
+                })
+            });
+        }
+    }
+
+    /// Get the number of licenses to score per parallel task, for an input
+    /// with the given number of n-grams.
+    #[cfg(all(feature = "rayon", not(target_arch = "wasm32")))]
+    fn chunk_size(&self, input_len: usize, options: &AnalysisOptions) -> usize {
+        options
+            .chunk_size
+            .or(self.analyze_chunk_size)
+            .unwrap_or_else(|| {
+                let cost = input_len.clamp(1, CHUNK_LICENSE_GRAMS);
+                (CHUNK_WORK / cost).max(1)
+            })
+    }
+
+    /// Turn an analysis result into a `Match`, given the name and score of
+    /// the best competing license and any exception found.
+    fn build_match<'a>(
+        &'a self,
+        m: &PartialMatch<'a>,
+        runner_up: Option<(&'a str, f32)>,
+        exception: Option<&'a str>,
+    ) -> Match<'a> {
+        let mut matched = Match {
             score: m.score,
             name: m.name,
             license_type: m.license_type,
+            family: self.family_of(m.name),
+            variant_label: m.label,
+            exception,
+            runner_up: runner_up.map(|r| r.0),
+            runner_up_score: runner_up.map_or(0.0, |r| r.1),
+            ambiguous: false,
             data: m.data,
-        }
+        };
+        matched.ambiguous = matched.is_ambiguous(DEFAULT_AMBIGUITY_EPSILON);
+        matched
+    }
+
+    /// Compare the given `TextData` against all licenses in the `Store`,
+    /// performing all parallel work on the given thread pool.
+    ///
+    /// Otherwise identical to `analyze`.
+    #[cfg(all(feature = "rayon", not(target_arch = "wasm32")))]
+    pub fn analyze_with_pool<'a>(&'a self, text: &TextData, pool: &rayon::ThreadPool) -> Match<'a> {
+        pool.install(|| self.analyze(text))
     }
 }
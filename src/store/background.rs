@@ -0,0 +1,164 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    fmt,
+    io::Read,
+    sync::{Mutex, OnceLock},
+    thread::{self, JoinHandle},
+};
+
+use anyhow::{format_err, Error};
+
+use crate::{
+    license::TextData,
+    store::{analyze::Match, base::Store},
+};
+
+/// A `Store` being loaded from a cache on a background thread.
+///
+/// Created by `Store::from_cache_background`. This lets programs with a
+/// costly cold start (such as serverless functions) overlap loading the
+/// cache with their other setup. Use `wait` to block until the store is
+/// loaded, or `try_store` to check without blocking.
+///
+/// # Examples
+///
+/// ```rust,should_panic
+/// # use std::fs::File;
+/// # use std::error::Error;
+/// use askalono::{Store, TextData};
+///
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// let handle = Store::from_cache_background(File::open("askalono-cache.bin.zstd")?);
+/// // [...] other setup
+/// let result = handle.analyze(&TextData::from("what's this"))?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct StoreHandle {
+    thread: Mutex<Option<JoinHandle<Result<Store, Error>>>>,
+    /// The loaded store, or why it couldn't be loaded. `Error` can't be
+    /// cloned, so failures are kept as messages.
+    store: OnceLock<Result<Store, String>>,
+}
+
+impl StoreHandle {
+    /// Check whether loading has finished, successfully or not. If this
+    /// returns true, `wait` won't block.
+    pub fn ready(&self) -> bool {
+        self.store.get().is_some()
+            || self
+                .thread
+                .lock()
+                .unwrap()
+                .as_ref()
+                .is_none_or(JoinHandle::is_finished)
+    }
+
+    /// Block until the store is loaded, then get it.
+    ///
+    /// Returns an error if the cache couldn't be loaded; every call returns
+    /// the same error.
+    pub fn wait(&self) -> Result<&Store, Error> {
+        let loaded = self.store.get_or_init(|| {
+            let thread = self
+                .thread
+                .lock()
+                .unwrap()
+                .take()
+                .expect("loading thread is joined only once");
+            match thread.join() {
+                Ok(loaded) => loaded.map_err(|e| e.to_string()),
+                Err(_) => Err("cache loading thread panicked".to_owned()),
+            }
+        });
+        loaded.as_ref().map_err(|e| format_err!("{}", e))
+    }
+
+    /// Get the store if it's finished loading, without blocking.
+    ///
+    /// Returns `Ok(None)` if it's still loading.
+    pub fn try_store(&self) -> Result<Option<&Store>, Error> {
+        if !self.ready() {
+            return Ok(None);
+        }
+        self.wait().map(Some)
+    }
+
+    /// Wait for the store to load, then compare the given `TextData` against
+    /// it. See `Store::analyze`.
+    pub fn analyze(&self, text: &TextData) -> Result<Match<'_>, Error> {
+        Ok(self.wait()?.analyze(text))
+    }
+
+    /// Compare the given `TextData` against the store if it's finished
+    /// loading, or return `Ok(None)` if it hasn't. See `Store::analyze`.
+    pub fn try_analyze(&self, text: &TextData) -> Result<Option<Match<'_>>, Error> {
+        Ok(self.try_store()?.map(|store| store.analyze(text)))
+    }
+}
+
+impl fmt::Debug for StoreHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StoreHandle")
+            .field("ready", &self.ready())
+            .finish()
+    }
+}
+
+impl Store {
+    /// Start loading a store from a cache on a background thread, returning
+    /// immediately. See `StoreHandle` and `from_cache`.
+    ///
+    /// Not available on wasm32, which has no threads.
+    pub fn from_cache_background<R>(readable: R) -> StoreHandle
+    where
+        R: Read + Send + 'static,
+    {
+        let thread = thread::Builder::new()
+            .name("askalono-cache".into())
+            .spawn(move || Store::from_cache(readable));
+        match thread {
+            Ok(thread) => StoreHandle {
+                thread: Mutex::new(Some(thread)),
+                store: OnceLock::new(),
+            },
+            Err(e) => StoreHandle {
+                thread: Mutex::new(None),
+                store: OnceLock::from(Err(format!("couldn't start loading thread: {}", e))),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn load_in_background() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+        let mut buf = Vec::new();
+        store.to_cache(&mut buf).unwrap();
+
+        let handle = Store::from_cache_background(Cursor::new(buf));
+        let text = TextData::from("aaaaa bbbbb ccccc");
+        assert_eq!("license-1", handle.analyze(&text).unwrap().name);
+        assert!(handle.ready());
+        assert_eq!(1, handle.try_store().unwrap().unwrap().len());
+        assert!(handle.try_analyze(&text).unwrap().is_some());
+    }
+
+    #[test]
+    fn load_failure() {
+        let handle = Store::from_cache_background(Cursor::new(b"not a cache".to_vec()));
+        let err = handle.wait().unwrap_err().to_string();
+        assert!(err.contains("cache version mismatch"), "{}", err);
+        assert!(handle.ready());
+        assert!(handle.try_store().is_err());
+    }
+}
@@ -1,19 +1,79 @@
 // Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::OnceLock,
+};
 
 use anyhow::{format_err, Error};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 
-use crate::{license::LicenseType, license::TextData};
+use crate::{
+    license::LicenseType,
+    license::TextData,
+    ngram::Gram,
+    store::{
+        fuzzy::{Vocabulary, MAX_FUZZY_DISTANCE},
+        idf::IdfWeights,
+        index::GramIndex,
+        minhash::LshIndex,
+        provenance::Provenance,
+        title::find_title,
+    },
+};
 
-#[derive(Serialize, Deserialize)]
+/// Write a set of n-grams in order, so that the same store is always
+/// written the same way.
+fn sorted_grams<S: Serializer>(grams: &HashSet<Gram>, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut grams: Vec<String> = grams.iter().map(Gram::to_string).collect();
+    grams.sort_unstable();
+    grams.serialize(serializer)
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub(crate) struct LicenseEntry {
     pub original: TextData,
     pub aliases: Vec<String>,
     pub headers: Vec<TextData>,
     pub alternates: Vec<TextData>,
+    #[serde(default)]
+    pub family: Option<String>,
+    /// Labels for `headers` and `alternates`, by index. These may be shorter
+    /// than the variant lists (caches built before labels existed have none);
+    /// see `header_label` and `alternate_label`.
+    #[serde(default)]
+    pub header_labels: Vec<Option<String>>,
+    #[serde(default)]
+    pub alternate_labels: Vec<Option<String>>,
+    /// Where the original text came from; see `Store::provenance`.
+    #[serde(default)]
+    pub provenance: Option<Provenance>,
+    /// Provenance of `headers` and `alternates`, by index. Like the labels,
+    /// these may be shorter than the variant lists.
+    #[serde(default)]
+    pub header_provenance: Vec<Option<Provenance>>,
+    #[serde(default)]
+    pub alternate_provenance: Vec<Option<Provenance>>,
+    /// The verbatim, un-normalized text of the license, if kept; see
+    /// `Store::original_text`.
+    #[serde(default)]
+    pub original_text: Option<String>,
+    /// The verbatim standard header, with placeholders; see
+    /// `Store::suggest_header`.
+    #[serde(default)]
+    pub header_template: Option<String>,
+    /// Clauses that set this license apart from its relatives; see
+    /// `Store::add_clause`.
+    #[serde(default)]
+    pub clauses: Vec<TextData>,
+    /// Whether the license is deprecated, and what replaces it; see
+    /// `Store::is_deprecated`.
+    #[serde(default)]
+    pub deprecated: bool,
+    #[serde(default)]
+    pub superseded_by: Option<String>,
 }
 
 /// A representation of a collection of known licenses.
@@ -36,20 +96,152 @@ pub(crate) struct LicenseEntry {
 /// # Ok(())
 /// # }
 /// ```
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct Store {
     pub(crate) licenses: HashMap<String, LicenseEntry>,
+    /// Licenses tried first when early exit is enabled, most popular first,
+    /// unless the analysis options give others; `None` for the default. See
+    /// `AnalysisOptions::popularity`. Not cached.
+    #[serde(skip)]
+    pub(crate) popularity: Option<Vec<String>>,
+    /// Licenses scored per parallel task in `analyze`, unless the analysis
+    /// options give a size, or `None` to choose by input size; see
+    /// `AnalysisOptions::chunk_size`. Not cached.
+    #[serde(skip)]
+    #[cfg_attr(
+        not(all(feature = "rayon", not(target_arch = "wasm32"))),
+        allow(dead_code)
+    )]
+    pub(crate) analyze_chunk_size: Option<usize>,
+    /// Known words to correct misspellings to, by the most edits allowed,
+    /// collected when first needed; see `AnalysisOptions::fuzzy_tokens`.
+    /// Not cached.
+    #[serde(skip)]
+    pub(crate) fuzzy_vocabularies: [OnceLock<Vocabulary>; MAX_FUZZY_DISTANCE as usize],
+    /// A hash of the store's contents, worked out when first needed; see
+    /// `fingerprint`. Not cached.
+    #[serde(skip)]
+    pub(crate) fingerprint: OnceLock<u64>,
+    /// N-grams excluded from scoring; see `add_stop_grams`.
+    #[serde(default, serialize_with = "sorted_grams")]
+    pub(crate) stop_grams: HashSet<Gram>,
+    /// Normalized title lines, mapped to the licenses they name; see
+    /// `add_title`.
+    #[serde(default)]
+    pub(crate) titles: HashMap<String, Vec<String>>,
+    /// Whether license n-grams are packed; see `compact_counts`.
+    #[serde(default)]
+    pub(crate) compact_counts: bool,
+    /// Whether license n-grams are hashed; see `hash_grams`.
+    #[serde(default)]
+    pub(crate) hashed_grams: bool,
+    /// Digests of the SPDX files licenses were loaded from, by license ID;
+    /// see `update_from_spdx`.
+    #[serde(default)]
+    pub(crate) spdx_digests: HashMap<String, u64>,
+    /// License exceptions, by name; see `add_exception`.
+    #[serde(default)]
+    pub(crate) exceptions: HashMap<String, TextData>,
+    /// N-gram weights for scoring, if enabled; see `set_idf_weighting`.
+    #[serde(default)]
+    pub(crate) idf: Option<IdfWeights>,
+    /// Inverted n-gram index for shortlisting candidates, if enabled; see
+    /// `set_candidate_index`.
+    #[serde(default)]
+    pub(crate) index: Option<GramIndex>,
+    /// MinHash buckets for pruning candidates, if enabled; see
+    /// `set_lsh_prefilter`.
+    #[serde(default)]
+    pub(crate) lsh: Option<LshIndex>,
+    /// The SPDX license list version licenses were last loaded from; see
+    /// `info`.
+    #[serde(default)]
+    pub(crate) list_version: Option<String>,
+    /// When the cache was written, if the store was loaded from one; see
+    /// `info`.
+    #[serde(default)]
+    pub(crate) built_at: Option<u64>,
 }
 
+/// The most commonly used licenses, roughly in order of how often they turn
+/// up in real-world code. See `AnalysisOptions::popularity`.
+pub(crate) const DEFAULT_POPULARITY: &[&str] = &[
+    "MIT",
+    "Apache-2.0",
+    "BSD-3-Clause",
+    "GPL-3.0-only",
+    "BSD-2-Clause",
+    "GPL-2.0-only",
+    "ISC",
+    "LGPL-2.1-only",
+    "MPL-2.0",
+    "LGPL-3.0-only",
+    "AGPL-3.0-only",
+    "Unlicense",
+    "CC0-1.0",
+    "GPL-3.0-or-later",
+    "GPL-2.0-or-later",
+];
+
+/// The default order in which license types are preferred when their scores
+/// tie. See `AnalysisOptions::type_preference`.
+pub(crate) const DEFAULT_TYPE_PREFERENCE: [LicenseType; 3] = [
+    LicenseType::Original,
+    LicenseType::Alternate,
+    LicenseType::Header,
+];
+
 impl LicenseEntry {
-    pub fn new(original: TextData) -> LicenseEntry {
+    pub fn new(original: TextData, provenance: Option<Provenance>) -> LicenseEntry {
         LicenseEntry {
             original,
             aliases: Vec::new(),
             alternates: Vec::new(),
             headers: Vec::new(),
+            family: None,
+            header_labels: Vec::new(),
+            alternate_labels: Vec::new(),
+            provenance,
+            header_provenance: Vec::new(),
+            alternate_provenance: Vec::new(),
+            original_text: None,
+            header_template: None,
+            clauses: Vec::new(),
+            deprecated: false,
+            superseded_by: None,
         }
     }
+
+    pub fn header_label(&self, index: usize) -> Option<&str> {
+        self.header_labels.get(index)?.as_deref()
+    }
+
+    pub fn alternate_label(&self, index: usize) -> Option<&str> {
+        self.alternate_labels.get(index)?.as_deref()
+    }
+}
+
+impl fmt::Debug for Store {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (mut headers, mut alternates, mut aliases) = (0, 0, 0);
+        for entry in self.licenses.values() {
+            headers += entry.headers.len();
+            alternates += entry.alternates.len();
+            aliases += entry.aliases.len();
+        }
+        let texts_stored = self
+            .licenses
+            .values()
+            .any(|entry| entry.original.text_processed().is_some());
+
+        f.debug_struct("Store")
+            .field("licenses", &self.licenses.len())
+            .field("headers", &headers)
+            .field("alternates", &alternates)
+            .field("aliases", &aliases)
+            .field("texts_stored", &texts_stored)
+            .finish()
+    }
 }
 
 impl Store {
@@ -60,6 +252,21 @@ impl Store {
     pub fn new() -> Store {
         Store {
             licenses: HashMap::new(),
+            popularity: None,
+            analyze_chunk_size: None,
+            fuzzy_vocabularies: Default::default(),
+            fingerprint: OnceLock::new(),
+            stop_grams: HashSet::new(),
+            titles: HashMap::new(),
+            compact_counts: false,
+            hashed_grams: false,
+            spdx_digests: HashMap::new(),
+            exceptions: HashMap::new(),
+            idf: None,
+            index: None,
+            lsh: None,
+            list_version: None,
+            built_at: None,
         }
     }
 
@@ -86,13 +293,142 @@ impl Store {
         Some(&self.licenses.get(name)?.original)
     }
 
+    /// Get the verbatim text of a license, exactly as it was loaded.
+    ///
+    /// Unlike the normalized lines available from `get_original`, this is
+    /// suitable for reproducing the license. It's only available if the
+    /// store was loaded with `load_spdx_with_originals` or the text was set
+    /// with `set_original_text`.
+    pub fn original_text(&self, name: &str) -> Option<&str> {
+        self.licenses.get(name)?.original_text.as_deref()
+    }
+
+    /// Keep the verbatim text of a license, for retrieval with
+    /// `original_text`.
+    pub fn set_original_text(&mut self, name: &str, text: String) -> Result<(), Error> {
+        let entry = self
+            .licenses
+            .get_mut(name)
+            .ok_or_else(|| format_err!("license {} not present in store", name))?;
+        entry.original_text = Some(text);
+        Ok(())
+    }
+
     /// Add a single license to the store.
     ///
     /// If the license with the given name already existed, it and all of its
     /// variants will be replaced.
-    pub fn add_license(&mut self, name: String, data: TextData) {
-        let entry = LicenseEntry::new(data);
-        self.licenses.insert(name, entry);
+    ///
+    /// The license has no family until one is set; see `set_family`. If
+    /// its text is stored and starts with a title line, that's registered as
+    /// its title; see `add_title`.
+    pub fn add_license(&mut self, name: String, mut data: TextData) {
+        self.prepare_data(&mut data);
+        let title = data
+            .lines_in_view()
+            .and_then(|lines| find_title(lines.iter().map(String::as_str)));
+
+        self.remove_titles(&name);
+        if let Some(title) = title {
+            self.titles.entry(title).or_default().push(name.clone());
+        }
+        let entry = LicenseEntry::new(data, Some(Provenance::new("user")));
+        self.licenses.insert(name.clone(), entry);
+        self.refresh_prefilters(&name);
+    }
+
+    /// Replace the original text of a license already in the store.
+    ///
+    /// Unlike `add_license`, the license's variants, aliases, family, and
+    /// clauses are kept. Its title is registered again from the new text,
+    /// and any verbatim text is dropped, as it no longer matches; see
+    /// `set_original_text`.
+    pub fn replace_license(&mut self, name: &str, mut data: TextData) -> Result<(), Error> {
+        if !self.licenses.contains_key(name) {
+            return Err(format_err!("license {} not present in store", name));
+        }
+        self.prepare_data(&mut data);
+        let title = data
+            .lines_in_view()
+            .and_then(|lines| find_title(lines.iter().map(String::as_str)));
+
+        self.remove_titles(name);
+        if let Some(title) = title {
+            self.titles.entry(title).or_default().push(name.to_owned());
+        }
+        let entry = self.licenses.get_mut(name).unwrap();
+        entry.original = data;
+        entry.original_text = None;
+        entry.provenance = Some(Provenance::new("user"));
+        self.refresh_prefilters(name);
+        Ok(())
+    }
+
+    /// Remove a license, along with its variants and aliases, from the
+    /// store.
+    ///
+    /// Licenses removed from a store filled with `load_spdx` stay removed
+    /// through `update_from_spdx`, unless SPDX changes them.
+    pub fn remove_license(&mut self, name: &str) -> Result<(), Error> {
+        self.licenses
+            .remove(name)
+            .ok_or_else(|| format_err!("license {} not present in store", name))?;
+        self.remove_titles(name);
+        self.refresh_prefilters(name);
+        Ok(())
+    }
+
+    /// Remove a header or alternate of a license from the store, by its
+    /// 0-indexed position among the license's variants of that type. Later
+    /// variants move down to fill its place.
+    ///
+    /// To remove a license's original text, remove the license itself with
+    /// `remove_license`.
+    pub fn remove_variant(
+        &mut self,
+        name: &str,
+        variant: LicenseType,
+        index: usize,
+    ) -> Result<(), Error> {
+        let entry = self
+            .licenses
+            .get_mut(name)
+            .ok_or_else(|| format_err!("license {} not present in store", name))?;
+        let (variants, labels, provenance) = match variant {
+            LicenseType::Alternate => (
+                &mut entry.alternates,
+                &mut entry.alternate_labels,
+                &mut entry.alternate_provenance,
+            ),
+            LicenseType::Header => (
+                &mut entry.headers,
+                &mut entry.header_labels,
+                &mut entry.header_provenance,
+            ),
+            _ => {
+                return Err(format_err!(
+                    "variant type not applicable for remove_variant"
+                ));
+            }
+        };
+        if index >= variants.len() {
+            return Err(format_err!(
+                "license {} has no {} at index {}",
+                name,
+                variant,
+                index
+            ));
+        }
+        variants.remove(index);
+        // labels and provenance may be shorter than the variants
+        if index < labels.len() {
+            labels.remove(index);
+        }
+        if index < provenance.len() {
+            provenance.remove(index);
+        }
+        self.refresh_prefilters(name);
+        Ok(())
     }
 
     /// Add a variant (a header or alternate formatting) of a given license to
@@ -106,24 +442,81 @@ impl Store {
         variant: LicenseType,
         data: TextData,
     ) -> Result<(), Error> {
+        self.add_variant_labeled(name, variant, data, None)
+    }
+
+    /// Add a variant of a given license to the store, with a label describing
+    /// it (such as "short header" or "2004 wording").
+    ///
+    /// The label is kept in the cache and reported in `Match::variant_label`
+    /// when this variant is the best match, which helps tell apart several
+    /// headers or alternates of the same license. Otherwise identical to
+    /// `add_variant`.
+    pub fn add_variant_labeled(
+        &mut self,
+        name: &str,
+        variant: LicenseType,
+        data: TextData,
+        label: Option<String>,
+    ) -> Result<(), Error> {
+        let mut data = data;
+        self.prepare_data(&mut data);
         let entry = self
             .licenses
             .get_mut(name)
             .ok_or_else(|| format_err!("license {} not present in store", name))?;
+        let provenance = Some(Provenance::new("user"));
         match variant {
             LicenseType::Alternate => {
+                entry.alternate_labels.resize(entry.alternates.len(), None);
+                entry
+                    .alternate_provenance
+                    .resize(entry.alternates.len(), None);
                 entry.alternates.push(data);
+                entry.alternate_labels.push(label);
+                entry.alternate_provenance.push(provenance);
             }
             LicenseType::Header => {
+                entry.header_labels.resize(entry.headers.len(), None);
+                entry.header_provenance.resize(entry.headers.len(), None);
                 entry.headers.push(data);
+                entry.header_labels.push(label);
+                entry.header_provenance.push(provenance);
             }
             _ => {
                 return Err(format_err!("variant type not applicable for add_variant"));
             }
         };
+        self.refresh_prefilters(name);
         Ok(())
     }
 
+    /// Rebuild the candidate index and LSH prefilter if they cover a license
+    /// that was just changed, so they don't prune by the license's old
+    /// n-grams. Licenses they don't cover are always scored, so adding one
+    /// leaves them as they are. Derived data is worked out again when next
+    /// needed; see `forget_derived`.
+    fn refresh_prefilters(&mut self, name: &str) {
+        self.forget_derived();
+        let indexed = self
+            .index
+            .as_ref()
+            .is_some_and(|index| index.contains(name));
+        if indexed {
+            self.set_candidate_index(self.candidate_index());
+        }
+        if self.lsh.as_ref().is_some_and(|lsh| lsh.contains(name)) {
+            self.set_lsh_prefilter(true);
+        }
+    }
+
+    /// Forget data worked out from the store's contents (fuzzy vocabularies
+    /// and the store's fingerprint), after they've changed.
+    pub(crate) fn forget_derived(&mut self) {
+        self.fuzzy_vocabularies = Default::default();
+        self.fingerprint = OnceLock::new();
+    }
+
     /// Get the list of aliases for a given license.
     pub fn aliases(&self, name: &str) -> Result<&Vec<String>, Error> {
         let entry = self
@@ -133,6 +526,248 @@ impl Store {
         Ok(&entry.aliases)
     }
 
+    /// Find the license a name refers to: the license itself, if one has
+    /// that name, or else the license listing it as an alias.
+    ///
+    /// Names that don't match exactly are compared case-insensitively, as
+    /// SPDX identifiers are. Returns `None` if nothing matches.
+    pub fn resolve_alias(&self, name: &str) -> Option<&str> {
+        if let Some((license, _)) = self.licenses.get_key_value(name) {
+            return Some(license);
+        }
+        let mut insensitive = None;
+        for (license, entry) in &self.licenses {
+            if entry.aliases.iter().any(|alias| alias == name) {
+                return Some(license);
+            }
+            if insensitive.is_none()
+                && std::iter::once(license)
+                    .chain(&entry.aliases)
+                    .any(|candidate| candidate.eq_ignore_ascii_case(name))
+            {
+                insensitive = Some(license.as_str());
+            }
+        }
+        insensitive
+    }
+
+    /// Get the family of a given license, if it has one.
+    ///
+    /// Families group related licenses together, such as all versions and
+    /// variants of the GPL (`GPL`) or all BSD clause variants (`BSD`), and are
+    /// derived from the identifiers of licenses loaded from the SPDX dataset.
+    /// Returns `None` if the license isn't in the store or has no
+    /// family.
+    pub fn family_of(&self, name: &str) -> Option<&str> {
+        self.licenses.get(name)?.family.as_deref()
+    }
+
+    /// Override the family of a given license.
+    ///
+    /// Useful for custom stores, whose licenses have no family unless given
+    /// one.
+    pub fn set_family(&mut self, name: &str, family: Option<String>) -> Result<(), Error> {
+        let entry = self
+            .licenses
+            .get_mut(name)
+            .ok_or_else(|| format_err!("license {} not present in store", name))?;
+        entry.family = family;
+        Ok(())
+    }
+
+    /// Set the licenses tried first when early exit is enabled, for analyses
+    /// whose options don't give their own. This preference isn't saved in
+    /// the cache.
+    #[deprecated(note = "set `AnalysisOptions::popularity` instead")]
+    pub fn set_popularity(&mut self, names: Vec<String>) {
+        self.popularity = Some(names);
+    }
+
+    /// Set how many licenses are scored in each parallel task, for analyses
+    /// whose options don't give their own size. `None` restores the default.
+    /// This isn't saved in the cache.
+    #[deprecated(note = "set `AnalysisOptions::chunk_size` instead")]
+    pub fn set_analyze_chunk_size(&mut self, size: Option<usize>) -> Result<(), Error> {
+        if size == Some(0) {
+            return Err(format_err!("analyze chunk size must be at least 1"));
+        }
+        self.analyze_chunk_size = size;
+        Ok(())
+    }
+
+    /// Store license n-gram counts in a compact form, to reduce memory use.
+    ///
+    /// Compact counts are stored in sorted arrays rather than hash maps and
+    /// saturate at 255, which takes considerably less memory for a large
+    /// store, at the cost of somewhat slower analysis. This is worthwhile in
+    /// memory-constrained environments. It applies to all licenses in the
+    /// store, including ones added later, and is kept in the cache: build
+    /// a cache from a compact store, and it'll be compact when loaded.
+    pub fn compact_counts(&mut self) {
+        self.compact_counts = true;
+        self.forget_derived();
+        self.pack_all();
+    }
+
+    /// Keep only 64-bit hashes of license n-grams, rather than the n-grams
+    /// themselves.
+    ///
+    /// Hashed n-grams are stored as compact counts are (see
+    /// `compact_counts`), and take as little memory. What they save is the
+    /// words: a cache built from a store with hashed n-grams doesn't list
+    /// the words of its licenses, and loading it doesn't add them to the
+    /// process-wide table of words, which otherwise keeps the words of every
+    /// store loaded until the process exits. In exchange, scanning is
+    /// somewhat slower, as a text's n-grams are hashed to be compared.
+    ///
+    /// Two distinct n-grams sharing a hash would be counted as one, but with
+    /// 64-bit hashes that's vanishingly unlikely, and would only nudge a
+    /// score; see `NgramSet::hash_grams` for the odds.
+    ///
+    /// Without the words, IDF weighting and the candidate index can't be
+    /// used, so this fails if either is enabled, and enabling them later
+    /// does nothing. `common_grams` and fuzzy token correction don't see
+    /// hashed n-grams, license n-grams are only spelled out (as by
+    /// `TextData::ngrams` or in match explanations) where the license's
+    /// text was kept, and the store can't be written as a `StaticStore`.
+    /// Like compact counts, this applies to all licenses in the store,
+    /// including ones added later, and is kept in the cache.
+    pub fn hash_grams(&mut self) -> Result<(), Error> {
+        if self.idf.is_some() || self.index.is_some() {
+            return Err(format_err!(
+                "n-grams can't be hashed in a store with IDF weighting or a candidate index"
+            ));
+        }
+        self.hashed_grams = true;
+        self.forget_derived();
+        self.pack_all();
+        Ok(())
+    }
+
+    /// Get a text ready to be kept in the store: add its words to the token
+    /// table (or hash its n-grams, if the store does), remove stop grams, and
+    /// pack it if the store uses compact counts.
+    pub(crate) fn prepare_data(&self, data: &mut TextData) {
+        if self.hashed_grams {
+            data.match_data.remove_grams(&self.stop_grams);
+            data.match_data.hash_grams();
+            return;
+        }
+        data.intern_words();
+        data.match_data.remove_grams(&self.stop_grams);
+        if self.compact_counts {
+            data.match_data.pack();
+        }
+    }
+
+    /// Add the words of every license, clause, and exception to the token
+    /// table, for store data read without adding them.
+    pub(crate) fn intern_all_words(&mut self) {
+        for entry in self.licenses.values_mut() {
+            std::iter::once(&mut entry.original)
+                .chain(&mut entry.headers)
+                .chain(&mut entry.alternates)
+                .chain(&mut entry.clauses)
+                .for_each(TextData::intern_words);
+        }
+        self.exceptions
+            .values_mut()
+            .for_each(TextData::intern_words);
+    }
+
+    /// Pack or hash the n-grams of every license, if the store uses compact
+    /// counts or hashed n-grams.
+    pub(crate) fn pack_all(&mut self) {
+        let hashed = self.hashed_grams;
+        if !self.compact_counts && !hashed {
+            return;
+        }
+        let compact = |data: &mut TextData| match hashed {
+            true => data.match_data.hash_grams(),
+            false => data.match_data.pack(),
+        };
+        for entry in self.licenses.values_mut() {
+            compact(&mut entry.original);
+            for data in entry.alternates.iter_mut().chain(entry.headers.iter_mut()) {
+                compact(data);
+            }
+        }
+        for data in self.exceptions.values_mut() {
+            compact(data);
+        }
+    }
+
+    /// Find the n-grams shared by the most licenses in the store.
+    ///
+    /// Returns up to `count` grams, most common first. These are typically
+    /// generic legal boilerplate ("the software", "of the") and are good
+    /// candidates for `add_stop_grams`.
+    pub fn common_grams(&self, count: usize) -> Vec<String> {
+        let mut licenses_with: HashMap<Gram, usize> = HashMap::new();
+        for entry in self.licenses.values() {
+            for (gram, _) in &entry.original.match_data {
+                *licenses_with.entry(gram).or_insert(0) += 1;
+            }
+        }
+
+        let mut grams: Vec<(String, usize)> = licenses_with
+            .into_iter()
+            .map(|(gram, licenses)| (gram.to_string(), licenses))
+            .collect();
+        grams.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        grams
+            .into_iter()
+            .take(count)
+            .map(|(gram, _)| gram)
+            .collect()
+    }
+
+    /// Exclude n-grams from scoring.
+    ///
+    /// Boilerplate shared by nearly every license adds noise to scores and
+    /// slows down comparisons; excluding it improves separation between
+    /// candidates. Each given phrase is normalized like any other text and
+    /// all of its n-grams are excluded, so both entries from `common_grams`
+    /// and longer phrases can be used.
+    ///
+    /// Stop grams are removed from all license data in the store (including
+    /// licenses added later) and are ignored in analyzed texts. They're kept
+    /// in the cache, but can't be removed once added.
+    pub fn add_stop_grams<I, S>(&mut self, phrases: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut added = HashSet::new();
+        for phrase in phrases {
+            let mut data = TextData::new(phrase.as_ref()).without_text();
+            data.intern_words();
+            added.extend(data.match_data.into_iter().map(|(gram, _)| gram));
+        }
+        self.extend_stop_grams(added);
+    }
+
+    /// Remove already-normalized n-grams from all license data in the store,
+    /// and exclude them from now on.
+    pub(crate) fn extend_stop_grams(&mut self, grams: HashSet<Gram>) {
+        for entry in self.licenses.values_mut() {
+            entry.original.match_data.remove_grams(&grams);
+            for data in entry.alternates.iter_mut().chain(entry.headers.iter_mut()) {
+                data.match_data.remove_grams(&grams);
+            }
+        }
+        for data in self.exceptions.values_mut() {
+            data.match_data.remove_grams(&grams);
+        }
+        self.stop_grams.extend(grams);
+        self.forget_derived();
+    }
+
+    /// Get the n-grams excluded from scoring. See `add_stop_grams`.
+    pub fn stop_grams(&self) -> impl Iterator<Item = String> + '_ {
+        self.stop_grams.iter().map(Gram::to_string)
+    }
+
     /// Set the list of aliases for a given license.
     pub fn set_aliases(&mut self, name: &str, aliases: Vec<String>) -> Result<(), Error> {
         let entry = self
@@ -143,3 +778,358 @@ impl Store {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::options::AnalysisOptions;
+
+    #[test]
+    fn clone_is_independent() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+
+        let mut cloned = store.clone();
+        cloned.add_license("license-2".into(), "ddddd eeeee fffff".into());
+
+        assert_eq!(1, store.len());
+        assert_eq!(2, cloned.len());
+    }
+
+    #[test]
+    fn original_texts() {
+        let mut store = Store::new();
+        let text = "Aaaaa  BBBBB,\r\n  ccccc!";
+        store.add_license("license-1".into(), text.into());
+        assert_eq!(None, store.original_text("license-1"));
+
+        store
+            .set_original_text("license-1", text.to_owned())
+            .unwrap();
+        assert_eq!(Some(text), store.original_text("license-1"));
+        assert!(store.set_original_text("missing", text.to_owned()).is_err());
+
+        // the normalized text is still what's matched
+        assert_ne!(text, store.get_original("license-1").unwrap().lines()[0]);
+    }
+
+    #[test]
+    fn families() {
+        let mut store = Store::new();
+        store.add_license("GPL-2.0-only".into(), "aaaaa bbbbb ccccc".into());
+        store.add_license("internal".into(), "ddddd eeeee fffff".into());
+        // custom licenses aren't given families from their names
+        assert_eq!(None, store.family_of("GPL-2.0-only"));
+        assert_eq!(None, store.family_of("internal"));
+        assert_eq!(None, store.family_of("missing"));
+
+        store
+            .set_family("internal", Some("Proprietary".into()))
+            .unwrap();
+        assert_eq!(Some("Proprietary"), store.family_of("internal"));
+    }
+
+    #[test]
+    fn variant_labels() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+        store
+            .add_variant("license-1", LicenseType::Header, "aaaaa".into())
+            .unwrap();
+        store
+            .add_variant_labeled(
+                "license-1",
+                LicenseType::Header,
+                "ddddd eeeee fffff".into(),
+                Some("short header".into()),
+            )
+            .unwrap();
+
+        let entry = &store.licenses["license-1"];
+        assert_eq!(None, entry.header_label(0));
+        assert_eq!(Some("short header"), entry.header_label(1));
+        assert_eq!(None, entry.alternate_label(0));
+
+        let matched = store.analyze(&"ddddd eeeee fffff".into());
+        assert_eq!(LicenseType::Header, matched.license_type);
+        assert_eq!(Some("short header"), matched.variant_label);
+    }
+
+    #[test]
+    fn remove_and_replace() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+        store.add_license("license-2".into(), "ddddd eeeee fffff".into());
+        store
+            .add_variant("license-1", LicenseType::Header, "ggggg hhhhh".into())
+            .unwrap();
+        store
+            .add_variant_labeled(
+                "license-1",
+                LicenseType::Header,
+                "iiiii jjjjj".into(),
+                Some("short header".into()),
+            )
+            .unwrap();
+        store
+            .set_aliases("license-1", vec!["license-one".into()])
+            .unwrap();
+
+        store
+            .remove_variant("license-1", LicenseType::Header, 0)
+            .unwrap();
+        let entry = &store.licenses["license-1"];
+        assert_eq!(1, entry.headers.len());
+        assert_eq!(Some("short header"), entry.header_label(0));
+        assert!(store
+            .remove_variant("license-1", LicenseType::Header, 1)
+            .is_err());
+        assert!(store
+            .remove_variant("license-1", LicenseType::Original, 0)
+            .is_err());
+
+        store
+            .replace_license("license-1", "kkkkk lllll mmmmm".into())
+            .unwrap();
+        let matched = store.analyze(&"kkkkk lllll mmmmm".into());
+        assert_eq!("license-1", matched.name);
+        assert_eq!(1.0, matched.score);
+        assert_eq!(1, store.licenses["license-1"].headers.len());
+        assert_eq!(Some("license-1"), store.resolve_alias("license-one"));
+        assert!(store.replace_license("license-3", "nnnnn".into()).is_err());
+
+        store.remove_license("license-1").unwrap();
+        assert_eq!(vec!["license-2"], store.licenses().collect::<Vec<_>>());
+        assert_eq!(None, store.resolve_alias("license-one"));
+        assert!(store.remove_license("license-1").is_err());
+    }
+
+    #[test]
+    fn type_preference_on_ties() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+        store
+            .add_variant("license-1", LicenseType::Header, "aaaaa bbbbb ccccc".into())
+            .unwrap();
+        store
+            .add_variant(
+                "license-1",
+                LicenseType::Alternate,
+                "aaaaa bbbbb ccccc".into(),
+            )
+            .unwrap();
+
+        let text = TextData::from("aaaaa bbbbb ccccc");
+        for _ in 0..10 {
+            assert_eq!(LicenseType::Original, store.analyze(&text).license_type);
+        }
+
+        let options = AnalysisOptions {
+            type_preference: Some([
+                LicenseType::Header,
+                LicenseType::Alternate,
+                LicenseType::Original,
+            ]),
+            ..Default::default()
+        };
+        let matched = store.analyze_with(&text, &options).unwrap();
+        assert_eq!(LicenseType::Header, matched.license_type);
+    }
+
+    #[test]
+    fn match_iter_is_ranked() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc ddddd".into());
+        store.add_license("license-2".into(), "aaaaa bbbbb eeeee fffff".into());
+        store.add_license("license-3".into(), "ggggg hhhhh iiiii jjjjj".into());
+        store
+            .add_variant("license-3", LicenseType::Header, "aaaaa bbbbb ccccc".into())
+            .unwrap();
+
+        let text = TextData::from("aaaaa bbbbb ccccc ddddd");
+        let options = AnalysisOptions::default();
+        let matches: Vec<_> = store.match_iter(&text, &options).unwrap().collect();
+        assert_eq!(4, matches.len());
+        assert!(matches.windows(2).all(|w| w[0].score >= w[1].score));
+
+        let best = store.analyze(&text);
+        assert_eq!(best.name, matches[0].name);
+        assert_eq!(best.runner_up, matches[0].runner_up);
+        assert_eq!(Some("license-1"), matches[1].runner_up);
+
+        // stop early once something in an allowlist shows up
+        let allowed = store
+            .match_iter(&text, &options)
+            .unwrap()
+            .find(|m| m.name == "license-2")
+            .unwrap();
+        assert!(allowed.score < best.score);
+    }
+
+    #[test]
+    fn analyze_top_n() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc ddddd".into());
+        store.add_license("license-2".into(), "aaaaa bbbbb eeeee fffff".into());
+        store.add_license("license-3".into(), "ggggg hhhhh iiiii jjjjj".into());
+        store
+            .add_variant("license-1", LicenseType::Header, "aaaaa bbbbb".into())
+            .unwrap();
+
+        let text = TextData::from("aaaaa bbbbb ccccc ddddd");
+        let options = AnalysisOptions::default();
+        let top = store.analyze_top_n(&text, 2, &options).unwrap();
+        let names: Vec<_> = top.iter().map(|m| m.name).collect();
+        assert_eq!(vec!["license-1", "license-2"], names);
+        assert!(top[0].score > top[1].score);
+
+        let best = store.analyze(&text);
+        assert_eq!(best.score, top[0].score);
+        assert_eq!(best.runner_up, top[0].runner_up);
+        assert_eq!(Some("license-1"), top[1].runner_up);
+
+        // each license only once
+        assert_eq!(3, store.analyze_top_n(&text, 10, &options).unwrap().len());
+        assert!(store.analyze_top_n(&text, 0, &options).unwrap().is_empty());
+    }
+
+    #[test]
+    fn early_exit() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+        store.add_license("license-2".into(), "aaaaa bbbbb ddddd".into());
+        store.add_license("license-3".into(), "eeeee fffff ggggg".into());
+
+        let text = TextData::from("aaaaa bbbbb ccccc");
+        assert_eq!(Some("license-2"), store.analyze(&text).runner_up);
+
+        // only license-3 and license-1 need to be scored
+        let options = AnalysisOptions {
+            early_exit: Some(0.99),
+            ..Default::default()
+        }
+        .popularity(vec!["license-3".into(), "license-1".into()]);
+        let matched = store.analyze_with(&text, &options).unwrap();
+        assert_eq!("license-1", matched.name);
+        assert_eq!(Some("license-3"), matched.runner_up);
+
+        // the store's popularity is only a default
+        #[allow(deprecated)]
+        store.set_popularity(vec!["license-2".into()]);
+        let matched = store.analyze_with(&text, &options).unwrap();
+        assert_eq!(Some("license-3"), matched.runner_up);
+        let defaults = AnalysisOptions {
+            early_exit: Some(0.99),
+            ..Default::default()
+        };
+        let matched = store.analyze_with(&text, &defaults).unwrap();
+        assert_eq!(Some("license-2"), matched.runner_up);
+
+        // anything else falls back to the whole store
+        let matched = store
+            .analyze_with(&TextData::from("aaaaa bbbbb ddddd"), &options)
+            .unwrap();
+        assert_eq!("license-2", matched.name);
+        assert_eq!(Some("license-1"), matched.runner_up);
+    }
+
+    #[test]
+    fn analyze_chunk_size() {
+        let mut store = Store::new();
+        for i in 0..20 {
+            store.add_license(
+                format!("license-{}", i),
+                format!("aaaaa bbbbb {}", i).into(),
+            );
+        }
+        let text = TextData::from("aaaaa bbbbb 7");
+        let expected = store.analyze(&text);
+        assert_eq!("license-7", expected.name);
+        let expected = (
+            expected.name.to_owned(),
+            expected.runner_up.map(str::to_owned),
+        );
+
+        for size in [1, 3, 100] {
+            let options = AnalysisOptions::default().chunk_size(size);
+            let matched = store.analyze_with(&text, &options).unwrap();
+            assert_eq!(expected.0, matched.name);
+            assert_eq!(expected.1.as_deref(), matched.runner_up);
+            assert_eq!(20, store.match_iter(&text, &options).unwrap().len());
+        }
+        let options = AnalysisOptions::default().chunk_size(0);
+        assert!(store.analyze_with(&text, &options).is_err());
+
+        #[allow(deprecated)]
+        {
+            store.set_analyze_chunk_size(Some(3)).unwrap();
+            assert!(store.set_analyze_chunk_size(Some(0)).is_err());
+        }
+        assert_eq!(expected.0, store.analyze(&text).name);
+    }
+
+    #[test]
+    fn stop_grams() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "the software aaaaa bbbbb".into());
+        store.add_license("license-2".into(), "the software ccccc ddddd".into());
+        assert_eq!(vec!["the software".to_owned()], store.common_grams(1));
+
+        let text = TextData::from("the software aaaaa ccccc");
+        let before = store.analyze(&text).score;
+
+        store.add_stop_grams(store.common_grams(1));
+        assert_eq!(1, store.stop_grams().count());
+        let matched = store.analyze(&text);
+        assert!(
+            matched.score < before,
+            "shared boilerplate no longer counts"
+        );
+
+        // phrases are normalized, and later licenses are filtered too
+        store.add_stop_grams(["SOFTWARE AAAAA"]);
+        store.add_license("license-3".into(), "the software aaaaa eeeee".into());
+        let entry = &store.licenses["license-3"];
+        assert_eq!(0, entry.original.match_data.get_str("the software"));
+        assert_eq!(0, entry.original.match_data.get_str("software aaaaa"));
+    }
+
+    #[test]
+    fn resolve_aliases() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+        store
+            .set_aliases("license-1", vec!["license-one".into()])
+            .unwrap();
+
+        assert_eq!(Some("license-1"), store.resolve_alias("license-1"));
+        assert_eq!(Some("license-1"), store.resolve_alias("license-one"));
+        assert_eq!(Some("license-1"), store.resolve_alias("LICENSE-One"));
+        assert_eq!(Some("license-1"), store.resolve_alias("License-1"));
+        assert_eq!(None, store.resolve_alias("license-2"));
+    }
+
+    #[test]
+    fn debug_summary() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+        store
+            .add_variant("license-1", LicenseType::Header, "aaaaa bbbbb".into())
+            .unwrap();
+        store
+            .set_aliases("license-1", vec!["license-one".into()])
+            .unwrap();
+
+        assert_eq!(
+            "Store { licenses: 1, headers: 1, alternates: 0, aliases: 1, texts_stored: true }",
+            format!("{:?}", store)
+        );
+
+        let mut store = Store::new();
+        store.add_license(
+            "license-1".into(),
+            TextData::from("aaaaa bbbbb ccccc").without_text(),
+        );
+        assert!(format!("{:?}", store).contains("texts_stored: false"));
+    }
+}
@@ -1,47 +1,404 @@
 // Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::{io::copy, io::prelude::*};
+use std::{
+    collections::{BTreeSet, HashMap},
+    io::copy,
+    io::prelude::*,
+    mem,
+};
 
-use anyhow::Error;
+use anyhow::{format_err, Error};
 use log::info;
 use rmp_serde::Serializer;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-use crate::store::base::Store;
+use crate::{
+    intern::intern_all,
+    license::TextData,
+    ngram::{Gram, NgramSet},
+    store::{
+        base::{LicenseEntry, Store},
+        migrate::{migration_for, MIGRATIONS},
+    },
+    timing::unix_now,
+};
 
-const CACHE_VERSION: &[u8] = b"askalono-04";
+/// The version of the cache format, which is also the version of the JSON
+/// format (see `Store::to_json`), as both hold the same data.
+pub(crate) const CACHE_VERSION: &str = "askalono-12";
+
+/// The number of licenses in each separately encoded chunk of a cache.
+const CHUNK_SIZE: usize = 32;
+
+/// The head of a serialized `Store`.
+///
+/// Most words appear in many licenses, so rather than spelling out n-grams
+/// in every license's data, each distinct word is stored once in `tokens`,
+/// and grams refer to their words by index.
+///
+/// The licenses themselves follow the head as a series of chunks, each
+/// MessagePack-encoded on its own, so that they can be decoded in parallel.
+/// `chunks` holds the length in bytes of each one. Licenses are sorted by
+/// name, so `chunk_starts` is enough to find the chunk holding any license
+/// without decoding the others; see `Store::load_subset`.
+#[derive(Serialize, Deserialize)]
+struct CacheHead {
+    tokens: Vec<String>,
+    chunks: Vec<u64>,
+    /// The rest of the store, with no licenses.
+    store: Store,
+    /// The name of the first license in each chunk. Empty in caches written
+    /// before it was recorded.
+    #[serde(default)]
+    chunk_starts: Vec<String>,
+}
+
+/// A license as stored in a cache chunk.
+#[derive(Serialize, Deserialize)]
+struct CachedLicense {
+    name: String,
+    /// The n-gram sets of the license: the original, followed by its headers
+    /// and then its alternates.
+    sets: Vec<InternedSet>,
+    /// The license, with its n-gram sets emptied out.
+    entry: LicenseEntry,
+}
+
+/// An n-gram set, as the indices in `CacheHead::tokens` of each gram's
+/// words, followed by its count.
+#[derive(Serialize, Deserialize)]
+struct InternedSet {
+    /// The number of words in each gram, which is always 2.
+    n: u8,
+    counts: Vec<(u32, u32, u32)>,
+    /// The hashes of each gram and their counts, in place of `counts`, if
+    /// the set is hashed; see `Store::hash_grams`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    hashes: Vec<(u64, u32)>,
+}
+
+/// Encode a store's cache body (everything after the version header).
+fn encode_store(store: &Store) -> Result<Vec<u8>, Error> {
+    let mut store = store.clone();
+    store.built_at = unix_now();
+    let mut licenses: Vec<(String, LicenseEntry)> =
+        mem::take(&mut store.licenses).into_iter().collect();
+    licenses.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+    // sorted, so that the same store is always written the same way
+    let tokens: BTreeSet<&str> = licenses
+        .iter()
+        .flat_map(|(_, entry)| entry_texts(entry))
+        .flat_map(|data| &data.match_data)
+        .flat_map(|(gram, _)| {
+            let (first, second) = gram.words();
+            [first, second]
+        })
+        .collect();
+    let tokens: Vec<String> = tokens.into_iter().map(str::to_owned).collect();
+    let ids: HashMap<&str, u32> = tokens
+        .iter()
+        .enumerate()
+        .map(|(id, token)| (token.as_str(), id as u32))
+        .collect();
+
+    let cached: Vec<CachedLicense> = licenses
+        .into_iter()
+        .map(|(name, mut entry)| {
+            let sets = entry_texts_mut(&mut entry)
+                .map(|data| {
+                    let set = mem::take(&mut data.match_data);
+                    if set.is_hashed() {
+                        let mut hashes: Vec<(u64, u32)> = set.gram_hashes().collect();
+                        hashes.sort_unstable();
+                        return InternedSet {
+                            n: 2,
+                            counts: Vec::new(),
+                            hashes,
+                        };
+                    }
+                    let mut counts: Vec<(u32, u32, u32)> = set
+                        .into_iter()
+                        .map(|(gram, count)| {
+                            let (first, second) = gram.words();
+                            (ids[first], ids[second], count)
+                        })
+                        .collect();
+                    counts.sort_unstable();
+                    InternedSet {
+                        n: 2,
+                        counts,
+                        hashes: Vec::new(),
+                    }
+                })
+                .collect();
+            CachedLicense { name, sets, entry }
+        })
+        .collect();
+
+    let mut chunks = Vec::new();
+    let mut chunk_starts = Vec::new();
+    let mut body = Vec::new();
+    for chunk in cached.chunks(CHUNK_SIZE) {
+        chunk_starts.push(chunk[0].name.clone());
+        let start = body.len();
+        chunk.serialize(&mut Serializer::new(&mut body))?;
+        chunks.push((body.len() - start) as u64);
+    }
+
+    // The full SPDX set fits comfortably in 4MiB
+    let mut buf = Vec::with_capacity(4 * 1024 * 1024);
+    CacheHead {
+        tokens,
+        chunks,
+        store,
+        chunk_starts,
+    }
+    .serialize(&mut Serializer::new(&mut buf))?;
+    buf.extend(body);
+    Ok(buf)
+}
+
+/// Decode a cache body produced by `encode_store`. If `subset` is given, only
+/// the chunks holding those licenses are decoded; other licenses sharing
+/// their chunks may be included too.
+fn decode_store(buf: &[u8], subset: Option<&[&str]>) -> Result<Store, Error> {
+    let mut rest = buf;
+    let head: CacheHead = rmp_serde::decode::from_read(&mut rest)?;
+
+    let mut chunks = Vec::with_capacity(head.chunks.len());
+    for &len in &head.chunks {
+        let len = usize::try_from(len)?;
+        if len > rest.len() {
+            anyhow::bail!("cache is corrupt: chunk extends past the end of the cache");
+        }
+        let (chunk, remaining) = rest.split_at(len);
+        chunks.push(chunk);
+        rest = remaining;
+    }
+
+    // skip chunks that can't hold any of the subset, if we can tell
+    if let Some(names) = subset {
+        if head.chunk_starts.len() == chunks.len() {
+            let wanted: BTreeSet<usize> = names
+                .iter()
+                .filter_map(|&name| {
+                    let after = head.chunk_starts.partition_point(|start| **start <= *name);
+                    after.checked_sub(1)
+                })
+                .collect();
+            chunks = wanted.into_iter().map(|i| chunks[i]).collect();
+        }
+    }
+
+    let (compact, hashed) = (head.store.compact_counts, head.store.hashed_grams);
+    let tokens = intern_all(head.tokens.iter().map(String::as_str))?;
+    let decode = |chunk: &&[u8]| decode_chunk(chunk, &tokens, compact, hashed);
+
+    #[cfg(all(feature = "rayon", not(target_arch = "wasm32")))]
+    let decoded: Vec<Result<Vec<(String, LicenseEntry)>, Error>> = {
+        use rayon::prelude::*;
+        chunks.par_iter().map(decode).collect()
+    };
+    #[cfg(not(all(feature = "rayon", not(target_arch = "wasm32"))))]
+    let decoded: Vec<Result<Vec<(String, LicenseEntry)>, Error>> =
+        chunks.iter().map(decode).collect();
+
+    let mut store = head.store;
+    for licenses in decoded {
+        store.licenses.extend(licenses?);
+    }
+    // clauses are read without adding their words to the token table
+    store.intern_all_words();
+    Ok(store)
+}
+
+fn decode_chunk(
+    chunk: &[u8],
+    tokens: &[u32],
+    compact: bool,
+    hashed: bool,
+) -> Result<Vec<(String, LicenseEntry)>, Error> {
+    let cached: Vec<CachedLicense> = rmp_serde::decode::from_slice(chunk)?;
+    cached
+        .into_iter()
+        .map(
+            |CachedLicense {
+                 name,
+                 sets,
+                 mut entry,
+             }| {
+                let texts: Vec<&mut TextData> = entry_texts_mut(&mut entry).collect();
+                if texts.len() != sets.len() {
+                    anyhow::bail!("cache is corrupt: wrong number of texts for {}", name);
+                }
+
+                for (data, set) in texts.into_iter().zip(sets) {
+                    if set.n != 2 {
+                        anyhow::bail!(
+                            "cache holds n-grams of {} for {}; only bigrams are supported",
+                            set.n,
+                            name
+                        );
+                    }
+                    if !set.hashes.is_empty() {
+                        data.match_data = NgramSet::from_hashes(set.hashes);
+                        continue;
+                    }
+                    let counts = set
+                        .counts
+                        .into_iter()
+                        .map(|(first, second, count)| {
+                            let token = |id: u32| tokens.get(id as usize).copied();
+                            Some((Gram::new(token(first)?, token(second)?), count))
+                        })
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(|| format_err!("cache is corrupt: unknown gram in {}", name))?;
+                    data.match_data = NgramSet::from_counts(counts);
+                    if hashed {
+                        data.match_data.hash_grams();
+                    } else if compact {
+                        data.match_data.pack();
+                    }
+                }
+                Ok((name, entry))
+            },
+        )
+        .collect()
+}
+
+fn entry_texts(entry: &LicenseEntry) -> impl Iterator<Item = &TextData> {
+    std::iter::once(&entry.original)
+        .chain(entry.headers.iter())
+        .chain(entry.alternates.iter())
+}
+
+fn entry_texts_mut(entry: &mut LicenseEntry) -> impl Iterator<Item = &mut TextData> {
+    std::iter::once(&mut entry.original)
+        .chain(entry.headers.iter_mut())
+        .chain(entry.alternates.iter_mut())
+}
+
+#[cfg(feature = "embedded-cache")]
+static EMBEDDED_CACHE: &[u8] = include_bytes!(env!("ASKALONO_EMBEDDED_CACHE_FILE"));
+
+/// Get the store embedded at build time, loading it on first use.
+///
+/// The cache is deserialized once per process and shared from then on, so
+/// this is a cheap way to get at a store from anywhere without threading one
+/// through your application. It's only available with the `embedded-cache`
+/// feature, which requires the `ASKALONO_EMBEDDED_CACHE` environment variable
+/// to hold the absolute path of a cache file at build time.
+///
+/// # Panics
+///
+/// Panics if no cache was embedded, because `ASKALONO_EMBEDDED_CACHE` wasn't
+/// set at build time, or if the embedded cache can't be loaded, such as when
+/// it was built by an incompatible version of askalono.
+#[cfg(feature = "embedded-cache")]
+pub fn global_store() -> &'static Store {
+    use std::sync::OnceLock;
+
+    static STORE: OnceLock<Store> = OnceLock::new();
+    STORE.get_or_init(|| {
+        if EMBEDDED_CACHE.is_empty() {
+            panic!("no cache was embedded; set ASKALONO_EMBEDDED_CACHE when building askalono");
+        }
+        Store::from_cache(EMBEDDED_CACHE).expect("embedded cache is invalid")
+    })
+}
+
+/// Read a cache in the current format or any that can be migrated, keeping
+/// only the named licenses if `subset` is given.
+fn read_cache<R>(mut readable: R, subset: Option<&[&str]>) -> Result<Store, Error>
+where
+    R: Read + Sized,
+{
+    let mut header = [0u8; 11];
+    readable.read_exact(&mut header)?;
+
+    let migrate = if header == CACHE_VERSION.as_bytes() {
+        None
+    } else if let Some(migrate) = migration_for(&header) {
+        info!(
+            "Migrating cache from '{}'",
+            String::from_utf8_lossy(&header)
+        );
+        Some(migrate)
+    } else {
+        let supported: Vec<_> = std::iter::once(CACHE_VERSION.as_bytes())
+            .chain(MIGRATIONS.iter().map(|&(version, _)| version))
+            .map(String::from_utf8_lossy)
+            .collect();
+        anyhow::bail!(
+            "cache version mismatch; expected one of '{}', found '{}'",
+            supported.join("', '"),
+            String::from_utf8_lossy(&header)
+        );
+    };
+
+    #[cfg(not(feature = "gzip"))]
+    let mut dec = zstd::Decoder::new(readable)?;
+    #[cfg(feature = "gzip")]
+    let mut dec = flate2::read::GzDecoder::new(readable);
+
+    let mut buf = Vec::new();
+    dec.read_to_end(&mut buf)?;
+    let mut store = match migrate {
+        None => decode_store(&buf, subset)?,
+        Some(migrate) => migrate(&buf)?,
+    };
+    if let Some(names) = subset {
+        store
+            .licenses
+            .retain(|name, _| names.contains(&name.as_str()));
+        // these describe the whole store, and a few licenses are quick to
+        // score anyway
+        store.index = None;
+        store.lsh = None;
+    }
+    Ok(store)
+}
 
 impl Store {
     /// Create a store from a cache file.
     ///
     /// This method is highly useful for quickly loading a cache, as creating
     /// one from text data is rather slow. This method can typically load
-    /// the full SPDX set from disk in 200-300 ms. The cache will be
+    /// the full SPDX set from disk in 200-300 ms, and licenses are decoded in
+    /// parallel when the `rayon` feature is enabled. The cache will be
     /// sanity-checked to ensure it was generated with a similar version of
     /// askalono.
-    pub fn from_cache<R>(mut readable: R) -> Result<Store, Error>
+    ///
+    /// Caches written by some earlier versions (currently, 0.4) can still be
+    /// loaded; they're converted as they're read, and `to_cache` writes them
+    /// back out in the current format. Data those versions didn't record,
+    /// such as provenance, is missing.
+    pub fn from_cache<R>(readable: R) -> Result<Store, Error>
     where
         R: Read + Sized,
     {
-        let mut header = [0u8; 11];
-        readable.read_exact(&mut header)?;
-
-        if header != CACHE_VERSION {
-            anyhow::bail!(
-                "cache version mismatch; expected '{}', found '{}'",
-                String::from_utf8_lossy(CACHE_VERSION),
-                String::from_utf8_lossy(&header)
-            );
-        }
-
-        #[cfg(not(feature = "gzip"))]
-        let dec = zstd::Decoder::new(readable)?;
-        #[cfg(feature = "gzip")]
-        let dec = flate2::read::GzDecoder::new(readable);
+        read_cache(readable, None)
+    }
 
-        let store = rmp_serde::decode::from_read(dec)?;
-        Ok(store)
+    /// Create a store from a cache file, with only the named licenses.
+    ///
+    /// This is for when only a few licenses matter and memory or time is
+    /// tight, as in wasm: only the parts of the cache holding those licenses
+    /// are decoded, which is much quicker than `from_cache` for a handful of
+    /// licenses. Names must be exact license names, not aliases; names not in
+    /// the cache are ignored. Everything besides the licenses themselves,
+    /// such as exceptions and stop grams, is loaded as usual, except that any
+    /// candidate index or LSH prefilter is dropped.
+    ///
+    /// Caches written before this was available, or by earlier versions, are
+    /// decoded in full and then narrowed down.
+    pub fn load_subset<R>(readable: R, names: &[&str]) -> Result<Store, Error>
+    where
+        R: Read + Sized,
+    {
+        read_cache(readable, Some(names))
     }
 
     /// Serialize the current store.
@@ -52,17 +409,11 @@ impl Store {
     where
         W: Write + Sized,
     {
-        let buf = {
-            // This currently sits around 3.7MiB, so go up to 4 to fit comfortably
-            let mut buf = Vec::with_capacity(4 * 1024 * 1024);
-            let mut serializer = Serializer::new(&mut buf);
-            self.serialize(&mut serializer)?;
-            buf
-        };
+        let buf = encode_store(self)?;
 
         info!("Pre-compressed output is {} bytes", buf.len());
 
-        writable.write_all(CACHE_VERSION)?;
+        writable.write_all(CACHE_VERSION.as_bytes())?;
 
         #[cfg(not(feature = "gzip"))]
         let mut enc = zstd::Encoder::new(writable, 21)?;
@@ -75,3 +426,145 @@ impl Store {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interned_round_trip() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc ddddd".into());
+        store.add_license("license-2".into(), "aaaaa bbbbb eeeee".into());
+        store
+            .add_variant(
+                "license-2",
+                crate::LicenseType::Header,
+                "bbbbb eeeee fffff".into(),
+            )
+            .unwrap();
+
+        let mut buf = Vec::new();
+        store.to_cache(&mut buf).unwrap();
+        let loaded = Store::from_cache(buf.as_slice()).unwrap();
+
+        for name in &["license-1", "license-2"] {
+            let (a, b) = (&store.licenses[*name], &loaded.licenses[*name]);
+            assert_eq!(a.original.match_data, b.original.match_data);
+            assert_eq!(a.headers.len(), b.headers.len());
+            for (x, y) in a.headers.iter().zip(&b.headers) {
+                assert_eq!(x.match_data, y.match_data);
+            }
+        }
+
+        // words are numbered as in the rest of the process after loading
+        let gram = Gram::intern("aaaaa bbbbb").unwrap();
+        for name in &["license-1", "license-2"] {
+            assert_eq!(1, loaded.licenses[*name].original.match_data.get(gram));
+        }
+    }
+
+    #[test]
+    fn compact_round_trip() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+        store.compact_counts();
+        store.add_license("license-2".into(), "ddddd eeeee fffff".into());
+
+        let mut buf = Vec::new();
+        store.to_cache(&mut buf).unwrap();
+        let loaded = Store::from_cache(buf.as_slice()).unwrap();
+        assert!(loaded.compact_counts);
+        let matched = loaded.analyze(&"ddddd eeeee fffff".into());
+        assert_eq!("license-2", matched.name);
+        assert_eq!(1.0, matched.score);
+    }
+
+    #[test]
+    fn hashed_round_trip() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+        store.set_idf_weighting(true);
+        assert!(store.hash_grams().is_err());
+        store.set_idf_weighting(false);
+        store.hash_grams().unwrap();
+        store.add_license("license-2".into(), "hashedcachetest eeeee fffff".into());
+        store.set_candidate_index(Some(1));
+        assert!(store.index.is_none());
+
+        let mut buf = Vec::new();
+        store.to_cache(&mut buf).unwrap();
+        let loaded = Store::from_cache(buf.as_slice()).unwrap();
+        assert!(loaded.hashed_grams);
+        assert!(loaded.licenses["license-2"].original.match_data.is_hashed());
+        // the words of hashed grams aren't kept
+        assert_eq!(None, crate::intern::lookup("hashedcachetest"));
+        let matched = loaded.analyze(&"hashedcachetest eeeee fffff".into());
+        assert_eq!("license-2", matched.name);
+        assert_eq!(1.0, matched.score);
+    }
+
+    #[test]
+    fn chunked_round_trip() {
+        let mut store = Store::new();
+        for i in 0..(CHUNK_SIZE * 2 + 5) {
+            store.add_license(
+                format!("license-{}", i),
+                format!("aaaaa bbbbb {}", i).into(),
+            );
+        }
+
+        let mut buf = Vec::new();
+        store.to_cache(&mut buf).unwrap();
+        let loaded = Store::from_cache(buf.as_slice()).unwrap();
+        assert_eq!(store.len(), loaded.len());
+        for (name, entry) in &store.licenses {
+            assert_eq!(
+                entry.original.match_data,
+                loaded.licenses[name].original.match_data
+            );
+        }
+
+        let body = encode_store(&store).unwrap();
+        assert!(decode_store(&body[..body.len() - 1], None).is_err());
+    }
+
+    #[test]
+    fn load_subset() {
+        let mut store = Store::new();
+        for i in 0..(CHUNK_SIZE * 3) {
+            store.add_license(
+                format!("license-{}", i),
+                format!("aaaaa bbbbb {}", i).into(),
+            );
+        }
+        store.set_candidate_index(Some(4));
+
+        let mut buf = Vec::new();
+        store.to_cache(&mut buf).unwrap();
+        let wanted = ["license-7", "license-90", "license-missing"];
+        let loaded = Store::load_subset(buf.as_slice(), &wanted).unwrap();
+        let mut names: Vec<&String> = loaded.licenses.keys().collect();
+        names.sort();
+        assert_eq!(vec!["license-7", "license-90"], names);
+        assert_eq!(None, loaded.candidate_index());
+
+        let matched = loaded.analyze(&"aaaaa bbbbb 90".into());
+        assert_eq!("license-90", matched.name);
+        assert_eq!(1.0, matched.score);
+
+        // only the chunks holding the subset are decoded
+        let body = encode_store(&store).unwrap();
+        let decoded = decode_store(&body, Some(&wanted)).unwrap();
+        assert!(decoded.len() <= CHUNK_SIZE * 2);
+        assert!(decoded.licenses.contains_key("license-90"));
+    }
+
+    #[test]
+    #[cfg(feature = "embedded-cache")]
+    fn global_store_is_shared() {
+        let store = global_store();
+        assert!(!store.is_empty());
+        assert!(std::ptr::eq(store, global_store()));
+    }
+}
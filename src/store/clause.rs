@@ -0,0 +1,167 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::borrow::Cow;
+
+use anyhow::{format_err, Error};
+use lazy_static::lazy_static;
+
+use crate::{license::TextData, ngram::NgramSet, store::base::Store};
+
+/// Clauses that tell apart otherwise near-identical licenses, checked for
+/// every store. See `Store::add_clause`.
+const DEFAULT_CLAUSES: &[&str] = &[
+    // BSD-4-Clause and friends, vs. BSD-3-Clause
+    "All advertising materials mentioning features or use of this software must display \
+     the following acknowledgement: This product includes software developed by",
+    // X11 and other MIT variants with a no-endorsement clause, vs. MIT
+    "Except as contained in this notice, the name of shall not be used in advertising or \
+     otherwise to promote the sale, use or other dealings in this Software without prior \
+     written authorization",
+];
+
+/// How much of a clause must appear in a text for it to count as present.
+const CLAUSE_PRESENT: f32 = 0.8;
+
+lazy_static! {
+    static ref DEFAULT_CLAUSE_DATA: Vec<TextData> = DEFAULT_CLAUSES
+        .iter()
+        .map(|&clause| TextData::new(clause).without_text())
+        .collect();
+}
+
+/// Check whether a clause appears in a text, given both as n-grams.
+pub(crate) fn has_clause(clause: &NgramSet, text: &NgramSet) -> bool {
+    clause.containment(text) >= CLAUSE_PRESENT
+}
+
+impl Store {
+    /// Register a clause that sets a license apart from others that are
+    /// almost the same.
+    ///
+    /// Some licenses differ from their closest relatives by a single clause,
+    /// such as BSD-4-Clause's advertising clause, which barely moves an
+    /// overall similarity score. When `analyze` finds licenses scoring
+    /// within a few points of the best, it checks every clause registered
+    /// on any of them: licenses that agree with the text on whether each
+    /// clause is present are preferred over those that don't, regardless of
+    /// score. Whether a license has a clause is judged from its own text,
+    /// so a clause only needs to be registered on one license of a family.
+    ///
+    /// Clauses for the BSD advertising clause and the MIT-style
+    /// no-endorsement clause are always checked. The license must already
+    /// exist.
+    pub fn add_clause(&mut self, name: &str, clause: TextData) -> Result<(), Error> {
+        let mut clause = clause.without_text();
+        clause.intern_words();
+        clause.match_data.remove_grams(&self.stop_grams);
+        let entry = self
+            .licenses
+            .get_mut(name)
+            .ok_or_else(|| format_err!("license {} not present in store", name))?;
+        entry.clauses.push(clause);
+        self.forget_derived();
+        Ok(())
+    }
+
+    /// Get the clauses that decide between the given licenses: the defaults
+    /// and any registered on them, with stop grams removed.
+    pub(crate) fn deciding_clauses<'a, I>(&'a self, names: I) -> Vec<Cow<'a, NgramSet>>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let registered = names
+            .into_iter()
+            .filter_map(|name| self.licenses.get(name))
+            .flat_map(|entry| entry.clauses.iter())
+            .map(|clause| Cow::Borrowed(&clause.match_data));
+        DEFAULT_CLAUSE_DATA
+            .iter()
+            .map(|clause| clause.match_data_excluding(&self.stop_grams))
+            .chain(registered)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::options::AnalysisOptions;
+
+    const BSD_3: &str = "Redistribution and use in source and binary forms, with or without \
+        modification, are permitted provided that the following conditions are met:\n\n\
+        Redistributions of source code must retain the above copyright notice, this list of \
+        conditions and the following disclaimer. Redistributions in binary form must \
+        reproduce the above copyright notice. Neither the name of the copyright holder nor \
+        the names of its contributors may be used to endorse or promote products derived \
+        from this software without specific prior written permission.";
+    const ADVERTISING: &str = "All advertising materials mentioning features or use of this \
+        software must display the following acknowledgement: This product includes software \
+        developed by the organization.";
+
+    fn store() -> Store {
+        let mut store = Store::new();
+        store.add_license("BSD-3-Clause".into(), BSD_3.into());
+        store.add_license(
+            "BSD-4-Clause".into(),
+            format!("{}\n\n{}", BSD_3, ADVERTISING).as_str().into(),
+        );
+        store
+    }
+
+    #[test]
+    fn default_clauses() {
+        let store = store();
+
+        // cut short, the advertising clause still counts
+        let text = format!(
+            "{}\n\nAll advertising materials mentioning features or use of this software must \
+             display the following acknowledgement: This product includes software developed",
+            BSD_3
+        );
+        let matched = store.analyze(&TextData::from(text.as_str()));
+        assert_eq!("BSD-4-Clause", matched.name);
+
+        // but a similar sentence isn't the clause, even if it's closer to
+        // BSD-4-Clause overall
+        let text = TextData::from(
+            format!(
+                "{}\n\nAdvertising materials mentioning features of this software must \
+                 display: This product includes software developed by the organization.",
+                BSD_3
+            )
+            .as_str(),
+        );
+        let closest = store
+            .match_iter(&text, &AnalysisOptions::default())
+            .unwrap()
+            .next()
+            .unwrap();
+        assert_eq!("BSD-4-Clause", closest.name);
+        let matched = store.analyze(&text);
+        assert_eq!("BSD-3-Clause", matched.name);
+    }
+
+    #[test]
+    fn registered_clauses() {
+        let mut store = Store::new();
+        store.add_license(
+            "license-1".into(),
+            "aaaaa bbbbb ccccc ddddd eeeee kkkkk".into(),
+        );
+        store.add_license(
+            "license-2".into(),
+            "aaaaa bbbbb ccccc ddddd eeeee hhhhh iiiii jjjjj".into(),
+        );
+        let text = TextData::from("aaaaa bbbbb ccccc ddddd eeeee hhhhh");
+        assert_eq!("license-2", store.analyze(&text).name);
+
+        // the text lacks license-2's clause
+        store
+            .add_clause("license-2", "hhhhh iiiii jjjjj".into())
+            .unwrap();
+        assert_eq!("license-1", store.analyze(&text).name);
+
+        assert!(store.add_clause("license-3", "hhhhh".into()).is_err());
+    }
+}
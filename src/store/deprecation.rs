@@ -0,0 +1,158 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{format_err, Error};
+
+use crate::store::base::Store;
+
+/// Deprecated SPDX identifiers whose replacements don't follow the GNU
+/// `-only`/`-or-later` pattern, with the expressions replacing them.
+const SUCCESSORS: &[(&str, &str)] = &[
+    ("BSD-2-Clause-FreeBSD", "BSD-2-Clause"),
+    ("BSD-2-Clause-NetBSD", "BSD-2-Clause"),
+    ("bzip2-1.0.5", "bzip2-1.0.6"),
+    ("eCos-2.0", "GPL-2.0-or-later WITH eCos-exception-2.0"),
+    (
+        "GPL-2.0-with-autoconf-exception",
+        "GPL-2.0-only WITH Autoconf-exception-2.0",
+    ),
+    (
+        "GPL-2.0-with-bison-exception",
+        "GPL-2.0-or-later WITH Bison-exception-2.2",
+    ),
+    (
+        "GPL-2.0-with-classpath-exception",
+        "GPL-2.0-only WITH Classpath-exception-2.0",
+    ),
+    (
+        "GPL-2.0-with-font-exception",
+        "GPL-2.0-only WITH Font-exception-2.0",
+    ),
+    (
+        "GPL-2.0-with-GCC-exception",
+        "GPL-2.0-only WITH GCC-exception-2.0",
+    ),
+    (
+        "GPL-3.0-with-autoconf-exception",
+        "GPL-3.0-only WITH Autoconf-exception-3.0",
+    ),
+    (
+        "GPL-3.0-with-GCC-exception",
+        "GPL-3.0-only WITH GCC-exception-3.1",
+    ),
+    ("Nunit", "zlib-acknowledgement"),
+    ("StandardML-NJ", "SMLNJ"),
+    ("wxWindows", "GPL-2.0-or-later WITH WxWindows-exception-3.1"),
+];
+
+/// License families whose bare identifiers (like `GPL-2.0`) were deprecated
+/// in favor of `-only` and `-or-later` forms.
+const GNU_PREFIXES: &[&str] = &["AGPL-", "GFDL-", "GPL-", "LGPL-"];
+
+/// Guess the expression replacing a deprecated SPDX identifier, if it's a
+/// well-known one.
+#[cfg_attr(not(feature = "spdx"), allow(dead_code))]
+pub(crate) fn spdx_successor(id: &str) -> Option<String> {
+    if let Some(&(_, successor)) = SUCCESSORS.iter().find(|(old, _)| *old == id) {
+        return Some(successor.to_owned());
+    }
+
+    if !GNU_PREFIXES.iter().any(|prefix| id.starts_with(prefix)) {
+        return None;
+    }
+    let (base, suffix) = match id.strip_suffix('+') {
+        Some(base) => (base, "-or-later"),
+        None => (id, "-only"),
+    };
+    // only bare versions, like `GPL-2.0`
+    let version = base.rsplit('-').next()?;
+    if !version.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return None;
+    }
+    Some(format!("{}{}", base, suffix))
+}
+
+impl Store {
+    /// Check whether a license is deprecated, such as an SPDX identifier
+    /// that has since been replaced (like `GPL-2.0`).
+    ///
+    /// Deprecated SPDX licenses are only in a store loaded with
+    /// `SpdxLoadOptions::include_deprecated`; see `set_deprecated` to mark
+    /// licenses of your own.
+    pub fn is_deprecated(&self, name: &str) -> bool {
+        self.licenses
+            .get(name)
+            .is_some_and(|entry| entry.deprecated)
+    }
+
+    /// Get what replaces a deprecated license, as an SPDX expression (such as
+    /// `GPL-2.0-only`), if known.
+    ///
+    /// This is worth suggesting when a text matches a deprecated license.
+    pub fn superseded_by(&self, name: &str) -> Option<&str> {
+        self.licenses.get(name)?.superseded_by.as_deref()
+    }
+
+    /// Mark a license as deprecated or not, along with what replaces it. See
+    /// `is_deprecated` and `superseded_by`.
+    pub fn set_deprecated(
+        &mut self,
+        name: &str,
+        deprecated: bool,
+        superseded_by: Option<String>,
+    ) -> Result<(), Error> {
+        let entry = self
+            .licenses
+            .get_mut(name)
+            .ok_or_else(|| format_err!("license {} not present in store", name))?;
+        entry.deprecated = deprecated;
+        entry.superseded_by = superseded_by.filter(|_| deprecated);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spdx_successors() {
+        for (id, successor) in &[
+            ("GPL-2.0", Some("GPL-2.0-only")),
+            ("GPL-2.0+", Some("GPL-2.0-or-later")),
+            ("LGPL-2.1+", Some("LGPL-2.1-or-later")),
+            ("AGPL-1.0", Some("AGPL-1.0-only")),
+            ("GFDL-1.3", Some("GFDL-1.3-only")),
+            ("StandardML-NJ", Some("SMLNJ")),
+            (
+                "GPL-2.0-with-classpath-exception",
+                Some("GPL-2.0-only WITH Classpath-exception-2.0"),
+            ),
+            ("GPL-2.0-only", None),
+            ("MIT", None),
+        ] {
+            assert_eq!(successor.map(str::to_owned), spdx_successor(id), "{}", id);
+        }
+    }
+
+    #[test]
+    fn deprecated() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+        assert!(!store.is_deprecated("license-1"));
+        assert_eq!(None, store.superseded_by("license-1"));
+
+        store
+            .set_deprecated("license-1", true, Some("license-2".into()))
+            .unwrap();
+        assert!(store.is_deprecated("license-1"));
+        assert_eq!(Some("license-2"), store.superseded_by("license-1"));
+
+        store
+            .set_deprecated("license-1", false, Some("license-2".into()))
+            .unwrap();
+        assert!(!store.is_deprecated("license-1"));
+        assert_eq!(None, store.superseded_by("license-1"));
+        assert!(store.set_deprecated("license-3", true, None).is_err());
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    fs::{read_dir, read_to_string},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{format_err, Error};
+use log::info;
+use serde::Deserialize;
+
+use crate::{
+    license::{LicenseType, TextData},
+    store::{base::Store, provenance::Provenance},
+};
+
+/// The metadata file that may accompany a license text in a directory loaded
+/// by `Store::load_directory`.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LicenseMetadata {
+    #[serde(default)]
+    aliases: Vec<String>,
+    #[serde(default)]
+    family: Option<String>,
+    #[serde(default)]
+    headers: Vec<String>,
+    #[serde(default)]
+    alternates: Vec<String>,
+}
+
+/// List the files with the given extension in a directory, in a stable
+/// order.
+fn paths_with_extension(dir: &Path, extension: &str) -> Result<Vec<PathBuf>, Error> {
+    let mut paths: Vec<_> = read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && p.extension().is_some_and(|e| e == extension))
+        .collect();
+
+    // sort without extensions; otherwise dashes and dots muck it up
+    paths.sort_by(|a, b| a.file_stem().unwrap().cmp(b.file_stem().unwrap()));
+    Ok(paths)
+}
+
+impl Store {
+    /// Fill the store with licenses from a directory of text files.
+    ///
+    /// Each `.txt` file in the directory is a license, named after the file
+    /// (so `Internal-1.0.txt` is `Internal-1.0`). Files are read as with
+    /// `TextData::from_file`. A license may have a TOML file of the same name
+    /// beside it (`Internal-1.0.toml`) listing more about it, all optional:
+    ///
+    /// ```toml
+    /// aliases = ["Internal"]
+    /// family = "Internal"
+    /// headers = ["Licensed under the Internal License, version 1.0."]
+    /// alternates = ["...the full text, worded differently..."]
+    /// ```
+    ///
+    /// This makes it easy to keep an organization's own licenses alongside
+    /// (or instead of) SPDX's, without writing any code to build a store. As
+    /// with `load_spdx`, `include_texts` decides whether normalized texts are
+    /// kept in the store.
+    ///
+    /// Licenses already in the store are replaced. Metadata files without a
+    /// license text, and metadata that can't be parsed, are errors.
+    ///
+    /// Requires the `fs` feature.
+    pub fn load_directory(&mut self, dir: &Path, include_texts: bool) -> Result<(), Error> {
+        let texts = paths_with_extension(dir, "txt")?;
+        for path in paths_with_extension(dir, "toml")? {
+            if !path.with_extension("txt").is_file() {
+                anyhow::bail!("{} has no license text beside it", path.display());
+            }
+        }
+
+        for path in &texts {
+            let name = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .ok_or_else(|| format_err!("{} isn't a valid license name", path.display()))?;
+            info!("Processing {}", name);
+
+            let provenance_of = |path: &Path| Provenance {
+                path: Some(path.display().to_string()),
+                ..Provenance::new("directory")
+            };
+            let prepare = |data: TextData| match include_texts {
+                true => data,
+                false => data.without_text(),
+            };
+
+            let metadata_path = path.with_extension("toml");
+            let metadata: LicenseMetadata = match metadata_path.is_file() {
+                true => toml::from_str(&read_to_string(&metadata_path)?)
+                    .map_err(|e| format_err!("{}: {}", metadata_path.display(), e))?,
+                false => LicenseMetadata::default(),
+            };
+
+            self.add_license(name.to_owned(), prepare(TextData::from_file(path)?));
+            self.set_provenance(name, provenance_of(path))?;
+            self.set_aliases(name, metadata.aliases)?;
+            self.set_family(name, metadata.family)?;
+            let variants = metadata
+                .headers
+                .into_iter()
+                .map(|text| (LicenseType::Header, text))
+                .chain(
+                    metadata
+                        .alternates
+                        .into_iter()
+                        .map(|text| (LicenseType::Alternate, text)),
+                );
+            let (mut headers, mut alternates) = (0, 0);
+            for (variant, text) in variants {
+                self.add_variant(name, variant, prepare(TextData::new(&text)))?;
+                let index = match variant {
+                    LicenseType::Header => &mut headers,
+                    _ => &mut alternates,
+                };
+                self.set_variant_provenance(name, variant, *index, provenance_of(&metadata_path))?;
+                *index += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_directory() {
+        let dir = std::env::temp_dir().join(format!("askalono-directory-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("Internal-1.0.txt"),
+            "This software is for internal use only.",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("Internal-1.0.toml"),
+            "aliases = [\"Internal\"]\n\
+             headers = [\"Licensed under the Internal License.\"]\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("Partner-1.0.txt"),
+            "This software may be shared with partners.",
+        )
+        .unwrap();
+
+        let mut store = Store::new();
+        store.load_directory(&dir, false).unwrap();
+        assert_eq!(2, store.len());
+        assert_eq!(
+            &vec!["Internal".to_owned()],
+            store.aliases("Internal-1.0").unwrap()
+        );
+        assert!(store.aliases("Partner-1.0").unwrap().is_empty());
+        assert_eq!("directory", store.provenance("Partner-1.0").unwrap().source);
+
+        let matched = store.analyze(&"Licensed under the Internal License.".into());
+        assert_eq!("Internal-1.0", matched.name);
+        assert_eq!(LicenseType::Header, matched.license_type);
+        assert!(store
+            .variant_provenance("Internal-1.0", LicenseType::Header, 0)
+            .unwrap()
+            .path
+            .as_ref()
+            .unwrap()
+            .ends_with("Internal-1.0.toml"));
+
+        // typos in metadata aren't silently ignored
+        std::fs::write(dir.join("Partner-1.0.toml"), "alias = [\"Partner\"]\n").unwrap();
+        assert!(Store::new().load_directory(&dir, false).is_err());
+        std::fs::remove_file(dir.join("Partner-1.0.toml")).unwrap();
+        std::fs::write(dir.join("Missing-1.0.toml"), "").unwrap();
+        let result = Store::new().load_directory(&dir, false);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,425 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    cmp::Ordering,
+    collections::HashSet,
+    fs::File,
+    io::{prelude::*, BufReader, SeekFrom},
+    path::Path,
+    sync::{Mutex, OnceLock},
+};
+
+use anyhow::{format_err, Error};
+use rmp_serde::Serializer;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    license::{LicenseType, TextData},
+    ngram::Gram,
+    store::{
+        analyze::{Match, DEFAULT_AMBIGUITY_EPSILON},
+        base::{Store, DEFAULT_TYPE_PREFERENCE},
+    },
+};
+
+const DISK_VERSION: &[u8] = b"askalono-disk-01";
+
+/// The index at the head of a disk store, kept in memory while it's open.
+#[derive(Serialize, Deserialize)]
+struct DiskIndex {
+    stop_grams: HashSet<Gram>,
+    licenses: Vec<DiskLicense>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DiskLicense {
+    name: String,
+    family: Option<String>,
+    /// The original text, followed by alternates and then headers.
+    variants: Vec<DiskVariant>,
+}
+
+/// Where a license variant's `TextData` is in the file, and enough about it
+/// to decide whether it's worth reading.
+#[derive(Serialize, Deserialize)]
+struct DiskVariant {
+    kind: LicenseType,
+    label: Option<String>,
+    /// The number of n-grams in the variant.
+    grams: usize,
+    /// The position of the variant's data, from the end of the index.
+    offset: u64,
+    len: u64,
+}
+
+/// A store kept on disk, with license data read only as needed.
+///
+/// A `Store` holds every license in memory, which is the fastest way to
+/// match against a few thousand texts, but gets uncomfortable with datasets
+/// much larger than SPDX's. A disk store only keeps an index of names and
+/// sizes in memory. Create one from a `Store` with `Store::to_disk_store`,
+/// then open it with `DiskStore::open`.
+///
+/// `analyze` skips licenses whose size alone rules out beating the best
+/// matches found so far, so most of the file isn't read for a typical text.
+/// Only the license data returned in a `Match` (or by `get_original`) stays
+/// in memory.
+///
+/// Results are the same as `Store::analyze` with default settings, except
+/// that titles, clauses, exceptions, and the other refinements configured on a
+/// `Store` aren't applied, and analysis isn't parallelized.
+///
+/// # Examples
+///
+/// ```
+/// # use std::error::Error;
+/// use askalono::{DiskStore, Store, TextData};
+///
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// let mut store = Store::new();
+/// store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+/// let path = std::env::temp_dir().join("askalono-doc-disk-store.bin");
+/// store.to_disk_store(std::fs::File::create(&path)?)?;
+///
+/// let disk = DiskStore::open(&path)?;
+/// let matched = disk.analyze(&TextData::from("aaaaa bbbbb ccccc"))?;
+/// assert_eq!("license-1", matched.name);
+/// # std::fs::remove_file(&path)?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct DiskStore {
+    file: Mutex<File>,
+    /// The position in the file that variant offsets start from.
+    body_start: u64,
+    index: DiskIndex,
+    /// Variants that have been read and kept, by license and variant index.
+    resident: Vec<Vec<OnceLock<TextData>>>,
+}
+
+/// A variant scored during analysis: its score, and its license and
+/// variant index.
+type Scored = (f32, usize, usize);
+
+impl DiskStore {
+    /// Open a disk store written by `Store::to_disk_store`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<DiskStore, Error> {
+        let mut file = File::open(path)?;
+        let mut reader = BufReader::new(&mut file);
+
+        let mut header = [0u8; 16];
+        reader.read_exact(&mut header)?;
+        if header != DISK_VERSION {
+            anyhow::bail!(
+                "disk store version mismatch; expected '{}', found '{}'",
+                String::from_utf8_lossy(DISK_VERSION),
+                String::from_utf8_lossy(&header)
+            );
+        }
+        let mut len = [0u8; 8];
+        reader.read_exact(&mut len)?;
+        let len = u64::from_le_bytes(len);
+        let index: DiskIndex = rmp_serde::decode::from_read(reader.by_ref().take(len))?;
+        drop(reader);
+
+        let resident = index
+            .licenses
+            .iter()
+            .map(|license| license.variants.iter().map(|_| OnceLock::new()).collect())
+            .collect();
+        Ok(DiskStore {
+            file: Mutex::new(file),
+            body_start: (DISK_VERSION.len() + 8) as u64 + len,
+            index,
+            resident,
+        })
+    }
+
+    /// Get the number of licenses in the store.
+    pub fn len(&self) -> usize {
+        self.index.licenses.len()
+    }
+
+    /// Check if the store is empty.
+    pub fn is_empty(&self) -> bool {
+        self.index.licenses.is_empty()
+    }
+
+    /// Get all licenses by name via iterator.
+    pub fn licenses(&self) -> impl Iterator<Item = &str> {
+        self.index
+            .licenses
+            .iter()
+            .map(|license| license.name.as_str())
+    }
+
+    /// Get a license's standard TextData, reading it from disk if needed.
+    pub fn get_original(&self, name: &str) -> Result<Option<&TextData>, Error> {
+        match self.index.licenses.iter().position(|l| l.name == name) {
+            Some(i) => self.resident(i, 0).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Compare the given `TextData` against all licenses in the store. See
+    /// `Store::analyze`.
+    ///
+    /// Returns an error if the store is empty or can't be read.
+    pub fn analyze<'a>(&'a self, text: &TextData) -> Result<Match<'a>, Error> {
+        let input = text.match_data_excluding(&self.index.stop_grams);
+        let input = input.as_ref();
+
+        // the best score each variant could have, given its size
+        let mut candidates: Vec<Scored> = Vec::new();
+        for (i, license) in self.index.licenses.iter().enumerate() {
+            for (j, variant) in license.variants.iter().enumerate() {
+                let total = input.len() + variant.grams;
+                let bound = match total {
+                    0 => 0.0,
+                    _ => 2.0 * input.len().min(variant.grams) as f32 / total as f32,
+                };
+                candidates.push((bound, i, j));
+            }
+        }
+        candidates.sort_unstable_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+
+        let mut best: Option<Scored> = None;
+        let mut runner_up: Option<Scored> = None;
+        for (bound, i, j) in candidates {
+            // nothing left can beat the two best licenses
+            if runner_up.is_some_and(|r| bound < r.0) {
+                break;
+            }
+            let score = match self.resident[i][j].get() {
+                Some(data) => data.match_data.dice(input),
+                None => self.read_variant(i, j)?.match_data.dice(input),
+            };
+            let scored = (score, i, j);
+
+            match best {
+                Some(b) if self.compare(scored, b) != Ordering::Greater => {
+                    let better_runner_up =
+                        runner_up.is_none_or(|r| self.compare(scored, r) == Ordering::Greater);
+                    if i != b.1 && better_runner_up {
+                        runner_up = Some(scored);
+                    }
+                }
+                Some(b) => {
+                    if i != b.1 {
+                        runner_up = Some(b);
+                    }
+                    best = Some(scored);
+                }
+                None => best = Some(scored),
+            }
+        }
+
+        let (score, i, j) = best.ok_or_else(|| format_err!("disk store has no licenses"))?;
+        let license = &self.index.licenses[i];
+        let variant = &license.variants[j];
+        let mut matched = Match {
+            score,
+            name: &license.name,
+            license_type: variant.kind,
+            family: license.family.as_deref(),
+            variant_label: variant.label.as_deref(),
+            exception: None,
+            runner_up: runner_up.map(|r| self.index.licenses[r.1].name.as_str()),
+            runner_up_score: runner_up.map_or(0.0, |r| r.0),
+            ambiguous: false,
+            data: self.resident(i, j)?,
+        };
+        matched.ambiguous = matched.is_ambiguous(DEFAULT_AMBIGUITY_EPSILON);
+        Ok(matched)
+    }
+
+    /// Order scored variants as `Store::analyze` does: by score, then by
+    /// license type, then by name.
+    fn compare(&self, a: Scored, b: Scored) -> Ordering {
+        let rank = |(_, i, j): Scored| {
+            let kind = self.index.licenses[i].variants[j].kind;
+            DEFAULT_TYPE_PREFERENCE.iter().position(|&t| t == kind)
+        };
+        let name = |(_, i, _): Scored| &self.index.licenses[i].name;
+        a.0.partial_cmp(&b.0)
+            .unwrap()
+            .then_with(|| rank(b).cmp(&rank(a)))
+            .then_with(|| name(b).cmp(name(a)))
+    }
+
+    /// Get a variant's data, reading it and keeping it in memory if it
+    /// hasn't been already.
+    fn resident(&self, i: usize, j: usize) -> Result<&TextData, Error> {
+        let cell = &self.resident[i][j];
+        if let Some(data) = cell.get() {
+            return Ok(data);
+        }
+        let data = self.read_variant(i, j)?;
+        Ok(cell.get_or_init(|| data))
+    }
+
+    fn read_variant(&self, i: usize, j: usize) -> Result<TextData, Error> {
+        let variant = &self.index.licenses[i].variants[j];
+        let mut buf = vec![0u8; usize::try_from(variant.len)?];
+        {
+            let mut file = self.file.lock().unwrap();
+            file.seek(SeekFrom::Start(self.body_start + variant.offset))?;
+            file.read_exact(&mut buf)?;
+        }
+        Ok(rmp_serde::from_slice(&buf)?)
+    }
+}
+
+impl Store {
+    /// Write this store as a `DiskStore`, to be opened with
+    /// `DiskStore::open`.
+    ///
+    /// Texts are included if the store has them. Runtime preferences, titles,
+    /// clauses, and provenance are left out.
+    pub fn to_disk_store<W: Write>(&self, mut out: W) -> Result<(), Error> {
+        let mut names: Vec<&String> = self.licenses.keys().collect();
+        names.sort_unstable();
+
+        let mut body = Vec::new();
+        let mut licenses = Vec::with_capacity(names.len());
+        for name in names {
+            let entry = &self.licenses[name];
+            let variants =
+                std::iter::once((LicenseType::Original, None, &entry.original))
+                    .chain(
+                        entry.alternates.iter().enumerate().map(|(i, alt)| {
+                            (LicenseType::Alternate, entry.alternate_label(i), alt)
+                        }),
+                    )
+                    .chain(
+                        entry
+                            .headers
+                            .iter()
+                            .enumerate()
+                            .map(|(i, head)| (LicenseType::Header, entry.header_label(i), head)),
+                    );
+
+            let mut disk_variants = Vec::new();
+            for (kind, label, data) in variants {
+                let offset = body.len() as u64;
+                data.serialize(&mut Serializer::new(&mut body))?;
+                disk_variants.push(DiskVariant {
+                    kind,
+                    label: label.map(str::to_owned),
+                    grams: data.match_data.len(),
+                    offset,
+                    len: body.len() as u64 - offset,
+                });
+            }
+            licenses.push(DiskLicense {
+                name: name.clone(),
+                family: entry.family.clone(),
+                variants: disk_variants,
+            });
+        }
+
+        let mut index = Vec::new();
+        DiskIndex {
+            stop_grams: self.stop_grams.clone(),
+            licenses,
+        }
+        .serialize(&mut Serializer::new(&mut index))?;
+
+        out.write_all(DISK_VERSION)?;
+        out.write_all(&(index.len() as u64).to_le_bytes())?;
+        out.write_all(&index)?;
+        out.write_all(&body)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(store: &Store, name: &str) -> DiskStore {
+        let path =
+            std::env::temp_dir().join(format!("askalono-{}-{}.bin", name, std::process::id()));
+        store.to_disk_store(File::create(&path).unwrap()).unwrap();
+        let disk = DiskStore::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        disk
+    }
+
+    #[test]
+    fn same_as_store() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa\nbbbbb\nccccc".into());
+        store.add_license("license-1b".into(), "aaaaa\nbbbbb\nccccc\nddddd".into());
+        store.add_license(
+            "license-2".into(),
+            "1234 5678 1234\n0000\n1010101010\n\n8888 9999".into(),
+        );
+        store
+            .add_variant_labeled(
+                "license-2",
+                LicenseType::Header,
+                "1234 5678 1234".into(),
+                Some("short".into()),
+            )
+            .unwrap();
+        store
+            .set_family("license-1b", Some("license-1".into()))
+            .unwrap();
+        let disk = open(&store, "same-as-store");
+        assert_eq!(3, disk.len());
+
+        for text in [
+            "aaaaa\nbbbbb\nccccc",
+            "aaaaa\nbbbbb\nccccc\nddddd\neeeee",
+            "1234 5678 1234",
+            "lorem ipsum 1234 5678 1234 0000",
+        ] {
+            let text = TextData::from(text);
+            let expected = store.analyze(&text);
+            let matched = disk.analyze(&text).unwrap();
+            assert_eq!(expected.name, matched.name);
+            assert_eq!(expected.score, matched.score);
+            assert_eq!(expected.license_type, matched.license_type);
+            assert_eq!(expected.variant_label, matched.variant_label);
+            assert_eq!(expected.family, matched.family);
+            assert_eq!(expected.runner_up, matched.runner_up);
+            assert_eq!(expected.runner_up_score, matched.runner_up_score);
+            assert_eq!(expected.matched_text(), matched.matched_text());
+        }
+
+        let original = disk.get_original("license-1").unwrap().unwrap();
+        assert_eq!(Some("aaaaa bbbbb ccccc"), original.text_processed());
+        assert!(disk.get_original("license-3").unwrap().is_none());
+    }
+
+    #[test]
+    fn skips_what_cant_match() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+        store.add_license("license-2".into(), "aaaaa bbbbb ddddd".into());
+        let long = "lorem ipsum dolor sit amet ".repeat(50);
+        store.add_license("license-3".into(), long.as_str().into());
+
+        // cut off the long license, which is written last; reading it fails
+        let mut data = Vec::new();
+        store.to_disk_store(&mut data).unwrap();
+        let path = std::env::temp_dir().join(format!("askalono-skips-{}.bin", std::process::id()));
+        std::fs::write(&path, &data[..data.len() - 10]).unwrap();
+        let disk = DiskStore::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let matched = disk.analyze(&"aaaaa bbbbb ccccc".into()).unwrap();
+        assert_eq!("license-1", matched.name);
+        assert_eq!(Some("license-2"), matched.runner_up);
+        assert!(disk.analyze(&long.as_str().into()).is_err());
+    }
+
+    #[test]
+    fn empty() {
+        let disk = open(&Store::new(), "empty");
+        assert!(disk.is_empty());
+        assert!(disk.analyze(&"aaaaa".into()).is_err());
+    }
+}
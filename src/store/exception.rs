@@ -0,0 +1,74 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{license::TextData, ngram::NgramSet, store::base::Store};
+
+/// How much of an exception must appear in a text for it to be found.
+const EXCEPTION_PRESENT: f32 = 0.9;
+
+impl Store {
+    /// Add the text of a license exception (such as
+    /// `Classpath-exception-2.0`) to the store.
+    ///
+    /// Exceptions aren't licenses of their own, and are never the result of
+    /// an analysis. Instead, when nearly all of an exception's text appears
+    /// in a text being analyzed, it's reported alongside whichever license
+    /// matched; see `Match::exception`. An exception with the same name is
+    /// replaced.
+    pub fn add_exception(&mut self, name: String, mut data: TextData) {
+        self.prepare_data(&mut data);
+        self.exceptions.insert(name, data);
+        self.forget_derived();
+    }
+
+    /// Get all exceptions by name via iterator. See `add_exception`.
+    pub fn exceptions(&self) -> impl Iterator<Item = &String> {
+        self.exceptions.keys()
+    }
+
+    /// Find the exception most completely contained in a text, if any is
+    /// contained enough to count.
+    pub(crate) fn find_exception(&self, text: &TextData) -> Option<&str> {
+        if self.exceptions.is_empty() {
+            return None;
+        }
+        let input = text.match_data_excluding(&self.stop_grams);
+        let input: &NgramSet = &input;
+        self.exceptions
+            .iter()
+            .map(|(name, data)| (name, data.match_data.containment(input)))
+            .filter(|&(_, present)| present >= EXCEPTION_PRESENT)
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap().then_with(|| b.0.cmp(a.0)))
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{license::TextData, store::base::Store};
+
+    #[test]
+    fn exceptions() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc ddddd eeeee".into());
+        store.add_exception("exception-1".into(), "fffff ggggg hhhhh".into());
+        store.add_exception("exception-2".into(), "iiiii jjjjj kkkkk".into());
+        assert_eq!(vec!["exception-1", "exception-2"], {
+            let mut names: Vec<_> = store.exceptions().collect();
+            names.sort();
+            names
+        });
+
+        let matched = store.analyze(&TextData::from("aaaaa bbbbb ccccc ddddd eeeee"));
+        assert_eq!(None, matched.exception);
+
+        let text = TextData::from("aaaaa bbbbb ccccc ddddd eeeee\n\nfffff ggggg hhhhh");
+        let matched = store.analyze(&text);
+        assert_eq!("license-1", matched.name);
+        assert_eq!(Some("exception-1"), matched.exception);
+
+        // only part of an exception isn't enough
+        let text = TextData::from("aaaaa bbbbb ccccc ddddd eeeee\n\niiiii jjjjj");
+        assert_eq!(None, store.analyze(&text).exception);
+    }
+}
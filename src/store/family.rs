@@ -0,0 +1,75 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+/// Well-known families whose SPDX identifiers don't follow the general
+/// `FAMILY-version` pattern, checked in order.
+const FAMILY_PREFIXES: &[(&str, &str)] = &[
+    ("0BSD", "BSD"),
+    ("CC0-", "CC"),
+    ("CC-", "CC"),
+    ("AGPL-", "AGPL"),
+    ("LGPL-", "LGPL"),
+    ("GPL-", "GPL"),
+    ("GFDL-", "GFDL"),
+    ("BSD-", "BSD"),
+    ("MIT-", "MIT"),
+];
+
+/// Derive a license family from an SPDX license identifier.
+///
+/// Families group related licenses (every version and variant of the GPL,
+/// every BSD clause count, and so on). Identifiers without an obvious family
+/// use their leading component, with any version numbers removed -- so
+/// `Apache-2.0` is in the `Apache` family, and `Zlib` is its own family.
+/// `LicenseRef-` and `DocumentRef-` identifiers have no family.
+///
+/// Only use this for identifiers known to be on the SPDX license list;
+/// arbitrary names would get arbitrary families.
+pub(crate) fn derive_family(id: &str) -> Option<String> {
+    if id.starts_with("LicenseRef-") || id.starts_with("DocumentRef-") {
+        return None;
+    }
+    for (prefix, family) in FAMILY_PREFIXES {
+        if id.starts_with(prefix) {
+            return Some((*family).to_owned());
+        }
+    }
+
+    let leading = id.split('-').next()?;
+    let family = leading.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    if family.is_empty() {
+        return None;
+    }
+    Some(family.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spdx_families() {
+        for (id, family) in &[
+            ("GPL-2.0-only", "GPL"),
+            ("GPL-3.0-or-later", "GPL"),
+            ("LGPL-2.1-only", "LGPL"),
+            ("AGPL-3.0-only", "AGPL"),
+            ("BSD-3-Clause", "BSD"),
+            ("0BSD", "BSD"),
+            ("CC-BY-SA-4.0", "CC"),
+            ("CC0-1.0", "CC"),
+            ("MPL-2.0", "MPL"),
+            ("Apache-2.0", "Apache"),
+            ("MIT", "MIT"),
+            ("MIT-0", "MIT"),
+            ("EPL-1.0", "EPL"),
+            ("OLDAP2.8", "OLDAP"),
+            ("Zlib", "Zlib"),
+        ] {
+            assert_eq!(Some(family.to_string()), derive_family(id), "{}", id);
+        }
+
+        assert_eq!(None, derive_family("2.0"));
+        assert_eq!(None, derive_family("LicenseRef-scancode-acme"));
+    }
+}
@@ -0,0 +1,282 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    iter,
+};
+
+use crate::{
+    license::TextData,
+    store::{base::Store, options::AnalysisOptions},
+};
+
+/// The most edits `AnalysisOptions::fuzzy_tokens` allows. Beyond this,
+/// unrelated words start to look alike, and the index grows quickly.
+pub(crate) const MAX_FUZZY_DISTANCE: u32 = 2;
+
+/// The words of a store's licenses, indexed to find near misses of unknown
+/// words.
+///
+/// Any two words within `d` edits of each other can both be reduced to some
+/// common string by deleting at most `d` characters from each, so every
+/// word is indexed under the strings its deletions produce. Looking up a
+/// word's own deletions then finds every candidate without comparing
+/// against the whole vocabulary.
+#[derive(Clone, Default)]
+pub(crate) struct Vocabulary {
+    max_distance: u32,
+    /// Known words, most common first.
+    words: Vec<String>,
+    known: HashSet<String>,
+    /// Deletion variants of known words, mapped to their indexes in `words`.
+    deletions: HashMap<String, Vec<u32>>,
+}
+
+impl Vocabulary {
+    fn new(store: &Store, max_distance: u32) -> Vocabulary {
+        // count how many n-grams each word appears in, to break ties
+        let mut counts: HashMap<&str, u32> = HashMap::new();
+        for entry in store.licenses.values() {
+            let texts = iter::once(&entry.original)
+                .chain(&entry.headers)
+                .chain(&entry.alternates);
+            for data in texts {
+                for (gram, _) in &data.match_data {
+                    let (first, second) = gram.words();
+                    for word in [first, second].into_iter().filter(|word| !word.is_empty()) {
+                        *counts.entry(word).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+
+        let mut words: Vec<(&str, u32)> = counts.into_iter().collect();
+        words.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        let words: Vec<String> = words.into_iter().map(|(word, _)| word.to_owned()).collect();
+
+        let mut deletions: HashMap<String, Vec<u32>> = HashMap::new();
+        let mut variants = HashSet::new();
+        for (id, word) in words.iter().enumerate() {
+            variants.clear();
+            let chars: Vec<char> = word.chars().collect();
+            add_deletions(&chars, max_distance, &mut variants);
+            for variant in variants.drain() {
+                deletions.entry(variant).or_default().push(id as u32);
+            }
+        }
+
+        Vocabulary {
+            max_distance,
+            known: words.iter().cloned().collect(),
+            words,
+            deletions,
+        }
+    }
+
+    /// Find the known word closest to an unknown token, if any is close
+    /// enough. Returns `None` for known tokens.
+    fn correct(&self, token: &str) -> Option<&str> {
+        if self.known.contains(token) || !token.chars().all(char::is_alphabetic) {
+            return None;
+        }
+
+        // one edit per three characters; short words are too easily confused
+        let chars: Vec<char> = token.chars().collect();
+        let allowed = (chars.len().saturating_sub(1) / 3).min(self.max_distance as usize) as u32;
+        if allowed == 0 {
+            return None;
+        }
+
+        let mut variants = HashSet::new();
+        add_deletions(&chars, allowed, &mut variants);
+        let mut best: Option<(u32, u32)> = None;
+        for id in variants
+            .iter()
+            .filter_map(|variant| self.deletions.get(variant))
+            .flatten()
+        {
+            let word: Vec<char> = self.words[*id as usize].chars().collect();
+            if let Some(distance) = edit_distance(&chars, &word, allowed) {
+                // closest first, then most common
+                if best.is_none_or(|best| (distance, *id) < best) {
+                    best = Some((distance, *id));
+                }
+            }
+        }
+        best.map(|(_, id)| self.words[id as usize].as_str())
+    }
+}
+
+/// Add every string reachable from `word` by deleting up to `max`
+/// characters, including `word` itself.
+fn add_deletions(word: &[char], max: u32, out: &mut HashSet<String>) {
+    if !out.insert(word.iter().collect()) || max == 0 {
+        return;
+    }
+    for i in 0..word.len() {
+        let mut shorter = word.to_vec();
+        shorter.remove(i);
+        add_deletions(&shorter, max - 1, out);
+    }
+}
+
+/// Count the insertions, deletions, substitutions, and swaps of adjacent
+/// characters needed to turn `a` into `b` (the optimal string alignment
+/// distance), or return `None` if it's more than `max`.
+fn edit_distance(a: &[char], b: &[char], max: u32) -> Option<u32> {
+    if a.len().abs_diff(b.len()) > max as usize {
+        return None;
+    }
+
+    // three rows of the usual dynamic programming table
+    let mut before: Vec<u32> = vec![0; b.len() + 1];
+    let mut previous: Vec<u32> = (0..=b.len() as u32).collect();
+    let mut current: Vec<u32> = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        current[0] = i as u32;
+        for j in 1..=b.len() {
+            let cost = u32::from(a[i - 1] != b[j - 1]);
+            let mut distance = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(before[j - 2] + 1);
+            }
+            current[j] = distance;
+        }
+        if current.iter().all(|&distance| distance > max) {
+            return None;
+        }
+        std::mem::swap(&mut before, &mut previous);
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    Some(previous[b.len()]).filter(|&distance| distance <= max)
+}
+
+impl Store {
+    /// Get the vocabulary for correcting words by up to `max_distance`
+    /// edits, collecting it from the store's licenses if it hasn't been
+    /// already.
+    fn vocabulary(&self, max_distance: u32) -> &Vocabulary {
+        self.fuzzy_vocabularies[max_distance as usize - 1]
+            .get_or_init(|| Vocabulary::new(self, max_distance))
+    }
+
+    /// Get the given text with unknown words corrected, if fuzzy tokens are
+    /// enabled. See `AnalysisOptions::fuzzy_tokens`.
+    pub(crate) fn fuzzy_corrected<'t>(
+        &self,
+        text: &'t TextData,
+        options: &AnalysisOptions,
+    ) -> Cow<'t, TextData> {
+        let vocabulary = match options.fuzzy_tokens {
+            Some(max_distance) => self.vocabulary(max_distance),
+            None => return Cow::Borrowed(text),
+        };
+
+        let mut corrections: HashMap<String, Option<&str>> = HashMap::new();
+        let corrected = text.replacing_tokens(|token| {
+            if let Some(&correction) = corrections.get(token) {
+                return correction;
+            }
+            let correction = vocabulary.correct(token);
+            corrections.insert(token.to_owned(), correction);
+            correction
+        });
+        match corrected {
+            Cow::Borrowed(_) => Cow::Borrowed(text),
+            Cow::Owned(corrected) => Cow::Owned(corrected),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distance(a: &str, b: &str, max: u32) -> Option<u32> {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+        edit_distance(&a, &b, max)
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(Some(0), distance("license", "license", 2));
+        assert_eq!(Some(1), distance("sofware", "software", 2));
+        assert_eq!(Some(2), distance("perrnission", "permission", 2));
+        assert_eq!(Some(1), distance("teh", "the", 2));
+        assert_eq!(Some(1), distance("licence", "license", 2));
+        assert_eq!(None, distance("warranty", "liability", 2));
+        assert_eq!(None, distance("a", "abcd", 2));
+    }
+
+    #[test]
+    fn corrects_unknown_words() {
+        let mut store = Store::new();
+        store.add_license(
+            "license-1".into(),
+            "permission is hereby granted to use this software".into(),
+        );
+        store.add_license(
+            "license-2".into(),
+            "the software is provided without warranty".into(),
+        );
+        let vocabulary = store.vocabulary(2);
+
+        assert_eq!(Some("permission"), vocabulary.correct("perrnission"));
+        assert_eq!(Some("software"), vocabulary.correct("sofware"));
+        assert_eq!(Some("warranty"), vocabulary.correct("warrenty"));
+        assert_eq!(None, vocabulary.correct("software"));
+        assert_eq!(None, vocabulary.correct("unrelated"));
+        // too short, and not a word
+        assert_eq!(None, vocabulary.correct("thi"));
+        assert_eq!(None, vocabulary.correct("s0ftware"));
+    }
+
+    #[test]
+    fn analyze_noisy_text() {
+        let mut store = Store::new();
+        store.add_license(
+            "license-1".into(),
+            "permission is hereby granted free of charge to any person obtaining a copy of \
+             this software"
+                .into(),
+        );
+        store.add_license(
+            "license-2".into(),
+            "the software is provided as is without warranty of any kind".into(),
+        );
+
+        let text = TextData::from(
+            "perrnission is hereby granted free of charqe to any persen obtaining a copy of \
+             this sofware",
+        );
+        let before = store.analyze(&text);
+        assert_eq!("license-1", before.name);
+        let before = before.score;
+
+        let options = AnalysisOptions {
+            fuzzy_tokens: Some(2),
+            ..Default::default()
+        };
+        let after = store.analyze_with(&text, &options).unwrap();
+        assert_eq!("license-1", after.name);
+        assert!(after.score > 0.99, "{}", after.score);
+        assert!(after.score > before);
+
+        // the vocabulary follows later changes to the store
+        store.add_license(
+            "license-3".into(),
+            "perrnission is hereby granted free of charqe to any persen obtaining a copy of \
+             this sofware"
+                .into(),
+        );
+        let matched = store.analyze_with(&text, &options).unwrap();
+        assert_eq!("license-3", matched.name);
+        assert_eq!(1.0, matched.score);
+    }
+}
@@ -0,0 +1,130 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{format_err, Error};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::store::base::Store;
+
+/// Options for filling in a license header with `Store::suggest_header`.
+///
+/// # Examples
+///
+/// ```
+/// use askalono::HeaderOptions;
+///
+/// let options = HeaderOptions {
+///     year: Some("2024".into()),
+///     owner: Some("Jane Doe".into()),
+///     comment_prefix: Some("// ".into()),
+/// };
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct HeaderOptions {
+    /// Replaces year placeholders such as `[yyyy]` or `<year>`.
+    pub year: Option<String>,
+    /// Replaces copyright owner placeholders such as `[name of copyright
+    /// owner]` or `<owner>`.
+    pub owner: Option<String>,
+    /// Prepended to every line, to turn the header into a source comment.
+    pub comment_prefix: Option<String>,
+}
+
+impl HeaderOptions {
+    /// Fill in a header template.
+    fn apply(&self, template: &str) -> String {
+        lazy_static! {
+            static ref YEAR: Regex = Regex::new(r"(?i)[<\[](yyyy|year)[>\]]").unwrap();
+            static ref OWNER: Regex = Regex::new(
+                r"(?i)[<\[](name of (the )?(copyright owner|author)|owner|copyright holders?|author|fullname)[>\]]"
+            )
+            .unwrap();
+        }
+
+        let mut text = template.trim_end().to_owned();
+        if let Some(ref year) = self.year {
+            text = YEAR.replace_all(&text, regex::NoExpand(year)).into_owned();
+        }
+        if let Some(ref owner) = self.owner {
+            text = OWNER
+                .replace_all(&text, regex::NoExpand(owner))
+                .into_owned();
+        }
+
+        match self.comment_prefix {
+            Some(ref prefix) => text
+                .lines()
+                .map(|line| format!("{}{}", prefix, line).trim_end().to_owned())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            None => text,
+        }
+    }
+}
+
+impl Store {
+    /// Get the standard header of a license, ready to be placed at the top of
+    /// a source file.
+    ///
+    /// Headers come from SPDX's `standardLicenseHeader` data (or
+    /// `set_header_template`), with year and owner placeholders filled in and
+    /// comment prefixes added according to `options`. Returns `None` if the
+    /// license isn't in the store or has no standard header.
+    pub fn suggest_header(&self, name: &str, options: &HeaderOptions) -> Option<String> {
+        let template = self.licenses.get(name)?.header_template.as_ref()?;
+        Some(options.apply(template))
+    }
+
+    /// Set the verbatim header template of a license, used by
+    /// `suggest_header`.
+    pub fn set_header_template(&mut self, name: &str, template: String) -> Result<(), Error> {
+        let entry = self
+            .licenses
+            .get_mut(name)
+            .ok_or_else(|| format_err!("license {} not present in store", name))?;
+        entry.header_template = Some(template);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_header() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+        assert_eq!(
+            None,
+            store.suggest_header("license-1", &HeaderOptions::default())
+        );
+
+        let template = "Copyright [yyyy] [name of copyright owner]\n\nLicensed under License 1.\n";
+        store
+            .set_header_template("license-1", template.to_owned())
+            .unwrap();
+        assert_eq!(
+            Some(template.trim_end().to_owned()),
+            store.suggest_header("license-1", &HeaderOptions::default())
+        );
+
+        let options = HeaderOptions {
+            year: Some("2024".into()),
+            owner: Some("Jane $1 Doe".into()),
+            comment_prefix: Some("# ".into()),
+        };
+        assert_eq!(
+            "# Copyright 2024 Jane $1 Doe\n#\n# Licensed under License 1.",
+            store.suggest_header("license-1", &options).unwrap()
+        );
+
+        assert_eq!(
+            "Copyright (C) 2024 Jane $1 Doe",
+            options
+                .apply("Copyright (C) <year> <name of author>")
+                .trim_start_matches("# ")
+        );
+    }
+}
@@ -0,0 +1,125 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ngram::Gram, store::base::Store};
+
+/// Inverse document frequencies of the n-grams in a store's licenses; see
+/// `Store::set_idf_weighting`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub(crate) struct IdfWeights {
+    weights: HashMap<Gram, f32>,
+    /// The weight of n-grams found in no license.
+    pub(crate) unseen: f32,
+}
+
+impl IdfWeights {
+    pub(crate) fn weight(&self, gram: Gram) -> f32 {
+        self.weights.get(&gram).copied().unwrap_or(self.unseen)
+    }
+}
+
+impl Store {
+    /// Weight n-grams by how rare they are among the store's licenses when
+    /// scoring.
+    ///
+    /// Boilerplate such as "of the" or "the software" appears in most
+    /// licenses, so it makes up much of the Dice score between any two of
+    /// them, and unrelated permissive licenses score higher against each
+    /// other than they should. With weighting on, each n-gram counts in
+    /// proportion to its smoothed inverse document frequency,
+    /// `ln((1 + licenses) / (1 + licenses containing it)) + 1`, so the
+    /// phrases that set licenses apart count for more. Only the original
+    /// text of each license is counted; n-grams in none of them get the
+    /// highest weight.
+    ///
+    /// Weights are computed from the licenses in the store when this is
+    /// called, so call it after adding licenses; `update_from_spdx` computes
+    /// them again itself. They're kept in the cache, which grows somewhat.
+    /// Weighting only applies to the default Dice scorer (see
+    /// `AnalysisOptions::scorer`), and not when optimizing a match's bounds.
+    ///
+    /// Weights are kept by n-gram, so a store with hashed n-grams (see
+    /// `hash_grams`) can't be weighted, and this does nothing.
+    pub fn set_idf_weighting(&mut self, enabled: bool) {
+        if !enabled || self.hashed_grams {
+            self.idf = None;
+            return;
+        }
+
+        let mut frequencies: HashMap<Gram, u32> = HashMap::new();
+        for entry in self.licenses.values() {
+            for (gram, _) in &entry.original.match_data {
+                *frequencies.entry(gram).or_insert(0) += 1;
+            }
+        }
+        let total = self.licenses.len() as f32;
+        let idf = |frequency: u32| ((1.0 + total) / (1.0 + frequency as f32)).ln() + 1.0;
+        let weights = frequencies
+            .into_iter()
+            .map(|(gram, frequency)| (gram, idf(frequency)))
+            .collect();
+        self.idf = Some(IdfWeights {
+            weights,
+            unseen: idf(0),
+        });
+    }
+
+    /// Whether n-grams are weighted when scoring. See `set_idf_weighting`.
+    pub fn idf_weighting(&self) -> bool {
+        self.idf.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::license::TextData;
+
+    fn create_store() -> Store {
+        let boilerplate = "permission is granted to use the software provided that";
+        let mut store = Store::new();
+        for (name, terms) in [
+            ("license-1", "aaaaa bbbbb ccccc"),
+            ("license-2", "ddddd eeeee fffff"),
+            ("license-3", "ggggg hhhhh iiiii"),
+        ] {
+            store.add_license(name.into(), format!("{} {}", boilerplate, terms).into());
+        }
+        store
+    }
+
+    #[test]
+    fn idf_weighting() {
+        let text = TextData::from(
+            "permission is granted to use the software provided that aaaaa bbbbb ccccc",
+        );
+        let mut store = create_store();
+        assert!(!store.idf_weighting());
+        let plain = store.analyze(&text);
+        assert_eq!("license-1", plain.name);
+        let plain_runner_up = plain.runner_up_score;
+
+        store.set_idf_weighting(true);
+        assert!(store.idf_weighting());
+        let weighted = store.analyze(&text);
+        assert_eq!("license-1", weighted.name);
+        assert!((weighted.score - 1.0).abs() < 1e-6);
+        // shared boilerplate counts for less
+        let weighted_runner_up = weighted.runner_up_score;
+        assert!(weighted_runner_up < plain_runner_up);
+
+        // and it's kept in the cache
+        let mut buf = Vec::new();
+        store.to_cache(&mut buf).unwrap();
+        let loaded = Store::from_cache(&buf[..]).unwrap();
+        assert!(loaded.idf_weighting());
+        assert_eq!(weighted_runner_up, loaded.analyze(&text).runner_up_score);
+
+        store.set_idf_weighting(false);
+        assert_eq!(plain_runner_up, store.analyze(&text).runner_up_score);
+    }
+}
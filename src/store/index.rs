@@ -0,0 +1,279 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ngram::{Gram, NgramSet},
+    store::base::Store,
+};
+
+/// An inverted index from n-grams to the license variants containing them,
+/// used to shortlist candidates in `analyze`; see
+/// `Store::set_candidate_index`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub(crate) struct GramIndex {
+    /// Indexed license names, sorted.
+    names: Vec<String>,
+    /// Every indexed variant, as its license's position in `names` and its
+    /// number of distinct n-grams.
+    variants: Vec<(u32, u32)>,
+    /// Positions in `variants` of the variants containing each n-gram.
+    postings: HashMap<Gram, Vec<u32>>,
+    /// How many licenses to shortlist.
+    candidates: usize,
+}
+
+impl GramIndex {
+    /// Whether the index covers the named license.
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.names
+            .binary_search_by(|n| n.as_str().cmp(name))
+            .is_ok()
+    }
+
+    /// Get the names of the licenses with a variant most likely to score
+    /// well against the input, best first.
+    ///
+    /// Variants are estimated by the Dice coefficient of their distinct
+    /// n-grams with the input's, which only takes a pass over the postings
+    /// of the input's n-grams.
+    pub(crate) fn shortlist(&self, input: &NgramSet) -> Vec<&str> {
+        let best = self.estimates(input);
+        let mut order: Vec<usize> = (0..self.names.len()).collect();
+        order.sort_by(|&a, &b| best[b].partial_cmp(&best[a]).unwrap().then(a.cmp(&b)));
+        order
+            .into_iter()
+            .take(self.candidates)
+            .map(|i| self.names[i].as_str())
+            .collect()
+    }
+
+    /// Get the name of the license with the best estimate, skipping those
+    /// `skip` accepts. Used to find a runner-up among the licenses
+    /// `shortlist` left out.
+    pub(crate) fn nearest(&self, input: &NgramSet, skip: impl Fn(&str) -> bool) -> Option<&str> {
+        let best = self.estimates(input);
+        let mut nearest: Option<usize> = None;
+        for (i, name) in self.names.iter().enumerate() {
+            if !skip(name) && nearest.is_none_or(|n| best[i] > best[n]) {
+                nearest = Some(i);
+            }
+        }
+        nearest.map(|i| self.names[i].as_str())
+    }
+
+    /// Estimate how well each license's best variant scores against the
+    /// input, by position in `names`.
+    fn estimates(&self, input: &NgramSet) -> Vec<f32> {
+        let mut shared = vec![0u32; self.variants.len()];
+        for (gram, _) in input {
+            if let Some(ids) = self.postings.get(&gram) {
+                for &id in ids {
+                    shared[id as usize] += 1;
+                }
+            }
+        }
+
+        let mut best = vec![0f32; self.names.len()];
+        for (&(license, size), &shared) in self.variants.iter().zip(&shared) {
+            let total = (size as usize + input.len()).max(1);
+            let estimate = 2.0 * shared as f32 / total as f32;
+            let best = &mut best[license as usize];
+            *best = best.max(estimate);
+        }
+        best
+    }
+}
+
+impl Store {
+    /// Have `analyze` shortlist candidates from an inverted n-gram index
+    /// instead of scoring every license. `None` removes the index.
+    ///
+    /// The index maps each n-gram to the license variants containing it. With
+    /// it, `analyze` first estimates every variant from the postings of the
+    /// text's n-grams alone, then fully scores only the `candidates` licenses
+    /// with the best estimates, along with any named by the text's title.
+    /// For large stores this is much faster. Estimates ignore how often each
+    /// n-gram occurs, so a license that would have won can occasionally miss
+    /// the shortlist when `candidates` is very small; a few dozen is plenty
+    /// in practice.
+    ///
+    /// The index is built from the licenses in the store when this is
+    /// called, so call it after adding licenses; `update_from_spdx` builds it
+    /// again itself, as does changing or removing a license it covers, and
+    /// licenses added later are always scored. It's kept in
+    /// the cache, which grows somewhat. Only `analyze` uses the index;
+    /// `analyze_top_n` and `match_iter` still score every license.
+    ///
+    /// The index is kept by n-gram, so a store with hashed n-grams (see
+    /// `hash_grams`) can't be indexed, and this does nothing.
+    pub fn set_candidate_index(&mut self, candidates: Option<usize>) {
+        let candidates = match candidates {
+            Some(candidates) if !self.hashed_grams => candidates,
+            _ => {
+                self.index = None;
+                return;
+            }
+        };
+
+        let mut names: Vec<&String> = self.licenses.keys().collect();
+        names.sort_unstable();
+        let mut variants = Vec::new();
+        let mut postings: HashMap<Gram, Vec<u32>> = HashMap::new();
+        for (license, name) in names.iter().enumerate() {
+            let entry = &self.licenses[*name];
+            let texts = std::iter::once(&entry.original)
+                .chain(&entry.alternates)
+                .chain(&entry.headers);
+            for data in texts {
+                let id = variants.len() as u32;
+                variants.push((license as u32, data.match_data.len() as u32));
+                for (gram, _) in &data.match_data {
+                    postings.entry(gram).or_default().push(id);
+                }
+            }
+        }
+
+        self.index = Some(GramIndex {
+            names: names.into_iter().cloned().collect(),
+            variants,
+            postings,
+            candidates,
+        });
+    }
+
+    /// How many licenses `analyze` shortlists, if it uses an index. See
+    /// `set_candidate_index`.
+    pub fn candidate_index(&self) -> Option<usize> {
+        self.index.as_ref().map(|index| index.candidates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::license::TextData;
+
+    fn create_store() -> Store {
+        let words = [
+            "aaaaa", "bbbbb", "ccccc", "ddddd", "eeeee", "fffff", "ggggg", "hhhhh", "iiiii",
+            "jjjjj",
+        ];
+        let mut store = Store::new();
+        for (i, word) in words.iter().enumerate() {
+            let text = format!(
+                "permission is granted {} provided that {} {}",
+                word, i, word
+            );
+            store.add_license(format!("license-{}", i), text.as_str().into());
+        }
+        store
+            .add_variant(
+                "license-3",
+                crate::LicenseType::Header,
+                "this file is licensed under ddddd".into(),
+            )
+            .unwrap();
+        store
+    }
+
+    #[test]
+    fn candidate_index() {
+        let texts = [
+            "permission is granted ddddd provided that 3 ddddd",
+            "this file is licensed under ddddd",
+            "permission is granted provided that hhhhh",
+            "entirely unrelated words",
+        ];
+        let mut store = create_store();
+        assert_eq!(None, store.candidate_index());
+        let linear: Vec<_> = texts
+            .iter()
+            .map(|t| {
+                let m = store.analyze(&TextData::from(*t));
+                (m.name.to_owned(), m.license_type, m.score)
+            })
+            .collect();
+
+        store.set_candidate_index(Some(2));
+        assert_eq!(Some(2), store.candidate_index());
+        let index = store.index.as_ref().unwrap();
+        assert!(index.contains("license-3"));
+        assert!(!index.contains("license-10"));
+        let input = TextData::from(texts[1]);
+        assert_eq!("license-3", index.shortlist(&input.match_data)[0]);
+
+        for (text, expected) in texts.iter().zip(&linear) {
+            let m = store.analyze(&TextData::from(*text));
+            assert_eq!(expected, &(m.name.to_owned(), m.license_type, m.score));
+        }
+
+        // licenses added after indexing are still scored
+        store.add_license("license-10".into(), "something else entirely".into());
+        assert_eq!(
+            "license-10",
+            store.analyze(&"something else entirely".into()).name
+        );
+
+        // and it's kept in the cache
+        let mut buf = Vec::new();
+        store.to_cache(&mut buf).unwrap();
+        let loaded = Store::from_cache(&buf[..]).unwrap();
+        assert_eq!(Some(2), loaded.candidate_index());
+
+        store.set_candidate_index(None);
+        assert_eq!(None, store.candidate_index());
+    }
+
+    #[test]
+    fn changes_after_indexing() {
+        let mut store = create_store();
+        store.set_candidate_index(Some(1));
+
+        store.add_license("license-1".into(), "one two three four five six".into());
+        let text = TextData::from("one two three four five six");
+        let m = store.analyze(&text);
+        assert_eq!("license-1", m.name);
+        // the best of the licenses left out is still scored as a runner-up
+        assert!(m.runner_up.is_some());
+
+        store
+            .replace_license("license-2", "seven eight nine ten eleven".into())
+            .unwrap();
+        let text = TextData::from("seven eight nine ten eleven");
+        assert_eq!("license-2", store.analyze(&text).name);
+
+        store
+            .add_variant(
+                "license-4",
+                crate::LicenseType::Alternate,
+                "twelve thirteen fourteen fifteen".into(),
+            )
+            .unwrap();
+        let text = TextData::from("twelve thirteen fourteen fifteen");
+        let m = store.analyze(&text);
+        assert_eq!(
+            ("license-4", crate::LicenseType::Alternate),
+            (m.name, m.license_type)
+        );
+
+        store
+            .remove_variant("license-4", crate::LicenseType::Alternate, 0)
+            .unwrap();
+        store.remove_license("license-5").unwrap();
+        let index = store.index.as_ref().unwrap();
+        assert!(!index.contains("license-5"));
+        assert_eq!(
+            "license-4",
+            index.shortlist(&TextData::from("permission is granted eeeee").match_data)[0]
+        );
+        assert!(index
+            .shortlist(&text.match_data)
+            .iter()
+            .all(|&name| name != "license-4"));
+        assert_eq!(Some(1), store.candidate_index());
+    }
+}
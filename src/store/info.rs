@@ -0,0 +1,63 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::Serialize;
+
+use crate::store::base::Store;
+
+/// A summary of what a `Store` holds and where it came from. See
+/// `Store::info`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct StoreInfo {
+    /// The version of the SPDX license list the store was last filled from
+    /// with `load_spdx` or `update_from_spdx`, if known.
+    pub list_version: Option<String>,
+    /// When the cache the store was loaded from was written, in seconds since
+    /// the Unix epoch. `None` for stores that weren't loaded from a cache (or
+    /// whose cache was written before this was recorded, or on wasm32).
+    pub built_at: Option<u64>,
+    /// The number of licenses in the store. See `Store::len`.
+    pub licenses: usize,
+}
+
+impl Store {
+    /// Get a summary of the store's dataset, so that results can be traced
+    /// back to it.
+    ///
+    /// The list version and build time are kept in the cache; `to_cache`
+    /// records the time it's called.
+    pub fn info(&self) -> StoreInfo {
+        StoreInfo {
+            list_version: self.list_version.clone(),
+            built_at: self.built_at,
+            licenses: self.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn info() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+        assert_eq!(
+            StoreInfo {
+                list_version: None,
+                built_at: None,
+                licenses: 1,
+            },
+            store.info()
+        );
+
+        let mut buf = Vec::new();
+        store.to_cache(&mut buf).unwrap();
+        let loaded = Store::from_cache(&buf[..]).unwrap();
+        assert!(loaded.info().built_at.is_some());
+        assert_eq!(1, loaded.info().licenses);
+        // the original store isn't changed by writing it out
+        assert_eq!(None, store.info().built_at);
+    }
+}
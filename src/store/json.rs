@@ -0,0 +1,135 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::io::prelude::*;
+
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::store::{base::Store, cache::CACHE_VERSION};
+
+#[derive(Serialize)]
+struct JsonStoreRef<'a> {
+    format: &'a str,
+    store: &'a Store,
+}
+
+#[derive(Deserialize)]
+struct JsonStore {
+    format: String,
+    store: Store,
+}
+
+impl Store {
+    /// Serialize the current store as JSON.
+    ///
+    /// This holds the same data as `to_cache`, but in a form that can be
+    /// inspected, edited by hand, and diffed. Object keys are sorted so that
+    /// exporting the same store twice gives the same output. It's much larger
+    /// and slower to load than a cache, so convert it back with `from_json`
+    /// and `to_cache` for regular use.
+    ///
+    /// Licenses are matched by their n-grams (`match_data`), not their text,
+    /// so editing a license's text won't change what it matches.
+    pub fn to_json<W>(&self, writable: W) -> Result<(), Error>
+    where
+        W: Write + Sized,
+    {
+        // going through a Value sorts object keys; hash map order is random
+        let value = serde_json::to_value(JsonStoreRef {
+            format: CACHE_VERSION,
+            store: self,
+        })?;
+        serde_json::to_writer_pretty(writable, &value)?;
+        Ok(())
+    }
+
+    /// Create a store from JSON written by `to_json`.
+    ///
+    /// Like `from_cache`, this checks that the JSON was written by a similar
+    /// version of askalono: its `format` must be the current cache version.
+    /// Unlike caches, older versions aren't migrated.
+    pub fn from_json<R>(readable: R) -> Result<Store, Error>
+    where
+        R: Read + Sized,
+    {
+        let JsonStore { format, mut store } = serde_json::from_reader(readable)?;
+        if format != CACHE_VERSION {
+            anyhow::bail!(
+                "JSON format mismatch; expected '{}', found '{}'",
+                CACHE_VERSION,
+                format
+            );
+        }
+        // sets are read without adding their words to the token table
+        store.intern_all_words();
+        store.pack_all();
+        Ok(store)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::license::TextData;
+
+    #[test]
+    fn round_trip() {
+        let mut store = Store::new();
+        store.compact_counts();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+        store.add_license("license-2".into(), "ddddd eeeee fffff".into());
+        store
+            .set_aliases("license-2", vec!["other-2".into()])
+            .unwrap();
+        store.set_lsh_prefilter(true);
+
+        let mut buf = Vec::new();
+        store.to_json(&mut buf).unwrap();
+        let mut again = Vec::new();
+        store.to_json(&mut again).unwrap();
+        assert_eq!(buf, again);
+
+        let loaded = Store::from_json(buf.as_slice()).unwrap();
+        assert_eq!(2, loaded.len());
+        assert!(loaded.lsh_prefilter());
+        assert_eq!(Some("license-2"), loaded.resolve_alias("other-2"));
+        let matched = loaded.analyze(&TextData::from("ddddd eeeee fffff"));
+        assert_eq!("license-2", matched.name);
+        assert_eq!(1.0, matched.score);
+
+        let mut json: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        json["format"] = "askalono-01".into();
+        let err = Store::from_json(json.to_string().as_bytes()).err().unwrap();
+        assert!(err.to_string().contains("format mismatch"));
+    }
+
+    #[test]
+    fn reexport() {
+        let mut store = Store::new();
+        store.add_license(
+            "license-1".into(),
+            "aaaaa bbbbb ccccc ddddd eeeee fffff ggggg hhhhh".into(),
+        );
+        store.add_license(
+            "license-2".into(),
+            "aaaaa bbbbb ccccc iiiii jjjjj kkkkk lllll mmmmm".into(),
+        );
+        store.add_stop_grams(["aaaaa bbbbb", "bbbbb ccccc", "fffff ggggg", "kkkkk lllll"]);
+        store.set_idf_weighting(true);
+        store.set_candidate_index(Some(2));
+        store.set_lsh_prefilter(true);
+
+        let mut buf = Vec::new();
+        store.to_json(&mut buf).unwrap();
+
+        // a loaded store has its own hash maps and sets, in their own order
+        let loaded = Store::from_json(buf.as_slice()).unwrap();
+        let mut again = Vec::new();
+        loaded.to_json(&mut again).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            String::from_utf8(again).unwrap()
+        );
+    }
+}
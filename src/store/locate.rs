@@ -0,0 +1,125 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::borrow::Cow;
+
+use serde::Serialize;
+
+use crate::{
+    license::{Scratch, TextData},
+    store::{analyze::Match, base::Store},
+};
+
+/// A license found in part of a text by `Store::analyze_all`.
+#[derive(Clone, Debug, Serialize)]
+pub struct LocatedMatch<'a> {
+    /// The license found, as matched against what remained of the text when
+    /// it was found. Its score is for that whole remainder; see
+    /// `located_score` for the score within `line_range`.
+    #[serde(flatten)]
+    pub matched: Match<'a>,
+    /// Confidence score of the license within `line_range`, from 0 to 1.
+    pub located_score: f32,
+    /// A 0-indexed (inclusive, exclusive) range of lines where the license
+    /// was found. See `TextData::lines_view`.
+    pub line_range: (usize, usize),
+}
+
+impl Store {
+    /// Find every license in the given text scoring at least `threshold`
+    /// where it's located, in the order they appear.
+    ///
+    /// This repeatedly analyzes the text, narrows the best match down to the
+    /// lines it covers (see `TextData::optimize_bounds`), and whites those
+    /// lines out to look for another, until nothing left scores well
+    /// enough. This is much like a `ScanStrategy` with `optimize` on and no
+    /// limit on passes, without having to configure one; use a strategy for
+    /// more control.
+    ///
+    /// If the text was stored without text (see `TextData::without_text`),
+    /// it can't be narrowed down, so at most one match is returned, covering
+    /// the whole view.
+    pub fn analyze_all<'a>(&'a self, text: &TextData, threshold: f32) -> Vec<LocatedMatch<'a>> {
+        let mut found = Vec::new();
+        if text.text_processed().is_none() {
+            let matched = self.analyze(text);
+            if matched.score >= threshold {
+                found.push(LocatedMatch {
+                    located_score: matched.score,
+                    line_range: text.lines_view(),
+                    matched,
+                });
+            }
+            return found;
+        }
+
+        let mut scratch = Scratch::default();
+        let mut current: Cow<'_, TextData> = Cow::Borrowed(text);
+        loop {
+            let matched = self.analyze(&current);
+            let (optimized, located_score) =
+                current.optimize_bounds_with(matched.data, &self.stop_grams, &mut scratch);
+            let line_range = optimized.lines_view();
+            // an empty range would be whited out forever
+            if located_score < threshold || line_range.0 == line_range.1 {
+                break;
+            }
+
+            found.push(LocatedMatch {
+                matched,
+                located_score,
+                line_range,
+            });
+            current = Cow::Owned(optimized.white_out());
+        }
+
+        found.sort_by_key(|m| m.line_range);
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_store() -> Store {
+        let mut store = Store::new();
+        store.add_license(
+            "license-1".into(),
+            "permission is granted to use this software in any way you like".into(),
+        );
+        store.add_license(
+            "license-2".into(),
+            "redistribution of this work is forbidden without written consent".into(),
+        );
+        store
+    }
+
+    #[test]
+    fn analyze_all() {
+        let store = create_store();
+        let text = TextData::from(
+            "fn main() {}\n\nredistribution of this work is forbidden without written \
+             consent\n\nsome() more(); code();\n\npermission is granted to use this \
+             software in any way you like\n\nthe end",
+        );
+        let found = store.analyze_all(&text, 0.9);
+        let names: Vec<&str> = found.iter().map(|m| m.matched.name).collect();
+        assert_eq!(vec!["license-2", "license-1"], names);
+        // trailing blank lines score the same, so ranges may include them
+        assert_eq!(2, found[0].line_range.0);
+        assert!(found[0].line_range.1 <= 4);
+        assert_eq!(6, found[1].line_range.0);
+        assert!(found.iter().all(|m| m.located_score > 0.99));
+
+        // nothing good enough
+        assert!(store.analyze_all(&text, 1.1).is_empty());
+
+        // without text, the whole text is all there is to match
+        let text = TextData::from("permission is granted to use this software in any way you like")
+            .without_text();
+        let found = store.analyze_all(&text, 0.9);
+        assert_eq!(1, found.len());
+        assert_eq!("license-1", found[0].matched.name);
+    }
+}
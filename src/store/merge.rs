@@ -0,0 +1,232 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::collections::HashSet;
+
+use anyhow::{format_err, Error};
+
+use crate::{ngram::Gram, store::base::Store};
+
+/// What `Store::merge` does when a license from the other store has a name
+/// or alias that's already in use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Keep the store's own licenses. Conflicting licenses from the other
+    /// store are left out, and conflicting aliases are dropped from the rest.
+    KeepExisting,
+    /// Prefer the other store's licenses. Licenses of the store in their way
+    /// are removed, along with any aliases in their way.
+    ReplaceExisting,
+    /// Fail on any conflict, leaving the store unchanged.
+    Fail,
+}
+
+impl Store {
+    /// Add every license and exception from another store to this one.
+    ///
+    /// This makes it possible to combine separately built caches (say, an
+    /// internal license set and the SPDX set) into one, which can then be
+    /// written out with `to_cache`. A license from `other` conflicts if its
+    /// name or one of its aliases is the name or an alias of a license
+    /// already here; an exception conflicts if it has the same name as one
+    /// already here. Conflicts are handled according to `policy`.
+    ///
+    /// Stop grams of both stores apply to the merged store, and whether
+    /// n-gram counts are compact is up to this store. If the other store's
+    /// n-grams are hashed, though, this store's are hashed too, which fails
+    /// if it can't be (see `hash_grams`). Settings that aren't kept in the
+    /// cache are left as they are.
+    pub fn merge(&mut self, other: Store, policy: MergePolicy) -> Result<(), Error> {
+        // every name and alias in use
+        let taken: HashSet<&str> = self
+            .licenses
+            .iter()
+            .flat_map(|(name, entry)| std::iter::once(name).chain(&entry.aliases))
+            .map(String::as_str)
+            .collect();
+
+        if policy == MergePolicy::Fail {
+            let mut conflicts: Vec<&str> = other
+                .licenses
+                .iter()
+                .flat_map(|(name, entry)| std::iter::once(name).chain(&entry.aliases))
+                .map(String::as_str)
+                .filter(|n| taken.contains(n))
+                .chain(
+                    other
+                        .exceptions
+                        .keys()
+                        .map(String::as_str)
+                        .filter(|n| self.exceptions.contains_key(*n)),
+                )
+                .collect();
+            if !conflicts.is_empty() {
+                conflicts.sort_unstable();
+                conflicts.dedup();
+                return Err(format_err!(
+                    "names already in store: {}",
+                    conflicts.join(", ")
+                ));
+            }
+        }
+        if other.hashed_grams {
+            self.hash_grams()?;
+        }
+
+        let Store {
+            licenses,
+            stop_grams,
+            titles,
+            spdx_digests,
+            exceptions,
+            ..
+        } = other;
+
+        let new_stop_grams: HashSet<Gram> =
+            stop_grams.difference(&self.stop_grams).copied().collect();
+        if !new_stop_grams.is_empty() {
+            self.extend_stop_grams(new_stop_grams);
+        }
+
+        let mut licenses: Vec<_> = licenses.into_iter().collect();
+        licenses.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        for (name, mut entry) in licenses {
+            let in_use =
+                |store: &Store, n: &str| store.licenses.contains_key(n) || store.is_alias(n);
+            match policy {
+                MergePolicy::KeepExisting => {
+                    if in_use(self, &name) {
+                        continue;
+                    }
+                    entry.aliases.retain(|alias| !in_use(self, alias));
+                }
+                MergePolicy::ReplaceExisting => {
+                    for n in std::iter::once(&name).chain(&entry.aliases) {
+                        if self.licenses.contains_key(n) {
+                            self.remove_license(n)?;
+                        }
+                        for existing in self.licenses.values_mut() {
+                            existing.aliases.retain(|alias| alias != n);
+                        }
+                    }
+                }
+                MergePolicy::Fail => {}
+            }
+
+            for data in std::iter::once(&mut entry.original)
+                .chain(&mut entry.headers)
+                .chain(&mut entry.alternates)
+                .chain(&mut entry.clauses)
+            {
+                data.match_data.remove_grams(&self.stop_grams);
+            }
+            for (title, names) in &titles {
+                if names.contains(&name) {
+                    self.titles
+                        .entry(title.clone())
+                        .or_default()
+                        .push(name.clone());
+                }
+            }
+            for n in std::iter::once(&name).chain(&entry.aliases) {
+                if let Some(&digest) = spdx_digests.get(n) {
+                    self.spdx_digests.insert(n.clone(), digest);
+                }
+            }
+            self.licenses.insert(name, entry);
+        }
+        self.forget_derived();
+
+        for (name, data) in exceptions {
+            if policy == MergePolicy::KeepExisting && self.exceptions.contains_key(&name) {
+                continue;
+            }
+            self.add_exception(name, data);
+        }
+
+        self.pack_all();
+        Ok(())
+    }
+
+    fn is_alias(&self, name: &str) -> bool {
+        self.licenses
+            .values()
+            .any(|entry| entry.aliases.iter().any(|alias| alias == name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::license::TextData;
+
+    fn create_stores() -> (Store, Store) {
+        let mut ours = Store::new();
+        ours.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+        ours.add_license("license-2".into(), "ddddd eeeee fffff".into());
+        ours.set_aliases("license-2", vec!["license-two".into()])
+            .unwrap();
+
+        let mut theirs = Store::new();
+        theirs.add_license("license-2".into(), "ggggg hhhhh iiiii".into());
+        theirs.add_license("license-3".into(), "jjjjj kkkkk lllll".into());
+        theirs
+            .set_aliases(
+                "license-3",
+                vec!["license-two".into(), "license-three".into()],
+            )
+            .unwrap();
+        theirs.add_exception("exception-1".into(), "mmmmm nnnnn ooooo".into());
+        theirs.add_stop_grams(["ppppp qqqqq"]);
+        (ours, theirs)
+    }
+
+    fn names(store: &Store) -> Vec<&String> {
+        let mut names: Vec<_> = store.licenses().collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn keep_existing() {
+        let (mut ours, theirs) = create_stores();
+        ours.merge(theirs, MergePolicy::KeepExisting).unwrap();
+        assert_eq!(vec!["license-1", "license-2", "license-3"], names(&ours));
+        assert_eq!(1.0, ours.analyze(&"ddddd eeeee fffff".into()).score);
+        assert_eq!(Some("license-2"), ours.resolve_alias("license-two"));
+        assert_eq!(Some("license-3"), ours.resolve_alias("license-three"));
+        assert_eq!(vec!["exception-1"], ours.exceptions().collect::<Vec<_>>());
+        assert_eq!(vec!["ppppp qqqqq"], ours.stop_grams().collect::<Vec<_>>());
+
+        let text = TextData::from("jjjjj kkkkk lllll\n\nmmmmm nnnnn ooooo");
+        let matched = ours.analyze(&text);
+        assert_eq!("license-3", matched.name);
+        assert_eq!(Some("exception-1"), matched.exception);
+    }
+
+    #[test]
+    fn replace_existing() {
+        let (mut ours, theirs) = create_stores();
+        ours.merge(theirs, MergePolicy::ReplaceExisting).unwrap();
+        assert_eq!(vec!["license-1", "license-2", "license-3"], names(&ours));
+        assert_eq!("license-2", ours.analyze(&"ggggg hhhhh iiiii".into()).name);
+        assert_eq!(1.0, ours.analyze(&"ggggg hhhhh iiiii".into()).score);
+        assert_eq!(Some("license-3"), ours.resolve_alias("license-two"));
+    }
+
+    #[test]
+    fn fail() {
+        let (mut ours, theirs) = create_stores();
+        let error = ours.merge(theirs, MergePolicy::Fail).unwrap_err();
+        assert_eq!(
+            "names already in store: license-2, license-two",
+            error.to_string()
+        );
+        assert_eq!(vec!["license-1", "license-2"], names(&ours));
+
+        let (_, theirs) = create_stores();
+        let mut empty = Store::new();
+        empty.merge(theirs, MergePolicy::Fail).unwrap();
+        assert_eq!(vec!["license-2", "license-3"], names(&empty));
+    }
+}
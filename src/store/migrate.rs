@@ -0,0 +1,209 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Loading caches written by earlier versions of askalono.
+//!
+//! Each supported format has a decoder that reads its (decompressed) body
+//! and migrates it to a current `Store` in memory. Formats are kept here
+//! rather than in `cache` so that the current one can change freely; once
+//! loaded, a migrated store is written back out in the current format by
+//! `to_cache`.
+
+use std::collections::HashMap;
+
+use anyhow::{format_err, Error};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    license::TextData,
+    ngram::{Gram, NgramSet},
+    store::{
+        base::{LicenseEntry, Store},
+        family::derive_family,
+    },
+};
+
+/// A decoder for the body of a cache in an earlier format.
+type Migration = fn(&[u8]) -> Result<Store, Error>;
+
+/// Earlier cache formats that can still be loaded, by version header.
+pub(crate) const MIGRATIONS: &[(&[u8], Migration)] = &[(b"askalono-04", migrate_04)];
+
+/// Find the decoder for a cache format, if it's still supported.
+pub(crate) fn migration_for(header: &[u8]) -> Option<Migration> {
+    MIGRATIONS
+        .iter()
+        .find(|(version, _)| *version == header)
+        .map(|&(_, migrate)| migrate)
+}
+
+/// The 0.4 format: the whole store MessagePack-encoded in one piece, with
+/// every license's n-grams spelled out.
+mod v04 {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    pub(super) struct Store {
+        pub licenses: HashMap<String, LicenseEntry>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub(super) struct LicenseEntry {
+        pub original: TextData,
+        pub aliases: Vec<String>,
+        pub headers: Vec<TextData>,
+        pub alternates: Vec<TextData>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub(super) struct TextData {
+        pub match_data: NgramSet,
+        pub lines_view: (usize, usize),
+        pub lines_normalized: Option<Vec<String>>,
+        pub text_processed: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub(super) struct NgramSet {
+        pub map: HashMap<String, u32>,
+        pub n: u8,
+        pub size: usize,
+    }
+}
+
+impl TryFrom<v04::TextData> for TextData {
+    type Error = Error;
+
+    fn try_from(data: v04::TextData) -> Result<TextData, Error> {
+        let v04::NgramSet { map, n, .. } = data.match_data;
+        if n != 2 {
+            return Err(format_err!(
+                "cache holds n-grams of {}; only bigrams are supported",
+                n
+            ));
+        }
+        let counts = map
+            .into_iter()
+            .map(|(gram, count)| Ok((Gram::intern(&gram)?, count)))
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(TextData::from_parts(
+            NgramSet::from_counts(counts),
+            data.lines_view,
+            data.lines_normalized,
+            data.text_processed,
+        ))
+    }
+}
+
+fn migrate_04(body: &[u8]) -> Result<Store, Error> {
+    let old: v04::Store = rmp_serde::decode::from_slice(body)?;
+    let mut store = Store::new();
+    for (name, old) in old.licenses {
+        // there's no telling where these came from, but caches this old were
+        // nearly always built from SPDX data
+        let mut entry = LicenseEntry::new(old.original.try_into()?, None);
+        entry.family = derive_family(&name);
+        entry.aliases = old.aliases;
+        entry.headers = old
+            .headers
+            .into_iter()
+            .map(TextData::try_from)
+            .collect::<Result<_, _>>()?;
+        entry.alternates = old
+            .alternates
+            .into_iter()
+            .map(TextData::try_from)
+            .collect::<Result<_, _>>()?;
+        store.licenses.insert(name, entry);
+    }
+    Ok(store)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use rmp_serde::Serializer;
+
+    use super::*;
+
+    fn text_04(text: &str) -> v04::TextData {
+        let data = TextData::new(text);
+        let map: HashMap<String, u32> = data.ngrams().collect();
+        v04::TextData {
+            match_data: v04::NgramSet {
+                size: map.values().map(|&count| count as usize).sum(),
+                map,
+                n: 2,
+            },
+            lines_view: data.lines_view(),
+            lines_normalized: Some(data.lines().to_vec()),
+            text_processed: data.text_processed().map(str::to_owned),
+        }
+    }
+
+    /// Write a cache the way askalono 0.4 did.
+    fn cache_04() -> Vec<u8> {
+        let mut licenses = HashMap::new();
+        licenses.insert(
+            "license-1".to_owned(),
+            v04::LicenseEntry {
+                original: text_04("aaaaa bbbbb ccccc ddddd"),
+                aliases: vec!["license-one".to_owned()],
+                headers: vec![text_04("ccccc ddddd eeeee")],
+                alternates: Vec::new(),
+            },
+        );
+        licenses.insert(
+            "license-2".to_owned(),
+            v04::LicenseEntry {
+                original: text_04("fffff ggggg hhhhh"),
+                aliases: Vec::new(),
+                headers: Vec::new(),
+                alternates: Vec::new(),
+            },
+        );
+
+        let mut body = Vec::new();
+        v04::Store { licenses }
+            .serialize(&mut Serializer::new(&mut body))
+            .unwrap();
+        let mut buf = b"askalono-04".to_vec();
+        #[cfg(not(feature = "gzip"))]
+        let mut enc = zstd::Encoder::new(&mut buf, 3).unwrap();
+        #[cfg(feature = "gzip")]
+        let mut enc = flate2::write::GzEncoder::new(&mut buf, flate2::Compression::default());
+        enc.write_all(&body).unwrap();
+        enc.finish().unwrap();
+        buf
+    }
+
+    #[test]
+    fn loads_04() {
+        let store = Store::from_cache(&cache_04()[..]).unwrap();
+        assert_eq!(2, store.len());
+        assert_eq!(Some("license-1"), store.resolve_alias("license-one"));
+        assert_eq!(None, store.provenance("license-1"));
+
+        let matched = store.analyze(&"ccccc ddddd eeeee".into());
+        assert_eq!("license-1", matched.name);
+        assert_eq!(crate::LicenseType::Header, matched.license_type);
+        assert_eq!(1.0, matched.score);
+        assert!(matched.matched_text().is_some());
+
+        // and it's written back out in the current format
+        let mut buf = Vec::new();
+        store.to_cache(&mut buf).unwrap();
+        let reloaded = Store::from_cache(&buf[..]).unwrap();
+        assert_eq!(2, reloaded.len());
+        assert_eq!(1.0, reloaded.analyze(&"fffff ggggg hhhhh".into()).score);
+    }
+
+    #[test]
+    fn unknown_versions() {
+        let err = Store::from_cache(&b"askalono-01xxxx"[..]).unwrap_err();
+        assert!(err.to_string().contains("askalono-04"));
+        assert!(migration_for(b"askalono-04").is_some());
+        assert!(migration_for(b"askalono-03").is_none());
+    }
+}
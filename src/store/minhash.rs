@@ -0,0 +1,292 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{collections::HashMap, hash::Hasher};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{memo::FnvHasher, ngram::NgramSet, store::base::Store};
+
+/// The number of LSH bands each MinHash signature is split into.
+const BANDS: usize = 32;
+
+/// The number of MinHash values in each band. With `BANDS`, texts with a
+/// Jaccard similarity of about 0.4 or more are likely to share a band.
+const ROWS: usize = 4;
+
+/// The number of MinHash values kept for each variant to estimate its
+/// similarity to texts it shares no bucket with.
+const SKETCH: usize = 32;
+
+/// Locality-sensitive hash buckets of the MinHash signatures of a store's
+/// license variants; see `Store::set_lsh_prefilter`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub(crate) struct LshIndex {
+    /// Indexed license names, sorted.
+    names: Vec<String>,
+    /// The position in `names` of every indexed variant's license.
+    variants: Vec<u32>,
+    /// For each band, the positions in `variants` of the variants whose
+    /// signatures hash to each key.
+    buckets: Vec<HashMap<u64, Vec<u32>>>,
+    /// The first `SKETCH` values of every variant's signature, truncated.
+    sketches: Vec<[u32; SKETCH]>,
+}
+
+/// Compute the MinHash signature of a set of n-grams, or `None` if it's
+/// empty.
+///
+/// Hashes are saved to disk, so each n-gram is hashed once with
+/// `FnvHasher`, then remixed for every row.
+fn signature(grams: &NgramSet) -> Option<[u64; BANDS * ROWS]> {
+    if grams.is_empty() {
+        return None;
+    }
+
+    let mut signature = [u64::MAX; BANDS * ROWS];
+    // grams are hashed as they're spelled, whether or not their words are
+    // in the token table, or they're only kept hashed
+    for (hash, _) in grams.gram_hashes() {
+        for (seed, min) in signature.iter_mut().enumerate() {
+            *min = (*min).min(mix(hash ^ (seed as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)));
+        }
+    }
+    Some(signature)
+}
+
+/// The splitmix64 finalizer.
+pub(crate) fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// Keep the first values of a signature, truncated; see `SKETCH`.
+fn sketch(signature: &[u64; BANDS * ROWS]) -> [u32; SKETCH] {
+    let mut sketch = [0; SKETCH];
+    for (value, min) in sketch.iter_mut().zip(signature) {
+        *value = *min as u32;
+    }
+    sketch
+}
+
+/// Hash each band of a signature into a bucket key.
+fn band_keys(signature: &[u64; BANDS * ROWS]) -> impl Iterator<Item = u64> + '_ {
+    signature.chunks(ROWS).map(|band| {
+        let mut hasher = FnvHasher::default();
+        for value in band {
+            hasher.write(&value.to_le_bytes());
+        }
+        hasher.finish()
+    })
+}
+
+impl LshIndex {
+    /// Whether the index covers the named license.
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.names
+            .binary_search_by(|n| n.as_str().cmp(name))
+            .is_ok()
+    }
+
+    /// Get the names of the licenses with a variant sharing an LSH bucket
+    /// with the input, in no particular order.
+    pub(crate) fn candidates(&self, input: &NgramSet) -> Vec<&str> {
+        let signature = match signature(input) {
+            Some(signature) => signature,
+            None => return Vec::new(),
+        };
+
+        let mut found = vec![false; self.names.len()];
+        for (bucket, key) in self.buckets.iter().zip(band_keys(&signature)) {
+            for &id in bucket.get(&key).into_iter().flatten() {
+                found[self.variants[id as usize] as usize] = true;
+            }
+        }
+        self.names
+            .iter()
+            .zip(found)
+            .filter(|&(_, found)| found)
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// Get the name of the license with the variant whose signature agrees
+    /// most with the input's, skipping those `skip` accepts. Used to find a
+    /// runner-up among the licenses `candidates` pruned.
+    pub(crate) fn nearest(&self, input: &NgramSet, skip: impl Fn(&str) -> bool) -> Option<&str> {
+        let sketch = sketch(&signature(input)?);
+        let mut best: Option<(usize, u32)> = None;
+        for (&license, other) in self.variants.iter().zip(&self.sketches) {
+            let name = &self.names[license as usize];
+            if skip(name) {
+                continue;
+            }
+            let agree = sketch.iter().zip(other).filter(|(a, b)| a == b).count();
+            if best.is_none_or(|(most, _)| agree > most) {
+                best = Some((agree, license));
+            }
+        }
+        best.map(|(_, license)| self.names[license as usize].as_str())
+    }
+}
+
+impl Store {
+    /// Have `analyze` prune its candidates by locality-sensitive hashing
+    /// before scoring them.
+    ///
+    /// This keeps a MinHash signature of every license variant, split into
+    /// bands and bucketed by hash. `analyze` then computes the text's
+    /// signature and only scores licenses with a variant sharing a bucket
+    /// with it, along with any named by the text's title. Licenses that would
+    /// have scored well almost always do; if none share a bucket, every
+    /// license is scored as usual. If only one license is left, the one whose
+    /// signature agrees most with the text's among the rest is scored too,
+    /// so `Match::runner_up` and `Match::ambiguous` still work. When a candidate index is also built (see
+    /// `set_candidate_index`), licenses either one suggests are scored.
+    ///
+    /// Signatures are computed from the licenses in the store when this is
+    /// called, so call it after adding licenses; `update_from_spdx` computes
+    /// them again itself, as does changing or removing a license they cover,
+    /// and licenses added later are always scored. They're
+    /// kept in the cache. See `AnalysisOptions::exact` to skip pruning when
+    /// accuracy matters more than speed.
+    pub fn set_lsh_prefilter(&mut self, enabled: bool) {
+        if !enabled {
+            self.lsh = None;
+            return;
+        }
+
+        let mut names: Vec<&String> = self.licenses.keys().collect();
+        names.sort_unstable();
+        let mut variants = Vec::new();
+        let mut buckets: Vec<HashMap<u64, Vec<u32>>> = vec![HashMap::new(); BANDS];
+        let mut sketches = Vec::new();
+        for (license, name) in names.iter().enumerate() {
+            let entry = &self.licenses[*name];
+            let texts = std::iter::once(&entry.original)
+                .chain(&entry.alternates)
+                .chain(&entry.headers);
+            for data in texts {
+                let id = variants.len() as u32;
+                variants.push(license as u32);
+                let signature = signature(&data.match_data);
+                sketches.push(signature.as_ref().map_or([u32::MAX; SKETCH], sketch));
+                if let Some(signature) = signature {
+                    for (bucket, key) in buckets.iter_mut().zip(band_keys(&signature)) {
+                        bucket.entry(key).or_default().push(id);
+                    }
+                }
+            }
+        }
+
+        self.lsh = Some(LshIndex {
+            names: names.into_iter().cloned().collect(),
+            variants,
+            buckets,
+            sketches,
+        });
+    }
+
+    /// Whether `analyze` prunes candidates by locality-sensitive hashing. See
+    /// `set_lsh_prefilter`.
+    pub fn lsh_prefilter(&self) -> bool {
+        self.lsh.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{license::TextData, store::options::AnalysisOptions};
+
+    fn create_store() -> Store {
+        let mut store = Store::new();
+        for (name, text) in [
+            ("license-1", "the quick brown fox jumps over the lazy dog"),
+            (
+                "license-2",
+                "lorem ipsum dolor sit amet consectetur adipiscing elit",
+            ),
+            (
+                "license-3",
+                "permission is hereby granted free of charge to any person",
+            ),
+        ] {
+            store.add_license(name.into(), text.into());
+        }
+        store
+    }
+
+    #[test]
+    fn signatures() {
+        let a = TextData::from("the quick brown fox jumps over the lazy dog");
+        let b = TextData::from("the quick brown fox jumps over the lazy dog");
+        assert_eq!(signature(&a.match_data), signature(&b.match_data));
+        assert!(signature(&TextData::from("").match_data).is_none());
+    }
+
+    #[test]
+    fn lsh_prefilter() {
+        let mut store = create_store();
+        assert!(!store.lsh_prefilter());
+        store.set_lsh_prefilter(true);
+        assert!(store.lsh_prefilter());
+
+        let lsh = store.lsh.as_ref().unwrap();
+        assert!(lsh.contains("license-2"));
+        let near = TextData::from("lorem ipsum dolor sit amet consectetur adipiscing");
+        assert_eq!(vec!["license-2"], lsh.candidates(&near.match_data));
+        let far = TextData::from("nothing in common at all here");
+        assert!(lsh.candidates(&far.match_data).is_empty());
+
+        // pruned or not, the answer is the same
+        let m = store.analyze(&near);
+        assert_eq!("license-2", m.name);
+        let runner_up = m.runner_up.map(str::to_owned);
+        let (score, runner_up_score) = (m.score, m.runner_up_score);
+        let options = AnalysisOptions {
+            exact: true,
+            ..Default::default()
+        };
+        let exact = store.analyze_with(&near, &options).unwrap();
+        assert_eq!(score, exact.score);
+        assert!(runner_up.is_some());
+        assert_eq!(
+            (exact.runner_up, exact.runner_up_score),
+            (runner_up.as_deref(), runner_up_score)
+        );
+        assert_eq!("license-1", store.analyze(&far).name);
+
+        // and it's kept in the cache
+        let mut buf = Vec::new();
+        store.to_cache(&mut buf).unwrap();
+        let loaded = Store::from_cache(&buf[..]).unwrap();
+        assert!(loaded.lsh_prefilter());
+        assert_eq!(score, loaded.analyze(&near).score);
+
+        store.set_lsh_prefilter(false);
+        assert!(!store.lsh_prefilter());
+    }
+
+    #[test]
+    fn changes_after_hashing() {
+        let mut store = create_store();
+        store.set_lsh_prefilter(true);
+        store
+            .replace_license(
+                "license-1",
+                "pack my box with five dozen liquor jugs".into(),
+            )
+            .unwrap();
+        let text = TextData::from("pack my box with five dozen liquor jugs");
+        assert_eq!(
+            vec!["license-1"],
+            store.lsh.as_ref().unwrap().candidates(&text.match_data)
+        );
+        assert_eq!("license-1", store.analyze(&text).name);
+
+        store.remove_license("license-2").unwrap();
+        assert!(!store.lsh.as_ref().unwrap().contains("license-2"));
+    }
+}
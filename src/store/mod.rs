@@ -1,11 +1,69 @@
 // Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+mod align;
 mod analyze;
+#[cfg(not(target_arch = "wasm32"))]
+mod background;
 mod base;
 mod cache;
+mod clause;
+mod deprecation;
+mod exception;
+mod family;
+mod fuzzy;
+mod header;
+mod idf;
+mod index;
+mod info;
+mod locate;
+mod merge;
+mod migrate;
+mod minhash;
+mod options;
+mod provenance;
+mod resolve;
+mod scorer;
+mod title;
+mod validate;
 
+#[cfg(feature = "fs")]
+mod directory;
+#[cfg(feature = "disk-store")]
+mod disk;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "scancode")]
+mod scancode;
 #[cfg(feature = "spdx")]
 mod spdx;
+#[cfg(feature = "static-store")]
+mod static_store;
 
-pub use self::{analyze::Match, base::Store};
+pub use self::{
+    analyze::{Match, MatchIter, DEFAULT_AMBIGUITY_EPSILON},
+    base::Store,
+    header::HeaderOptions,
+    info::StoreInfo,
+    locate::LocatedMatch,
+    merge::MergePolicy,
+    options::AnalysisOptions,
+    provenance::Provenance,
+    scorer::Scorer,
+    validate::ValidationIssue,
+};
+
+pub(crate) use self::analyze::any_license;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::background::StoreHandle;
+#[cfg(feature = "embedded-cache")]
+pub use self::cache::global_store;
+#[cfg(feature = "disk-store")]
+pub use self::disk::DiskStore;
+#[cfg(feature = "spdx")]
+pub use self::spdx::{SpdxLoadOptions, SpdxUpdate};
+#[cfg(feature = "static-store")]
+pub use self::static_store::{StaticCounts, StaticLicense, StaticStore};
+#[cfg(feature = "static-store")]
+pub(crate) use self::static_store::{StaticGrams, StaticIter};
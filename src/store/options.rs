@@ -0,0 +1,254 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{format_err, Error};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    license::LicenseType,
+    store::{base::DEFAULT_TYPE_PREFERENCE, fuzzy::MAX_FUZZY_DISTANCE, scorer::Scorer},
+};
+
+/// Options for comparing a text against a store's licenses with
+/// `Store::analyze_with`, or with a `ScanStrategy` (see
+/// `ScanStrategyBuilder::analysis`).
+///
+/// These belong to each analysis rather than to the store, so one store can
+/// be shared by scans with different needs. By default, every license is
+/// scored by Dice, as `Store::analyze` does.
+///
+/// # Examples
+///
+/// ```
+/// use askalono::{AnalysisOptions, Scorer, Store, TextData};
+///
+/// let mut store = Store::new();
+/// store.add_license("license-1".into(), "aaaaa bbbbb ccccc ddddd".into());
+///
+/// let options = AnalysisOptions {
+///     scorer: Scorer::Containment,
+///     ..Default::default()
+/// };
+/// let text = TextData::from("zzzzz yyyyy aaaaa bbbbb ccccc ddddd xxxxx wwwww");
+/// let matched = store.analyze_with(&text, &options).unwrap();
+/// assert_eq!(1.0, matched.score);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnalysisOptions {
+    /// How texts are scored against licenses. See `Scorer`.
+    ///
+    /// Scores are then that scorer's, including those compared against
+    /// thresholds such as a strategy's confidence threshold or
+    /// `early_exit`, which may need adjusting. Optimizing a match to find
+    /// its bounds always uses Dice, since other scorers don't penalize
+    /// surrounding text as they'd need to.
+    pub scorer: Scorer,
+    /// Stop early when a popular license scores at least this much.
+    ///
+    /// Most texts are one of a handful of common licenses, so scoring the
+    /// entire store is usually wasted effort. With early exit enabled, the
+    /// most popular licenses (see `popularity`) are scored first,
+    /// in order, and if any of them reaches this score, it's taken as the
+    /// match without looking at the rest of the store. This can miss a
+    /// slightly better match elsewhere, so keep it close to 1. It must be
+    /// above 0 and at most 1; `None` (the default) always scores everything.
+    pub early_exit: Option<f32>,
+    /// The licenses tried first when early exit is enabled, most popular
+    /// first. Names not in the store are ignored.
+    ///
+    /// `None` (the default) uses the store's list, which is of the most
+    /// common SPDX licenses, starting with MIT and Apache-2.0.
+    pub popularity: Option<Vec<String>>,
+    /// How many licenses are scored in each parallel task.
+    ///
+    /// By default (`None`), this is chosen by the size of the text being
+    /// analyzed: short texts are cheap to compare, so they're scored in a
+    /// few large tasks to keep scheduling overhead down, and long texts are
+    /// split more finely to spread them across more threads. Smaller chunks
+    /// may help on machines with many cores. It must be at least 1, and has
+    /// no effect without the `rayon` feature. `None` falls back to the
+    /// store's chunk size, if one was set.
+    pub chunk_size: Option<usize>,
+    /// Score every license even if the store has a candidate index or LSH
+    /// prefilter, for the best accuracy at the cost of speed. See
+    /// `Store::set_candidate_index` and `Store::set_lsh_prefilter`.
+    pub exact: bool,
+    /// Double-check close calls by aligning texts word by word.
+    ///
+    /// N-gram scores are fast, but in the middle of their range they're a
+    /// weak signal: a text that scores 0.75 against two similar licenses
+    /// may not really be either. With a band of scores set (low to high,
+    /// within 0 to 1), whenever the best score falls within it (inclusive),
+    /// the few best-scoring licenses are compared again by how many of
+    /// their words appear in the text in the same order. The license that
+    /// aligns best becomes the match, and its alignment score (from 0 to 1)
+    /// becomes the match score. Alignment is much slower than n-gram
+    /// scoring, which is why it's limited to the band.
+    ///
+    /// This needs license texts, so it has no effect on stores built or
+    /// loaded without them. `None` (the default) turns it off.
+    pub alignment_fallback: Option<(f32, f32)>,
+    /// Correct likely misspellings in texts before comparing them, allowing
+    /// up to this many edits per word.
+    ///
+    /// Texts that went through OCR or were retyped by hand ("perrnission",
+    /// "sofware") lose many of the n-grams they'd otherwise share with their
+    /// license. With this set, each word of a text that appears in none of
+    /// the store's licenses is replaced with the closest word that does,
+    /// counting insertions, deletions, substitutions, and swaps of adjacent
+    /// characters as one edit each. Ties go to the word that's more common
+    /// in the store. Words are allowed one edit per three characters, so
+    /// words shorter than four characters, and words containing digits, are
+    /// never changed. Line bounds found with `TextData::optimize_bounds`
+    /// still use the text as written.
+    ///
+    /// This may be 1 or 2; `None` (the default) turns correction off. The
+    /// store collects its vocabulary the first time it's needed.
+    pub fuzzy_tokens: Option<u32>,
+    /// The order in which license types are preferred when a text scores
+    /// identically against several of them.
+    ///
+    /// Short licenses often match their full text and their header equally
+    /// well. By default (`None`) the original text wins, then alternates,
+    /// then headers; remaining ties are broken by license name so that
+    /// results are always deterministic. Each type must appear exactly
+    /// once.
+    pub type_preference: Option<[LicenseType; 3]>,
+}
+
+impl AnalysisOptions {
+    /// Check that the options are usable, as described on each field.
+    pub fn validate(&self) -> Result<(), Error> {
+        if let Some(score) = self.early_exit {
+            if !(score > 0.0 && score <= 1.0) {
+                return Err(format_err!(
+                    "early exit score must be above 0 and at most 1; got {}",
+                    score
+                ));
+            }
+        }
+        if let Some((low, high)) = self.alignment_fallback {
+            if !(0.0..=1.0).contains(&low) || !(0.0..=1.0).contains(&high) || low > high {
+                return Err(format_err!(
+                    "alignment band must be within 0 to 1, low to high; got {} to {}",
+                    low,
+                    high
+                ));
+            }
+        }
+        if let Some(distance) = self.fuzzy_tokens {
+            if !(1..=MAX_FUZZY_DISTANCE).contains(&distance) {
+                return Err(format_err!(
+                    "fuzzy token distance must be between 1 and {}; got {}",
+                    MAX_FUZZY_DISTANCE,
+                    distance
+                ));
+            }
+        }
+        if self.chunk_size == Some(0) {
+            return Err(format_err!("analyze chunk size must be at least 1"));
+        }
+        if let Some(order) = self.type_preference {
+            if DEFAULT_TYPE_PREFERENCE.iter().any(|t| !order.contains(t)) {
+                return Err(format_err!(
+                    "type preference must list each license type once; got {:?}",
+                    order
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the licenses tried first when early exit is enabled. See
+    /// `popularity`.
+    pub fn popularity(mut self, names: Vec<String>) -> Self {
+        self.popularity = Some(names);
+        self
+    }
+
+    /// Set how many licenses are scored in each parallel task. See
+    /// `chunk_size`.
+    pub fn chunk_size(mut self, size: usize) -> Self {
+        self.chunk_size = Some(size);
+        self
+    }
+
+    /// Get the position of a license type in the type preference.
+    pub(crate) fn type_rank(&self, license_type: LicenseType) -> usize {
+        let preference = self.type_preference.unwrap_or(DEFAULT_TYPE_PREFERENCE);
+        preference
+            .iter()
+            .position(|&p| p == license_type)
+            .unwrap_or(preference.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validation() {
+        assert!(AnalysisOptions::default().validate().is_ok());
+
+        let invalid = [
+            AnalysisOptions {
+                early_exit: Some(0.0),
+                ..Default::default()
+            },
+            AnalysisOptions {
+                early_exit: Some(1.5),
+                ..Default::default()
+            },
+            AnalysisOptions {
+                alignment_fallback: Some((0.85, 0.6)),
+                ..Default::default()
+            },
+            AnalysisOptions {
+                alignment_fallback: Some((-0.1, 0.6)),
+                ..Default::default()
+            },
+            AnalysisOptions {
+                alignment_fallback: Some((0.6, 1.1)),
+                ..Default::default()
+            },
+            AnalysisOptions {
+                fuzzy_tokens: Some(0),
+                ..Default::default()
+            },
+            AnalysisOptions {
+                fuzzy_tokens: Some(3),
+                ..Default::default()
+            },
+            AnalysisOptions::default().chunk_size(0),
+            AnalysisOptions {
+                type_preference: Some([
+                    LicenseType::Header,
+                    LicenseType::Header,
+                    LicenseType::Original,
+                ]),
+                ..Default::default()
+            },
+        ];
+        for options in &invalid {
+            assert!(options.validate().is_err(), "{:?}", options);
+        }
+
+        let valid = AnalysisOptions {
+            scorer: Scorer::Cosine,
+            early_exit: Some(0.99),
+            popularity: Some(vec!["MIT".into()]),
+            chunk_size: Some(4),
+            exact: true,
+            alignment_fallback: Some((0.6, 0.85)),
+            fuzzy_tokens: Some(1),
+            type_preference: Some([
+                LicenseType::Header,
+                LicenseType::Alternate,
+                LicenseType::Original,
+            ]),
+        };
+        assert!(valid.validate().is_ok());
+    }
+}
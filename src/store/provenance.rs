@@ -0,0 +1,160 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{format_err, Error};
+use serde::{Deserialize, Serialize};
+
+use crate::{license::LicenseType, store::base::Store, timing::unix_now};
+
+/// A record of where a license text in a `Store` came from.
+///
+/// Provenance is kept in the cache, so it's possible to trace any match back
+/// to the dataset that supplied its text. Texts loaded by `load_spdx` have a
+/// source of `spdx`; texts added with `add_license` or `add_variant` have a
+/// source of `user` unless overridden with `set_provenance`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Provenance {
+    /// The dataset or tool the text came from, such as `spdx` or `user`.
+    pub source: String,
+    /// The version of the dataset, if known (for SPDX, the license list
+    /// version).
+    pub version: Option<String>,
+    /// The file the text was read from, if any.
+    pub path: Option<String>,
+    /// When the text was added to the store, in seconds since the Unix epoch.
+    /// Not available on wasm32.
+    pub loaded_at: Option<u64>,
+}
+
+impl Provenance {
+    /// Create a record for a text from the given source, loaded now.
+    pub fn new(source: &str) -> Provenance {
+        Provenance {
+            source: source.to_owned(),
+            loaded_at: unix_now(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Store {
+    /// Get the provenance of a license's original text.
+    ///
+    /// Returns `None` if the license isn't in the store, or was loaded from a
+    /// cache built before provenance was recorded.
+    pub fn provenance(&self, name: &str) -> Option<&Provenance> {
+        self.licenses.get(name)?.provenance.as_ref()
+    }
+
+    /// Get the provenance of a header or alternate of a license, by its index
+    /// in the order the variants were added.
+    pub fn variant_provenance(
+        &self,
+        name: &str,
+        variant: LicenseType,
+        index: usize,
+    ) -> Option<&Provenance> {
+        let entry = self.licenses.get(name)?;
+        match variant {
+            LicenseType::Header => entry.header_provenance.get(index)?.as_ref(),
+            LicenseType::Alternate => entry.alternate_provenance.get(index)?.as_ref(),
+            LicenseType::Original => entry.provenance.as_ref(),
+        }
+    }
+
+    /// Override the provenance of a license's original text.
+    ///
+    /// Useful when building a store from a dataset of your own, to record
+    /// where each text came from.
+    pub fn set_provenance(&mut self, name: &str, provenance: Provenance) -> Result<(), Error> {
+        let entry = self
+            .licenses
+            .get_mut(name)
+            .ok_or_else(|| format_err!("license {} not present in store", name))?;
+        entry.provenance = Some(provenance);
+        Ok(())
+    }
+
+    /// Override the provenance of a header or alternate of a license. See
+    /// `set_provenance`.
+    pub fn set_variant_provenance(
+        &mut self,
+        name: &str,
+        variant: LicenseType,
+        index: usize,
+        provenance: Provenance,
+    ) -> Result<(), Error> {
+        let entry = self
+            .licenses
+            .get_mut(name)
+            .ok_or_else(|| format_err!("license {} not present in store", name))?;
+        let (count, records) = match variant {
+            LicenseType::Header => (entry.headers.len(), &mut entry.header_provenance),
+            LicenseType::Alternate => (entry.alternates.len(), &mut entry.alternate_provenance),
+            LicenseType::Original => {
+                entry.provenance = Some(provenance);
+                return Ok(());
+            }
+        };
+        if index >= count {
+            return Err(format_err!(
+                "license {} has no {} at index {}",
+                name,
+                variant,
+                index
+            ));
+        }
+        records.resize(count, None);
+        records[index] = Some(provenance);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_provenance() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+        store
+            .add_variant("license-1", LicenseType::Header, "aaaaa bbbbb".into())
+            .unwrap();
+
+        let provenance = store.provenance("license-1").unwrap();
+        assert_eq!("user", provenance.source);
+        assert_eq!(None, provenance.path);
+        assert_eq!(
+            "user",
+            store
+                .variant_provenance("license-1", LicenseType::Header, 0)
+                .unwrap()
+                .source
+        );
+        assert_eq!(
+            None,
+            store.variant_provenance("license-1", LicenseType::Header, 1)
+        );
+
+        let custom = Provenance {
+            source: "internal-dataset".into(),
+            version: Some("2024.1".into()),
+            path: Some("licenses/license-1.txt".into()),
+            loaded_at: None,
+        };
+        store.set_provenance("license-1", custom.clone()).unwrap();
+        assert_eq!(Some(&custom), store.provenance("license-1"));
+        store
+            .set_variant_provenance("license-1", LicenseType::Header, 0, custom.clone())
+            .unwrap();
+        assert_eq!(
+            Some(&custom),
+            store.variant_provenance("license-1", LicenseType::Header, 0)
+        );
+        assert!(store
+            .set_variant_provenance("license-1", LicenseType::Alternate, 0, custom)
+            .is_err());
+    }
+}
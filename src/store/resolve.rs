@@ -0,0 +1,165 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::store::{base::Store, title::title_key};
+
+/// Words left out when comparing license names loosely, as they're often
+/// added or dropped ("Apache License, Version 2.0" for `Apache-2.0`).
+const NOISE_WORDS: &[&str] = &["license", "licence", "the", "version", "ver", "v"];
+
+/// Reduce a license name to the words and version numbers that tell it
+/// apart, so that differently written names of the same license compare
+/// equal.
+///
+/// Letters and digits are split apart (`GPLv3` is `gpl 3`), trailing `.0`s
+/// are dropped from versions (`2.0` is `2`), and a trailing `+` means "or
+/// later".
+fn name_key(name: &str) -> String {
+    let mut words: Vec<String> = Vec::new();
+    let mut word = String::new();
+    let mut in_number = false;
+    for c in name.chars().flat_map(char::to_lowercase) {
+        let continues = match in_number {
+            true => c.is_ascii_digit() || c == '.',
+            false => c.is_alphabetic(),
+        };
+        if !continues && !word.is_empty() {
+            // "v" run into a version, as in "GPLv3"
+            if c.is_ascii_digit() && word.len() > 1 && word.ends_with('v') {
+                word.pop();
+            }
+            words.push(std::mem::take(&mut word));
+        }
+        if c.is_ascii_digit() || c.is_alphabetic() || (in_number && c == '.') {
+            in_number = c.is_ascii_digit() || c == '.';
+            word.push(c);
+        } else if c == '+' {
+            words.extend(["or".to_owned(), "later".to_owned()]);
+        }
+    }
+    words.push(word);
+
+    words
+        .into_iter()
+        .map(|word| {
+            let mut word = word.trim_end_matches('.');
+            while let Some(shorter) = word.strip_suffix(".0") {
+                word = shorter;
+            }
+            word.to_owned()
+        })
+        .filter(|word| !word.is_empty() && !NOISE_WORDS.contains(&word.as_str()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl Store {
+    /// Find the license a loosely written name most likely refers to, such as
+    /// one typed by a person or found in package metadata.
+    ///
+    /// Names are tried in turn:
+    ///
+    /// * exactly, as with `resolve_alias`;
+    /// * as a license title, like "Apache License, Version 2.0" (see
+    ///   `add_title`);
+    /// * ignoring case, punctuation, words like "license" and "version", and
+    ///   trailing zeroes in version numbers, against license names and
+    ///   aliases, so "Apache 2" is `Apache-2.0` and "GPLv3+" is
+    ///   `GPL-3.0-or-later`;
+    /// * the same, as the `-only` form of the name, so "GPLv3" is
+    ///   `GPL-3.0-only`.
+    ///
+    /// Where a step finds more than one license, the first by name is
+    /// returned. Returns `None` if nothing matches.
+    pub fn resolve_fuzzy(&self, name: &str) -> Option<&str> {
+        if let Some(license) = self.resolve_alias(name) {
+            return Some(license);
+        }
+        if let Some(licenses) = title_key(name).and_then(|key| self.titles.get(&key)) {
+            if let Some(license) = licenses.iter().min() {
+                return Some(license);
+            }
+        }
+
+        let key = name_key(name);
+        if key.is_empty() {
+            return None;
+        }
+        let only = format!("{} only", key);
+        for wanted in [&key, &only] {
+            let found = self
+                .licenses
+                .iter()
+                .filter(|(license, entry)| {
+                    std::iter::once(*license)
+                        .chain(&entry.aliases)
+                        .any(|candidate| name_key(candidate) == *wanted)
+                })
+                .map(|(license, _)| license.as_str())
+                .min();
+            if found.is_some() {
+                return found;
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_keys() {
+        for (name, key) in &[
+            ("Apache-2.0", "apache 2"),
+            ("Apache License, Version 2.0", "apache 2"),
+            ("apache2", "apache 2"),
+            ("GPLv3", "gpl 3"),
+            ("GPL-3.0-or-later", "gpl 3 or later"),
+            ("GPL-3.0+", "gpl 3 or later"),
+            ("LGPL-2.1-only", "lgpl 2.1 only"),
+            ("0BSD", "0 bsd"),
+            ("MIT License", "mit"),
+            ("--", ""),
+        ] {
+            assert_eq!(*key, name_key(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn resolve_fuzzy() {
+        let mut store = Store::new();
+        for name in &[
+            "Apache-2.0",
+            "GPL-3.0-only",
+            "GPL-3.0-or-later",
+            "LGPL-2.1-only",
+            "MIT",
+        ] {
+            store.add_license((*name).into(), format!("text of {}", name).as_str().into());
+        }
+        store.set_aliases("MIT", vec!["Expat".to_owned()]).unwrap();
+        store
+            .add_title("GPL-3.0-only", "GNU General Public License v3.0 only")
+            .unwrap();
+
+        for (query, license) in &[
+            ("Apache-2.0", Some("Apache-2.0")),
+            ("apache-2.0", Some("Apache-2.0")),
+            ("Apache 2", Some("Apache-2.0")),
+            ("Apache License, Version 2.0", Some("Apache-2.0")),
+            ("GPLv3", Some("GPL-3.0-only")),
+            ("GPLv3+", Some("GPL-3.0-or-later")),
+            ("GPL 3.0 or later", Some("GPL-3.0-or-later")),
+            ("GNU General Public License v3.0 only", Some("GPL-3.0-only")),
+            ("LGPL 2.1", Some("LGPL-2.1-only")),
+            ("MIT License", Some("MIT")),
+            ("expat", Some("MIT")),
+            ("BSD", None),
+            ("", None),
+        ] {
+            assert_eq!(*license, store.resolve_fuzzy(query), "{}", query);
+        }
+    }
+}
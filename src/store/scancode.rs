@@ -0,0 +1,255 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    ffi::OsStr,
+    fs::{read_dir, read_to_string},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{format_err, Error};
+use log::{debug, info};
+use serde::{de::Error as _, Deserialize, Deserializer};
+
+use crate::{
+    license::TextData,
+    store::{base::Store, family::derive_family, provenance::Provenance},
+};
+
+/// The metadata of a license in the ScanCode LicenseDB. Only what's used
+/// here is read; the rest is ignored.
+#[derive(Deserialize)]
+struct ScancodeLicense {
+    key: String,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    short_name: Option<String>,
+    #[serde(default)]
+    spdx_license_key: Option<String>,
+    #[serde(default)]
+    other_spdx_license_keys: Vec<String>,
+    #[serde(default, deserialize_with = "yes_no")]
+    is_deprecated: bool,
+    #[serde(default, deserialize_with = "yes_no")]
+    is_exception: bool,
+    #[serde(default, deserialize_with = "yes_no")]
+    is_unknown: bool,
+    #[serde(default, deserialize_with = "yes_no")]
+    is_generic: bool,
+}
+
+/// Read a flag, which ScanCode writes as `yes` or `no`.
+fn yes_no<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Flag {
+        Bool(bool),
+        Word(String),
+    }
+
+    match Flag::deserialize(deserializer)? {
+        Flag::Bool(flag) => Ok(flag),
+        Flag::Word(word) => match word.as_str() {
+            "yes" | "true" => Ok(true),
+            "no" | "false" => Ok(false),
+            _ => Err(D::Error::custom(format!(
+                "expected yes or no, found {}",
+                word
+            ))),
+        },
+    }
+}
+
+impl ScancodeLicense {
+    /// The name to store the license under: its SPDX identifier, or else a
+    /// `LicenseRef-` built from its key, as ScanCode reports it.
+    fn name(&self) -> String {
+        match self.spdx_license_key {
+            Some(ref id) => id.clone(),
+            None => format!("LicenseRef-scancode-{}", self.key),
+        }
+    }
+}
+
+/// Split a `.LICENSE` file into its metadata and text. Current LicenseDB
+/// releases put the metadata in YAML front matter; older ones keep it in a
+/// `.yml` file alongside.
+fn read_license(path: &Path) -> Result<(ScancodeLicense, String), Error> {
+    let contents = read_to_string(path)?;
+    let (metadata, text) = match contents.strip_prefix("---\n") {
+        Some(rest) => {
+            let end = rest
+                .find("\n---\n")
+                .ok_or_else(|| format_err!("unterminated front matter"))?;
+            (rest[..end].to_owned(), rest[end + 5..].to_owned())
+        }
+        None => (read_to_string(path.with_extension("yml"))?, contents),
+    };
+    let license = serde_yaml::from_str(&metadata)?;
+    Ok((license, text))
+}
+
+/// List the `.LICENSE` files in a directory, in a stable order.
+fn license_paths(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut paths: Vec<_> = read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && p.extension().unwrap_or_else(|| OsStr::new("")) == "LICENSE")
+        .collect();
+
+    // sort without extensions; otherwise dashes and dots muck it up
+    paths.sort_by(|a, b| a.file_stem().unwrap().cmp(b.file_stem().unwrap()));
+    Ok(paths)
+}
+
+impl Store {
+    /// Fill the store with licenses from the ScanCode LicenseDB.
+    ///
+    /// `dir` is the `licenses` directory of ScanCode's `scancode-toolkit`
+    /// repository (under `src/licensedcode/data`), holding a `.LICENSE` file
+    /// for each license. ScanCode knows about many more licenses and variants
+    /// than SPDX does, so this helps with uncommon texts, at the cost of a
+    /// much larger store.
+    ///
+    /// Licenses are named by their SPDX identifier where they have one, and
+    /// otherwise by ScanCode's `LicenseRef-scancode-` identifier; ScanCode's
+    /// own key and any other SPDX identifiers become aliases. Exceptions are
+    /// added as with `add_exception`. Deprecated, unknown, and generic
+    /// licenses are skipped.
+    ///
+    /// Licenses already in the store are left as they are, so this can be
+    /// combined with `load_spdx`: load SPDX first, and ScanCode then only
+    /// fills in what SPDX lacks. `include_texts` is as in `load_spdx`.
+    ///
+    /// This is only available with the `scancode` feature.
+    pub fn load_scancode(&mut self, dir: &Path, include_texts: bool) -> Result<(), Error> {
+        for path in license_paths(dir)? {
+            let (license, text) =
+                read_license(&path).map_err(|e| format_err!("{}: {}", path.display(), e))?;
+            let name = license.name();
+
+            if license.is_deprecated || license.is_unknown || license.is_generic {
+                debug!("Skipping {} (deprecated, unknown, or generic)", name);
+                continue;
+            }
+            if text.trim().is_empty() {
+                debug!("Skipping {} (no text)", name);
+                continue;
+            }
+
+            let content = match include_texts {
+                true => TextData::new(&text),
+                false => TextData::new(&text).without_text(),
+            };
+            if license.is_exception {
+                info!("Processing exception {}", name);
+                if !self.exceptions.contains_key(&name) {
+                    self.add_exception(name, content);
+                }
+                continue;
+            }
+            if self.licenses.contains_key(&name) {
+                debug!("Skipping {} (already present)", name);
+                continue;
+            }
+
+            info!("Processing {}", name);
+            self.add_license(name.clone(), content);
+            self.set_provenance(
+                &name,
+                Provenance {
+                    path: Some(path.display().to_string()),
+                    ..Provenance::new("scancode")
+                },
+            )?;
+
+            let aliases = std::iter::once(license.key.clone())
+                .chain(license.other_spdx_license_keys.iter().cloned())
+                .filter(|alias| *alias != name)
+                .collect();
+            self.set_aliases(&name, aliases)?;
+            let family = license.spdx_license_key.as_deref().and_then(derive_family);
+            self.set_family(&name, family)?;
+            for title in license.short_name.iter().chain(&license.name) {
+                self.add_title(&name, title).ok();
+            }
+        }
+
+        self.pack_all();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_scancode() {
+        let dir = std::env::temp_dir().join(format!("askalono-scancode-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("acme-internal.LICENSE"),
+            "---\nkey: acme-internal\nshort_name: Acme Internal License\n\
+             category: Proprietary Free\n---\n\n\
+             This software is for use inside Acme only.\n",
+        )
+        .unwrap();
+        // older releases keep metadata in a separate file
+        std::fs::write(
+            dir.join("mit.LICENSE"),
+            "Permission is granted to do anything with this software.\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("mit.yml"),
+            "key: mit\nspdx_license_key: MIT\nother_spdx_license_keys:\n  - LicenseRef-MIT-Bad\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("old-thing.LICENSE"),
+            "---\nkey: old-thing\nis_deprecated: yes\n---\nAn old license.\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("acme-exception.LICENSE"),
+            "---\nkey: acme-exception\nis_exception: yes\n---\nAcme grants more.\n",
+        )
+        .unwrap();
+
+        let mut store = Store::new();
+        store.add_license("MIT".into(), "something else entirely".into());
+        let result = store.load_scancode(&dir, false);
+        std::fs::remove_dir_all(&dir).unwrap();
+        result.unwrap();
+
+        let mut names: Vec<&String> = store.licenses().collect();
+        names.sort();
+        assert_eq!(vec!["LicenseRef-scancode-acme-internal", "MIT"], names);
+        assert_eq!(
+            &vec!["acme-internal".to_owned()],
+            store.aliases("LicenseRef-scancode-acme-internal").unwrap()
+        );
+        assert_eq!(None, store.family_of("LicenseRef-scancode-acme-internal"));
+        assert_eq!(
+            "scancode",
+            store
+                .provenance("LicenseRef-scancode-acme-internal")
+                .unwrap()
+                .source
+        );
+        // what was already there is kept
+        assert_eq!("user", store.provenance("MIT").unwrap().source);
+        assert!(store
+            .exceptions()
+            .any(|name| name == "LicenseRef-scancode-acme-exception"));
+
+        let matched = store.analyze(&"This software is for use inside Acme only.".into());
+        assert_eq!("LicenseRef-scancode-acme-internal", matched.name);
+        assert_eq!(1.0, matched.score);
+    }
+}
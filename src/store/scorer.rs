@@ -0,0 +1,101 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ngram::NgramSet, store::base::Store};
+
+/// How `Store::analyze_with` scores a text against each license, from 0 to
+/// 1. See `AnalysisOptions::scorer`.
+///
+/// Every scorer compares the texts' n-grams (word pairs), with repeats
+/// counted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scorer {
+    /// The Sørensen–Dice coefficient: twice the number of shared n-grams,
+    /// over the total number in both texts. This is what `TextData::match_score`
+    /// computes, and the default.
+    #[default]
+    Dice,
+    /// The Jaccard index: the number of shared n-grams over the number in
+    /// either text. It ranks licenses as Dice does, but scores anything short
+    /// of a perfect match lower.
+    Jaccard,
+    /// The cosine similarity of the texts' n-gram counts. Repeated phrases
+    /// weigh more heavily than with Dice.
+    Cosine,
+    /// The fraction of the license's n-grams found in the text, as
+    /// `TextData::containment_score` computes it. This finds a license (or,
+    /// more usefully, a short header) inside a much larger text, which Dice
+    /// scores poorly, but says nothing of what else the text holds: a file
+    /// with a header anywhere in it scores 1.
+    Containment,
+}
+
+impl Scorer {
+    /// Score a text's n-grams against a license's.
+    pub(crate) fn score(self, license: &NgramSet, text: &NgramSet) -> f32 {
+        match self {
+            Scorer::Dice => license.dice(text),
+            Scorer::Jaccard => license.jaccard(text),
+            Scorer::Cosine => license.cosine(text),
+            Scorer::Containment => license.containment(text),
+        }
+    }
+}
+
+impl Store {
+    /// Score a text's n-grams against a license's with the given scorer and
+    /// the store's weights.
+    pub(crate) fn score(&self, scorer: Scorer, license: &NgramSet, text: &NgramSet) -> f32 {
+        match (scorer, &self.idf) {
+            (Scorer::Dice, Some(idf)) => {
+                license.weighted_dice(text, |gram| idf.weight(gram), idf.unseen)
+            }
+            (scorer, _) => scorer.score(license, text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{license::TextData, store::options::AnalysisOptions};
+
+    #[test]
+    fn scorers() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc ddddd".into());
+        store.add_license(
+            "license-2".into(),
+            "eeeee fffff ggggg hhhhh iiiii jjjjj kkkkk lllll mmmmm nnnnn".into(),
+        );
+
+        // a short license alongside most of a longer one
+        let text = TextData::from(
+            "ooooo ppppp qqqqq rrrrr sssss ttttt\naaaaa bbbbb ccccc ddddd\n\
+             eeeee fffff ggggg hhhhh iiiii jjjjj kkkkk lllll",
+        );
+        let dice = store.analyze(&text);
+        assert_eq!("license-2", dice.name);
+        assert_eq!(text.match_score(dice.data), dice.score);
+
+        let mut options = AnalysisOptions {
+            scorer: Scorer::Containment,
+            ..Default::default()
+        };
+        let contained = store.analyze_with(&text, &options).unwrap();
+        assert_eq!("license-1", contained.name);
+        assert_eq!(1.0, contained.score);
+
+        for scorer in [Scorer::Jaccard, Scorer::Cosine] {
+            options.scorer = scorer;
+            let matched = store
+                .analyze_with(&"aaaaa bbbbb ccccc ddddd".into(), &options)
+                .unwrap();
+            assert_eq!("license-1", matched.name);
+            assert!((matched.score - 1.0).abs() < 1e-6, "{:?}", scorer);
+        }
+    }
+}
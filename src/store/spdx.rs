@@ -2,20 +2,130 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::{
+    collections::{HashMap, HashSet},
     ffi::OsStr,
-    fs::{read_dir, File},
-    io::prelude::*,
-    path::Path,
+    fs::{read_dir, read_to_string},
+    hash::Hasher,
+    path::{Path, PathBuf},
 };
 
 use anyhow::{format_err, Error};
 use log::{debug, info};
+use serde_json::{from_str, Value};
 
 use crate::{
     license::TextData,
-    store::base::{LicenseEntry, Store},
+    memo::FnvHasher,
+    store::{
+        base::{LicenseEntry, Store},
+        deprecation::spdx_successor,
+        family::derive_family,
+        provenance::Provenance,
+        title::find_title,
+    },
 };
 
+/// A summary of the changes made by `Store::update_from_spdx`, by SPDX
+/// license ID. Each list is sorted.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SpdxUpdate {
+    /// Licenses that weren't in the store before.
+    pub added: Vec<String>,
+    /// Licenses whose data changed, and were processed again.
+    pub changed: Vec<String>,
+    /// Licenses that were removed from the dataset or deprecated, and were
+    /// removed from the store.
+    pub removed: Vec<String>,
+}
+
+/// How `Store::load_spdx_with_options` loads SPDX license files.
+#[derive(Clone, Debug, Default)]
+pub struct SpdxLoadOptions {
+    /// Keep normalized license text data in the store. See
+    /// `Store::load_spdx`.
+    pub include_texts: bool,
+    /// Also keep the verbatim text of each license. See
+    /// `Store::load_spdx_with_originals`.
+    pub include_originals: bool,
+    /// Load deprecated licenses (like `GPL-2.0`) instead of skipping them.
+    /// They're marked as such, with what replaces them where it's well known;
+    /// see `Store::is_deprecated` and `Store::superseded_by`. A deprecated
+    /// license with the same text as a current one is only an alias of it.
+    pub include_deprecated: bool,
+}
+
+/// A license file read from an SPDX directory.
+struct SpdxFile {
+    id: String,
+    path: PathBuf,
+    data: Value,
+    /// A hash of the file's contents, to tell whether it's changed.
+    digest: u64,
+}
+
+/// How SPDX license files are loaded into a store, along with the list
+/// version they're from.
+struct SpdxOptions {
+    load: SpdxLoadOptions,
+    list_version: Option<String>,
+}
+
+/// Read every license file in an SPDX `json/details` directory, in a stable
+/// order, along with the license list version. Deprecated licenses come
+/// last, so that current licenses with the same text aren't made aliases of
+/// them.
+fn read_spdx_dir(dir: &Path) -> Result<(Vec<SpdxFile>, Option<String>), Error> {
+    let files = json_paths(dir)?
+        .into_iter()
+        .map(|path| {
+            let data = read_to_string(&path)?;
+            let mut hasher = FnvHasher::default();
+            hasher.write(data.as_bytes());
+            let data: Value = from_str(&data)?;
+            let id = data["licenseId"]
+                .as_str()
+                .ok_or_else(|| format_err!("missing licenseId"))?
+                .to_owned();
+            Ok(SpdxFile {
+                id,
+                path,
+                data,
+                digest: hasher.finish(),
+            })
+        })
+        .collect::<Result<Vec<SpdxFile>, Error>>()?;
+    let (current, deprecated): (Vec<_>, Vec<_>) =
+        files.into_iter().partition(|file| !file.is_deprecated());
+    let files = current.into_iter().chain(deprecated).collect();
+
+    // the list version lives in the index next to the details directory
+    let list_version = read_to_string(dir.join("../licenses.json"))
+        .ok()
+        .and_then(|index| from_str::<Value>(&index).ok())
+        .and_then(|index| index["licenseListVersion"].as_str().map(str::to_owned));
+
+    Ok((files, list_version))
+}
+
+impl SpdxFile {
+    fn is_deprecated(&self) -> bool {
+        self.data["isDeprecatedLicenseId"].as_bool() == Some(true)
+    }
+}
+
+/// List the JSON files in a directory, in a stable order.
+fn json_paths(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut paths: Vec<_> = read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file() && p.extension().unwrap_or_else(|| OsStr::new("")) == "json")
+        .collect();
+
+    // sort without extensions; otherwise dashes and dots muck it up
+    paths.sort_by(|a, b| a.file_stem().unwrap().cmp(b.file_stem().unwrap()));
+    Ok(paths)
+}
+
 impl Store {
     /// Fill the store with SPDX JSON data.
     ///
@@ -31,77 +141,316 @@ impl Store {
     /// the store. This yields a larger store when serialized, but has the
     /// benefit of allowing you to diff your result against what askalono has
     /// stored.
+    ///
+    /// Where a license has a `standardLicenseTemplate` (or
+    /// `standardLicenseHeaderTemplate`), its variable and optional text is
+    /// left out of matching, as the SPDX matching guidelines describe. See
+    /// `TextData::with_template`.
+    ///
+    /// License exceptions are loaded too, from the `json/exceptions`
+    /// directory beside `json/details`, if it exists. See `add_exception`.
     pub fn load_spdx(&mut self, dir: &Path, include_texts: bool) -> Result<(), Error> {
-        use serde_json::{from_str, Value};
+        let options = SpdxLoadOptions {
+            include_texts,
+            ..Default::default()
+        };
+        self.load_spdx_with_options(dir, &options)
+    }
 
-        // locate all json files in the directory
-        let mut paths: Vec<_> = read_dir(dir)?
-            .filter_map(|e| e.ok())
-            .map(|e| e.path())
-            .filter(|p| p.is_file() && p.extension().unwrap_or_else(|| OsStr::new("")) == "json")
-            .collect();
+    /// Fill the store with SPDX JSON data, keeping the verbatim text of each
+    /// license in addition to its normalized form.
+    ///
+    /// Normalized texts are good enough for diffing and display, but not for
+    /// reproducing a license exactly (such as in an attribution file).
+    /// Verbatim texts are available from `original_text`, at the cost of an
+    /// even larger store. Otherwise identical to `load_spdx`.
+    pub fn load_spdx_with_originals(
+        &mut self,
+        dir: &Path,
+        include_texts: bool,
+    ) -> Result<(), Error> {
+        let options = SpdxLoadOptions {
+            include_texts,
+            include_originals: true,
+            ..Default::default()
+        };
+        self.load_spdx_with_options(dir, &options)
+    }
+
+    /// Fill the store with SPDX JSON data, choosing what's loaded. See
+    /// `SpdxLoadOptions`; otherwise identical to `load_spdx`.
+    pub fn load_spdx_with_options(
+        &mut self,
+        dir: &Path,
+        options: &SpdxLoadOptions,
+    ) -> Result<(), Error> {
+        let (files, list_version) = read_spdx_dir(dir)?;
+        self.list_version = list_version.clone();
+        let options = SpdxOptions {
+            load: options.clone(),
+            list_version,
+        };
 
-        // sort without extensions; otherwise dashes and dots muck it up
-        paths.sort_by(|a, b| a.file_stem().unwrap().cmp(b.file_stem().unwrap()));
+        for file in &files {
+            self.load_spdx_file(file, &options)?;
+        }
+        self.load_spdx_exceptions(dir, options.load.include_texts)?;
 
-        for path in paths {
-            let mut f = File::open(path)?;
-            let mut data = String::new();
-            f.read_to_string(&mut data)?;
-            let val: Value = from_str(&data)?;
+        self.pack_all();
+        Ok(())
+    }
 
-            let name = val["licenseId"]
-                .as_str()
-                .ok_or_else(|| format_err!("missing licenseId"))?;
+    /// Update a store previously filled with `load_spdx` to match a newer
+    /// copy of the SPDX dataset.
+    ///
+    /// Only licenses whose JSON files were added, changed, or removed since
+    /// the store was filled are re-processed, which is much faster than
+    /// building a new store. Whether texts (and verbatim texts, and
+    /// deprecated licenses) are kept is decided by what the store already
+    /// has. Write the store back out with
+    /// `to_cache` afterwards.
+    ///
+    /// Exceptions are always loaded again, as there are few of them. Licenses
+    /// that didn't come from SPDX are left alone. Stores from caches
+    /// built before this was available have no record of their SPDX files, so
+    /// every license is treated as changed.
+    pub fn update_from_spdx(&mut self, dir: &Path) -> Result<SpdxUpdate, Error> {
+        let (files, list_version) = read_spdx_dir(dir)?;
+        self.list_version = list_version.clone();
+        let options = SpdxOptions {
+            load: SpdxLoadOptions {
+                include_texts: self
+                    .licenses
+                    .values()
+                    .any(|entry| entry.original.text_processed().is_some()),
+                include_originals: self
+                    .licenses
+                    .values()
+                    .any(|entry| entry.original_text.is_some()),
+                include_deprecated: files
+                    .iter()
+                    .any(|file| file.is_deprecated() && self.has_license_or_alias(&file.id)),
+            },
+            list_version,
+        };
+
+        let was_present: HashSet<String> = self
+            .spdx_digests
+            .keys()
+            .filter(|&id| self.has_license_or_alias(id))
+            .cloned()
+            .collect();
+
+        // forget everything that changed or disappeared from the dataset
+        let new_digests: HashMap<&str, u64> = files
+            .iter()
+            .map(|file| (file.id.as_str(), file.digest))
+            .collect();
+        let stale: Vec<String> = self
+            .spdx_digests
+            .iter()
+            .filter(|&(id, digest)| new_digests.get(id.as_str()) != Some(digest))
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in &stale {
+            self.forget_spdx(id);
+        }
 
-            let deprecated = val["isDeprecatedLicenseId"]
-                .as_bool()
-                .ok_or_else(|| format_err!("missing isDeprecatedLicenseId"))?;
-            if deprecated {
-                debug!("Skipping {} (deprecated)", name);
+        // then load anything not yet recorded. this includes aliases of
+        // changed licenses, which may no longer be identical
+        let mut update = SpdxUpdate::default();
+        for file in &files {
+            if self.spdx_digests.contains_key(&file.id) {
                 continue;
             }
+            self.load_spdx_file(file, &options)?;
+            let now_present = self.has_license_or_alias(&file.id);
+            match (was_present.contains(&file.id), now_present) {
+                (false, true) => update.added.push(file.id.clone()),
+                (true, true) if stale.contains(&file.id) => update.changed.push(file.id.clone()),
+                (true, false) => update.removed.push(file.id.clone()),
+                _ => {}
+            }
+        }
+        update.removed.extend(
+            stale
+                .into_iter()
+                .filter(|id| was_present.contains(id) && !new_digests.contains_key(id.as_str())),
+        );
+        update.added.sort();
+        update.changed.sort();
+        update.removed.sort();
+        self.load_spdx_exceptions(dir, options.load.include_texts)?;
+        if self.idf_weighting() {
+            self.set_idf_weighting(true);
+        }
+        if let Some(candidates) = self.candidate_index() {
+            self.set_candidate_index(Some(candidates));
+        }
+        if self.lsh_prefilter() {
+            self.set_lsh_prefilter(true);
+        }
+
+        self.pack_all();
+        Ok(update)
+    }
+
+    /// Load a single SPDX license file, recording its digest.
+    fn load_spdx_file(&mut self, file: &SpdxFile, options: &SpdxOptions) -> Result<(), Error> {
+        self.spdx_digests.insert(file.id.clone(), file.digest);
+
+        let val = &file.data;
+        let name = file.id.as_str();
+        let path = &file.path;
+        let include_texts = options.load.include_texts;
+        let provenance_of = |path: &Path| Provenance {
+            version: options.list_version.clone(),
+            path: Some(path.display().to_string()),
+            ..Provenance::new("spdx")
+        };
+
+        let deprecated = val["isDeprecatedLicenseId"]
+            .as_bool()
+            .ok_or_else(|| format_err!("missing isDeprecatedLicenseId"))?;
+        if deprecated && !options.load.include_deprecated {
+            debug!("Skipping {} (deprecated)", name);
+            return Ok(());
+        }
+
+        let text = val["licenseText"]
+            .as_str()
+            .ok_or_else(|| format_err!("missing licenseText"))?;
+        let header = val["standardLicenseHeader"].as_str();
+        let template = val["standardLicenseTemplate"].as_str();
+        let header_template = val["standardLicenseHeaderTemplate"].as_str();
+
+        info!("Processing {}", name);
+
+        let mut content = match include_texts {
+            true => TextData::new(text),
+            false => TextData::new(text).without_text(),
+        };
+        if let Some(template) = template {
+            content = content.with_template(template);
+        }
+        content.intern_words();
+        content.match_data.remove_grams(&self.stop_grams);
+
+        // check if an identical license is already present
+        let mut already_existed = false;
+        self.licenses.iter_mut().for_each(|(key, ref mut value)| {
+            if value.original.eq_data(&content) {
+                value.aliases.push(name.to_string());
+                info!("{} already stored; added as an alias for {}", name, key);
+                already_existed = true;
+            }
+        });
 
-            let text = val["licenseText"]
-                .as_str()
-                .ok_or_else(|| format_err!("missing licenseText"))?;
-            let header = val["standardLicenseHeader"].as_str();
+        if already_existed {
+            return Ok(());
+        }
 
-            info!("Processing {}", name);
+        let license = self
+            .licenses
+            .entry(name.to_owned())
+            .or_insert_with(|| LicenseEntry::new(content, Some(provenance_of(path))));
+        license.family = derive_family(name);
+        if options.load.include_originals {
+            license.original_text = Some(text.to_owned());
+        }
+        if deprecated {
+            license.deprecated = true;
+            license.superseded_by = spdx_successor(name);
+        }
 
-            let content = match include_texts {
-                true => TextData::new(text),
-                false => TextData::new(text).without_text(),
+        if let Some(header_text) = header {
+            let mut header_data = match include_texts {
+                false => TextData::new(header_text),
+                true => TextData::new(header_text).without_text(),
             };
+            if let Some(template) = header_template {
+                header_data = header_data.with_template(template);
+            }
+            header_data.intern_words();
+            header_data.match_data.remove_grams(&self.stop_grams);
+            license.headers = vec![header_data];
+            license.header_labels = vec![Some("standard license header".to_owned())];
+            license.header_provenance = vec![Some(provenance_of(path))];
+            license.header_template = Some(header_text.to_owned());
+        }
 
-            // check if an identical license is already present
-            let mut already_existed = false;
-            self.licenses.iter_mut().for_each(|(key, ref mut value)| {
-                if value.original.eq_data(&content) {
-                    value.aliases.push(name.to_string());
-                    info!("{} already stored; added as an alias for {}", name, key);
-                    already_existed = true;
-                }
-            });
-
-            if already_existed {
+        // index both the text's own title line and SPDX's full name;
+        // names too long to be titles just aren't indexed
+        let titles = find_title(text.lines())
+            .into_iter()
+            .chain(val["name"].as_str().map(str::to_owned));
+        for title in titles {
+            self.add_title(name, &title).ok();
+        }
+
+        Ok(())
+    }
+
+    /// Load every exception in the SPDX `json/exceptions` directory beside a
+    /// `json/details` directory, if there is one. Deprecated exceptions are
+    /// removed from the store.
+    fn load_spdx_exceptions(&mut self, dir: &Path, include_texts: bool) -> Result<(), Error> {
+        let dir = dir.join("../exceptions");
+        if !dir.is_dir() {
+            return Ok(());
+        }
+
+        for path in json_paths(&dir)? {
+            let val: Value = from_str(&read_to_string(&path)?)?;
+            let name = val["licenseExceptionId"]
+                .as_str()
+                .ok_or_else(|| format_err!("missing licenseExceptionId"))?;
+            if val["isDeprecatedLicenseId"].as_bool() == Some(true) {
+                debug!("Skipping exception {} (deprecated)", name);
+                self.exceptions.remove(name);
                 continue;
             }
+            let text = val["licenseExceptionText"]
+                .as_str()
+                .ok_or_else(|| format_err!("missing licenseExceptionText"))?;
 
-            let license = self
-                .licenses
-                .entry(name.to_owned())
-                .or_insert_with(|| LicenseEntry::new(content));
-
-            if let Some(header_text) = header {
-                let header_data = match include_texts {
-                    false => TextData::new(header_text),
-                    true => TextData::new(header_text).without_text(),
-                };
-                license.headers = vec![header_data];
+            // many exceptions open with "the copyright holders give you
+            // permission...", which would be taken for a copyright statement
+            // and dropped if it were the first line
+            info!("Processing exception {}", name);
+            let text = format!("\n{}", text);
+            let content = match include_texts {
+                true => TextData::new(&text),
+                false => TextData::new(&text).without_text(),
+            };
+            self.add_exception(name.to_owned(), content);
+        }
+        Ok(())
+    }
+
+    /// Remove an SPDX license from the store, whether it's a license of its
+    /// own or an alias of another. Aliases of a removed license are forgotten
+    /// too, so that they're loaded again by `update_from_spdx`.
+    fn forget_spdx(&mut self, id: &str) {
+        self.spdx_digests.remove(id);
+        self.forget_derived();
+        if let Some(entry) = self.licenses.remove(id) {
+            self.remove_titles(id);
+            for alias in &entry.aliases {
+                self.spdx_digests.remove(alias);
+            }
+        } else {
+            for entry in self.licenses.values_mut() {
+                entry.aliases.retain(|alias| alias != id);
             }
         }
+    }
 
-        Ok(())
+    fn has_license_or_alias(&self, id: &str) -> bool {
+        self.licenses.contains_key(id)
+            || self
+                .licenses
+                .values()
+                .any(|entry| entry.aliases.iter().any(|alias| alias == id))
     }
 }
@@ -0,0 +1,550 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, HashMap},
+    io::Write,
+    slice,
+    sync::Arc,
+};
+
+use anyhow::{format_err, Error};
+
+use crate::{
+    intern::{self, intern_static},
+    license::TextData,
+    ngram::{Gram, NgramSet},
+    store::{
+        base::{LicenseEntry, Store},
+        minhash::mix,
+        provenance::Provenance,
+    },
+};
+
+/// A store compiled into a program as static data.
+///
+/// Generate the Rust source for one at build time with `Store::write_static`,
+/// include it in your crate, and turn it into a `Store` with
+/// `Store::from_static`. Unlike `Store::from_cache`, nothing is decompressed
+/// or decoded at runtime: each text's n-grams are kept in a perfect hash
+/// table that's used where it is, so loading only sets up the store's
+/// per-license details and adds its words to the token table.
+///
+/// The fields are public so that generated code can construct this; there's
+/// no need to use them directly.
+///
+/// # Examples
+///
+/// In `build.rs`:
+///
+/// ```rust,no_run
+/// # use std::{env, fs::File, path::Path};
+/// # use askalono::Store;
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let store = Store::from_cache(File::open("askalono-cache.bin.zstd")?)?;
+/// let out = Path::new(&env::var("OUT_DIR")?).join("askalono-store.rs");
+/// store.write_static(File::create(out)?)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// In your crate:
+///
+/// ```rust,ignore
+/// static STORE_DATA: askalono::StaticStore =
+///     include!(concat!(env!("OUT_DIR"), "/askalono-store.rs"));
+///
+/// let store = askalono::Store::from_static(&STORE_DATA)?;
+/// ```
+#[derive(Debug)]
+pub struct StaticStore {
+    /// Every word in the store's n-grams, sorted. Texts refer to words by
+    /// index.
+    pub tokens: &'static [&'static str],
+    /// The licenses in the store.
+    pub licenses: &'static [StaticLicense],
+    /// N-grams excluded from scoring; see `Store::add_stop_grams`.
+    pub stop_grams: &'static [&'static str],
+    /// Normalized title lines and the licenses they name; see
+    /// `Store::add_title`.
+    pub titles: &'static [(&'static str, &'static [&'static str])],
+    /// License exceptions and their n-grams; see `Store::add_exception`.
+    pub exceptions: &'static [(&'static str, StaticCounts)],
+    /// Whether n-grams are weighted when scoring; the weights are computed
+    /// again when the store is loaded. See `Store::set_idf_weighting`.
+    pub idf_weighting: bool,
+}
+
+/// The n-grams of a text in a `StaticStore`, in a perfect hash table: each
+/// gram has a slot of its own, found by hashing it with the seed of its
+/// bucket, so looking one up takes a single probe.
+#[derive(Debug)]
+pub struct StaticCounts {
+    /// The seed of each bucket, chosen so that its grams land in distinct
+    /// slots.
+    pub seeds: &'static [u32],
+    /// Each gram, as the indices in `StaticStore::tokens` of its words,
+    /// followed by its count.
+    pub slots: &'static [(u32, u32, u32)],
+    /// The total of the counts.
+    pub size: usize,
+}
+
+/// How many grams share a bucket of a `StaticCounts` table, on average.
+/// Larger buckets need fewer seeds, but take longer to place.
+const BUCKET_SIZE: usize = 4;
+
+/// Hash a gram by the indices of its words.
+fn gram_hash(first: u32, second: u32) -> u64 {
+    mix(((first as u64) << 32) | second as u64)
+}
+
+/// The bucket of a gram with the given hash.
+fn bucket_of(hash: u64, buckets: usize) -> usize {
+    ((hash >> 32) % buckets as u64) as usize
+}
+
+/// The slot of a gram with the given hash, when its bucket has `seed`.
+fn slot_of(hash: u64, seed: u32, slots: usize) -> usize {
+    (mix(hash ^ (seed as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15)) % slots as u64) as usize
+}
+
+impl StaticCounts {
+    /// Get the count of a gram by the indices of its words.
+    fn get(&self, first: u32, second: u32) -> u32 {
+        if self.slots.is_empty() {
+            return 0;
+        }
+        let hash = gram_hash(first, second);
+        let seed = self.seeds[bucket_of(hash, self.seeds.len())];
+        match self.slots[slot_of(hash, seed, self.slots.len())] {
+            (f, s, count) if (f, s) == (first, second) => count,
+            _ => 0,
+        }
+    }
+}
+
+/// Lay out grams, given by the indices of their words along with their
+/// counts, in a perfect hash table with a slot for each. Returns the seed of
+/// each bucket and the slots.
+fn perfect_hash(grams: &[(u32, u32, u32)]) -> (Vec<u32>, Vec<(u32, u32, u32)>) {
+    if grams.is_empty() {
+        return (Vec::new(), Vec::new());
+    }
+    let hashes: Vec<u64> = grams.iter().map(|&(f, s, _)| gram_hash(f, s)).collect();
+    let buckets = grams.len().div_ceil(BUCKET_SIZE);
+    let mut members = vec![Vec::new(); buckets];
+    for (i, &hash) in hashes.iter().enumerate() {
+        members[bucket_of(hash, buckets)].push(i);
+    }
+
+    // place the largest buckets first, while there are plenty of free slots
+    let mut order: Vec<usize> = (0..buckets).collect();
+    order.sort_by_key(|&bucket| Reverse(members[bucket].len()));
+
+    let len = grams.len();
+    let mut seeds = vec![0; buckets];
+    let mut slots = vec![(0, 0, 0); len];
+    let mut taken = vec![false; len];
+    let mut chosen = Vec::with_capacity(BUCKET_SIZE);
+    for bucket in order {
+        let members = &members[bucket];
+        if members.is_empty() {
+            break;
+        }
+        let seed = (0..=u32::MAX)
+            .find(|&seed| {
+                chosen.clear();
+                members.iter().all(|&i| {
+                    let slot = slot_of(hashes[i], seed, len);
+                    let free = !taken[slot] && !chosen.contains(&slot);
+                    chosen.push(slot);
+                    free
+                })
+            })
+            .expect("some seed places every gram of a bucket");
+        for (&i, &slot) in members.iter().zip(&chosen) {
+            taken[slot] = true;
+            slots[slot] = grams[i];
+        }
+        seeds[bucket] = seed;
+    }
+    (seeds, slots)
+}
+
+/// The words of a `StaticStore`, and their numbers in the token table.
+#[derive(Debug)]
+struct StaticTokens {
+    /// The number of each word in `StaticStore::tokens`.
+    ids: Vec<u32>,
+    /// The index in `StaticStore::tokens` of each word number, or
+    /// `u32::MAX` for words not in the store.
+    indices: Vec<u32>,
+}
+
+impl StaticTokens {
+    fn new(tokens: &[&'static str]) -> Result<StaticTokens, Error> {
+        let ids = intern_static(tokens)?;
+        let mut indices = vec![u32::MAX; intern::len() as usize];
+        for (index, &id) in ids.iter().enumerate() {
+            indices[id as usize] = index as u32;
+        }
+        Ok(StaticTokens { ids, indices })
+    }
+
+    fn index(&self, id: u32) -> Option<u32> {
+        self.indices
+            .get(id as usize)
+            .copied()
+            .filter(|&index| index != u32::MAX)
+    }
+}
+
+/// The n-grams of a text in a `StaticStore`, as held by an `NgramSet`.
+#[derive(Clone, Debug)]
+pub(crate) struct StaticGrams {
+    counts: &'static StaticCounts,
+    tokens: Arc<StaticTokens>,
+}
+
+impl StaticGrams {
+    pub(crate) fn get(&self, gram: Gram) -> u32 {
+        let (first, second) = gram.ids();
+        match (self.tokens.index(first), self.tokens.index(second)) {
+            (Some(first), Some(second)) => self.counts.get(first, second),
+            _ => 0,
+        }
+    }
+
+    /// The number of distinct grams.
+    pub(crate) fn distinct(&self) -> usize {
+        self.counts.slots.len()
+    }
+
+    /// The total of the counts.
+    pub(crate) fn size(&self) -> usize {
+        self.counts.size
+    }
+
+    pub(crate) fn iter(&self) -> StaticIter<'_> {
+        StaticIter {
+            slots: self.counts.slots.iter(),
+            ids: &self.tokens.ids,
+        }
+    }
+}
+
+/// An iterator over the grams of a `StaticGrams` and their counts.
+pub struct StaticIter<'a> {
+    slots: slice::Iter<'static, (u32, u32, u32)>,
+    ids: &'a [u32],
+}
+
+impl Iterator for StaticIter<'_> {
+    type Item = (Gram, u32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.slots.next().map(|&(first, second, count)| {
+            let gram = Gram::new(self.ids[first as usize], self.ids[second as usize]);
+            (gram, count)
+        })
+    }
+}
+
+/// A license in a `StaticStore`.
+#[derive(Debug)]
+pub struct StaticLicense {
+    /// The name of the license.
+    pub name: &'static str,
+    /// Other names of the license.
+    pub aliases: &'static [&'static str],
+    /// The family of the license, if any.
+    pub family: Option<&'static str>,
+    /// The n-grams of the license text.
+    pub original: StaticCounts,
+    /// The n-grams of the license's headers, along with their labels.
+    pub headers: &'static [(StaticCounts, Option<&'static str>)],
+    /// The n-grams of the license's alternates, along with their labels.
+    pub alternates: &'static [(StaticCounts, Option<&'static str>)],
+}
+
+impl Store {
+    /// Create a store from data compiled into the program. See
+    /// `StaticStore`.
+    ///
+    /// The n-gram tables are used where they are rather than copied. As with
+    /// a cache built without texts, the store has no license texts. The
+    /// provenance of every license has a source of `static`. IDF weights
+    /// are computed when the store is loaded, if it had them; a candidate
+    /// index or LSH prefilter isn't kept, but can be set up again with
+    /// `set_candidate_index` or `set_lsh_prefilter`.
+    ///
+    /// This only fails if the store's words can't be added to the token
+    /// table, because it's full.
+    pub fn from_static(data: &'static StaticStore) -> Result<Store, Error> {
+        let tokens = Arc::new(StaticTokens::new(data.tokens)?);
+        let text = |counts: &'static StaticCounts| {
+            let grams = StaticGrams {
+                counts,
+                tokens: Arc::clone(&tokens),
+            };
+            TextData::from_match_data(NgramSet::from_static(grams))
+        };
+
+        let mut store = Store::new();
+        for license in data.licenses {
+            let mut entry =
+                LicenseEntry::new(text(&license.original), Some(Provenance::new("static")));
+            entry.aliases = license.aliases.iter().map(|&a| a.to_owned()).collect();
+            entry.family = license.family.map(str::to_owned);
+            for (counts, label) in license.headers {
+                entry.headers.push(text(counts));
+                entry.header_labels.push(label.map(str::to_owned));
+            }
+            for (counts, label) in license.alternates {
+                entry.alternates.push(text(counts));
+                entry.alternate_labels.push(label.map(str::to_owned));
+            }
+            store.licenses.insert(license.name.to_owned(), entry);
+        }
+        for (name, counts) in data.exceptions {
+            store.exceptions.insert((*name).to_owned(), text(counts));
+        }
+
+        store.stop_grams = data
+            .stop_grams
+            .iter()
+            .map(|&g| Gram::intern(g))
+            .collect::<Result<_, _>>()?;
+        store.titles = data
+            .titles
+            .iter()
+            .map(|&(key, names)| {
+                (
+                    key.to_owned(),
+                    names.iter().map(|&n| n.to_owned()).collect(),
+                )
+            })
+            .collect();
+        store.set_idf_weighting(data.idf_weighting);
+        Ok(store)
+    }
+
+    /// Write this store as Rust source for a `StaticStore`, to be compiled
+    /// into a program and loaded with `from_static`.
+    ///
+    /// The output is a single expression of type `askalono::StaticStore`. Only
+    /// what's needed for matching is included: license texts and provenance
+    /// are left out, and so are the candidate index and LSH prefilter, which
+    /// can be set up again on the loaded store (see `from_static`). Whether
+    /// n-grams are weighted is kept, but not the weights themselves, as
+    /// they're computed from the licenses. A store with hashed n-grams (see
+    /// `hash_grams`) can't be written.
+    pub fn write_static<W: Write>(&self, mut out: W) -> Result<(), Error> {
+        if self.hashed_grams {
+            return Err(format_err!(
+                "a store with hashed n-grams can't be written as a static store"
+            ));
+        }
+        let tokens: BTreeSet<&str> = self
+            .licenses
+            .values()
+            .flat_map(|entry| {
+                std::iter::once(&entry.original)
+                    .chain(&entry.headers)
+                    .chain(&entry.alternates)
+            })
+            .chain(self.exceptions.values())
+            .flat_map(|data| &data.match_data)
+            .flat_map(|(gram, _)| {
+                let (first, second) = gram.words();
+                [first, second]
+            })
+            .collect();
+        let tokens: Vec<&str> = tokens.into_iter().collect();
+        let ids: HashMap<&str, u32> = tokens
+            .iter()
+            .enumerate()
+            .map(|(id, &token)| (token, id as u32))
+            .collect();
+        let counts = |data: &TextData| {
+            let mut counts: Vec<(u32, u32, u32)> = data
+                .match_data
+                .into_iter()
+                .map(|(gram, count)| {
+                    let (first, second) = gram.words();
+                    (ids[first], ids[second], count)
+                })
+                .collect();
+            // keep the output the same from build to build
+            counts.sort_unstable();
+            let (seeds, slots) = perfect_hash(&counts);
+            format!(
+                "askalono::StaticCounts {{ seeds: &{:?}, slots: &{:?}, size: {} }}",
+                seeds,
+                slots,
+                data.match_data.len()
+            )
+        };
+
+        writeln!(out, "askalono::StaticStore {{")?;
+        writeln!(out, "    tokens: &{:?},", tokens)?;
+
+        let mut names: Vec<&String> = self.licenses.keys().collect();
+        names.sort();
+        writeln!(out, "    licenses: &[")?;
+        for name in names {
+            let entry = &self.licenses[name];
+            writeln!(out, "        askalono::StaticLicense {{")?;
+            writeln!(out, "            name: {:?},", name)?;
+            writeln!(out, "            aliases: &{:?},", entry.aliases)?;
+            writeln!(out, "            family: {:?},", entry.family)?;
+            writeln!(out, "            original: {},", counts(&entry.original))?;
+            writeln!(out, "            headers: &[")?;
+            for (i, data) in entry.headers.iter().enumerate() {
+                let label = entry.header_label(i);
+                writeln!(out, "                ({}, {:?}),", counts(data), label)?;
+            }
+            writeln!(out, "            ],")?;
+            writeln!(out, "            alternates: &[")?;
+            for (i, data) in entry.alternates.iter().enumerate() {
+                let label = entry.alternate_label(i);
+                writeln!(out, "                ({}, {:?}),", counts(data), label)?;
+            }
+            writeln!(out, "            ],")?;
+            writeln!(out, "        }},")?;
+        }
+        writeln!(out, "    ],")?;
+
+        let mut stop_grams: Vec<String> = self.stop_grams.iter().map(Gram::to_string).collect();
+        stop_grams.sort();
+        writeln!(out, "    stop_grams: &{:?},", stop_grams)?;
+        let mut titles: Vec<(&String, &Vec<String>)> = self.titles.iter().collect();
+        titles.sort();
+        writeln!(out, "    titles: &[")?;
+        for (key, names) in titles {
+            writeln!(out, "        ({:?}, &{:?}),", key, names)?;
+        }
+        writeln!(out, "    ],")?;
+        let mut exceptions: Vec<(&String, &TextData)> = self.exceptions.iter().collect();
+        exceptions.sort_by(|a, b| a.0.cmp(b.0));
+        writeln!(out, "    exceptions: &[")?;
+        for (name, data) in exceptions {
+            writeln!(out, "        ({:?}, {}),", name, counts(data))?;
+        }
+        writeln!(out, "    ],")?;
+        writeln!(out, "    idf_weighting: {},", self.idf.is_some())?;
+        writeln!(out, "}}")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::license::LicenseType;
+
+    /// Build a table at runtime, as `write_static` would generate it.
+    fn table(grams: &[(u32, u32, u32)]) -> StaticCounts {
+        let (seeds, slots) = perfect_hash(grams);
+        StaticCounts {
+            seeds: Box::leak(seeds.into_boxed_slice()),
+            slots: Box::leak(slots.into_boxed_slice()),
+            size: grams.iter().map(|&(_, _, count)| count as usize).sum(),
+        }
+    }
+
+    #[test]
+    fn perfect_hash_tables() {
+        let grams: Vec<(u32, u32, u32)> = (0..1000).map(|i| (i % 37, i, i + 1)).collect();
+        let counts = table(&grams);
+        assert_eq!(grams.len(), counts.slots.len());
+        for &(first, second, count) in &grams {
+            assert_eq!(count, counts.get(first, second));
+        }
+        assert_eq!(0, counts.get(1, 0));
+        assert_eq!(0, counts.get(1000, 1000));
+        assert_eq!(0, table(&[]).get(0, 1));
+    }
+
+    #[test]
+    fn from_static() {
+        let data: &'static StaticStore = Box::leak(Box::new(StaticStore {
+            tokens: &["aaaaa", "bbbbb", "ccccc", "ddddd", "eeeee"],
+            licenses: Box::leak(Box::new([
+                StaticLicense {
+                    name: "license-1",
+                    aliases: &["license-one"],
+                    family: Some("license"),
+                    original: table(&[(0, 1, 1), (1, 2, 1)]),
+                    headers: Box::leak(Box::new([(table(&[(0, 1, 1)]), Some("short"))])),
+                    alternates: &[],
+                },
+                StaticLicense {
+                    name: "license-2",
+                    aliases: &[],
+                    family: None,
+                    original: table(&[(3, 4, 2)]),
+                    headers: &[],
+                    alternates: &[],
+                },
+            ])),
+            stop_grams: &[],
+            titles: &[],
+            exceptions: Box::leak(Box::new([("exception-1", table(&[(2, 3, 1), (3, 4, 1)]))])),
+            idf_weighting: true,
+        }));
+
+        let store = Store::from_static(data).unwrap();
+        assert_eq!(2, store.len());
+        assert_eq!(Some("license"), store.family_of("license-1"));
+        assert_eq!(Some("short"), store.licenses["license-1"].header_label(0));
+        let original = &store.get_original("license-2").unwrap().match_data;
+        assert_eq!(2, original.len());
+        assert_eq!(2, original.get_str("ddddd eeeee"));
+        assert_eq!(
+            1,
+            store.exceptions["exception-1"]
+                .match_data
+                .get_str("ccccc ddddd")
+        );
+        assert!(store.idf_weighting());
+
+        let matched = store.analyze(&TextData::from("aaaaa bbbbb ccccc"));
+        assert_eq!("license-1", matched.name);
+        assert_eq!(LicenseType::Original, matched.license_type);
+        assert!(matched.score > 0.99);
+    }
+
+    #[test]
+    fn write_static() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+        store.add_license("license-2".into(), "ddddd eeeee".into());
+        store
+            .add_variant("license-1", LicenseType::Alternate, "aaaaa bbbbb".into())
+            .unwrap();
+
+        let mut out = Vec::new();
+        store.write_static(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("askalono::StaticStore {"));
+        assert!(out.contains(r#"tokens: &["aaaaa", "bbbbb", "ccccc", "ddddd", "eeeee"],"#));
+        assert!(out.contains("original: askalono::StaticCounts { seeds: &[0], slots: &["));
+        assert!(out.contains("slots: &[(0, 1, 1)], size: 1 }, None),"));
+        assert!(out.contains("exceptions: &[\n    ],"));
+        assert!(out.contains("idf_weighting: false,"));
+
+        store.add_exception("exception-1".into(), "fffff ggggg".into());
+        store.set_idf_weighting(true);
+        let mut out = Vec::new();
+        store.write_static(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains(r#""ddddd", "eeeee", "fffff", "ggggg"],"#));
+        assert!(out.contains(
+            r#"("exception-1", askalono::StaticCounts { seeds: &[0], slots: &[(5, 6, 1)]"#
+        ));
+        assert!(out.contains("idf_weighting: true,"));
+    }
+}
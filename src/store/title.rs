@@ -0,0 +1,152 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{format_err, Error};
+
+use crate::{license::TextData, preproc::apply_normalizers, store::base::Store};
+
+/// Lines longer than this are assumed to be the start of a license's body
+/// rather than its title.
+const MAX_TITLE_WORDS: usize = 10;
+
+/// Normalize a line into the form used to look up titles, or `None` if it
+/// doesn't look like a title.
+///
+/// Punctuation and case are ignored, so "Apache License, Version 2.0" and
+/// "apache license version 2.0" are the same title.
+pub(crate) fn title_key(line: &str) -> Option<String> {
+    let words: Vec<String> = apply_normalizers(line)
+        .concat()
+        .split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric())
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect();
+
+    if words.is_empty() || words.len() > MAX_TITLE_WORDS || words[0] == "copyright" {
+        return None;
+    }
+    Some(words.join(" "))
+}
+
+/// Find the title of a text: its first non-empty line, as long as more text
+/// follows it. (A one-line text is all body.)
+pub(crate) fn find_title<'t, I>(lines: I) -> Option<String>
+where
+    I: IntoIterator<Item = &'t str>,
+{
+    let mut lines = lines.into_iter().filter(|line| !line.trim().is_empty());
+    let first = lines.next()?;
+    lines.next()?;
+    title_key(first)
+}
+
+impl Store {
+    /// Register a title for a license.
+    ///
+    /// Titles let analysis prioritize licenses whose canonical title ("MIT
+    /// License", "Apache License, Version 2.0") appears on the first line of
+    /// a text. Titles are taken from the first line of each license as it's
+    /// added, so this is only needed for titles that don't appear in the
+    /// license text itself. The license must already exist.
+    pub fn add_title(&mut self, name: &str, title: &str) -> Result<(), Error> {
+        if !self.licenses.contains_key(name) {
+            return Err(format_err!("license {} not present in store", name));
+        }
+        let key = title_key(title)
+            .ok_or_else(|| format_err!("{:?} doesn't look like a license title", title))?;
+
+        let names = self.titles.entry(key).or_default();
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_owned());
+        }
+        self.forget_derived();
+        Ok(())
+    }
+
+    /// Forget all titles of the given license.
+    pub(crate) fn remove_titles(&mut self, name: &str) {
+        self.titles.retain(|_, names| {
+            names.retain(|n| n != name);
+            !names.is_empty()
+        });
+        self.forget_derived();
+    }
+
+    /// Get the licenses whose title matches the first line of the given text,
+    /// if any.
+    pub(crate) fn title_candidates(&self, text: &TextData) -> &[String] {
+        text.lines_in_view()
+            .and_then(|lines| find_title(lines.iter().map(String::as_str)))
+            .and_then(|key| self.titles.get(&key))
+            .map_or(&[], Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::options::AnalysisOptions;
+
+    #[test]
+    fn title_keys() {
+        assert_eq!(
+            Some("apache license version 20".to_owned()),
+            title_key("  Apache License, Version 2.0")
+        );
+        assert_eq!(title_key("MIT License"), title_key("mit license"));
+        assert_eq!(None, title_key("Copyright (c) <year> <owner>"));
+        assert_eq!(None, title_key("---"));
+        assert_eq!(
+            None,
+            title_key("Permission is hereby granted, free of charge, to any person obtaining")
+        );
+
+        assert_eq!(
+            Some("mit license".to_owned()),
+            find_title(vec!["", "MIT License", "", "Permission is hereby granted"])
+        );
+        assert_eq!(None, find_title(vec!["MIT License", ""]));
+    }
+
+    #[test]
+    fn titles_break_ties() {
+        let body = "\n\nall of these words are exactly the same";
+        let mut store = Store::new();
+        store.add_license("license-a".into(), format!("Alpha License{}", body).into());
+        store.add_license("license-b".into(), format!("Beta License{}", body).into());
+
+        // the title line isn't part of scoring, so only the title tells
+        // these apart
+        let text = TextData::from(format!("Beta License{}", body).as_str());
+        let matched = store.analyze(&text);
+        assert_eq!("license-b", matched.name);
+        assert_eq!(1.0, matched.score);
+        assert_eq!(
+            "license-b",
+            store
+                .match_iter(&text, &AnalysisOptions::default())
+                .unwrap()
+                .next()
+                .unwrap()
+                .name
+        );
+
+        let text = TextData::from(format!("Unknown License{}", body).as_str());
+        assert_eq!("license-a", store.analyze(&text).name);
+
+        store.add_title("license-a", "The Unknown License").unwrap();
+        store.add_title("license-a", "Unknown License").unwrap();
+        assert_eq!("license-a", store.analyze(&text).name);
+        assert!(store.add_title("license-c", "Gamma License").is_err());
+
+        // replacing a license forgets its old title
+        store.add_license("license-b".into(), format!("Gamma License{}", body).into());
+        let text = TextData::from(format!("Beta License{}", body).as_str());
+        assert_eq!("license-a", store.analyze(&text).name);
+    }
+}
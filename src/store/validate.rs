@@ -0,0 +1,239 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{collections::HashMap, fmt};
+
+use crate::{license::LicenseType, ngram::NgramSet, store::base::Store};
+
+/// A problem found in a `Store` by `Store::validate`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValidationIssue {
+    /// A license text or variant has no n-grams (it's empty, or made up
+    /// entirely of stop grams), so nothing will ever match it.
+    EmptyText {
+        /// The license the text belongs to.
+        name: String,
+        /// Which kind of text is empty.
+        kind: LicenseType,
+        /// Index of the header or alternate; 0 for originals.
+        index: usize,
+    },
+    /// Two licenses have identical texts, so which one matches is decided
+    /// only by tie-breaking. One should likely be an alias of the other.
+    DuplicateText {
+        /// The license found to be a duplicate.
+        name: String,
+        /// The license it duplicates (the first by name).
+        other: String,
+    },
+    /// An alias of a license is also the name or alias of another license.
+    AliasCollision {
+        /// The colliding alias.
+        alias: String,
+        /// The license the alias was set on.
+        name: String,
+        /// The license that already owns the name or alias.
+        other: String,
+    },
+    /// A header or alternate has the same text as its license's original,
+    /// so it adds nothing.
+    RedundantVariant {
+        /// The license the variant belongs to.
+        name: String,
+        /// Whether the variant is a header or an alternate.
+        kind: LicenseType,
+        /// Index of the variant, in the order variants were added.
+        index: usize,
+    },
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationIssue::EmptyText { name, kind, index } => {
+                write!(f, "{} ({} {}) has no matchable text", name, kind, index)
+            }
+            ValidationIssue::DuplicateText { name, other } => {
+                write!(f, "{} has the same text as {}", name, other)
+            }
+            ValidationIssue::AliasCollision { alias, name, other } => write!(
+                f,
+                "alias {} of {} is also a name or alias of {}",
+                alias, name, other
+            ),
+            ValidationIssue::RedundantVariant { name, kind, index } => write!(
+                f,
+                "{} ({} {}) is identical to the original text",
+                name, kind, index
+            ),
+        }
+    }
+}
+
+impl Store {
+    /// Check the store for problems that would make matching unreliable.
+    ///
+    /// This is intended for anyone building a store from their own dataset,
+    /// to run before shipping a cache. Issues are returned in a stable order
+    /// (by license name); an empty list means nothing was found.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut names: Vec<&String> = self.licenses.keys().collect();
+        names.sort();
+
+        let mut issues = Vec::new();
+        let mut by_text: HashMap<&NgramSet, &str> = HashMap::new();
+        let mut by_alias: HashMap<&str, &str> =
+            names.iter().map(|n| (n.as_str(), n.as_str())).collect();
+
+        for name in names {
+            let entry = &self.licenses[name];
+            let variants = entry
+                .headers
+                .iter()
+                .enumerate()
+                .map(|(i, data)| (LicenseType::Header, i, data))
+                .chain(
+                    entry
+                        .alternates
+                        .iter()
+                        .enumerate()
+                        .map(|(i, data)| (LicenseType::Alternate, i, data)),
+                );
+
+            if entry.original.match_data.is_empty() {
+                issues.push(ValidationIssue::EmptyText {
+                    name: name.clone(),
+                    kind: LicenseType::Original,
+                    index: 0,
+                });
+            } else {
+                // report duplicates against the first license with the text
+                let first = *by_text.entry(&entry.original.match_data).or_insert(name);
+                if first != name {
+                    issues.push(ValidationIssue::DuplicateText {
+                        name: name.clone(),
+                        other: first.to_owned(),
+                    });
+                }
+            }
+
+            for (kind, index, data) in variants {
+                if data.match_data.is_empty() {
+                    issues.push(ValidationIssue::EmptyText {
+                        name: name.clone(),
+                        kind,
+                        index,
+                    });
+                } else if data.match_data == entry.original.match_data {
+                    issues.push(ValidationIssue::RedundantVariant {
+                        name: name.clone(),
+                        kind,
+                        index,
+                    });
+                }
+            }
+
+            for alias in &entry.aliases {
+                match by_alias.get(alias.as_str()) {
+                    Some(&other) if other != name => {
+                        issues.push(ValidationIssue::AliasCollision {
+                            alias: alias.clone(),
+                            name: name.clone(),
+                            other: other.to_owned(),
+                        });
+                    }
+                    Some(_) => {}
+                    None => {
+                        by_alias.insert(alias, name);
+                    }
+                }
+            }
+        }
+
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clean_store() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+        store.add_license("license-2".into(), "ddddd eeeee fffff".into());
+        store
+            .add_variant("license-1", LicenseType::Header, "aaaaa bbbbb".into())
+            .unwrap();
+        store
+            .set_aliases("license-1", vec!["license-one".into()])
+            .unwrap();
+        assert!(store.validate().is_empty());
+    }
+
+    #[test]
+    fn issues() {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+        store.add_license("license-2".into(), "aaaaa bbbbb ccccc".into());
+        store.add_license("license-3".into(), "zzzzz".into());
+        store.add_license("license-4".into(), "aaaaa bbbbb ccccc".into());
+        store
+            .add_variant("license-1", LicenseType::Header, "aaaaa bbbbb ccccc".into())
+            .unwrap();
+        store
+            .add_variant("license-1", LicenseType::Alternate, "".into())
+            .unwrap();
+        store
+            .set_aliases("license-2", vec!["license-1".into(), "shared".into()])
+            .unwrap();
+        store
+            .set_aliases("license-3", vec!["shared".into()])
+            .unwrap();
+
+        let issues = store.validate();
+        assert_eq!(
+            vec![
+                ValidationIssue::RedundantVariant {
+                    name: "license-1".into(),
+                    kind: LicenseType::Header,
+                    index: 0,
+                },
+                ValidationIssue::EmptyText {
+                    name: "license-1".into(),
+                    kind: LicenseType::Alternate,
+                    index: 0,
+                },
+                ValidationIssue::DuplicateText {
+                    name: "license-2".into(),
+                    other: "license-1".into(),
+                },
+                ValidationIssue::AliasCollision {
+                    alias: "license-1".into(),
+                    name: "license-2".into(),
+                    other: "license-1".into(),
+                },
+                ValidationIssue::EmptyText {
+                    name: "license-3".into(),
+                    kind: LicenseType::Original,
+                    index: 0,
+                },
+                ValidationIssue::AliasCollision {
+                    alias: "shared".into(),
+                    name: "license-3".into(),
+                    other: "license-2".into(),
+                },
+                ValidationIssue::DuplicateText {
+                    name: "license-4".into(),
+                    other: "license-1".into(),
+                },
+            ],
+            issues
+        );
+        assert_eq!(
+            "license-2 has the same text as license-1",
+            issues[2].to_string()
+        );
+    }
+}
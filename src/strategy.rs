@@ -4,13 +4,22 @@
 use std::borrow::Cow;
 use std::fmt;
 
-use anyhow::Error;
+use anyhow::{format_err, Error};
+use lazy_static::lazy_static;
 use log::{info, trace};
-use serde::Serialize;
+use regex::Regex;
+use serde::{Deserialize, Serialize, Serializer};
+use tracing::{field, trace_span};
 
 use crate::{
-    license::{LicenseType, TextData},
-    store::{Match, Store},
+    deviation::Deviation,
+    license::{LicenseType, Scratch, TextData},
+    limits::InputLimits,
+    memo::ResultCache,
+    ngram::NgramSet,
+    schema::{ContainedRecord, FragmentRecord, ScanRecord},
+    store::{any_license, AnalysisOptions, Match, Store, DEFAULT_AMBIGUITY_EPSILON},
+    timing::{timed, Stopwatch},
 };
 
 /// A struct describing a license that was identified, as well as its type.
@@ -20,23 +29,53 @@ pub struct IdentifiedLicense<'a> {
     pub name: &'a str,
     /// The type of the license that was matched.
     pub kind: LicenseType,
+    /// The family of the license, if known. See `Store::family_of`.
+    pub family: Option<&'a str>,
+    /// The label of the matched header or alternate, if it has one. See
+    /// `Store::add_variant_labeled`.
+    pub variant_label: Option<&'a str>,
+    /// The name of a license exception found along with the license, if
+    /// any. See `Match::exception`. For licenses found within a text (see
+    /// `ScanResult::containing`), only the lines the license was found in
+    /// are searched.
+    pub exception: Option<&'a str>,
+    /// The name of the store the license was found in, if it was given one.
+    /// See `ScanStrategy::add_store`.
+    pub store: Option<&'a str>,
     /// A reference to the license data inside the store.
+    ///
+    /// This is not included in serialized output.
+    #[serde(skip)]
     pub data: &'a TextData,
 }
 
+impl<'a> IdentifiedLicense<'a> {
+    /// Get the normalized text of the license variant that was identified,
+    /// with lines separated by newlines.
+    ///
+    /// Returns `None` if the `Store` was built or loaded without texts.
+    pub fn matched_text(&self) -> Option<String> {
+        self.data.normalized_text()
+    }
+}
+
 impl<'a> fmt::Debug for IdentifiedLicense<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("IdentifiedLicense")
             .field("name", &self.name)
             .field("kind", &self.kind)
+            .field("family", &self.family)
+            .field("variant_label", &self.variant_label)
+            .field("exception", &self.exception)
+            .field("store", &self.store)
             .finish()
     }
 }
 
 /// Information about scanned content.
 ///
-/// Produced by `ScanStrategy.scan`.
-#[derive(Serialize, Debug)]
+/// Produced by `ScanStrategy.scan`. Serialized in the form of a `ScanRecord`.
+#[derive(Debug)]
 pub struct ScanResult<'a> {
     /// The confidence of the match from 0.0 to 1.0.
     pub score: f32,
@@ -45,10 +84,57 @@ pub struct ScanResult<'a> {
     pub license: Option<IdentifiedLicense<'a>>,
     /// Any licenses discovered inside the text, if `optimize` was enabled.
     pub containing: Vec<ContainedResult<'a>>,
+    /// Whether the overall match was too close to call between two licenses.
+    /// See `ScanStrategyBuilder::ambiguity_epsilon`.
+    pub ambiguous: bool,
+    /// An SPDX expression combining the distinct licenses in `containing`,
+    /// if there's more than one, in the order they appear.
+    ///
+    /// They're joined with `OR` when the rest of the text offers a choice
+    /// between them (saying the software is "dual licensed", or may be used
+    /// under either license "at your option"), and with `AND` otherwise.
+    pub expression: Option<String>,
+    /// Any license fragments found in the text, if `fragments` was enabled
+    /// and nothing else was identified.
+    pub fragments: Vec<FragmentResult<'a>>,
+    /// How the text differs from the identified license, word by word, if
+    /// `deviations` was enabled. See `Deviation`.
+    pub deviations: Vec<Deviation>,
+    /// Counts and timings of the work done for this scan, if `metrics` was
+    /// enabled.
+    ///
+    /// This is not included in serialized output.
+    pub metrics: Option<ScanMetrics>,
+}
+
+/// Counts and timings of the work a scan did, for tuning a `ScanStrategy`.
+///
+/// Produced by scans with `ScanStrategyBuilder::metrics` enabled. Times are in
+/// milliseconds, and are always zero on wasm32. Normalizing the text being
+/// scanned happens when its `TextData` is created, before the scan begins,
+/// so it isn't included.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScanMetrics {
+    /// Time taken by the whole scan.
+    pub total_ms: f64,
+    /// Time spent preparing portions of the text for comparison: narrowing
+    /// it to windows, and erasing licenses already found.
+    pub normalize_ms: f64,
+    /// How many times a `Store` analyzed the text or a portion of it. Scans
+    /// with several stores count one call for each.
+    pub analyze_calls: u32,
+    /// Time spent in those calls.
+    pub analyze_ms: f64,
+    /// How many windows a TopDown or Sliding scan evaluated.
+    pub windows_evaluated: u32,
+    /// How many candidate bounds `optimize` scored.
+    pub optimize_iterations: u32,
 }
 
 /// A struct describing a single license identified within a larger text.
-#[derive(Serialize, Debug, Clone)]
+///
+/// Serialized in the form of a `ContainedRecord`.
+#[derive(Debug, Clone)]
 pub struct ContainedResult<'a> {
     /// The confidence of the match within the line range from 0.0 to 1.0.
     pub score: f32,
@@ -59,8 +145,68 @@ pub struct ContainedResult<'a> {
     ///
     /// See `TextData.lines_view()` for more information.
     pub line_range: (usize, usize),
+    /// Whether another license scored nearly as well as this one when it was
+    /// identified. See `ScanStrategyBuilder::ambiguity_epsilon`.
+    pub ambiguous: bool,
+    /// Other line ranges where the same license was found, if
+    /// `collapse_repeats` was enabled, in the order they were found.
+    pub repeats: Vec<(usize, usize)>,
+    /// A 0-indexed (inclusive, exclusive) range of byte offsets of
+    /// `line_range` in the text that was scanned, for tools that highlight
+    /// spans rather than lines. See `TextData::original_offsets`.
+    ///
+    /// This is `None` if the scanned `TextData` doesn't have its original
+    /// text, and for results from a `ScanStream`.
+    pub byte_range: Option<(usize, usize)>,
+    /// Like `byte_range`, but in characters (Unicode scalar values).
+    pub char_range: Option<(usize, usize)>,
 }
 
+/// A struct describing a portion of a license found within a text that
+/// doesn't match the license as a whole, such as a single clause pasted into a
+/// source file header.
+///
+/// Serialized in the form of a `FragmentRecord`.
+#[derive(Debug, Clone)]
+pub struct FragmentResult<'a> {
+    /// The fraction of the scanned text found within the license, from 0.0 to
+    /// 1.0.
+    pub score: f32,
+    /// The license the fragment was taken from.
+    pub license: IdentifiedLicense<'a>,
+    /// A 0-indexed (inclusive, exclusive) range of line numbers of the scanned
+    /// text containing the fragment.
+    pub line_range: (usize, usize),
+    /// A 0-indexed (inclusive, exclusive) range of line numbers of the
+    /// license's normalized text that the fragment covers, or `None` if the
+    /// `Store` doesn't have license texts.
+    ///
+    /// See `IdentifiedLicense::matched_text`.
+    pub license_line_range: Option<(usize, usize)>,
+}
+
+impl<'a> Serialize for ScanResult<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ScanRecord::from(self).serialize(serializer)
+    }
+}
+
+impl<'a> Serialize for ContainedResult<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ContainedRecord::from(self).serialize(serializer)
+    }
+}
+
+impl<'a> Serialize for FragmentResult<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        FragmentRecord::from(self).serialize(serializer)
+    }
+}
+
+/// The fewest n-grams a text may have to be reported as a fragment; anything
+/// shorter is too likely to turn up in many licenses by coincidence.
+const MIN_FRAGMENT_GRAMS: usize = 10;
+
 /// A `ScanStrategy` can be used as a high-level wrapped over a `Store`'s
 /// analysis logic.
 ///
@@ -86,15 +232,22 @@ pub struct ContainedResult<'a> {
 /// ```
 pub struct ScanStrategy<'a> {
     store: &'a Store,
-    mode: ScanMode,
-    confidence_threshold: f32,
-    shallow_limit: f32,
-    optimize: bool,
-    max_passes: u16,
-    step_size: usize,
+    store_name: Option<&'a str>,
+    extra_stores: Vec<(&'a str, &'a Store)>,
+    config: ScanStrategyBuilder,
+    #[cfg(all(feature = "rayon", not(target_arch = "wasm32")))]
+    thread_pool: Option<&'a rayon::ThreadPool>,
+    result_cache: Option<&'a ResultCache>,
+    filter: Option<Box<LicenseFilter<'a>>>,
 }
 
+/// A predicate choosing which licenses are considered by a `ScanStrategy`.
+/// See `ScanStrategy::filter`.
+type LicenseFilter<'a> = dyn Fn(&str, LicenseType) -> bool + Send + Sync + 'a;
+
 /// Available scanning strategy modes.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ScanMode {
     /// Elimination is a general-purpose strategy that iteratively locates the
     /// highest license match in a file, then the next, and so on until not
@@ -105,30 +258,94 @@ pub enum ScanMode {
     /// text files containing multiple licenses (and not much else). It's more
     /// accurate than Elimination, but significantly slower.
     TopDown,
+
+    /// Sliding moves a window of `window_size` lines down the text, keeping
+    /// its n-grams up to date as lines enter and leave it rather than
+    /// reprocessing each window. Licenses are then located, as in TopDown,
+    /// around the windows that fall within one. It finds licenses much like
+    /// TopDown does, at a fraction of the cost.
+    Sliding,
 }
 
-impl<'a> ScanStrategy<'a> {
-    /// Construct a new scanning strategy tied to the given `Store`.
-    ///
-    /// By default, the strategy has conservative defaults and won't perform
-    /// any deeper investigaton into the contents of files.
-    pub fn new(store: &'a Store) -> ScanStrategy<'a> {
+/// Configuration for a `ScanStrategy` that isn't yet tied to a `Store`.
+///
+/// Every option of a strategy lives here. A `ScanStrategy` keeps one, which
+/// can be changed through `ScanStrategy::config_mut`.
+///
+/// This is also useful when scanning preferences come from somewhere other
+/// than code, such as a configuration file: the builder can be deserialized
+/// (any missing options take their default values), then bound to a store
+/// with `build`, which validates the options before producing a
+/// `ScanStrategy`.
+///
+/// # Examples
+///
+/// ```
+/// # use std::error::Error;
+/// use askalono::{ScanStrategyBuilder, Store};
+///
+/// # fn main() -> Result<(), Box<dyn Error>> {
+/// let store = Store::new();
+/// let mut strategy = ScanStrategyBuilder::default()
+///     .confidence_threshold(0.8)
+///     .build(&store)?;
+/// strategy.config_mut().fragments(true).deviations(true);
+///
+/// let invalid = ScanStrategyBuilder::default()
+///     .confidence_threshold(1.5)
+///     .build(&store);
+/// assert!(invalid.is_err());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScanStrategyBuilder {
+    mode: ScanMode,
+    confidence_threshold: f32,
+    shallow_limit: f32,
+    optimize: bool,
+    max_passes: u16,
+    step_size: usize,
+    window_size: usize,
+    input_limits: InputLimits,
+    ambiguity_epsilon: f32,
+    fragments: bool,
+    deviations: bool,
+    metrics: bool,
+    collapse_repeats: bool,
+    merge_overlaps: bool,
+    analysis: AnalysisOptions,
+}
+
+impl Default for ScanStrategyBuilder {
+    fn default() -> Self {
         Self {
-            store,
             mode: ScanMode::Elimination,
             confidence_threshold: 0.9,
             shallow_limit: 0.99,
             optimize: false,
             max_passes: 10,
             step_size: 5,
+            window_size: 10,
+            input_limits: InputLimits::default(),
+            ambiguity_epsilon: DEFAULT_AMBIGUITY_EPSILON,
+            fragments: false,
+            deviations: false,
+            metrics: false,
+            collapse_repeats: false,
+            merge_overlaps: true,
+            analysis: AnalysisOptions::default(),
         }
     }
+}
 
+impl ScanStrategyBuilder {
     /// Set the scanning mode.
     ///
     /// See ScanMode for a description of options. The default mode is
     /// Elimination, which is a fast, good general-purpose matcher.
-    pub fn mode(mut self, mode: ScanMode) -> Self {
+    pub fn mode(&mut self, mode: ScanMode) -> &mut Self {
         self.mode = mode;
         self
     }
@@ -141,7 +358,7 @@ impl<'a> ScanStrategy<'a> {
     ///
     /// Set this to 1.0 for only exact matches, and 0.0 to report even the
     /// weakest match.
-    pub fn confidence_threshold(mut self, confidence_threshold: f32) -> Self {
+    pub fn confidence_threshold(&mut self, confidence_threshold: f32) -> &mut Self {
         self.confidence_threshold = confidence_threshold;
         self
     }
@@ -156,7 +373,7 @@ impl<'a> ScanStrategy<'a> {
     /// This is really only useful in conjunction with `optimize`. A value of
     /// 0.0 will fast-return on any match meeting the confidence threshold,
     /// while a value of 1.0 will only stop on a perfect match.
-    pub fn shallow_limit(mut self, shallow_limit: f32) -> Self {
+    pub fn shallow_limit(&mut self, shallow_limit: f32) -> &mut Self {
         self.shallow_limit = shallow_limit;
         self
     }
@@ -166,7 +383,7 @@ impl<'a> ScanStrategy<'a> {
     /// This is ignored if the shallow limit is met. It's not enabled by
     /// default, however, so if you want deeper results you should set
     /// `shallow_limit` fairly high and enable this.
-    pub fn optimize(mut self, optimize: bool) -> Self {
+    pub fn optimize(&mut self, optimize: bool) -> &mut Self {
         self.optimize = optimize;
         self
     }
@@ -178,65 +395,654 @@ impl<'a> ScanStrategy<'a> {
     /// scenarios, but if you have a document with a large number of licenses
     /// then you may want to tune this to a value above the number of licenses
     /// you expect to be identified.
-    pub fn max_passes(mut self, max_passes: u16) -> Self {
+    pub fn max_passes(&mut self, max_passes: u16) -> &mut Self {
         self.max_passes = max_passes;
         self
     }
 
-    /// Configure the scanning interval (in lines) for TopDown mode.
+    /// Configure the scanning interval (in lines) for TopDown and Sliding
+    /// modes.
     ///
     /// A smaller step size will be more accurate at a significant cost of
     /// speed.
-    pub fn step_size(mut self, step_size: usize) -> Self {
+    pub fn step_size(&mut self, step_size: usize) -> &mut Self {
         self.step_size = step_size;
         self
     }
 
+    /// Configure the size of the window (in lines) for Sliding mode.
+    ///
+    /// Windows should be smaller than the licenses being looked for, so that
+    /// a window can fall entirely within one; larger windows are scored more
+    /// reliably. The default is 10 lines.
+    pub fn window_size(&mut self, window_size: usize) -> &mut Self {
+        self.window_size = window_size;
+        self
+    }
+
+    /// Limit the size of text that will be scanned.
+    ///
+    /// Limits are checked against the lines in view of the `TextData` being
+    /// scanned, with bytes counted on normalized text. Oversized input will
+    /// make `scan` return an error, or if truncation is enabled, only the
+    /// leading lines that fit will be scanned. Input without stored text
+    /// can't be measured and is scanned as-is.
+    ///
+    /// To avoid processing large inputs at all, also consider
+    /// `TextData::new_with_limits`.
+    pub fn input_limits(&mut self, input_limits: InputLimits) -> &mut Self {
+        self.input_limits = input_limits;
+        self
+    }
+
+    /// Set the margin under which two candidate licenses are considered too
+    /// close to call, from 0.0 to 1.0. Defaults to
+    /// `DEFAULT_AMBIGUITY_EPSILON`.
+    ///
+    /// Results where the best license and the best different license score
+    /// within this margin of each other are flagged as `ambiguous`, so they
+    /// can be routed to human review rather than trusting the winner.
+    pub fn ambiguity_epsilon(&mut self, ambiguity_epsilon: f32) -> &mut Self {
+        self.ambiguity_epsilon = ambiguity_epsilon;
+        self
+    }
+
+    /// Indicate whether to look for license fragments when nothing else is
+    /// identified.
+    ///
+    /// A fragment is text found almost entirely within a single license, but
+    /// too short to match it as a whole -- for example, a warranty disclaimer
+    /// copied into a file header. Fragments are reported in
+    /// `ScanResult::fragments` if the portion of the text found within the
+    /// license meets the confidence threshold. This is disabled by default.
+    pub fn fragments(&mut self, fragments: bool) -> &mut Self {
+        self.fragments = fragments;
+        self
+    }
+
+    /// Indicate whether to list how the text differs from the license it was
+    /// identified as.
+    ///
+    /// A text can score well against a license and still add or drop a
+    /// sentence that changes its meaning. With this enabled, the words
+    /// inserted into or deleted from the license's text are reported in
+    /// `ScanResult::deviations`. This needs license texts, so nothing is
+    /// reported for stores built or loaded without them. This is disabled by
+    /// default.
+    pub fn deviations(&mut self, deviations: bool) -> &mut Self {
+        self.deviations = deviations;
+        self
+    }
+
+    /// Indicate whether to count and time the work each scan does.
+    ///
+    /// With this enabled, `ScanResult::metrics` reports how many analyses,
+    /// windows, and bound optimizations a scan took, and how long they took,
+    /// to help choose options such as `step_size` and `max_passes`. Results
+    /// returned from a `result_cache` report only their total time. This is
+    /// disabled by default.
+    pub fn metrics(&mut self, metrics: bool) -> &mut Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Indicate whether to report a license found several times in a text
+    /// once, rather than once per copy.
+    ///
+    /// Files that bundle the notices of many dependencies often repeat the
+    /// same license text over and over. With this enabled, an Elimination
+    /// scan reports each license (from each store) in `containing` only the
+    /// first time it's found, with the line ranges of its other copies in
+    /// `ContainedResult::repeats`. Each copy still takes one of the scan's
+    /// `max_passes`. This is disabled by default.
+    pub fn collapse_repeats(&mut self, collapse_repeats: bool) -> &mut Self {
+        self.collapse_repeats = collapse_repeats;
+        self
+    }
+
+    /// Indicate whether to merge finds of a contained license whose line
+    /// ranges overlap.
+    ///
+    /// A scan can find the same license more than once in overlapping parts
+    /// of a text, such as its header and then its full text. With this
+    /// enabled, a find lying entirely within another find of the same
+    /// license (from the same store) is dropped, and finds that partly
+    /// overlap, directly or through others, are merged into one spanning
+    /// them all. It keeps the license (and so its kind) and ambiguity of the
+    /// widest of them, and the best score. Finds that are merely
+    /// adjacent are left alone, since they may be separate copies. This is
+    /// enabled by default; disable it to get `containing` as found.
+    pub fn merge_overlaps(&mut self, merge_overlaps: bool) -> &mut Self {
+        self.merge_overlaps = merge_overlaps;
+        self
+    }
+
+    /// Set how texts are compared against each store's licenses, such as
+    /// the scorer used or whether close calls are re-scored by alignment.
+    ///
+    /// See `AnalysisOptions`. By default, every license is scored by Dice.
+    pub fn analysis(&mut self, analysis: AnalysisOptions) -> &mut Self {
+        self.analysis = analysis;
+        self
+    }
+
+    /// Check that the configured options are usable.
+    pub fn validate(&self) -> Result<(), Error> {
+        if !(0.0..=1.0).contains(&self.confidence_threshold) {
+            return Err(format_err!(
+                "confidence threshold must be between 0.0 and 1.0; got {}",
+                self.confidence_threshold
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.shallow_limit) {
+            return Err(format_err!(
+                "shallow limit must be between 0.0 and 1.0; got {}",
+                self.shallow_limit
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.ambiguity_epsilon) {
+            return Err(format_err!(
+                "ambiguity epsilon must be between 0.0 and 1.0; got {}",
+                self.ambiguity_epsilon
+            ));
+        }
+        if self.step_size == 0 {
+            return Err(format_err!("step size must be at least 1"));
+        }
+        self.input_limits.validate()?;
+        if self.window_size == 0 {
+            return Err(format_err!("window size must be at least 1"));
+        }
+        self.analysis.validate()
+    }
+
+    /// Validate the configuration and bind it to a `Store`, producing a
+    /// `ScanStrategy`.
+    pub fn build<'a>(&self, store: &'a Store) -> Result<ScanStrategy<'a>, Error> {
+        self.validate()?;
+        Ok(ScanStrategy {
+            store,
+            store_name: None,
+            extra_stores: Vec::new(),
+            config: self.clone(),
+            #[cfg(all(feature = "rayon", not(target_arch = "wasm32")))]
+            thread_pool: None,
+            result_cache: None,
+            filter: None,
+        })
+    }
+}
+
+impl<'a> ScanStrategy<'a> {
+    /// Construct a new scanning strategy tied to the given `Store`.
+    ///
+    /// By default, the strategy has conservative defaults and won't perform
+    /// any deeper investigaton into the contents of files.
+    ///
+    /// To validate options before binding to a store, or to read them from a
+    /// configuration source, see `ScanStrategyBuilder`.
+    pub fn new(store: &'a Store) -> ScanStrategy<'a> {
+        Self {
+            store,
+            store_name: None,
+            extra_stores: Vec::new(),
+            config: ScanStrategyBuilder::default(),
+            #[cfg(all(feature = "rayon", not(target_arch = "wasm32")))]
+            thread_pool: None,
+            result_cache: None,
+            filter: None,
+        }
+    }
+
+    /// Consult another store in addition to the one the strategy was created
+    /// with.
+    ///
+    /// This makes it possible to keep a dataset of your own (such as
+    /// organization-internal licenses) separate from the SPDX store and update
+    /// either independently, rather than merging them into one cache. Every
+    /// store is searched, the best match among them wins, and licenses found
+    /// in this store are tagged with `name` in `IdentifiedLicense::store`.
+    ///
+    /// Licenses with the same name in several stores are treated as the same
+    /// license when deciding whether a result is ambiguous. A `ResultCache`
+    /// isn't used when scanning multiple stores.
+    pub fn add_store(mut self, name: &'a str, store: &'a Store) -> Self {
+        self.extra_stores.push((name, store));
+        self
+    }
+
+    /// Set the name that licenses found in the strategy's original store are
+    /// tagged with in `IdentifiedLicense::store`. By default, they aren't
+    /// tagged. See `add_store`.
+    pub fn store_name(mut self, name: &'a str) -> Self {
+        self.store_name = Some(name);
+        self
+    }
+
+    /// Get the strategy's options.
+    pub fn config(&self) -> &ScanStrategyBuilder {
+        &self.config
+    }
+
+    /// Change the strategy's options. See `ScanStrategyBuilder` for what can
+    /// be set; options are validated when scanning.
+    pub fn config_mut(&mut self) -> &mut ScanStrategyBuilder {
+        &mut self.config
+    }
+
+    /// Set the scanning mode. See `ScanStrategyBuilder::mode`.
+    pub fn mode(mut self, mode: ScanMode) -> Self {
+        self.config.mode(mode);
+        self
+    }
+
+    /// Set the confidence threshold. See
+    /// `ScanStrategyBuilder::confidence_threshold`.
+    pub fn confidence_threshold(mut self, confidence_threshold: f32) -> Self {
+        self.config.confidence_threshold(confidence_threshold);
+        self
+    }
+
+    /// Set the fast-exit limit. See `ScanStrategyBuilder::shallow_limit`.
+    pub fn shallow_limit(mut self, shallow_limit: f32) -> Self {
+        self.config.shallow_limit(shallow_limit);
+        self
+    }
+
+    /// Enable deeper scans. See `ScanStrategyBuilder::optimize`.
+    pub fn optimize(mut self, optimize: bool) -> Self {
+        self.config.optimize(optimize);
+        self
+    }
+
+    /// Set the maximum number of identifications. See
+    /// `ScanStrategyBuilder::max_passes`.
+    pub fn max_passes(mut self, max_passes: u16) -> Self {
+        self.config.max_passes(max_passes);
+        self
+    }
+
+    /// Set the TopDown and Sliding scanning interval. See
+    /// `ScanStrategyBuilder::step_size`.
+    pub fn step_size(mut self, step_size: usize) -> Self {
+        self.config.step_size(step_size);
+        self
+    }
+    /// Run all parallel analysis for this strategy on the given thread pool.
+    ///
+    /// By default, scans use the current (usually global) rayon thread pool.
+    /// Supplying a dedicated pool keeps askalono's work from competing with
+    /// other parallel work in your application.
+    #[cfg(all(feature = "rayon", not(target_arch = "wasm32")))]
+    pub fn thread_pool(mut self, thread_pool: &'a rayon::ThreadPool) -> Self {
+        self.thread_pool = Some(thread_pool);
+        self
+    }
+
+    /// Remember scan results in the given cache, returning the prior result
+    /// when an identical text is scanned with the same options.
+    ///
+    /// See `ResultCache` for details.
+    pub fn result_cache(mut self, result_cache: &'a ResultCache) -> Self {
+        self.result_cache = Some(result_cache);
+        self
+    }
+
+    /// Only consider the licenses accepted by the given predicate, which is
+    /// given the name of each license along with the type of each of its
+    /// variants.
+    ///
+    /// This is useful when only a few licenses matter, such as checking
+    /// whether a text is "Apache-2.0" or "MIT": licenses that aren't accepted
+    /// aren't scored at all, which makes scans much faster. The filter
+    /// applies to every store. Scans return an error if it accepts nothing
+    /// in any of them. Results aren't remembered in a `result_cache` while a
+    /// filter is set, since the cache can't tell filters apart.
+    ///
+    /// ```
+    /// # use std::error::Error;
+    /// use askalono::{ScanStrategy, Store};
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let mut store = Store::new();
+    /// store.add_license("license-1".into(), "aaaaa bbbbb ccccc".into());
+    /// store.add_license("license-2".into(), "ddddd eeeee fffff".into());
+    ///
+    /// let strategy = ScanStrategy::new(&store).filter(|name, _| name == "license-2");
+    /// let result = strategy.scan(&"aaaaa bbbbb ccccc".into())?;
+    /// assert!(result.license.is_none());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&str, LicenseType) -> bool + Send + Sync + 'a,
+    {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
     /// Scan the given text content using this strategy's configured
     /// preferences.
     ///
     /// Returns a `ScanResult` containing all discovered information.
-    pub fn scan(&self, text: &TextData) -> Result<ScanResult, Error> {
-        match self.mode {
-            ScanMode::Elimination => Ok(self.scan_elimination(text)),
-            ScanMode::TopDown => Ok(self.scan_topdown(text)),
+    pub fn scan(&self, text: &TextData) -> Result<ScanResult<'a>, Error> {
+        self.scan_with(text, &mut Scratch::default())
+    }
+
+    /// Scan as with `scan`, reusing the given working buffers. See `Scanner`.
+    pub(crate) fn scan_with(
+        &self,
+        text: &TextData,
+        scratch: &mut Scratch,
+    ) -> Result<ScanResult<'a>, Error> {
+        let stopwatch = Stopwatch::start();
+        scratch.metrics = ScanMetrics::default();
+        self.config.validate()?;
+        self.check_filter()?;
+        let result_cache = self
+            .result_cache
+            .filter(|_| self.extra_stores.is_empty() && self.filter.is_none());
+        if let Some(cache) = result_cache {
+            if let Some(mut result) = cache.get_scan(self.store, &self.config, text) {
+                result.tag_store(self.store_name);
+                result.expression = combined_expression(text, &result.containing);
+                self.record_metrics(&mut result, scratch, &stopwatch);
+                return Ok(result);
+            }
+        }
+
+        #[cfg(all(feature = "rayon", not(target_arch = "wasm32")))]
+        let mut result = match self.thread_pool {
+            Some(pool) => pool.install(|| self.scan_in_current_pool(text, scratch)),
+            None => self.scan_in_current_pool(text, scratch),
+        }?;
+        #[cfg(not(all(feature = "rayon", not(target_arch = "wasm32"))))]
+        let mut result = self.scan_in_current_pool(text, scratch)?;
+
+        if let Some(cache) = result_cache {
+            cache.put_scan(self.store, &self.config, text, &result);
+        }
+        result.expression = combined_expression(text, &result.containing);
+        self.record_metrics(&mut result, scratch, &stopwatch);
+        Ok(result)
+    }
+
+    /// Attach the metrics gathered in `scratch` to a result, if enabled.
+    fn record_metrics(
+        &self,
+        result: &mut ScanResult<'a>,
+        scratch: &Scratch,
+        stopwatch: &Stopwatch,
+    ) {
+        if self.config.metrics {
+            result.metrics = Some(ScanMetrics {
+                total_ms: stopwatch.elapsed_ms(),
+                ..scratch.metrics
+            });
+        }
+    }
+
+    fn scan_in_current_pool(
+        &self,
+        text: &TextData,
+        scratch: &mut Scratch,
+    ) -> Result<ScanResult<'a>, Error> {
+        let text = timed(&mut scratch.metrics.normalize_ms, || self.limit_input(text))?;
+        let mut result = self.scan_window(&text, scratch);
+
+        if self.config.fragments && result.license.is_none() && result.containing.is_empty() {
+            result.fragments.extend(self.find_fragment(&text, scratch));
+        }
+
+        if self.config.deviations {
+            if let Some(ref license) = result.license {
+                result.deviations = text.deviations_from(license.data).unwrap_or_default();
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Scan a piece of a larger text in this strategy's mode, without
+    /// applying input limits or any of the whole-text extras. See
+    /// `scan_stream`.
+    pub(crate) fn scan_window(&self, text: &TextData, scratch: &mut Scratch) -> ScanResult<'a> {
+        let mut result = match self.config.mode {
+            ScanMode::Elimination => self.scan_elimination(text, scratch),
+            ScanMode::TopDown => self.scan_topdown(text, scratch),
+            ScanMode::Sliding => self.scan_sliding(text, scratch),
+        };
+        if self.config.merge_overlaps {
+            merge_overlaps(&mut result.containing);
+        }
+        result
+    }
+
+    fn find_fragment(&self, text: &TextData, scratch: &mut Scratch) -> Option<FragmentResult<'a>> {
+        self.stores()
+            .filter_map(|(store_name, store)| {
+                self.find_fragment_in(text, store_name, store, scratch)
+            })
+            .max_by(|a, b| a.score.partial_cmp(&b.score).unwrap())
+    }
+
+    fn find_fragment_in(
+        &self,
+        text: &TextData,
+        store_name: Option<&'a str>,
+        store: &'a Store,
+        scratch: &mut Scratch,
+    ) -> Option<FragmentResult<'a>> {
+        // compare with stop grams removed, as in analysis
+        let stop_grams = &store.stop_grams;
+        let text = text.excluding_grams(stop_grams);
+        if text.match_data.len() < MIN_FRAGMENT_GRAMS {
+            return None;
+        }
+
+        // find the license containing the most of this text
+        let (name, original, score) = store
+            .licenses
+            .iter()
+            .filter(|(name, _)| self.accepts(name, LicenseType::Original))
+            .map(|(name, entry)| {
+                (
+                    name,
+                    &entry.original,
+                    text.containment_score(&entry.original),
+                )
+            })
+            .max_by(|a, b| a.2.partial_cmp(&b.2).unwrap().then_with(|| b.0.cmp(a.0)))?;
+        trace!(
+            "best fragment candidate {} with containment {}",
+            name,
+            score
+        );
+
+        if score < self.config.confidence_threshold {
+            return None;
+        }
+
+        // locate the covered portion of the license, if we can
+        let license_line_range = original.text_processed().map(|_| {
+            original
+                .optimize_bounds_with(&text, stop_grams, scratch)
+                .0
+                .lines_view()
+        });
+
+        Some(FragmentResult {
+            score,
+            license: IdentifiedLicense {
+                name,
+                kind: LicenseType::Original,
+                family: store.family_of(name),
+                variant_label: None,
+                exception: None,
+                store: store_name,
+                data: original,
+            },
+            line_range: text.lines_view(),
+            license_line_range,
+        })
+    }
+
+    /// The stores this strategy consults, with their names.
+    fn stores(&self) -> impl Iterator<Item = (Option<&'a str>, &'a Store)> + '_ {
+        std::iter::once((self.store_name, self.store)).chain(
+            self.extra_stores
+                .iter()
+                .filter(|(_, store)| !store.is_empty())
+                .map(|&(name, store)| (Some(name), store)),
+        )
+    }
+
+    /// Whether the filter, if any, accepts a license variant. See `filter`.
+    fn accepts(&self, name: &str, kind: LicenseType) -> bool {
+        self.filter.as_ref().is_none_or(|filter| filter(name, kind))
+    }
+
+    /// Check that the filter, if any, accepts something in some store.
+    pub(crate) fn check_filter(&self) -> Result<(), Error> {
+        if self.filter.is_none() {
+            return Ok(());
+        }
+        let accepted = self.stores().any(|(_, store)| {
+            store.licenses.iter().any(|(name, entry)| {
+                self.accepts(name, LicenseType::Original)
+                    || (!entry.headers.is_empty() && self.accepts(name, LicenseType::Header))
+                    || (!entry.alternates.is_empty() && self.accepts(name, LicenseType::Alternate))
+            })
+        });
+        if !accepted {
+            return Err(format_err!("the license filter accepts no licenses"));
+        }
+        Ok(())
+    }
+
+    /// Analyze a text against every store, returning the best match.
+    fn analyze(&self, text: &TextData, metrics: &mut ScanMetrics) -> StoreMatch<'a> {
+        let mut best: Option<StoreMatch<'a>> = None;
+        let mut contenders = Vec::new();
+        for (store_name, store) in self.stores() {
+            metrics.analyze_calls += 1;
+            let options = &self.config.analysis;
+            let matched = timed(&mut metrics.analyze_ms, || match self.filter {
+                Some(ref filter) => store.analyze_matching(text, options, filter),
+                None => store.analyze_matching(text, options, &any_license),
+            });
+            let matched = match matched {
+                Some(m) => m,
+                None => continue,
+            };
+            contenders.push((matched.name, matched.score));
+            if let Some(runner_up) = matched.runner_up {
+                contenders.push((runner_up, matched.runner_up_score));
+            }
+            if best
+                .as_ref()
+                .is_none_or(|b| matched.score > b.matched.score)
+            {
+                best = Some(StoreMatch {
+                    matched,
+                    store,
+                    store_name,
+                });
+            }
+        }
+
+        let mut best = best.expect("a strategy always has a store");
+        if !self.extra_stores.is_empty() {
+            // the runner-up may have come from another store
+            let matched = &mut best.matched;
+            let runner_up = contenders
+                .into_iter()
+                .filter(|&(name, _)| name != matched.name)
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+            matched.runner_up = runner_up.map(|(name, _)| name);
+            matched.runner_up_score = runner_up.map_or(0.0, |(_, score)| score);
+            matched.ambiguous = matched.is_ambiguous(DEFAULT_AMBIGUITY_EPSILON);
+        }
+        best
+    }
+
+    fn limit_input<'t>(&self, text: &'t TextData) -> Result<Cow<'t, TextData>, Error> {
+        let limits = &self.config.input_limits;
+        limits.validate()?;
+        if limits.is_unlimited() || text.text_processed().is_none() {
+            return Ok(Cow::Borrowed(text));
+        }
+
+        // find how many leading lines fit within both limits
+        let lines = text.lines();
+        let max_lines = limits.max_lines.unwrap_or(usize::MAX);
+        let max_bytes = limits.max_bytes.unwrap_or(usize::MAX);
+        let mut bytes = 0usize;
+        let mut fits = 0;
+        for line in lines.iter().take(max_lines) {
+            // count the newline that would join this line to the previous
+            bytes += line.len() + if fits > 0 { 1 } else { 0 };
+            if bytes > max_bytes {
+                break;
+            }
+            fits += 1;
+        }
+
+        if fits == lines.len() {
+            return Ok(Cow::Borrowed(text));
         }
+        if !limits.truncate {
+            return Err(format_err!(
+                "input of {} lines exceeds the configured scan limits",
+                lines.len()
+            ));
+        }
+
+        let (start, _) = text.lines_view();
+        Ok(Cow::Owned(text.with_view(start, start + fits)))
     }
 
-    fn scan_elimination(&self, text: &TextData) -> ScanResult {
-        let mut analysis = self.store.analyze(text);
-        let score = analysis.score;
+    fn scan_elimination(&self, text: &TextData, scratch: &mut Scratch) -> ScanResult<'a> {
+        let mut found = self.analyze(text, &mut scratch.metrics);
+        let score = found.matched.score;
+        let ambiguous = found.matched.is_ambiguous(self.config.ambiguity_epsilon);
         let mut license = None;
         let mut containing = Vec::new();
-        info!("Elimination top-level analysis: {:?}", analysis);
+        info!("Elimination top-level analysis: {:?}", found.matched);
 
         // meets confidence threshold? record that
-        if analysis.score > self.confidence_threshold {
-            license = Some(IdentifiedLicense {
-                name: analysis.name,
-                kind: analysis.license_type,
-                data: analysis.data,
-            });
+        if score > self.config.confidence_threshold {
+            license = Some(found.identified());
 
             // above the shallow limit -> exit
-            if analysis.score > self.shallow_limit {
+            if score > self.config.shallow_limit {
                 return ScanResult {
                     score,
                     license,
                     containing,
+                    ambiguous,
+                    expression: None,
+                    fragments: Vec::new(),
+                    deviations: Vec::new(),
+                    metrics: None,
                 };
             }
         }
 
-        if self.optimize {
+        if self.config.optimize {
             // repeatedly try to dig deeper
             // this loop effectively iterates once for each license it finds
             let mut current_text: Cow<'_, TextData> = Cow::Borrowed(text);
-            for _n in 0..self.max_passes {
-                let (optimized, optimized_score) = current_text.optimize_bounds(analysis.data);
+            for _n in 0..self.config.max_passes {
+                let (optimized, optimized_score) = current_text.optimize_bounds_with(
+                    found.matched.data,
+                    &found.store.stop_grams,
+                    scratch,
+                );
 
                 // stop if we didn't find anything acceptable
-                if optimized_score < self.confidence_threshold {
+                if optimized_score < self.config.confidence_threshold {
                     break;
                 }
 
@@ -247,19 +1053,31 @@ impl<'a> ScanStrategy<'a> {
                     optimized.lines_view().0,
                     optimized.lines_view().1
                 );
-                containing.push(ContainedResult {
+                let offsets = optimized.original_offsets();
+                let contained = ContainedResult {
                     score: optimized_score,
-                    license: IdentifiedLicense {
-                        name: analysis.name,
-                        kind: analysis.license_type,
-                        data: analysis.data,
-                    },
+                    license: found.identified_in(&optimized),
                     line_range: optimized.lines_view(),
+                    ambiguous: found.matched.is_ambiguous(self.config.ambiguity_epsilon),
+                    repeats: Vec::new(),
+                    byte_range: offsets.map(|(bytes, _)| bytes),
+                    char_range: offsets.map(|(_, chars)| chars),
+                };
+                let first = containing.iter_mut().find(|c| {
+                    self.config.collapse_repeats
+                        && c.license.name == contained.license.name
+                        && c.license.store == contained.license.store
                 });
+                match first {
+                    Some(first) => first.repeats.push(contained.line_range),
+                    None => containing.push(contained),
+                }
 
                 // and white-out + reanalyze for next iteration
-                current_text = Cow::Owned(optimized.white_out());
-                analysis = self.store.analyze(&current_text);
+                current_text = Cow::Owned(timed(&mut scratch.metrics.normalize_ms, || {
+                    optimized.white_out()
+                }));
+                found = self.analyze(&current_text, &mut scratch.metrics);
             }
         }
 
@@ -267,17 +1085,22 @@ impl<'a> ScanStrategy<'a> {
             score,
             license,
             containing,
+            ambiguous,
+            expression: None,
+            fragments: Vec::new(),
+            deviations: Vec::new(),
+            metrics: None,
         }
     }
 
-    fn scan_topdown(&self, text: &TextData) -> ScanResult {
+    fn scan_topdown(&self, text: &TextData, scratch: &mut Scratch) -> ScanResult<'a> {
         let (_, text_end) = text.lines_view();
         let mut containing = Vec::new();
 
         // find licenses working down thru the text's lines
         let mut current_start = 0usize;
         while current_start < text_end {
-            let result = self.topdown_find_contained_license(text, current_start);
+            let result = self.topdown_find_contained_license(text, current_start, scratch);
 
             let contained = match result {
                 Some(c) => c,
@@ -292,16 +1115,122 @@ impl<'a> ScanStrategy<'a> {
             score: 0.0,
             license: None,
             containing,
+            ambiguous: false,
+            expression: None,
+            fragments: Vec::new(),
+            deviations: Vec::new(),
+            metrics: None,
+        }
+    }
+
+    fn scan_sliding(&self, text: &TextData, scratch: &mut Scratch) -> ScanResult<'a> {
+        let size = self.config.window_size;
+        let (view_start, view_end) = text.lines_view();
+
+        // find runs of windows lying within a license, widened by a window
+        // on either side to take in the license's edges
+        let mut regions: Vec<(usize, usize)> = Vec::new();
+        let mut windows = 0;
+        let mut analyze_ms = 0.0;
+        text.for_each_window(
+            size,
+            self.config.step_size,
+            scratch,
+            |(start, end), grams| {
+                windows += 1;
+                if !timed(&mut analyze_ms, || self.within_license(grams)) {
+                    return;
+                }
+                trace!("window ({}, {}) is within a license", start, end);
+
+                let region = (
+                    start.saturating_sub(size).max(view_start),
+                    (end + size).min(view_end),
+                );
+                match regions.last_mut() {
+                    Some(last) if region.0 <= last.1 => last.1 = last.1.max(region.1),
+                    _ => regions.push(region),
+                }
+            },
+        );
+        scratch.metrics.windows_evaluated += windows;
+        scratch.metrics.analyze_ms += analyze_ms;
+
+        // then pick out the licenses in each region, looking again at
+        // whatever's left on either side of one once it's found
+        let mut containing = Vec::new();
+        while let Some((start, end)) = regions.pop() {
+            if start >= end {
+                continue;
+            }
+            let view = timed(&mut scratch.metrics.normalize_ms, || {
+                text.with_view(start, end)
+            });
+            let analysis = self.analyze(&view, &mut scratch.metrics);
+            let contained = match self.locate_contained(text, (start, end), analysis, scratch) {
+                Some(c) => c,
+                None => continue,
+            };
+
+            let (found_start, found_end) = contained.line_range;
+            if found_start >= found_end {
+                continue;
+            }
+            regions.push((start, found_start));
+            regions.push((found_end, end));
+            containing.push(contained);
+        }
+        containing.sort_by_key(|c| c.line_range);
+
+        ScanResult {
+            score: 0.0,
+            license: None,
+            containing,
+            ambiguous: false,
+            expression: None,
+            fragments: Vec::new(),
+            deviations: Vec::new(),
+            metrics: None,
         }
     }
 
+    /// Whether the n-grams of a window lie within a license (or one of its
+    /// variants) in any of the stores: whether enough of them are found in
+    /// it to meet the confidence threshold.
+    fn within_license(&self, grams: &NgramSet) -> bool {
+        self.stores().any(|(_, store)| {
+            let mut grams = Cow::Borrowed(grams);
+            if !store.stop_grams.is_empty() {
+                grams.to_mut().remove_grams(&store.stop_grams);
+            }
+            store
+                .licenses
+                .iter()
+                .flat_map(|(name, entry)| {
+                    let original = std::iter::once(&entry.original)
+                        .filter(|_| self.accepts(name, LicenseType::Original));
+                    let headers = entry
+                        .headers
+                        .iter()
+                        .filter(|_| self.accepts(name, LicenseType::Header));
+                    let alternates = entry
+                        .alternates
+                        .iter()
+                        .filter(|_| self.accepts(name, LicenseType::Alternate));
+                    original.chain(headers).chain(alternates)
+                })
+                .any(|data| grams.containment(&data.match_data) >= self.config.confidence_threshold)
+        })
+    }
+
     fn topdown_find_contained_license(
         &self,
         text: &TextData,
         starting_at: usize,
-    ) -> Option<ContainedResult> {
+        scratch: &mut Scratch,
+    ) -> Option<ContainedResult<'a>> {
         let (_, text_end) = text.lines_view();
-        let mut found: (usize, usize, Option<Match<'_>>) = (0, 0, None);
+        let mut found: (usize, usize, Option<StoreMatch<'a>>) = (0, 0, None);
 
         trace!(
             "topdown_find_contained_license starting at line {}",
@@ -312,34 +1241,45 @@ impl<'a> ScanStrategy<'a> {
         let mut hit_threshold = false;
 
         // move the start of window...
-        'start: for start in (starting_at..text_end).step_by(self.step_size) {
+        'start: for start in (starting_at..text_end).step_by(self.config.step_size) {
             // ...and also the end of window to find high scores.
-            for end in (start..=text_end).step_by(self.step_size) {
-                let view = text.with_view(start, end);
-                let analysis = self.store.analyze(&view);
+            for end in (start..=text_end).step_by(self.config.step_size) {
+                let span = trace_span!(
+                    "topdown_window",
+                    start,
+                    end,
+                    score = field::Empty,
+                    elapsed_ms = field::Empty,
+                );
+                let _enter = span.enter();
+                let stopwatch = Stopwatch::start();
+
+                scratch.metrics.windows_evaluated += 1;
+                let view = timed(&mut scratch.metrics.normalize_ms, || {
+                    text.with_view(start, end)
+                });
+                let analysis = self.analyze(&view, &mut scratch.metrics);
+                let score = analysis.matched.score;
+                span.record("score", score);
+                span.record("elapsed_ms", stopwatch.elapsed_ms());
 
                 // just getting a feel for the data at this point, not yet
                 // optimizing the view.
 
                 // entering threshold: save the starting location
-                if !hit_threshold && analysis.score >= self.confidence_threshold {
+                if !hit_threshold && score >= self.config.confidence_threshold {
                     hit_threshold = true;
-                    trace!(
-                        "hit_threshold at ({}, {}) with score {}",
-                        start,
-                        end,
-                        analysis.score
-                    );
+                    trace!("hit_threshold at ({}, {}) with score {}", start, end, score);
                 }
 
                 if hit_threshold {
-                    if analysis.score < self.confidence_threshold {
+                    if score < self.config.confidence_threshold {
                         // exiting threshold
                         trace!(
                             "exiting threshold at ({}, {}) with score {}",
                             start,
                             end,
-                            analysis.score
+                            score
                         );
                         break 'start;
                     } else {
@@ -352,13 +1292,25 @@ impl<'a> ScanStrategy<'a> {
 
         // at this point we have a *rough* bounds for a match.
         // now we can optimize to find the best one
-        let matched = match found.2 {
-            Some(m) => m,
-            None => return None,
-        };
-        let check = matched.data;
-        let view = text.with_view(found.0, found.1);
-        let (optimized, optimized_score) = view.optimize_bounds(check);
+        let found_match = found.2?;
+        self.locate_contained(text, (found.0, found.1), found_match, scratch)
+    }
+
+    /// Optimize the rough bounds of a license found in a text, returning it
+    /// if the optimized match still meets the confidence threshold.
+    fn locate_contained(
+        &self,
+        text: &TextData,
+        (start, end): (usize, usize),
+        found_match: StoreMatch<'a>,
+        scratch: &mut Scratch,
+    ) -> Option<ContainedResult<'a>> {
+        let matched = &found_match.matched;
+        let view = timed(&mut scratch.metrics.normalize_ms, || {
+            text.with_view(start, end)
+        });
+        let (optimized, optimized_score) =
+            view.optimize_bounds_with(matched.data, &found_match.store.stop_grams, scratch);
 
         trace!(
             "optimized {} {} at ({:?})",
@@ -367,25 +1319,161 @@ impl<'a> ScanStrategy<'a> {
             optimized.lines_view()
         );
 
-        if optimized_score < self.confidence_threshold {
+        if optimized_score < self.config.confidence_threshold {
             return None;
         }
 
+        let offsets = optimized.original_offsets();
         Some(ContainedResult {
             score: optimized_score,
-            license: IdentifiedLicense {
-                name: matched.name,
-                kind: matched.license_type,
-                data: matched.data,
-            },
+            license: found_match.identified_in(&optimized),
             line_range: optimized.lines_view(),
+            ambiguous: matched.is_ambiguous(self.config.ambiguity_epsilon),
+            repeats: Vec::new(),
+            byte_range: offsets.map(|(bytes, _)| bytes),
+            char_range: offsets.map(|(_, chars)| chars),
         })
     }
 }
 
+/// A match from one of the stores consulted by a `ScanStrategy`.
+struct StoreMatch<'a> {
+    matched: Match<'a>,
+    store: &'a Store,
+    store_name: Option<&'a str>,
+}
+
+impl<'a> StoreMatch<'a> {
+    fn identified(&self) -> IdentifiedLicense<'a> {
+        IdentifiedLicense {
+            name: self.matched.name,
+            kind: self.matched.license_type,
+            family: self.matched.family,
+            variant_label: self.matched.variant_label,
+            exception: self.matched.exception,
+            store: self.store_name,
+            data: self.matched.data,
+        }
+    }
+
+    /// Identify the license as found in part of a text, reporting only an
+    /// exception whose text is in that part rather than anywhere in the
+    /// text that was analyzed.
+    fn identified_in(&self, region: &TextData) -> IdentifiedLicense<'a> {
+        IdentifiedLicense {
+            exception: self.store.find_exception(region),
+            ..self.identified()
+        }
+    }
+}
+
+impl<'a> ScanResult<'a> {
+    /// Tag every license in the result as coming from the named store.
+    fn tag_store(&mut self, store: Option<&'a str>) {
+        let licenses = self
+            .license
+            .iter_mut()
+            .chain(self.containing.iter_mut().map(|c| &mut c.license))
+            .chain(self.fragments.iter_mut().map(|f| &mut f.license));
+        for license in licenses {
+            license.store = store;
+        }
+    }
+}
+
+/// Merge finds of the same license whose line ranges overlap, keeping the
+/// order of the first of each. See `ScanStrategyBuilder::merge_overlaps`.
+fn merge_overlaps<'a>(containing: &mut Vec<ContainedResult<'a>>) {
+    // sweep each license's finds by where they start, so finds joined only
+    // through a later one still end up together
+    let key = |c: &ContainedResult<'a>| (c.license.name, c.license.store, c.line_range.0);
+    let mut finds: Vec<_> = containing.drain(..).enumerate().collect();
+    finds.sort_by(|(_, a), (_, b)| key(a).cmp(&key(b)));
+
+    let width = |range: (usize, usize)| range.1 - range.0;
+    let spans = |a: Option<(usize, usize)>, b: Option<(usize, usize)>| {
+        a.zip(b).map(|(a, b)| (a.0.min(b.0), a.1.max(b.1)))
+    };
+    // each merged find, with the position of its first find and the width
+    // of its widest
+    let mut merged: Vec<(usize, usize, ContainedResult<'a>)> = Vec::with_capacity(finds.len());
+    for (position, contained) in finds {
+        let overlapping = merged.last_mut().filter(|(_, _, m)| {
+            m.license.name == contained.license.name
+                && m.license.store == contained.license.store
+                && contained.line_range.0 < m.line_range.1
+        });
+        let (first, widest, m) = match overlapping {
+            Some(last) => last,
+            None => {
+                merged.push((position, width(contained.line_range), contained));
+                continue;
+            }
+        };
+
+        *first = (*first).min(position);
+        m.line_range.1 = m.line_range.1.max(contained.line_range.1);
+        m.byte_range = spans(m.byte_range, contained.byte_range);
+        m.char_range = spans(m.char_range, contained.char_range);
+        m.score = m.score.max(contained.score);
+        m.repeats.extend_from_slice(&contained.repeats);
+        if width(contained.line_range) > *widest {
+            *widest = width(contained.line_range);
+            m.license = contained.license;
+            m.ambiguous = contained.ambiguous;
+        }
+    }
+
+    merged.sort_unstable_by_key(|(first, _, _)| *first);
+    containing.extend(merged.into_iter().map(|(_, _, m)| m));
+}
+
+/// Join the distinct licenses found inside a text into an SPDX expression,
+/// if there's more than one. See `ScanResult::expression`.
+fn combined_expression(text: &TextData, containing: &[ContainedResult<'_>]) -> Option<String> {
+    lazy_static! {
+        static ref CHOICE: Regex = Regex::new(
+            r"(?i)\b(dual(ly)?[- ]licen[cs]ed|at (your|the licensee's) (option|choice)|under (the terms of )?either)\b"
+        )
+        .unwrap();
+    }
+
+    let mut found: Vec<&ContainedResult<'_>> = containing.iter().collect();
+    found.sort_by_key(|c| c.line_range);
+    let mut names: Vec<String> = Vec::new();
+    for c in found {
+        let name = match c.license.exception {
+            Some(exception) => format!("{} WITH {}", c.license.name, exception),
+            None => c.license.name.to_owned(),
+        };
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    if names.len() < 2 {
+        return None;
+    }
+
+    // look for a choice only outside the licenses themselves, which may
+    // offer choices of their own (like "any later version")
+    let start = text.lines_view().0;
+    let choice = text.lines_in_view().is_some_and(|lines| {
+        lines.iter().enumerate().any(|(i, line)| {
+            let i = i + start;
+            containing
+                .iter()
+                .all(|c| i < c.line_range.0 || i >= c.line_range.1)
+                && CHOICE.is_match(line)
+        })
+    });
+    let operator = if choice { " OR " } else { " AND " };
+    Some(names.join(operator))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{deviation::DeviationKind, schema::SCHEMA_VERSION, store::Scorer};
 
     #[test]
     fn can_construct() {
@@ -398,6 +1486,108 @@ mod tests {
             .max_passes(100);
     }
 
+    #[test]
+    fn builder_validates() {
+        let store = Store::new();
+        assert!(ScanStrategyBuilder::default().build(&store).is_ok());
+        assert!(ScanStrategyBuilder::default()
+            .confidence_threshold(-0.1)
+            .build(&store)
+            .is_err());
+        assert!(ScanStrategyBuilder::default()
+            .shallow_limit(1.1)
+            .build(&store)
+            .is_err());
+        assert!(ScanStrategyBuilder::default()
+            .step_size(0)
+            .build(&store)
+            .is_err());
+        assert!(ScanStrategyBuilder::default()
+            .input_limits(InputLimits {
+                max_lines: Some(0),
+                ..Default::default()
+            })
+            .build(&store)
+            .is_err());
+        assert!(ScanStrategyBuilder::default()
+            .analysis(AnalysisOptions {
+                early_exit: Some(1.5),
+                ..Default::default()
+            })
+            .build(&store)
+            .is_err());
+    }
+
+    #[test]
+    fn builder_from_config() {
+        let config: ScanStrategyBuilder = serde_json::from_str(
+            r#"{"mode": "topdown", "step_size": 2, "analysis": {"scorer": "cosine"}}"#,
+        )
+        .unwrap();
+        let mut expected = ScanStrategyBuilder::default();
+        expected
+            .mode(ScanMode::TopDown)
+            .step_size(2)
+            .analysis(AnalysisOptions {
+                scorer: Scorer::Cosine,
+                ..Default::default()
+            });
+        assert_eq!(expected, config);
+    }
+
+    #[test]
+    fn analysis_options() {
+        let store = create_dummy_store();
+        let test_data = TextData::new("zzzzz\naaaaa\nbbbbb\nccccc\nyyyyy\nxxxxx");
+
+        let result = ScanStrategy::new(&store).scan(&test_data).unwrap();
+        assert!(result.score < 1.0);
+
+        let result = ScanStrategyBuilder::default()
+            .analysis(AnalysisOptions {
+                scorer: Scorer::Containment,
+                ..Default::default()
+            })
+            .build(&store)
+            .unwrap()
+            .scan(&test_data)
+            .unwrap();
+        assert_eq!("license-1", result.license.unwrap().name);
+        assert_eq!(1.0, result.score);
+    }
+
+    #[test]
+    fn input_limits() {
+        let store = create_dummy_store();
+        let test_data = TextData::new("aaaaa\nbbbbb\nccccc\n1234 5678 1234\n0000");
+
+        let limits = InputLimits {
+            max_lines: Some(3),
+            ..Default::default()
+        };
+        let mut strategy = ScanStrategy::new(&store);
+        strategy.config_mut().input_limits(limits);
+        assert!(strategy.scan(&test_data).is_err());
+
+        // truncating to the first three lines leaves only license-1
+        strategy.config_mut().input_limits(InputLimits {
+            truncate: true,
+            ..limits
+        });
+        let result = strategy.scan(&test_data).unwrap();
+        assert_eq!("license-1", result.license.unwrap().name);
+        assert_eq!(1.0, result.score);
+
+        // 17 bytes also fits exactly three lines
+        strategy.config_mut().input_limits(InputLimits {
+            max_bytes: Some(17),
+            truncate: true,
+            ..Default::default()
+        });
+        let result = strategy.scan(&test_data).unwrap();
+        assert_eq!(1.0, result.score);
+    }
+
     #[test]
     fn shallow_scan() {
         let store = create_dummy_store();
@@ -450,7 +1640,26 @@ mod tests {
         );
     }
 
+    #[cfg(all(feature = "rayon", not(target_arch = "wasm32")))]
+    #[test]
+    fn scan_with_thread_pool() {
+        let store = create_dummy_store();
+        let test_data = TextData::new("aaaaa\nbbbbb\nccccc");
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+
+        let matched = store.analyze_with_pool(&test_data, &pool);
+        assert_eq!("license-1", matched.name);
+
+        let strategy = ScanStrategy::new(&store).thread_pool(&pool);
+        let result = strategy.scan(&test_data).unwrap();
+        assert_eq!("license-1", result.license.unwrap().name);
+    }
+
     #[test]
+    #[allow(clippy::unused_enumerate_index)]
     fn find_multiple_licenses_elimination() {
         let store = create_dummy_store();
         // this TextData matches license-2 with an overall score of ~0.46 and optimized
@@ -494,6 +1703,38 @@ mod tests {
     }
 
     #[test]
+    fn exceptions_in_regions() {
+        let mut store = create_dummy_store();
+        store.add_exception("exception-1".into(), "aaaaa\nbbbbb".into());
+        let test_data =
+            TextData::new("lorem\nipsum abc def ghi jkl\n1234 5678 1234\n0000\n1010101010\n\n8888 9999\nwhatsit hello\narst neio qwfp colemak is the best keyboard layout\naaaaa\nbbbbb\nccccc");
+
+        // the exception is only reported with the license whose lines have it
+        for mode in [ScanMode::Elimination, ScanMode::TopDown] {
+            let strategy = ScanStrategy::new(&store)
+                .mode(mode)
+                .confidence_threshold(0.5)
+                .optimize(true)
+                .shallow_limit(1.0)
+                .step_size(1);
+            let result = strategy.scan(&test_data).unwrap();
+            let mut found: Vec<_> = result
+                .containing
+                .iter()
+                .map(|c| (c.license.name, c.license.exception))
+                .collect();
+            found.sort();
+            assert_eq!(
+                vec![("license-1", Some("exception-1")), ("license-2", None)],
+                found,
+                "{:?}",
+                mode
+            );
+        }
+    }
+
+    #[test]
+    #[allow(clippy::unused_enumerate_index)]
     fn find_multiple_licenses_topdown() {
         env_logger::init();
 
@@ -538,6 +1779,434 @@ mod tests {
         );
     }
 
+    #[test]
+    fn find_multiple_licenses_sliding() {
+        let store = create_dummy_store();
+        let test_data =
+            TextData::new("lorem\nipsum abc def ghi jkl\n1234 5678 1234\n0000\n1010101010\n\n8888 9999\nwhatsit hello\narst neio qwfp colemak is the best keyboard layout\naaaaa\nbbbbb\nccccc");
+
+        let strategy = ScanStrategyBuilder::default()
+            .mode(ScanMode::Sliding)
+            .confidence_threshold(0.5)
+            .window_size(2)
+            .step_size(1)
+            .metrics(true)
+            .build(&store)
+            .unwrap();
+        let result = strategy.scan(&test_data).unwrap();
+        assert!(result.license.is_none());
+
+        let found: Vec<_> = result
+            .containing
+            .iter()
+            .map(|c| (c.license.name, c.line_range))
+            .collect();
+        assert_eq!(vec![("license-2", (2, 7)), ("license-1", (9, 12))], found);
+        assert!(result.containing.iter().all(|c| c.score > 0.5));
+
+        let metrics = result.metrics.unwrap();
+        assert_eq!(11, metrics.windows_evaluated);
+        assert!(metrics.analyze_calls < metrics.windows_evaluated);
+
+        // nothing to find
+        let result = strategy
+            .scan(&TextData::new("lorem\nipsum\ndolor"))
+            .unwrap();
+        assert!(result.containing.is_empty());
+        assert_eq!(0, result.metrics.unwrap().analyze_calls);
+    }
+
+    #[test]
+    fn serialized_schema() {
+        let mut store = create_dummy_store();
+        store
+            .set_family("license-1", Some("license".into()))
+            .unwrap();
+        let test_data = TextData::new("aaaaa\nbbbbb\nccccc");
+
+        let matched = store.analyze(&test_data);
+        let json = serde_json::to_value(&matched).unwrap();
+        assert_eq!(SCHEMA_VERSION as u64, json["schema_version"]);
+        assert_eq!("license-1", json["name"]);
+        assert_eq!("original", json["license_type"]);
+        assert_eq!(
+            Some("aaaaa\nbbbbb\nccccc".to_owned()),
+            matched.matched_text()
+        );
+        assert!(json.get("data").is_none(), "license data is not serialized");
+
+        let strategy = ScanStrategy::new(&store);
+        let result = strategy.scan(&test_data).unwrap();
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(SCHEMA_VERSION as u64, json["schema_version"]);
+        assert_eq!("license-1", json["license"]);
+        assert_eq!(
+            Some("aaaaa\nbbbbb\nccccc".to_owned()),
+            result.license.as_ref().unwrap().matched_text()
+        );
+        assert_eq!("license", json["family"]);
+        assert!(json.get("data").is_none());
+    }
+
+    #[test]
+    fn ambiguity() {
+        let mut store = create_dummy_store();
+        store.add_license("license-1b".into(), "aaaaa\nbbbbb\nccccc\nddddd".into());
+
+        // clearly license-1, but license-1b isn't far off
+        let test_data = TextData::new("aaaaa\nbbbbb\nccccc");
+        let matched = store.analyze(&test_data);
+        assert_eq!("license-1", matched.name);
+        assert_eq!(Some("license-1b"), matched.runner_up);
+        assert!(!matched.ambiguous);
+        assert!(matched.is_ambiguous(0.5));
+
+        let result = ScanStrategy::new(&store).scan(&test_data).unwrap();
+        assert!(!result.ambiguous);
+        let result = ScanStrategyBuilder::default()
+            .ambiguity_epsilon(0.5)
+            .build(&store)
+            .unwrap()
+            .scan(&test_data)
+            .unwrap();
+        assert!(result.ambiguous);
+
+        // a lone license can't be ambiguous
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa\nbbbbb\nccccc".into());
+        let matched = store.analyze(&test_data);
+        assert_eq!(None, matched.runner_up);
+        assert!(!matched.is_ambiguous(1.0));
+    }
+
+    #[test]
+    fn fragments() {
+        let mut store = create_dummy_store();
+        store.add_license(
+            "license-3".into(),
+            "the first clause says one thing\nthe second clause says another thing entirely\nthe third clause disclaims all warranty of any kind whatsoever\nthe fourth clause is about liability and damages\nthe fifth clause is the last".into(),
+        );
+        let test_data = TextData::new(
+            "this file is part of something\nthe third clause disclaims all warranty of any kind whatsoever",
+        );
+
+        let result = ScanStrategy::new(&store)
+            .confidence_threshold(0.5)
+            .scan(&test_data)
+            .unwrap();
+        assert!(result.license.is_none());
+        assert!(result.fragments.is_empty(), "fragments are off by default");
+
+        let result = ScanStrategyBuilder::default()
+            .confidence_threshold(0.5)
+            .fragments(true)
+            .build(&store)
+            .unwrap()
+            .scan(&test_data)
+            .unwrap();
+        assert!(result.license.is_none());
+        assert_eq!(1, result.fragments.len());
+        let fragment = &result.fragments[0];
+        assert_eq!("license-3", fragment.license.name);
+        assert!(fragment.score > 0.5, "score was {}", fragment.score);
+        assert_eq!((0, 2), fragment.line_range);
+        assert_eq!(Some((2, 3)), fragment.license_line_range);
+    }
+
+    #[test]
+    fn metrics() {
+        let store = create_dummy_store();
+        let test_data = TextData::new("lorem\naaaaa\nbbbbb\nccccc\nipsum\nddddd\neeeee\nfffff");
+
+        let result = ScanStrategy::new(&store).scan(&test_data).unwrap();
+        assert_eq!(None, result.metrics, "metrics are off by default");
+
+        let result = ScanStrategyBuilder::default()
+            .mode(ScanMode::TopDown)
+            .step_size(1)
+            .metrics(true)
+            .build(&store)
+            .unwrap()
+            .scan(&test_data)
+            .unwrap();
+        let metrics = result.metrics.unwrap();
+        assert!(metrics.windows_evaluated > 0);
+        assert_eq!(metrics.windows_evaluated, metrics.analyze_calls);
+        assert!(metrics.optimize_iterations > 0);
+        assert!(metrics.total_ms >= metrics.analyze_ms);
+
+        // a single analysis when nothing more is needed
+        let result = ScanStrategyBuilder::default()
+            .optimize(true)
+            .metrics(true)
+            .build(&store)
+            .unwrap()
+            .scan(&TextData::new("aaaaa\nbbbbb\nccccc"))
+            .unwrap();
+        let metrics = result.metrics.unwrap();
+        assert_eq!(1, metrics.analyze_calls);
+        assert_eq!(0, metrics.windows_evaluated);
+        assert_eq!(0, metrics.optimize_iterations);
+    }
+
+    #[test]
+    fn deviations() {
+        let mut store = Store::new();
+        store.add_license(
+            "license-1".into(),
+            "permission is granted to use this software\nprovided that this notice is kept\nthe software comes without warranty".into(),
+        );
+        let test_data = TextData::new(
+            "permission is granted to use this software\nexcept for military purposes\nprovided that this notice is kept\nthe software comes without warranty",
+        );
+
+        let result = ScanStrategy::new(&store)
+            .confidence_threshold(0.5)
+            .scan(&test_data)
+            .unwrap();
+        assert_eq!("license-1", result.license.unwrap().name);
+        assert!(
+            result.deviations.is_empty(),
+            "deviations are off by default"
+        );
+
+        let result = ScanStrategyBuilder::default()
+            .confidence_threshold(0.5)
+            .deviations(true)
+            .build(&store)
+            .unwrap()
+            .scan(&test_data)
+            .unwrap();
+        assert_eq!(
+            vec![Deviation {
+                kind: DeviationKind::Insertion,
+                text: "except for military purposes".into(),
+                position: 7,
+            }],
+            result.deviations
+        );
+    }
+
+    #[test]
+    fn collapse_repeats() {
+        let store = create_dummy_store();
+        let test_data = TextData::new(
+            "aaaaa\nbbbbb\nccccc\nfirst package\naaaaa\nbbbbb\nccccc\nsecond package\naaaaa\nbbbbb\nccccc",
+        );
+        let mut strategy = ScanStrategy::new(&store)
+            .confidence_threshold(0.5)
+            .optimize(true);
+
+        let result = strategy.scan(&test_data).unwrap();
+        assert_eq!(3, result.containing.len());
+
+        strategy.config_mut().collapse_repeats(true);
+        let result = strategy.scan(&test_data).unwrap();
+        assert_eq!(1, result.containing.len());
+        let contained = &result.containing[0];
+        assert_eq!("license-1", contained.license.name);
+        let mut ranges = contained.repeats.clone();
+        ranges.push(contained.line_range);
+        ranges.sort();
+        assert_eq!(vec![(0, 3), (4, 7), (8, 11)], ranges);
+    }
+
+    #[test]
+    fn contained_offsets() {
+        let store = create_dummy_store();
+        let text = "©©© unrelated\naaaaa\nbbbbb\nccccc\nmore unrelated text";
+        let strategy = ScanStrategy::new(&store)
+            .confidence_threshold(0.5)
+            .optimize(true);
+
+        let result = strategy.scan(&TextData::new(text)).unwrap();
+        let contained = &result.containing[0];
+        assert_eq!((1, 4), contained.line_range);
+        let (start, end) = contained.byte_range.unwrap();
+        assert_eq!("aaaaa\nbbbbb\nccccc", &text[start..end]);
+        assert_eq!(Some((14, 31)), contained.char_range);
+    }
+
+    #[test]
+    fn filter() {
+        let store = create_dummy_store();
+        let test_data = TextData::new("lorem\naaaaa\nbbbbb\nccccc\nipsum");
+        let strategy = || {
+            ScanStrategy::new(&store)
+                .mode(ScanMode::TopDown)
+                .confidence_threshold(0.5)
+                .step_size(1)
+        };
+
+        let result = strategy()
+            .filter(|name, _| name == "license-1")
+            .scan(&test_data)
+            .unwrap();
+        assert_eq!(1, result.containing.len());
+        assert_eq!("license-1", result.containing[0].license.name);
+
+        let result = strategy()
+            .filter(|name, _| name == "license-2")
+            .scan(&test_data)
+            .unwrap();
+        assert!(result.containing.is_empty());
+
+        let result = strategy()
+            .filter(|_, kind| kind == LicenseType::Header)
+            .scan(&test_data);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn merge_overlaps() {
+        let data = TextData::new("aaaaa");
+        let found = |name, line_range: (usize, usize), score| ContainedResult {
+            score,
+            license: IdentifiedLicense {
+                name,
+                kind: LicenseType::Original,
+                family: None,
+                variant_label: None,
+                exception: None,
+                store: None,
+                data: &data,
+            },
+            line_range,
+            ambiguous: false,
+            repeats: Vec::new(),
+            byte_range: Some((line_range.0 * 10, line_range.1 * 10)),
+            char_range: None,
+        };
+        let mut containing = vec![
+            found("license-1", (0, 10), 0.9),
+            found("license-1", (2, 5), 0.95),
+            found("license-2", (4, 12), 0.8),
+            found("license-1", (8, 14), 0.97),
+            found("license-1", (14, 20), 0.9),
+        ];
+        super::merge_overlaps(&mut containing);
+
+        let merged: Vec<_> = containing
+            .iter()
+            .map(|c| (c.license.name, c.line_range, c.score, c.byte_range))
+            .collect();
+        assert_eq!(
+            vec![
+                ("license-1", (0, 14), 0.97, Some((0, 140))),
+                ("license-2", (4, 12), 0.8, Some((40, 120))),
+                ("license-1", (14, 20), 0.9, Some((140, 200))),
+            ],
+            merged
+        );
+
+        // finds joined only through a later one are merged too
+        let mut containing = vec![
+            found("license-1", (0, 5), 0.9),
+            found("license-1", (10, 15), 0.9),
+            found("license-1", (4, 11), 0.8),
+        ];
+        containing[2].license.kind = LicenseType::Header;
+        super::merge_overlaps(&mut containing);
+
+        let merged: Vec<_> = containing
+            .iter()
+            .map(|c| (c.license.kind, c.line_range, c.score, c.byte_range))
+            .collect();
+        assert_eq!(
+            vec![(LicenseType::Header, (0, 15), 0.9, Some((0, 150)))],
+            merged
+        );
+    }
+
+    #[test]
+    fn expression() {
+        let store = create_dummy_store();
+        let strategy = ScanStrategy::new(&store)
+            .mode(ScanMode::TopDown)
+            .confidence_threshold(0.5)
+            .step_size(1);
+        let licenses = "1234 5678 1234\n0000\n1010101010\n\n8888 9999\n\naaaaa\nbbbbb\nccccc";
+
+        let result = strategy.scan(&TextData::new(licenses)).unwrap();
+        assert_eq!(2, result.containing.len());
+        assert_eq!(
+            Some("license-2 AND license-1"),
+            result.expression.as_deref()
+        );
+
+        let text = format!("This project is dual licensed:\n\n{}", licenses);
+        let result = strategy.scan(&TextData::new(&text)).unwrap();
+        assert_eq!(Some("license-2 OR license-1"), result.expression.as_deref());
+
+        let result = strategy
+            .scan(&TextData::new("aaaaa\nbbbbb\nccccc\n\naaaaa\nbbbbb\nccccc"))
+            .unwrap();
+        assert_eq!(None, result.expression);
+    }
+
+    #[test]
+    fn multiple_stores() {
+        let store = create_dummy_store();
+        let mut internal = Store::new();
+        internal.add_license("internal-1".into(), "ddddd\neeeee\nfffff".into());
+        let test_data = TextData::new("ddddd\neeeee\nfffff");
+
+        let result = ScanStrategy::new(&store).scan(&test_data).unwrap();
+        assert!(result.license.is_none());
+
+        let strategy = ScanStrategy::new(&store)
+            .store_name("spdx")
+            .add_store("internal", &internal)
+            .optimize(true)
+            .confidence_threshold(0.5);
+        let result = strategy.scan(&test_data).unwrap();
+        let license = result.license.unwrap();
+        assert_eq!("internal-1", license.name);
+        assert_eq!(Some("internal"), license.store);
+        assert!(!result.ambiguous);
+
+        let result = strategy
+            .scan(&TextData::new("aaaaa\nbbbbb\nccccc"))
+            .unwrap();
+        assert_eq!(Some("spdx"), result.license.unwrap().store);
+
+        // licenses from both stores can be found in one text
+        let result = strategy
+            .scan(&TextData::new(
+                "aaaaa\nbbbbb\nccccc\nsomething else entirely\nddddd\neeeee\nfffff",
+            ))
+            .unwrap();
+        let mut found: Vec<_> = result
+            .containing
+            .iter()
+            .map(|c| (c.license.name, c.license.store))
+            .collect();
+        found.sort();
+        assert_eq!(
+            vec![
+                ("internal-1", Some("internal")),
+                ("license-1", Some("spdx"))
+            ],
+            found
+        );
+    }
+
+    #[test]
+    fn multiple_stores_ambiguity() {
+        let store = create_dummy_store();
+        let mut other = Store::new();
+        other.add_license("license-1b".into(), "aaaaa\nbbbbb\nccccc\nddddd".into());
+        let test_data = TextData::new("aaaaa\nbbbbb\nccccc");
+
+        let mut strategy = ScanStrategy::new(&store).add_store("other", &other);
+        strategy.config_mut().ambiguity_epsilon(0.5);
+        let result = strategy.scan(&test_data).unwrap();
+        let license = result.license.as_ref().unwrap();
+        assert_eq!("license-1", license.name);
+        assert_eq!(None, license.store);
+        assert!(result.ambiguous, "runner-up from another store counts");
+    }
+
     fn create_dummy_store() -> Store {
         let mut store = Store::new();
         store.add_license("license-1".into(), "aaaaa\nbbbbb\nccccc".into());
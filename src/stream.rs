@@ -0,0 +1,363 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader, Read},
+};
+
+use anyhow::Error;
+
+use crate::{
+    license::{Scratch, TextData},
+    strategy::{ContainedResult, ScanStrategy},
+};
+
+/// Lines in a window unless configured otherwise.
+const DEFAULT_WINDOW_LINES: usize = 2000;
+
+/// Lines longer than this are cut short; nothing in a license comes close.
+const MAX_LINE_BYTES: usize = 64 * 1024;
+
+impl<'a> ScanStrategy<'a> {
+    /// Scan text read from `reader` a window of lines at a time, producing
+    /// each license found as soon as it's certain.
+    ///
+    /// Unlike `scan`, this never holds more than one window of the text in
+    /// memory, nor any results that have already been produced, so it can
+    /// handle inputs of any size. Licenses are found as in `scan` with the
+    /// strategy's mode, so Elimination strategies need `optimize` enabled to
+    /// find anything that doesn't fill a whole window. Input limits don't
+    /// apply, and `collapse_repeats` has no effect: every license found is
+    /// produced separately.
+    ///
+    /// Line ranges are relative to the start of the input. See `ScanStream`
+    /// for how the window size affects results.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,should_panic
+    /// # use std::fs::File;
+    /// # use std::error::Error;
+    /// use askalono::{ScanStrategy, Store};
+    ///
+    /// # fn main() -> Result<(), Box<dyn Error>> {
+    /// let store = Store::from_cache(File::open("askalono-cache.bin.zstd")?)?;
+    /// let strategy = ScanStrategy::new(&store).optimize(true);
+    /// for contained in strategy.scan_stream(File::open("NOTICE")?) {
+    ///     let contained = contained?;
+    ///     println!("{} at {:?}", contained.license.name, contained.line_range);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn scan_stream<R: Read>(&self, reader: R) -> ScanStream<'_, 'a, R> {
+        ScanStream {
+            strategy: self,
+            reader: BufReader::new(reader),
+            window: Vec::new(),
+            window_start: 0,
+            window_lines: DEFAULT_WINDOW_LINES,
+            pending: VecDeque::new(),
+            scratch: Scratch::default(),
+            eof: false,
+            done: false,
+        }
+    }
+}
+
+/// An iterator over the licenses found in streamed text, created by
+/// `ScanStrategy::scan_stream`.
+///
+/// The text is scanned in overlapping windows. A license is produced once it
+/// ends in the first half of a window, so that it's known to be complete;
+/// anything found after that is scanned again with the next window, which
+/// starts where the first half ended (or earlier, to keep a license found
+/// there whole). A license longer than half a window may therefore be missed
+/// or found in pieces, so the window should be at least twice the length of
+/// the longest license expected.
+///
+/// Reading stops at the first error, such as an I/O error, which is produced
+/// in place of a license.
+pub struct ScanStream<'s, 'a, R> {
+    strategy: &'s ScanStrategy<'a>,
+    reader: BufReader<R>,
+    window: Vec<String>,
+    window_start: usize,
+    window_lines: usize,
+    pending: VecDeque<ContainedResult<'a>>,
+    scratch: Scratch,
+    eof: bool,
+    done: bool,
+}
+
+impl<'s, 'a, R: Read> ScanStream<'s, 'a, R> {
+    /// Set the number of lines scanned at once.
+    ///
+    /// The default of 2000 comfortably fits two copies of the longest SPDX
+    /// licenses. Larger windows use more memory, and since a window is
+    /// rescanned from its midpoint, each line is scanned about twice
+    /// regardless. Windows are at least two lines.
+    pub fn window_lines(mut self, window_lines: usize) -> Self {
+        self.window_lines = window_lines.max(2);
+        self
+    }
+
+    /// Read lines until the window is full or the input runs out.
+    fn fill(&mut self) -> Result<(), Error> {
+        let mut buf = Vec::new();
+        while !self.eof && self.window.len() < self.window_lines {
+            buf.clear();
+            let read = (&mut self.reader)
+                .take(MAX_LINE_BYTES as u64)
+                .read_until(b'\n', &mut buf)?;
+            if read == 0 {
+                self.eof = true;
+                break;
+            }
+            if buf.last() == Some(&b'\n') {
+                buf.pop();
+            } else if read == MAX_LINE_BYTES {
+                skip_line(&mut self.reader)?;
+            }
+            self.window.push(
+                String::from_utf8_lossy(&buf)
+                    .trim_end_matches('\r')
+                    .to_owned(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Scan the current window, queue what it's certain about, and slide it
+    /// forward past that.
+    fn advance(&mut self) {
+        if self.window.is_empty() {
+            self.done = self.eof;
+            return;
+        }
+
+        let len = self.window.len();
+        let text = TextData::new(&self.window.join("\n"));
+        let result = self.strategy.scan_window(&text, &mut self.scratch);
+        let mut found = result.containing;
+        if found.is_empty() {
+            // the whole window matched (this only happens with Elimination)
+            if let Some(license) = result.license {
+                found.push(ContainedResult {
+                    score: result.score,
+                    license,
+                    line_range: (0, len),
+                    ambiguous: result.ambiguous,
+                    repeats: Vec::new(),
+                    byte_range: None,
+                    char_range: None,
+                });
+            }
+        }
+        // repeats are produced one by one
+        let repeats: Vec<ContainedResult<'a>> = found
+            .iter_mut()
+            .flat_map(|contained| {
+                let template = ContainedResult {
+                    repeats: Vec::new(),
+                    ..contained.clone()
+                };
+                contained
+                    .repeats
+                    .drain(..)
+                    .map(move |line_range| ContainedResult {
+                        line_range,
+                        ..template.clone()
+                    })
+            })
+            .collect();
+        found.extend(repeats);
+        found.sort_by_key(|contained| contained.line_range);
+
+        // slide to the middle of the window, but not past the start of
+        // anything that may continue beyond it
+        let cut = if self.eof {
+            len
+        } else {
+            let middle = len / 2;
+            let cut = found
+                .iter()
+                .filter(|contained| contained.line_range.1 > middle)
+                .map(|contained| contained.line_range.0)
+                .fold(middle, usize::min);
+            match cut {
+                // a license fills the first half of the window; as good as
+                // it'll get
+                0 => found
+                    .iter()
+                    .map(|contained| contained.line_range.1)
+                    .fold(middle, usize::max),
+                cut => cut,
+            }
+        };
+
+        for mut contained in found {
+            if contained.line_range.0 >= cut {
+                continue;
+            }
+            let (start, end) = contained.line_range;
+            contained.line_range = (start + self.window_start, end + self.window_start);
+            // relative to the window, and lines may have been altered to fit
+            contained.byte_range = None;
+            contained.char_range = None;
+            self.pending.push_back(contained);
+        }
+        self.window.drain(..cut);
+        self.window_start += cut;
+        self.done = self.eof;
+    }
+}
+
+impl<'s, 'a, R: Read> Iterator for ScanStream<'s, 'a, R> {
+    type Item = Result<ContainedResult<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(contained) = self.pending.pop_front() {
+                return Some(Ok(contained));
+            }
+            if self.done {
+                return None;
+            }
+            let checked = self
+                .fill()
+                .and_then(|()| self.strategy.config().validate())
+                .and_then(|()| self.strategy.check_filter());
+            if let Err(e) = checked {
+                self.done = true;
+                return Some(Err(e));
+            }
+            self.advance();
+        }
+    }
+}
+
+/// Discard the rest of the current line.
+fn skip_line<R: BufRead>(reader: &mut R) -> Result<(), Error> {
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(());
+        }
+        match buf.iter().position(|&b| b == b'\n') {
+            Some(newline) => {
+                reader.consume(newline + 1);
+                return Ok(());
+            }
+            None => {
+                let len = buf.len();
+                reader.consume(len);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ScanMode, Store};
+
+    fn create_dummy_store() -> Store {
+        let mut store = Store::new();
+        store.add_license("license-1".into(), "aaaaa\nbbbbb\nccccc".into());
+        store.add_license("license-2".into(), "ddddd\neeeee\nfffff".into());
+        store
+    }
+
+    #[test]
+    fn finds_every_license() {
+        let store = create_dummy_store();
+        let mut text = String::new();
+        for n in 0..20 {
+            let license = match n % 3 {
+                0 => "aaaaa\nbbbbb\nccccc",
+                _ => "ddddd\neeeee\nfffff",
+            };
+            text.push_str(&format!("package {}\n{}\n\n", n, license));
+        }
+
+        let expected: Vec<_> = (0..20)
+            .map(|n| {
+                let name = match n % 3 {
+                    0 => "license-1",
+                    _ => "license-2",
+                };
+                ((n * 5 + 1, n * 5 + 5), name)
+            })
+            .collect();
+
+        // Elimination loses some of these repeats in larger windows, just
+        // as it does scanning the whole text
+        let elimination = ScanStrategy::new(&store)
+            .confidence_threshold(0.8)
+            .optimize(true)
+            .max_passes(100);
+        let topdown = ScanStrategy::new(&store)
+            .mode(ScanMode::TopDown)
+            .confidence_threshold(0.8)
+            .step_size(1);
+        let cases = [
+            (&elimination, 8),
+            (&elimination, 13),
+            (&topdown, 8),
+            (&topdown, 50),
+            (&topdown, 1000),
+        ];
+        for (strategy, window_lines) in cases {
+            let streamed: Vec<_> = strategy
+                .scan_stream(text.as_bytes())
+                .window_lines(window_lines)
+                .map(|c| c.map(|c| (c.line_range, c.license.name)))
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_eq!(expected, streamed, "window of {} lines", window_lines);
+        }
+    }
+
+    #[test]
+    fn whole_window() {
+        let store = create_dummy_store();
+        let strategy = ScanStrategy::new(&store);
+        let found: Vec<_> = strategy
+            .scan_stream("aaaaa\r\nbbbbb\r\nccccc\r\n".as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(1, found.len());
+        assert_eq!("license-1", found[0].license.name);
+        assert_eq!((0, 3), found[0].line_range);
+
+        assert_eq!(0, strategy.scan_stream("".as_bytes()).count());
+    }
+
+    #[test]
+    fn long_lines() {
+        let store = create_dummy_store();
+        let strategy = ScanStrategy::new(&store)
+            .confidence_threshold(0.8)
+            .optimize(true);
+        let text = format!("{}\naaaaa\nbbbbb\nccccc", "x".repeat(MAX_LINE_BYTES * 3));
+        let found: Vec<_> = strategy
+            .scan_stream(text.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(1, found.len());
+        assert_eq!((1, 4), found[0].line_range);
+    }
+
+    #[test]
+    fn invalid_config() {
+        let store = create_dummy_store();
+        let strategy = ScanStrategy::new(&store)
+            .mode(ScanMode::TopDown)
+            .step_size(0);
+        let mut stream = strategy.scan_stream("aaaaa\nbbbbb\nccccc".as_bytes());
+        assert!(stream.next().unwrap().is_err());
+        assert!(stream.next().is_none());
+    }
+}
@@ -0,0 +1,61 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+/// A minimal stopwatch used to attach timing information to diagnostics.
+///
+/// `Instant` isn't available on wasm32, so there it always reports zero.
+pub(crate) struct Stopwatch {
+    #[cfg(not(target_arch = "wasm32"))]
+    start: Instant,
+}
+
+impl Stopwatch {
+    pub fn start() -> Stopwatch {
+        Stopwatch {
+            #[cfg(not(target_arch = "wasm32"))]
+            start: Instant::now(),
+        }
+    }
+
+    /// Milliseconds elapsed since the stopwatch was started.
+    pub fn elapsed_ms(&self) -> f64 {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.start.elapsed().as_secs_f64() * 1000.0
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            0.0
+        }
+    }
+}
+
+/// Run `f`, adding the milliseconds it took to `total`.
+pub(crate) fn timed<T>(total: &mut f64, f: impl FnOnce() -> T) -> T {
+    let stopwatch = Stopwatch::start();
+    let result = f();
+    *total += stopwatch.elapsed_ms();
+    result
+}
+
+/// The current time in seconds since the Unix epoch, if the platform can tell
+/// us. The system clock isn't available on wasm32.
+pub(crate) fn unix_now() -> Option<u64> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|since| since.as_secs())
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    {
+        None
+    }
+}
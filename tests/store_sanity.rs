@@ -5,8 +5,9 @@ mod common;
 
 use std::fs::File;
 use std::io::prelude::*;
+use std::path::Path;
 
-use askalono::TextData;
+use askalono::{HeaderOptions, SpdxLoadOptions, Store, TextData};
 
 #[test]
 fn store_loads() {
@@ -14,6 +15,48 @@ fn store_loads() {
     assert!(!store.is_empty(), "store should have licenses");
 }
 
+#[test]
+fn spdx_provenance() {
+    let store = common::load_store();
+    let provenance = store.provenance("MIT").expect("MIT has provenance");
+    assert_eq!("spdx", provenance.source);
+    assert!(provenance.path.as_ref().unwrap().ends_with("MIT.json"));
+    assert!(provenance.loaded_at.is_some());
+}
+
+#[test]
+fn spdx_original_texts() {
+    let mut store = Store::new();
+    store
+        .load_spdx_with_originals(Path::new(common::SPDX_JSON), false)
+        .unwrap();
+
+    let mut expected = String::new();
+    File::open(format!("{}/MIT.txt", common::SPDX_TEXT))
+        .unwrap()
+        .read_to_string(&mut expected)
+        .unwrap();
+    assert_eq!(
+        expected.trim_end(),
+        store.original_text("MIT").unwrap().trim_end()
+    );
+    assert_eq!(None, common::load_store().original_text("MIT"));
+}
+
+#[test]
+fn spdx_header_suggestion() {
+    let store = common::load_store();
+    let options = HeaderOptions {
+        year: Some("2024".into()),
+        owner: Some("Jane Doe".into()),
+        ..Default::default()
+    };
+    let header = store.suggest_header("Apache-2.0", &options).unwrap();
+    assert!(header.starts_with("Copyright 2024 Jane Doe\n"));
+    assert!(header.contains("Licensed under the Apache License, Version 2.0"));
+    assert_eq!(None, store.suggest_header("MIT", &options));
+}
+
 #[test]
 fn self_licenses() {
     let store = common::load_store();
@@ -50,3 +93,175 @@ fn empty_match() {
 
     assert_eq!(0f32, matched.score);
 }
+
+#[test]
+fn spdx_exceptions() {
+    let store = common::load_store();
+    assert!(store
+        .exceptions()
+        .any(|name| name == "Classpath-exception-2.0"));
+
+    let exception: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(format!(
+            "{}/../exceptions/Classpath-exception-2.0.json",
+            common::SPDX_JSON
+        ))
+        .unwrap(),
+    )
+    .unwrap();
+    let gpl = std::fs::read_to_string(format!("{}/GPL-2.0-only.txt", common::SPDX_TEXT)).unwrap();
+    let text = format!(
+        "{}\n\n{}",
+        gpl,
+        exception["licenseExceptionText"].as_str().unwrap()
+    );
+
+    let matched = store.analyze(&TextData::from(text));
+    assert!(matched.name.starts_with("GPL-2.0"), "{}", matched.name);
+    assert_eq!(Some("Classpath-exception-2.0"), matched.exception);
+    assert_eq!(None, store.analyze(&TextData::from(gpl)).exception);
+}
+
+#[test]
+fn spdx_incremental_update() {
+    let dir = std::env::temp_dir().join(format!("askalono-spdx-update-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let copy = |license: &str| {
+        std::fs::copy(
+            format!("{}/{}.json", common::SPDX_JSON, license),
+            dir.join(format!("{}.json", license)),
+        )
+        .unwrap();
+    };
+    for license in &["MIT", "Apache-2.0", "ISC", "BSD-2-Clause"] {
+        copy(license);
+    }
+
+    let mut store = Store::new();
+    store.load_spdx(&dir, false).unwrap();
+    assert_eq!(4, store.len());
+
+    // add one, remove one, and change one
+    copy("BSD-3-Clause");
+    std::fs::remove_file(dir.join("ISC.json")).unwrap();
+    let mit_path = dir.join("MIT.json");
+    let mut mit: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&mit_path).unwrap()).unwrap();
+    let text = format!(
+        "{}\n\nSome extra terms.",
+        mit["licenseText"].as_str().unwrap()
+    );
+    mit["licenseText"] = text.into();
+    std::fs::write(&mit_path, mit.to_string()).unwrap();
+
+    let update = store.update_from_spdx(&dir).unwrap();
+    let mut fresh = Store::new();
+    fresh.load_spdx(&dir, false).unwrap();
+    let unchanged = store.update_from_spdx(&dir).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(vec!["BSD-3-Clause"], update.added);
+    assert_eq!(vec!["MIT"], update.changed);
+    assert_eq!(vec!["ISC"], update.removed);
+    assert_eq!(askalono::SpdxUpdate::default(), unchanged);
+
+    let mut names: Vec<_> = store.licenses().collect();
+    names.sort();
+    let mut fresh_names: Vec<_> = fresh.licenses().collect();
+    fresh_names.sort();
+    assert_eq!(fresh_names, names);
+    for name in names {
+        assert_eq!(
+            fresh.get_original(name),
+            store.get_original(name),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn spdx_license_template() {
+    let dir = std::env::temp_dir().join(format!("askalono-spdx-template-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mit_path = dir.join("MIT.json");
+    let mut mit: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(format!("{}/MIT.json", common::SPDX_JSON)).unwrap(),
+    )
+    .unwrap();
+    let text = mit["licenseText"].as_str().unwrap().to_owned();
+
+    let mut plain = Store::new();
+    std::fs::write(&mit_path, mit.to_string()).unwrap();
+    plain.load_spdx(&dir, false).unwrap();
+
+    mit["standardLicenseTemplate"] = text
+        .replace(
+            "any person",
+            "<<var;name=\"grantee\";original=\"any person\";match=\".+\">>",
+        )
+        .into();
+    let mut templated = Store::new();
+    std::fs::write(&mit_path, mit.to_string()).unwrap();
+    templated.load_spdx(&dir, false).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let filled = TextData::from(text.replace(
+        "any person",
+        "Acme Widgets, Inc. and any of its subsidiaries or affiliates",
+    ));
+    let plain_match = plain.analyze(&filled);
+    let templated_match = templated.analyze(&filled);
+    assert_eq!("MIT", templated_match.name);
+    assert!(
+        templated_match.score > plain_match.score,
+        "{} should beat {}",
+        templated_match.score,
+        plain_match.score
+    );
+}
+
+#[test]
+fn spdx_deprecated() {
+    let options = SpdxLoadOptions {
+        include_deprecated: true,
+        ..Default::default()
+    };
+    let mut store = Store::new();
+    store
+        .load_spdx_with_options(Path::new(common::SPDX_JSON), &options)
+        .unwrap();
+    // GPL-2.0 has the same text as GPL-2.0-only, so it's only an alias
+    assert!(store
+        .aliases("GPL-2.0-only")
+        .unwrap()
+        .contains(&"GPL-2.0".to_owned()));
+    assert!(!store.is_deprecated("GPL-2.0-only"));
+    assert!(!common::load_store()
+        .aliases("GPL-2.0-only")
+        .unwrap()
+        .contains(&"GPL-2.0".to_owned()));
+
+    // deprecated licenses with texts of their own can be matched
+    let dir = std::env::temp_dir().join(format!("askalono-spdx-deprecated-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let license = serde_json::json!({
+        "licenseId": "GPL-1.0",
+        "isDeprecatedLicenseId": true,
+        "licenseText": "This program is distributed under the first GNU license.",
+    });
+    std::fs::write(dir.join("GPL-1.0.json"), license.to_string()).unwrap();
+    let mut skipped = Store::new();
+    skipped.load_spdx(&dir, false).unwrap();
+    let mut store = Store::new();
+    store.load_spdx_with_options(&dir, &options).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(skipped.is_empty());
+    let matched = store.analyze(&TextData::from(
+        "This program is distributed under the first GNU license.",
+    ));
+    assert_eq!("GPL-1.0", matched.name);
+    assert!(store.is_deprecated("GPL-1.0"));
+    assert_eq!(Some("GPL-1.0-only"), store.superseded_by("GPL-1.0"));
+}